@@ -0,0 +1,28 @@
+use std::{path::Path, process::Command};
+
+// Bakes the git revision spackle was built from into SPACKLE_GIT_SHA, when it's built from a
+// git checkout
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // HEAD moves on checkout, and the branch it points at on commit, as a loose ref or in
+    // packed-refs. Watching a path that doesn't exist makes cargo rerun the script on every
+    // build, so only the ones present are watched, and none outside a checkout.
+    for path in [".git/HEAD", ".git/refs/heads", ".git/packed-refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+
+    let sha = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|sha| !sha.is_empty());
+
+    if let Some(sha) = sha {
+        println!("cargo:rustc-env=SPACKLE_GIT_SHA={}", sha);
+    }
+}
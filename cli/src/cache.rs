@@ -0,0 +1,65 @@
+use std::process::exit;
+
+use colored::Colorize;
+use spackle::cache;
+
+use crate::util::present;
+
+pub fn clear() {
+    let Some(dir) = cache::projects_dir() else {
+        eprintln!(
+            "{}\n{}",
+            "❌ Could not find the cache directory".bright_red(),
+            "Set HOME or XDG_CACHE_HOME".red()
+        );
+        exit(1);
+    };
+
+    match cache::clear(&dir) {
+        Ok(cleared) => {
+            println!(
+                "🧹 Removed {}, freeing {}",
+                present::count(cleared.entries, "cached project", "cached projects"),
+                format_size(cleared.bytes).bold()
+            );
+            println!("  {}", format!("📁 {}", dir.display()).dimmed());
+        }
+        Err(e) => {
+            eprintln!(
+                "{}\n{}",
+                "❌ Error clearing cache".bright_red(),
+                e.to_string().red()
+            );
+            exit(1);
+        }
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+    }
+}
@@ -2,18 +2,62 @@ use std::{error::Error, process::exit, time::Instant};
 
 use colored::Colorize;
 use spackle::{
+    hook::CommandStatus,
+    prelude::Project,
     slot,
     template::{self, ValidateError},
-    Project,
 };
 
-pub fn run(project: &Project) {
+pub fn run(project: &Project, check_commands: bool, strict_variables: bool) {
     println!("🔍 Validating project configuration\n");
 
     let start_time = Instant::now();
 
-    match template::validate(&project.path, &project.config.slots) {
-        Ok(()) => {
+    // Linted first, since the findings often explain why validation fails
+    match template::lint(&project.path, &project.config.slots, &project.config.hooks) {
+        Ok(findings) => {
+            for finding in findings {
+                eprintln!(
+                    "  {}\n  {}\n",
+                    format!(
+                        "⚠️ Template {} may not render as intended",
+                        finding.template.bold()
+                    )
+                    .bright_yellow(),
+                    finding.kind.to_string().yellow()
+                );
+            }
+        }
+        Err(e) => {
+            eprintln!(
+                "  {}\n  {}\n",
+                "⚠️ Could not lint template files".bright_yellow(),
+                e.to_string().yellow()
+            );
+        }
+    }
+
+    match template::validate(
+        &project.path,
+        &project.config.templates,
+        &project.config.slots,
+        strict_variables,
+    ) {
+        Ok(templates) if templates.is_empty() => {
+            println!(
+                "  {}",
+                format!(
+                    "👌 No templates found ({} project)",
+                    if project.is_hooks_only() {
+                        "hooks-only"
+                    } else {
+                        "static-only"
+                    }
+                )
+                .dimmed()
+            );
+        }
+        Ok(_) => {
             println!("  {}", "👌 Template files are valid".dimmed());
         }
         Err(e) => {
@@ -56,9 +100,92 @@ pub fn run(project: &Project) {
         }
     }
 
+    // Prompts that don't render are shown as written, so these don't fail the check
+    for slot in &project.config.slots {
+        if let Err(e) = slot.validate_prompts() {
+            eprintln!(
+                "  {}\n  {}\n",
+                "⚠️ Slot prompt won't be rendered".bright_yellow(),
+                e.to_string().yellow()
+            );
+        }
+    }
+
+    for pattern in project.config.unmatched_managed(&project.path) {
+        eprintln!(
+            "  {}\n",
+            format!(
+                "⚠️ Managed pattern {} doesn't match any files",
+                pattern.bold()
+            )
+            .bright_yellow()
+        );
+    }
+
+    check_hook_writes(project);
+
+    if check_commands {
+        check_hook_commands(project);
+    }
+
     print_elapsed_time(start_time);
 }
 
+// Warns about hooks that declare writes outside the output directory
+fn check_hook_writes(project: &Project) {
+    for hook in &project.config.hooks {
+        let outside = hook.writes_outside_output();
+        if outside.is_empty() {
+            continue;
+        }
+
+        eprintln!(
+            "  {}\n  {}\n",
+            format!(
+                "⚠️ Hook {} writes outside the output directory",
+                hook.key.bold()
+            )
+            .bright_yellow(),
+            outside
+                .iter()
+                .map(|write| write.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+                .yellow()
+        );
+    }
+}
+
+// Warns about hooks whose commands can't be found on PATH
+fn check_hook_commands(project: &Project) {
+    for hook in &project.config.hooks {
+        match hook.command_status() {
+            CommandStatus::Found(_) | CommandStatus::Relative => {}
+            CommandStatus::NotFound => {
+                eprintln!(
+                    "  {}\n  {}\n",
+                    format!(
+                        "⚠️ Command for hook {} could not be found on PATH",
+                        hook.key.bold()
+                    )
+                    .bright_yellow(),
+                    hook.command.first().cloned().unwrap_or_default().yellow()
+                );
+            }
+            CommandStatus::Templated => {
+                println!(
+                    "  {}\n",
+                    format!(
+                        "ℹ Skipping command check for hook {} as it is templated",
+                        hook.key
+                    )
+                    .dimmed()
+                );
+            }
+        }
+    }
+}
+
 fn print_elapsed_time(start_time: Instant) {
     println!(
         "  ✅ done {}",
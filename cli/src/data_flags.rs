@@ -0,0 +1,291 @@
+use colored::Colorize;
+use spackle::{
+    hook::parse_toggle,
+    prelude::{Hook, Slot},
+};
+use std::{fmt::Display, fs, path::Path};
+
+/// Which kind of config item a data flag targets
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Namespace {
+    Slot,
+    Hook,
+}
+
+impl Display for Namespace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Namespace::Slot => write!(f, "slot"),
+            Namespace::Hook => write!(f, "hook"),
+        }
+    }
+}
+
+/// A key=value pair passed on the command line. Flags passed via --data
+/// have no namespace and may target either a slot or a hook.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataFlag {
+    pub namespace: Option<Namespace>,
+    pub key: String,
+    pub value: String,
+}
+
+/// Parses a --set value of the form `slot.key=value` or `hook.key=value`
+pub fn parse_set(s: &str) -> Result<DataFlag, String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or("must be in the form of slot.key=value or hook.key=value")?;
+
+    let (namespace, key) = match key.split_once('.') {
+        Some(("slot", key)) => (Namespace::Slot, key),
+        Some(("hook", key)) => (Namespace::Hook, key),
+        _ => return Err("key must start with slot. or hook.".to_string()),
+    };
+
+    Ok(DataFlag {
+        namespace: Some(namespace),
+        key: key.to_string(),
+        value: value.to_string(),
+    })
+}
+
+/// Parses --data values of the form `key=value`, skipping (and reporting) any that are malformed
+pub fn parse_data(data: &[String]) -> Vec<DataFlag> {
+    data.iter()
+        .filter_map(|e| match e.split_once('=') {
+            Some((key, value)) => Some(DataFlag {
+                namespace: None,
+                key: key.to_string(),
+                value: value.to_string(),
+            }),
+            None => {
+                eprintln!(
+                    "❌ {}\n",
+                    "Invalid data argument, must be in the form of key=value. Skipping."
+                        .bright_red()
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+/// Parses a TOML table of hook toggles into hook data flags. Values may be booleans
+/// or strings such as "yes" and "no".
+pub fn parse_hook_toggles(contents: &str) -> Result<Vec<DataFlag>, String> {
+    let table = contents.parse::<toml::Table>().map_err(|e| e.to_string())?;
+
+    let mut flags = table
+        .into_iter()
+        .map(|(key, value)| {
+            let enabled = match &value {
+                toml::Value::Boolean(enabled) => *enabled,
+                toml::Value::String(s) => parse_toggle(&key, s).map_err(|e| e.to_string())?,
+                _ => return Err(format!("{} must be a boolean, found {}", key, value)),
+            };
+
+            Ok(DataFlag {
+                namespace: Some(Namespace::Hook),
+                key,
+                value: enabled.to_string(),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    flags.sort_by(|a, b| a.key.cmp(&b.key));
+
+    Ok(flags)
+}
+
+/// Reads hook toggles from a TOML file, see [parse_hook_toggles]
+pub fn load_hook_file(path: &Path) -> Result<Vec<DataFlag>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+
+    parse_hook_toggles(&contents)
+}
+
+/// A data flag that doesn't correspond to an item in its namespace,
+/// along with the closest item it may have been meant for
+#[derive(Debug, PartialEq)]
+pub struct Unrecognized {
+    pub flag: DataFlag,
+    pub suggestion: Option<(Namespace, String)>,
+}
+
+impl Display for Unrecognized {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.flag.namespace {
+            Some(namespace) => write!(f, "{}.{}", namespace, self.flag.key)?,
+            None => write!(f, "{}", self.flag.key)?,
+        }
+
+        if let Some((namespace, key)) = &self.suggestion {
+            write!(
+                f,
+                " (did you mean the {} '{}'? use --set {}.{}=<value>)",
+                namespace, key, namespace, key
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Finds the data flags that don't match a slot or hook in their namespace
+pub fn find_unrecognized(flags: &[DataFlag], slots: &[Slot], hooks: &[Hook]) -> Vec<Unrecognized> {
+    let keys = slots
+        .iter()
+        .map(|s| (Namespace::Slot, &s.key))
+        .chain(hooks.iter().map(|h| (Namespace::Hook, &h.key)))
+        .collect::<Vec<_>>();
+
+    flags
+        .iter()
+        .filter(|flag| {
+            !keys.iter().any(|(namespace, key)| {
+                **key == flag.key && flag.namespace.is_none_or(|n| n == *namespace)
+            })
+        })
+        .map(|flag| {
+            let suggestion = keys
+                .iter()
+                .map(|(namespace, key)| (edit_distance(&flag.key, key), namespace, key))
+                .filter(|(distance, _, _)| *distance <= 2)
+                .min_by_key(|(distance, _, _)| *distance)
+                .map(|(_, namespace, key)| (*namespace, key.to_string()));
+
+            Unrecognized {
+                flag: flag.clone(),
+                suggestion,
+            }
+        })
+        .collect()
+}
+
+// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = prev + usize::from(a_char != *b_char);
+            prev = row[j + 1];
+            row[j + 1] = substitution.min(prev + 1).min(row[j] + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flag(namespace: Option<Namespace>, key: &str) -> DataFlag {
+        DataFlag {
+            namespace,
+            key: key.to_string(),
+            value: "x".to_string(),
+        }
+    }
+
+    #[test]
+    fn set() {
+        assert_eq!(
+            parse_set("slot.name=x"),
+            Ok(flag(Some(Namespace::Slot), "name"))
+        );
+        assert_eq!(
+            parse_set("hook.init_git=x"),
+            Ok(flag(Some(Namespace::Hook), "init_git"))
+        );
+        assert!(parse_set("name=x").is_err());
+        assert!(parse_set("slot.name").is_err());
+        assert!(parse_set("other.name=x").is_err());
+    }
+
+    #[test]
+    fn hook_toggles() {
+        let flags = parse_hook_toggles(
+            r#"
+            docker = true
+            ci = "no"
+            lint = "Yes"
+            "#,
+        )
+        .expect("parse_hook_toggles failed, should have succeeded");
+
+        assert_eq!(
+            flags
+                .iter()
+                .map(|f| (f.key.as_str(), f.value.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("ci", "false"), ("docker", "true"), ("lint", "true")]
+        );
+        assert!(flags.iter().all(|f| f.namespace == Some(Namespace::Hook)));
+
+        assert!(parse_hook_toggles("docker = \"maybe\"").is_err());
+        assert!(parse_hook_toggles("docker = 1").is_err());
+    }
+
+    #[test]
+    fn hook_file_enables_optional_hook() {
+        let hooks = vec![Hook {
+            key: "docker".to_string(),
+            command: vec!["true".to_string()],
+            default: Some(false),
+            ..Hook::default()
+        }];
+
+        let data = parse_hook_toggles("docker = \"yes\"")
+            .unwrap()
+            .into_iter()
+            .map(|f| (f.key, f.value))
+            .collect();
+        assert!(spackle::hook::validate_data(&data, &hooks).is_ok());
+
+        let plan = spackle::hook::plan_hooks(&hooks, &[], &data, &[]);
+        assert_eq!(plan.queued.len(), 1);
+    }
+
+    #[test]
+    fn unrecognized() {
+        let slots = vec![Slot {
+            key: "name".to_string(),
+            ..Slot::default()
+        }];
+        let hooks = vec![Hook {
+            key: "init_git".to_string(),
+            ..Hook::default()
+        }];
+
+        let flags = vec![
+            flag(None, "name"),
+            flag(Some(Namespace::Hook), "init_git"),
+            flag(Some(Namespace::Slot), "init_git"),
+            flag(None, "init_gti"),
+            flag(None, "unrelated"),
+        ];
+
+        assert_eq!(
+            find_unrecognized(&flags, &slots, &hooks),
+            vec![
+                Unrecognized {
+                    flag: flag(Some(Namespace::Slot), "init_git"),
+                    suggestion: Some((Namespace::Hook, "init_git".to_string())),
+                },
+                Unrecognized {
+                    flag: flag(None, "init_gti"),
+                    suggestion: Some((Namespace::Hook, "init_git".to_string())),
+                },
+                Unrecognized {
+                    flag: flag(None, "unrelated"),
+                    suggestion: None,
+                },
+            ]
+        );
+    }
+}
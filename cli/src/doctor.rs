@@ -0,0 +1,380 @@
+use std::{env, fs, path::Path, process::exit};
+
+use colored::Colorize;
+use spackle::{
+    cache,
+    hook::{self, CommandStatus},
+    manifest::Manifest,
+    paths::is_within,
+    prelude::{load_project, Project},
+    run_log, slot, template,
+};
+
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+struct Finding {
+    status: Status,
+    message: String,
+    hint: Option<String>,
+}
+
+impl Finding {
+    fn pass(message: impl Into<String>) -> Self {
+        Finding {
+            status: Status::Pass,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Finding {
+            status: Status::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Finding {
+            status: Status::Fail,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+pub fn run(project_path: &Path, out_dir: Option<&Path>, run_as: Option<&str>) {
+    println!("🩺 Diagnosing environment\n");
+
+    let mut findings = Vec::new();
+
+    match load_project(&project_path.to_path_buf()) {
+        Ok(project) => {
+            findings.push(Finding::pass("Project config parses and is valid"));
+            findings.extend(check_project(&project));
+        }
+        Err(e) => findings.push(Finding::fail(
+            format!(
+                "Project config could not be loaded: {}",
+                e.to_string().replace('\n', ": ")
+            ),
+            format!(
+                "Ensure {} is a spackle project and its config is valid",
+                project_path.to_string_lossy()
+            ),
+        )),
+    }
+
+    findings.extend(check_git(project_path));
+    findings.push(check_output_writable());
+
+    if let Some(user) = run_as {
+        findings.push(check_run_as(user));
+    }
+
+    if let Some(out_dir) = out_dir {
+        findings.extend(check_manifest(out_dir));
+        findings.push(check_run_log(out_dir));
+    }
+
+    let mut failed = false;
+    for finding in findings {
+        let (icon, message) = match finding.status {
+            Status::Pass => ("✅", finding.message.normal()),
+            Status::Warn => ("⚠️", finding.message.bright_yellow()),
+            Status::Fail => {
+                failed = true;
+                ("❌", finding.message.bright_red())
+            }
+        };
+
+        println!("  {} {}", icon, message);
+        if let Some(hint) = finding.hint {
+            println!("     {}", format!("↳ {}", hint).dimmed());
+        }
+    }
+
+    println!();
+
+    if failed {
+        exit(1);
+    }
+}
+
+fn check_project(project: &Project) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    match slot::validate(&project.config.slots) {
+        Ok(()) => findings.push(Finding::pass("Slot defaults are valid")),
+        Err(e) => findings.push(Finding::fail(
+            format!("Slot configuration is invalid: {}", e),
+            "Fix the slot defaults in the project config",
+        )),
+    }
+
+    if project.path.is_dir() {
+        match template::validate(
+            &project.path,
+            &project.config.templates,
+            &project.config.slots,
+            project.config.strict_variables(),
+        ) {
+            Ok(_) => findings.push(Finding::pass("Template files are valid")),
+            Err(_) => findings.push(Finding::fail(
+                "Template files have errors",
+                "Run `spackle check` for details",
+            )),
+        }
+    }
+
+    for hook in &project.config.hooks {
+        let program = hook.command.first().cloned().unwrap_or_default();
+
+        match hook.command_status() {
+            CommandStatus::Found(path) => findings.push(Finding::pass(format!(
+                "Command for hook {} found at {}",
+                hook.key,
+                path.to_string_lossy()
+            ))),
+            CommandStatus::NotFound => findings.push(Finding::fail(
+                format!("Command for hook {} not found: {}", hook.key, program),
+                format!("Install {} or add it to your PATH", program),
+            )),
+            CommandStatus::Templated => findings.push(Finding::warn(
+                format!("Command for hook {} is templated", hook.key),
+                "It can only be resolved at fill time",
+            )),
+            CommandStatus::Relative => findings.push(Finding::warn(
+                format!("Command for hook {} is a relative path", hook.key),
+                "It is resolved against the output directory at fill time",
+            )),
+        }
+    }
+
+    findings
+}
+
+// Remote projects are cached as git checkouts, and saved answers record the revision of
+// projects in a git repository, so git is only needed for those
+fn check_git(project_path: &Path) -> Option<Finding> {
+    let is_remote = cache::projects_dir().is_some_and(|dir| is_within(project_path, &dir));
+    let in_repository = project_path
+        .canonicalize()
+        .ok()?
+        .ancestors()
+        .any(|dir| dir.join(".git").exists());
+    if !is_remote && !in_repository {
+        return None;
+    }
+
+    Some(match hook::find_executable("git") {
+        Some(path) => Finding::pass(format!("git found at {}", path.to_string_lossy())),
+        None if is_remote => Finding::fail(
+            "git not found, which remote projects are fetched with",
+            "Install git or add it to your PATH",
+        ),
+        None => Finding::warn(
+            "git not found, so saved answers won't record the project's revision",
+            "Install git or add it to your PATH",
+        ),
+    })
+}
+
+// Hooks can be run as another user by library consumers, which needs the user to exist
+#[cfg(unix)]
+fn check_run_as(user: &str) -> Finding {
+    match hook::find_user(user) {
+        Some(_) => Finding::pass(format!("User {} exists to run hooks as", user)),
+        None => Finding::fail(
+            format!("User {} does not exist", user),
+            "Create the user or run hooks as an existing one",
+        ),
+    }
+}
+
+#[cfg(not(unix))]
+fn check_run_as(user: &str) -> Finding {
+    Finding::warn(
+        format!("Can't check user {} on this platform", user),
+        "Hooks can only be run as another user on Unix",
+    )
+}
+
+// The output path defaults to being relative to the working directory
+fn check_output_writable() -> Finding {
+    let cwd = match env::current_dir() {
+        Ok(cwd) => cwd,
+        Err(e) => {
+            return Finding::fail(
+                format!("Could not read the working directory: {}", e),
+                "Run spackle from an existing directory",
+            )
+        }
+    };
+
+    let probe = cwd.join(format!(".spackle-doctor-{}", std::process::id()));
+    match fs::write(&probe, "") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            Finding::pass("Working directory is writable")
+        }
+        Err(e) => Finding::fail(
+            format!("Working directory is not writable: {}", e),
+            "Run spackle from a writable directory or pass an absolute --out path",
+        ),
+    }
+}
+
+// Checks that the output directory wasn't filled by a newer major version of spackle
+fn check_manifest(out_dir: &Path) -> Option<Finding> {
+    let filled_by = Manifest::load(out_dir).ok()??.spackle?;
+
+    Some(if filled_by.is_newer_major() {
+        Finding::warn(
+            format!(
+                "Output was filled by spackle {}, a newer major version than this one ({})",
+                filled_by,
+                spackle::version()
+            ),
+            "Upgrade spackle before filling or updating this output again",
+        )
+    } else {
+        Finding::pass(format!("Output was filled by spackle {}", filled_by))
+    })
+}
+
+// Summarizes the most recent run log of the output directory
+fn check_run_log(out_dir: &Path) -> Finding {
+    let Some(path) = run_log::latest(out_dir) else {
+        return Finding::warn(
+            format!("No run log found in {}", out_dir.to_string_lossy()),
+            "Fills write a run log unless the project sets run_log = false",
+        );
+    };
+
+    let events = match run_log::read(&path) {
+        Ok(events) => events,
+        Err(e) => {
+            return Finding::warn(
+                format!("Could not read the last run log: {}", e),
+                path.to_string_lossy(),
+            )
+        }
+    };
+
+    let event = |name: &str| {
+        events
+            .iter()
+            .filter_map(move |event| event["event"].get(name))
+            .collect::<Vec<_>>()
+    };
+
+    let Some(finished) = event("Finished").pop() else {
+        return Finding::warn(
+            "The last run log ends before the run finished",
+            path.to_string_lossy(),
+        );
+    };
+
+    let summary = format!(
+        "{} files, {} hooks run, {} failed",
+        finished["files"], finished["hooks_run"], finished["hooks_failed"]
+    );
+    if finished["ok"].as_bool().unwrap_or(false) {
+        return Finding::pass(format!("Last run succeeded: {}", summary));
+    }
+
+    let failures = event("Failed")
+        .into_iter()
+        .chain(event("RenderFailed"))
+        .filter_map(|failure| failure["message"].as_str())
+        .map(str::to_string)
+        .chain(
+            event("HookFinished")
+                .into_iter()
+                .filter(|hook| hook["code"].is_string())
+                .map(|hook| {
+                    format!(
+                        "hook {} {}",
+                        hook["key"].as_str().unwrap_or_default(),
+                        hook["result"].as_str().unwrap_or_default()
+                    )
+                }),
+        )
+        .collect::<Vec<_>>();
+
+    Finding::fail(
+        format!("Last run failed: {}", summary),
+        format!(
+            "{}Attach {} to bug reports",
+            failures
+                .iter()
+                .map(|failure| format!("{}\n       ", failure.replace('\n', ": ")))
+                .collect::<String>(),
+            path.to_string_lossy()
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // A fresh directory under the system's temporary directory, unique to the test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("spackle-doctor-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn project_findings() {
+        let project = load_project(&PathBuf::from("../tests/data/invalid_hook_cmd")).unwrap();
+        let findings = check_project(&project);
+
+        assert!(matches!(findings[0].status, Status::Pass));
+        assert!(findings.iter().any(|finding| {
+            matches!(finding.status, Status::Fail) && finding.message.contains("not found")
+        }));
+    }
+
+    #[test]
+    fn git() {
+        let dir = temp_dir("git");
+        assert!(check_git(&dir).is_none());
+
+        fs::create_dir(dir.join(".git")).unwrap();
+        fs::create_dir(dir.join("project")).unwrap();
+        let finding = check_git(&dir.join("project")).unwrap();
+        // Without git, a local project only warns
+        assert!(matches!(finding.status, Status::Pass | Status::Warn));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_as() {
+        assert!(matches!(check_run_as("root").status, Status::Pass));
+        assert!(matches!(
+            check_run_as("spackle-no-such-user").status,
+            Status::Fail
+        ));
+    }
+
+    #[test]
+    fn missing_run_log() {
+        let dir = temp_dir("run-log");
+        assert!(matches!(check_run_log(&dir).status, Status::Warn));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
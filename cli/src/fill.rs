@@ -1,128 +1,442 @@
-use crate::{check, util::file_path_completer::FilePathCompleter, Cli};
+use crate::{
+    check,
+    data_flags::{self, DataFlag, Namespace},
+    util::{
+        expand_path::expand_path,
+        file_path_completer::FilePathCompleter,
+        history::{History, HistoryCompleter},
+        present,
+    },
+    Cli, FillArgs,
+};
 use anyhow::{Context, Result};
 use colored::Colorize;
 use fronma::parser::parse_with_engine;
-use inquire::{validator::Validation, Confirm, CustomType, Text};
-use rocket::{futures::StreamExt, tokio};
+use inquire::{validator::Validation, Confirm, CustomType, Editor, MultiSelect, Select, Text};
+use rocket::{futures::StreamExt, serde::json::serde_json, tokio};
 use spackle::{
-    config::{self},
-    hook::{self, Hook, HookError, HookResult, HookResultKind, HookStreamResult},
-    slot::{self, Slot, SlotType},
-    Project,
+    answers::{Answers, ProjectSource},
+    config::{self, ProjectLayout},
+    hook::{self, HookPlan, OutputStream},
+    manifest::Manifest,
+    paths::{is_removable, is_within},
+    plan::SkipReport,
+    prelude::{
+        load_render_vars, new_run_id, GenerateError, GenerateOptions, Hook, HookResult,
+        HookResultKind, HookStreamResult, Project, Slot, SlotType, Timings,
+    },
+    run_log::{Phase, RunEvent, RunLog, REDACTED},
+    shell::shell_quote,
+    slot,
+    template::ENV_KEY,
+    user_config::UserConfig,
+};
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    process::exit,
+    time::{Duration, Instant},
 };
-use std::{collections::HashMap, fs, path::PathBuf, process::exit, time::Instant};
 use tera::Tera;
 use tokio::pin;
 
-fn parse_flag_data(flag_data: &Vec<String>) -> HashMap<String, String> {
-    flag_data
-        .iter()
-        .filter_map(|e| match e.split_once('=') {
-            Some((key, value)) => Some((key.to_string(), value.to_string())),
-            None => {
-                eprintln!(
-                    "❌ {}\n",
-                    "Invalid data argument, must be in the form of key=value. Skipping."
-                        .bright_red()
-                );
-                None
-            }
-        })
-        .collect()
+// Whether an editor has been configured for editor prompts to open
+fn has_editor() -> bool {
+    env::var_os("VISUAL")
+        .or_else(|| env::var_os("EDITOR"))
+        .is_some()
 }
 
-fn collect_data(
-    flag_data: &Vec<String>,
-    slots: &Vec<Slot>,
-    hooks: &Vec<Hook>,
-) -> Result<HashMap<String, String>> {
-    let mut collected: HashMap<String, String> = HashMap::new();
+// Composes the help shown under a prompt from an item's description, default and docs link,
+// so every prompt type presents them the same way
+fn help_message(
+    description: Option<&str>,
+    default: Option<&str>,
+    docs_url: Option<&str>,
+) -> Option<String> {
+    let parts = [
+        description.map(|description| description.to_string()),
+        default.map(|default| format!("default: {}", default)),
+        docs_url.map(|url| format!("more: {}", url)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+
+    (!parts.is_empty()).then(|| parts.join(" · "))
+}
 
-    for (key, value) in parse_flag_data(flag_data) {
-        collected.insert(key, value);
-    }
+// Prompts for a slot's value using an input suited to its type. Text inputs suggest the values
+// previously entered for the slot.
+fn prompt_slot(slot: &Slot, history: &History) -> Result<String> {
+    let help = help_message(
+        slot.description.as_deref(),
+        slot.default.as_deref(),
+        slot.docs_url.as_deref(),
+    );
 
-    // at this point we've collected all the flags, so we should identify
-    // if any additional slots are needed and if we're in a tty context prompt
-    // for more slot info before validating
-    if atty::is(atty::Stream::Stdout) {
-        println!("📮 Collecting data\n");
+    match &slot.r#type {
+        SlotType::String if slot.editor && has_editor() => {
+            let slot_name = slot.get_name();
+            let mut input = Editor::new(&slot_name);
 
-        let missing_slots: Vec<&Slot> = slots
-            .iter()
-            .filter(|slot| !collected.contains_key(&slot.key))
-            .collect();
+            if let Some(help) = &help {
+                input = input.with_help_message(help);
+            }
 
-        for slot in missing_slots {
-            match &slot.r#type {
-                SlotType::String => {
-                    let slot_name = slot.get_name();
-                    let mut input = Text::new(&slot_name);
+            if let Some(default) = &slot.default {
+                input = input.with_predefined_text(default);
+            }
 
-                    if let Some(description) = &slot.description {
-                        input = input.with_help_message(description);
-                    }
+            let value = input
+                .prompt()
+                .with_context(|| format!("Error getting input for slot: {}", slot.key))?;
+
+            // Editors usually add a trailing newline that isn't part of the value
+            Ok(value.trim_end().to_string())
+        }
+        SlotType::String | SlotType::Path => {
+            let slot_name = slot.get_name();
+            let mut input = Text::new(&slot_name);
+
+            if let Some(help) = &help {
+                input = input.with_help_message(help);
+            }
+
+            if let Some(default) = &slot.default {
+                // We can unwrap here because we've done prior validation
+                input = input.with_default(default);
+            }
 
+            if let Some(example) = &slot.example {
+                input = input.with_placeholder(example);
+            }
+
+            let previous = history.values(&slot.key);
+            if !slot.sensitive && !previous.is_empty() {
+                input = input.with_autocomplete(HistoryCompleter::new(previous));
+            }
+
+            // Any text is a valid string or path, so there's nothing to validate as it's typed
+            let value = input
+                .prompt()
+                .with_context(|| format!("Error getting input for slot: {}", slot.key))?;
+
+            Ok(value.to_string())
+        }
+        SlotType::Boolean => {
+            let slot_name = slot.get_name();
+            let mut input = Confirm::new(&slot_name);
+
+            if let Some(help) = &help {
+                input = input.with_help_message(help);
+            }
+
+            if let Some(default) = &slot.default {
+                // We can unwrap here because we've done prior validation
+                input = input.with_default(default.parse::<bool>().unwrap());
+            }
+
+            let value = input
+                .prompt()
+                .with_context(|| format!("Error getting input for slot: {}", slot.key))?;
+
+            Ok(value.to_string())
+        }
+        SlotType::Choice => {
+            let slot_name = slot.get_name();
+            let mut input = Select::new(&slot_name, slot.choices.clone());
+
+            if let Some(help) = &help {
+                input = input.with_help_message(help);
+            }
+
+            if let Some(index) = slot
+                .default
+                .as_ref()
+                .and_then(|default| slot.choices.iter().position(|choice| choice == default))
+            {
+                input = input.with_starting_cursor(index);
+            }
+
+            let value = input
+                .prompt()
+                .with_context(|| format!("Error getting input for slot: {}", slot.key))?;
+
+            Ok(value)
+        }
+        SlotType::List if !slot.choices.is_empty() => {
+            let slot_name = slot.get_name();
+            let defaults = slot
+                .default
+                .as_deref()
+                .map(Slot::list_values)
+                .unwrap_or_default();
+            let selected = slot
+                .choices
+                .iter()
+                .enumerate()
+                .filter(|(_, choice)| defaults.contains(&choice.as_str()))
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+
+            let mut input =
+                MultiSelect::new(&slot_name, slot.choices.clone()).with_default(&selected);
+
+            if let Some(help) = &help {
+                input = input.with_help_message(help);
+            }
+
+            let value = input
+                .prompt()
+                .with_context(|| format!("Error getting input for slot: {}", slot.key))?;
+
+            Ok(value.join(","))
+        }
+        SlotType::List => {
+            let slot_name = slot.get_name();
+            let mut values = Vec::new();
+
+            if let Some(help) = &help {
+                println!("{}", help.dimmed());
+            }
+
+            // Collect one element at a time until an empty entry
+            loop {
+                let prompt = format!("{} #{}", slot_name, values.len() + 1);
+                let mut input =
+                    Text::new(&prompt).with_help_message("Leave empty to finish the list");
+
+                if values.is_empty() {
                     if let Some(default) = &slot.default {
-                        // We can unwrap here because we've done prior validation
                         input = input.with_default(default);
                     }
+                }
 
-                    let value = input
-                        .prompt()
-                        .with_context(|| format!("Error getting input for slot: {}", slot.key))?;
+                if let Some(example) = &slot.example {
+                    input = input.with_placeholder(example);
+                }
+
+                let value = input
+                    .prompt()
+                    .with_context(|| format!("Error getting input for slot: {}", slot.key))?;
 
-                    collected.insert(slot.key.clone(), value.to_string());
+                if value.is_empty() {
+                    break;
                 }
-                SlotType::Boolean => {
-                    let slot_name = slot.get_name();
-                    let mut input = Confirm::new(&slot_name);
 
-                    if let Some(description) = &slot.description {
-                        input = input.with_help_message(description);
-                    }
+                values.push(value);
+            }
 
-                    if let Some(default) = &slot.default {
-                        // We can unwrap here because we've done prior validation
-                        input = input.with_default(default.parse::<bool>().unwrap());
-                    }
+            Ok(values.join(","))
+        }
+        SlotType::Number => {
+            let slot_name = slot.get_name();
+            let mut input =
+                CustomType::<f64>::new(&slot_name).with_error_message("Please type a valid number");
 
-                    let value = input
-                        .prompt()
-                        .with_context(|| format!("Error getting input for slot: {}", slot.key))?;
+            if let Some(help) = &help {
+                input = input.with_help_message(help);
+            }
 
-                    collected.insert(slot.key.clone(), value.to_string());
-                }
-                SlotType::Number => {
-                    let slot_name = slot.get_name();
-                    let mut input = CustomType::<f64>::new(&slot_name)
-                        .with_error_message("Please type a valid number");
+            if let Some(default) = &slot.default {
+                // We can unwrap here because we've done prior validation
+                input = input.with_default(default.parse::<f64>().unwrap());
+            }
 
-                    if let Some(description) = &slot.description {
-                        input = input.with_help_message(description);
-                    }
+            if let Some(example) = &slot.example {
+                input = input.with_placeholder(example);
+            }
 
-                    if let Some(default) = &slot.default {
-                        // We can unwrap here because we've done prior validation
-                        input = input.with_default(default.parse::<f64>().unwrap());
-                    }
+            let value = input
+                .prompt()
+                .with_context(|| format!("Error getting input for slot: {}", slot.key))?;
 
-                    let value = input
-                        .prompt()
-                        .with_context(|| format!("Error getting input for slot: {}", slot.key))?;
+            Ok(value.to_string())
+        }
+    }
+}
 
-                    collected.insert(slot.key.clone(), value.to_string());
-                }
+/// Builds the `--set` arguments that reproduce the given data without prompting,
+/// split into values that differ from their default and values that match it.
+/// Sensitive values are replaced with [REDACTED], which is never taken for a default.
+fn equivalent_sets(
+    data: &HashMap<String, String>,
+    slots: &[Slot],
+    hooks: &[Hook],
+) -> (Vec<String>, Vec<String>) {
+    let slot_sets = slots.iter().filter_map(|slot| {
+        data.get(&slot.key).map(|value| {
+            if slot.sensitive {
+                (format!("slot.{}={}", slot.key, REDACTED), false)
+            } else {
+                (
+                    format!("slot.{}={}", slot.key, value),
+                    slot.default.as_ref() == Some(value),
+                )
             }
+        })
+    });
+    let hook_sets = hooks.iter().filter_map(|hook| {
+        data.get(&hook.key).map(|value| {
+            (
+                format!("hook.{}={}", hook.key, value),
+                hook.default.unwrap_or(true).to_string() == *value,
+            )
+        })
+    });
+
+    let (defaults, set): (Vec<_>, Vec<_>) = slot_sets
+        .chain(hook_sets)
+        .partition(|(_, is_default)| *is_default);
+
+    let to_args = |sets: Vec<(String, bool)>| {
+        sets.into_iter()
+            .map(|(set, _)| format!("--set {}", shell_quote(&set)))
+            .collect()
+    };
+
+    (to_args(set), to_args(defaults))
+}
+
+/// Prints the command that repeats this fill non-interactively, e.g. for use in CI
+fn print_equivalent_command(
+    data: &HashMap<String, String>,
+    out_path: &Path,
+    args: &FillArgs,
+    cli: &Cli,
+    project: &Project,
+) {
+    let (set, defaults) = equivalent_sets(data, &project.config.slots, &project.config.hooks);
+
+    let mut lines = vec![format!(
+        "spackle fill -p {} -o {}",
+        shell_quote(&cli.project_path.to_string_lossy()),
+        shell_quote(&out_path.to_string_lossy())
+    )];
+    if let Some(path) = &args.render_vars_file {
+        lines.push(format!(
+            "--render-vars-file {}",
+            shell_quote(&path.to_string_lossy())
+        ));
+    }
+    if args.data_stdin_env {
+        lines.push("--data-stdin-env".to_string());
+    }
+    lines.extend(set);
+
+    let default_count = defaults.len();
+    let lines = lines
+        .into_iter()
+        .map(|line| line.normal())
+        .chain(defaults.into_iter().map(|line| line.dimmed()))
+        .collect::<Vec<_>>();
+
+    println!("🔁 To repeat this fill without prompts, run\n");
+    for (i, line) in lines.iter().enumerate() {
+        let indent = if i == 0 { "" } else { "  " };
+        let continuation = if i + 1 < lines.len() { " \\" } else { "" };
+        println!("{}{}{}", indent, line, continuation);
+    }
+
+    if default_count > 0 {
+        println!(
+            "\n{}",
+            "Dimmed values match their default, remove them to keep following the default".dimmed()
+        );
+    }
+
+    if project
+        .config
+        .slots
+        .iter()
+        .any(|slot| slot.sensitive && data.contains_key(&slot.key))
+    {
+        println!(
+            "\n{}",
+            format!(
+                "Sensitive values are shown as {}, fill them in first",
+                REDACTED
+            )
+            .dimmed()
+        );
+    }
+
+    println!();
+}
+
+fn collect_data(
+    flag_data: HashMap<String, String>,
+    slots: &Vec<Slot>,
+    hooks: &Vec<Hook>,
+    exclusive_groups: &[Vec<String>],
+    non_interactive: bool,
+    history: &History,
+) -> Result<HashMap<String, String>> {
+    let mut collected = flag_data;
+    let prompt_slots = atty::is(atty::Stream::Stdout) && !non_interactive;
+
+    // at this point we've collected all the flags, so we should identify
+    // if any additional slots are needed and if we're in a tty context prompt
+    // for more slot info before validating
+    if prompt_slots {
+        println!("📮 Collecting data\n");
+
+        // Slots tied to hooks are collected after the hooks are toggled
+        let missing_slots: Vec<&Slot> = slots
+            .iter()
+            .filter(|slot| slot.needed_by.is_empty() && !collected.contains_key(&slot.key))
+            .collect();
+
+        for slot in missing_slots {
+            // Skip slots whose exclusive group already has a value
+            let answered_in_group = exclusive_groups
+                .iter()
+                .filter(|group| group.contains(&slot.key))
+                .flatten()
+                .any(|key| collected.get(key).is_some_and(|value| !value.is_empty()));
+
+            if answered_in_group {
+                collected.insert(slot.key.clone(), "".to_string());
+                continue;
+            }
+
+            // Conditions that can't be evaluated yet are reported during validation
+            if !slot.is_required(&collected).unwrap_or(true) {
+                collected.insert(slot.key.clone(), "".to_string());
+                continue;
+            }
+
+            let value = prompt_slot(&slot.with_rendered_prompts(&collected), history)?;
+            collected.insert(slot.key.clone(), value);
         }
     }
 
     for hook in hooks {
+        // Hooks toggled by flags or the hook file aren't asked about again
+        if collected.contains_key(&hook.key) {
+            continue;
+        }
+
+        if non_interactive {
+            collected.insert(hook.key.clone(), hook.default.unwrap_or(true).to_string());
+            continue;
+        }
+
         let prompt = format!("Run {}?", hook.name.clone().unwrap_or(hook.key.clone()));
         let mut input = Confirm::new(prompt.as_str());
 
-        if let Some(description) = &hook.description {
-            input = input.with_help_message(description);
+        let help = help_message(
+            hook.description.as_deref(),
+            hook.default
+                .map(|default| if default { "on" } else { "off" }),
+            hook.docs_url.as_deref(),
+        );
+        if let Some(help) = &help {
+            input = input.with_help_message(help);
         }
 
         if let Some(default) = hook.default {
@@ -137,25 +451,150 @@ fn collect_data(
         collected.insert(hook.key.clone(), value.to_string());
     }
 
+    if prompt_slots {
+        let hook_slots: Vec<&Slot> = slots
+            .iter()
+            .filter(|slot| {
+                !slot.needed_by.is_empty()
+                    && !collected.contains_key(&slot.key)
+                    && slot.is_needed(hooks, &collected)
+            })
+            .collect();
+
+        if !hook_slots.is_empty() {
+            println!();
+        }
+
+        for slot in hook_slots {
+            if !slot.is_required(&collected).unwrap_or(true) {
+                collected.insert(slot.key.clone(), "".to_string());
+                continue;
+            }
+
+            let value = prompt_slot(&slot.with_rendered_prompts(&collected), history)?;
+            collected.insert(slot.key.clone(), value);
+        }
+    }
+
     println!();
 
     Ok(collected)
 }
 
 pub fn run(
-    flag_data: &Vec<String>,
-    overwrite: &bool,
-    out_path: &Option<PathBuf>,
+    args: &FillArgs,
     project: &Project,
     cli: &Cli,
+    answers: Option<&Answers>,
+    user_config: &UserConfig,
 ) {
+    let non_interactive = args.non_interactive || user_config.non_interactive();
+
     // First, run spackle check
-    check::run(project);
+    check::run(
+        project,
+        true,
+        args.strict_vars
+            .unwrap_or(project.config.strict_variables()),
+    );
 
     println!("");
 
-    let collected_data = match collect_data(flag_data, &project.config.slots, &project.config.hooks)
-    {
+    let hook_file_flags = match &args.hook_file {
+        Some(path) => match data_flags::load_hook_file(path) {
+            Ok(flags) => flags,
+            Err(e) => {
+                eprintln!("{}\n{}", "❌ Error reading hook file".bright_red(), e.red());
+
+                exit(1);
+            }
+        },
+        None => Vec::new(),
+    };
+
+    if let Some(answers) = answers {
+        let rev = ProjectSource::of(&project.path).rev;
+        if answers.project.rev.is_some() && answers.project.rev != rev {
+            eprintln!(
+                "{}\n",
+                format!(
+                    "⚠️ The answers were saved at project revision {}, the project is now at {}",
+                    answers.project.rev.as_deref().unwrap_or_default(),
+                    rev.as_deref().unwrap_or("an unknown revision")
+                )
+                .yellow()
+            );
+        }
+    }
+
+    // Later flags take precedence, so the hook file, --data and --set override the answers file
+    let answer_flags = answers
+        .map(|answers| {
+            let slots = answers.slots.iter().map(|(key, value)| DataFlag {
+                namespace: Some(Namespace::Slot),
+                key: key.clone(),
+                value: value.clone(),
+            });
+            let hooks = answers.hooks.iter().map(|(key, enabled)| DataFlag {
+                namespace: Some(Namespace::Hook),
+                key: key.clone(),
+                value: enabled.to_string(),
+            });
+
+            slots.chain(hooks).collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let flags = answer_flags
+        .into_iter()
+        .chain(hook_file_flags)
+        .chain(data_flags::parse_data(&args.data))
+        .chain(args.set.iter().cloned())
+        .collect::<Vec<_>>();
+
+    // Warn about data that doesn't align with a slot or hook before prompting for the rest
+    let unrecognized =
+        data_flags::find_unrecognized(&flags, &project.config.slots, &project.config.hooks);
+
+    if !unrecognized.is_empty() {
+        eprintln!(
+            "{}\n{}\n{}\n",
+            "⚠️ Unrecognized data provided".bright_yellow(),
+            "Please ensure all data passed via the --data (-d), --set and --hook-file flags corresponds to a slot or hook. Unrecognized:".yellow(),
+            unrecognized
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<String>>()
+                .join("\n")
+                .yellow()
+                .dimmed(),
+        );
+    }
+
+    // Namespaced flags that target the wrong kind of item are dropped rather than applied to the other one
+    let flag_data = flags
+        .into_iter()
+        .filter(|flag| flag.namespace.is_none() || !unrecognized.iter().any(|u| u.flag == *flag))
+        .map(|flag| (flag.key, flag.value))
+        .collect();
+
+    // Defaults from commands are resolved up front so prompts can offer them
+    let (slots, default_errors) = project.resolve_defaults(&GenerateOptions::default());
+    for e in default_errors {
+        eprintln!(
+            "{}\n",
+            format!("⚠️ {}, using the static default", e).yellow()
+        );
+    }
+
+    let mut history = History::load();
+    let collected_data = match collect_data(
+        flag_data,
+        &slots,
+        &project.config.hooks,
+        &project.config.exclusive_groups,
+        non_interactive,
+        &history,
+    ) {
         Ok(slot_data) => slot_data,
         Err(e) => {
             eprintln!("❌ {}", format!("{:?}", e).red());
@@ -169,7 +608,25 @@ pub fn run(
         .map(|(k, v)| (k.clone(), v.clone()))
         .collect();
 
-    if let Err(e) = slot::validate_data(&slot_data, &project.config.slots) {
+    // Slots only needed by disabled hooks aren't required
+    let needed_slots: Vec<Slot> = project
+        .config
+        .slots
+        .iter()
+        .filter(|slot| slot.is_needed(&project.config.hooks, &collected_data))
+        .cloned()
+        .collect();
+    let needed_slot_data: HashMap<String, String> = slot_data
+        .iter()
+        .filter(|(key, _)| needed_slots.iter().any(|slot| slot.key == **key))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+
+    if let Err(e) = slot::validate_data(
+        &needed_slot_data,
+        &needed_slots,
+        &project.config.exclusive_groups,
+    ) {
         eprintln!(
             "{}\n{}",
             "❌ Error with supplied slot data".bright_red(),
@@ -191,6 +648,29 @@ pub fn run(
         exit(1);
     }
 
+    // Remember the values for the next fill's prompts. History is a convenience, so failing to
+    // save it doesn't stop the fill
+    history.record(&project.config.slots, &slot_data);
+    if let Err(e) = history.save() {
+        if cli.verbose {
+            eprintln!(
+                "{}\n",
+                format!("⚠️ Could not save slot history: {}", e).yellow()
+            );
+        }
+    }
+
+    // Slots left out because their required_if condition is false, or because the hooks that
+    // need them are disabled, are recorded as intentionally empty
+    let mut collected_data = collected_data;
+    let mut slot_data = slot_data;
+    for slot in &project.config.slots {
+        if !slot_data.contains_key(&slot.key) {
+            collected_data.insert(slot.key.clone(), "".to_string());
+            slot_data.insert(slot.key.clone(), "".to_string());
+        }
+    }
+
     let hook_data: HashMap<String, String> = collected_data
         .iter()
         .filter(|(key, _)| project.config.hooks.iter().any(|hook| hook.key == **key))
@@ -206,32 +686,115 @@ pub fn run(
         exit(1);
     }
 
-    // Check if any data entries don't align with slots or hooks
-    let unknown_data: Vec<&String> = collected_data
-        .iter()
-        .filter(|(key, _)| !slot_data.contains_key(*key) && !hook_data.contains_key(*key))
-        .map(|(key, _)| key)
-        .collect();
+    // Hook answers are stored as true/false no matter how they were spelled
+    let supplied_data = collected_data;
+    let collected_data = match hook::normalize_toggles(&supplied_data, &project.config.hooks) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!(
+                "{}\n{}",
+                "❌ Error with supplied hook data".bright_red(),
+                e.to_string().red()
+            );
+
+            exit(1);
+        }
+    };
+
+    if cli.verbose {
+        for coercion in spackle::coercions(&supplied_data, &collected_data) {
+            println!(
+                "{}",
+                format!("ℹ Normalized {}", coercion).truecolor(128, 128, 128)
+            );
+        }
+    }
+
+    if let Some(path) = &args.save_answers {
+        match Answers::new(project, &collected_data).and_then(|answers| answers.write(path)) {
+            Ok(()) => println!(
+                "{}\n",
+                format!("💾 Saved answers to {}", path.display()).dimmed()
+            ),
+            Err(e) => {
+                eprintln!("{}\n", format!("⚠️ {}", e).yellow());
+            }
+        }
+    }
+
+    // Render vars are added after validation since they aren't tied to slots or hooks
+    let (collected_data, slot_data) = match &args.render_vars_file {
+        Some(path) => {
+            let render_vars = match load_render_vars(path)
+                .and_then(|vars| project.check_render_vars(&vars).map(|_| vars))
+            {
+                Ok(vars) => vars,
+                Err(e) => {
+                    eprintln!(
+                        "{}\n{}",
+                        "❌ Error with supplied render vars".bright_red(),
+                        e.to_string().red()
+                    );
 
-    if !unknown_data.is_empty() {
+                    exit(1);
+                }
+            };
+
+            let mut collected_data = collected_data;
+            let mut slot_data = slot_data;
+            collected_data.extend(render_vars.clone());
+            slot_data.extend(render_vars);
+
+            (collected_data, slot_data)
+        }
+        None => (collected_data, slot_data),
+    };
+
+    // The environment is a single nested variable, so it can't share its key with a slot or hook either
+    let env = args.data_stdin_env.then(|| {
+        if let Err(e) = project.check_env() {
+            eprintln!(
+                "{}\n{}",
+                "❌ Error with --data-stdin-env".bright_red(),
+                e.to_string().red()
+            );
+
+            exit(1);
+        }
+
+        env::vars().collect::<HashMap<_, _>>()
+    });
+
+    for key in spackle::overridden_keys(&collected_data) {
         eprintln!(
-            "{}\n{}\n{}\n",
-            "⚠️ Unrecognized data provided".bright_yellow(),
-            "Please ensure all data passed via the --data (-d) flag corresponds to a slot or hook. Unrecognized:".yellow(),
-            unknown_data
-                .iter()
-                .map(|e| e.to_string())
-                .collect::<Vec<String>>()
-                .join(", ")
-                .yellow()
-                .dimmed(),
+            "{}\n",
+            format!("⚠️ {} is set by spackle, ignoring the supplied value", key).yellow()
         );
     }
 
-    let out_path = match &out_path {
-        Some(path) => path,
+    let default_out_path = user_config
+        .out_dir
+        .as_ref()
+        .map(|dir| expand_path(dir).join(project.render_name(&collected_data)));
+    let out_path = match (&args.out_path, &default_out_path) {
+        (Some(path), _) => path,
+        (None, Some(path)) => {
+            println!(
+                "{}\n",
+                format!("📮 Writing to {} from the user config", path.display()).dimmed()
+            );
+            path
+        }
+        (None, None) if non_interactive => {
+            eprintln!(
+                "{}\n{}",
+                "❌ No output path given".bright_red(),
+                "Pass one with --out (-o), or set out_dir in the user config".red()
+            );
+            exit(1);
+        }
         // Cannot use CustomType here because PathBuf does not implement ToString
-        None => {
+        (None, None) => {
             println!("📮 Collecting output path\n");
 
             let path = &Text::new("Enter the output path")
@@ -249,7 +812,7 @@ pub fn run(
             println!();
 
             match path {
-                Ok(p) => &PathBuf::from(p),
+                Ok(p) => &expand_path(&PathBuf::from(p)),
                 Err(e) => {
                     eprintln!("❌ {}", e.to_string().red());
                     exit(1);
@@ -258,13 +821,29 @@ pub fn run(
         }
     };
 
-    // Ensure the output path doesn't exist
-    if *overwrite {
+    // Writing into the project would copy the output into itself on the next run, or even this one
+    let is_dir = project.layout() == ProjectLayout::Directory;
+    if is_dir && is_within(out_path, &cli.project_path) {
+        let message = format!(
+            "Output path {} is inside the project directory",
+            out_path.to_string_lossy()
+        );
+
+        if args.strict {
+            eprintln!("❌ {}", message.bright_red());
+            exit(2);
+        }
+
+        eprintln!("{}\n", format!("⚠️ {}", message).yellow());
+    }
+
+    // Ensure the output path doesn't exist. Hooks-only projects run in existing directories
+    if args.overwrite {
         println!(
             "{}\n",
             format!("⚠️ Overwriting existing output path").yellow()
         );
-    } else if out_path.exists() {
+    } else if out_path.exists() && !project.is_hooks_only() {
         eprintln!(
             "{}\n{}",
             "❌ Path already exists".bright_red(),
@@ -274,6 +853,26 @@ pub fn run(
         exit(2);
     }
 
+    // Output from a newer spackle may record things this one doesn't understand
+    if let Ok(Some(Manifest {
+        spackle: Some(filled_by),
+        ..
+    })) = Manifest::load(out_path)
+    {
+        if filled_by.is_newer_major() {
+            eprintln!(
+                "{}\n",
+                format!(
+                    "⚠️ {} was filled by spackle {}, a newer major version than this one ({})",
+                    out_path.to_string_lossy(),
+                    filled_by,
+                    spackle::version()
+                )
+                .yellow()
+            );
+        }
+    }
+
     // Create all parent directories
     if let Some(parent) = out_path.parent() {
         if let Err(e) = fs::create_dir_all(parent) {
@@ -282,43 +881,127 @@ pub fn run(
         }
     }
 
-    if cli.project_path.is_dir() {
-        run_multi(&collected_data, out_path, cli, project);
+    if atty::is(atty::Stream::Stdout) {
+        print_equivalent_command(&collected_data, out_path, args, cli, project);
+    }
+
+    if is_dir {
+        // Share one run id across the copy, render and hook steps
+        let mut collected_data = collected_data;
+        let run_id = new_run_id();
+        collected_data.insert("_run_id".to_string(), run_id.clone());
+
+        // Providing our own tmp dir stops spackle from removing it after the hooks run
+        if args.keep_tmp {
+            let tmp_dir = env::temp_dir().join(format!("spackle-{}", run_id));
+            if let Err(e) = fs::create_dir_all(&tmp_dir) {
+                eprintln!("❌ {}", e.to_string().red());
+                exit(1);
+            }
+
+            println!(
+                "{}\n",
+                format!("🗂  Keeping temporary directory at {}", tmp_dir.display()).dimmed()
+            );
+
+            collected_data.insert(
+                "_tmp_dir".to_string(),
+                tmp_dir.to_string_lossy().to_string(),
+            );
+        }
+
+        // Rendering can't take longer than the whole fill is allowed to
+        let render_timeout = match (args.render_timeout, args.timeout) {
+            (Some(render_timeout), Some(timeout)) => Some(render_timeout.min(timeout)),
+            (render_timeout, timeout) => render_timeout.or(timeout),
+        };
+
+        let options = GenerateOptions {
+            max_total_bytes: args.max_render_bytes,
+            max_file_bytes: args.max_file_bytes,
+            render_timeout: render_timeout.map(Duration::from_secs),
+            timeout: args.timeout.map(Duration::from_secs),
+            strict_variables: args.strict_vars,
+            env,
+            hook_output_limit: args.hook_output_limit,
+            verify: args.verify,
+            ..Default::default()
+        };
+
+        run_multi(&collected_data, out_path, &options, args, cli, project);
     } else {
-        run_single(&slot_data, out_path, cli);
+        run_single(&slot_data, env.as_ref(), out_path, cli);
     }
 }
 
-pub fn run_multi(data: &HashMap<String, String>, out_dir: &PathBuf, cli: &Cli, project: &Project) {
+// Copies and renders the project into the output directory
+fn write_output(
+    data: &HashMap<String, String>,
+    out_dir: &PathBuf,
+    options: &GenerateOptions,
+    summary_only: bool,
+    cli: &Cli,
+    project: &Project,
+    outcome: &mut Outcome,
+) {
     let start_time = Instant::now();
 
-    println!("🖨️  Writing output {}\n", out_dir.to_string_lossy().bold());
+    if !summary_only {
+        println!("🖨️  Writing output {}\n", out_dir.to_string_lossy().bold());
+    }
 
-    match project.copy_files(out_dir, &data) {
-        Ok(r) => {
-            println!(
-                "  Copied {} {} {}",
-                r.copied_count,
-                if r.copied_count == 1 { "file" } else { "files" },
-                format!("in {:?}", start_time.elapsed()).dimmed()
-            );
+    // A missing import is caught before anything is written
+    if let Err(e) = project.check_imports(out_dir, data) {
+        eprintln!("❌ {}", e.to_string().red());
+        outcome.fail(Some(e.code()), e.to_string());
+        write_run_log(outcome, out_dir);
+        exit(1);
+    }
+
+    outcome.record(RunEvent::PhaseStarted(Phase::Copy));
+    match project.copy_files(out_dir, &data, options) {
+        Ok(r) if !r.mismatches.is_empty() => {
+            let _ = fs::remove_dir_all(out_dir);
+
+            let mismatches = r
+                .mismatches
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            let e = GenerateError::VerifyError(r.mismatches);
+            eprintln!("❌ {}", e.to_string().bright_red());
+            for mismatch in mismatches {
+                eprintln!("  {}", mismatch.red());
+            }
 
-            if r.skipped_count > 0 {
+            outcome.fail(Some(e.code()), e.to_string());
+            write_run_log(outcome, out_dir);
+            exit(1);
+        }
+        Ok(r) => {
+            outcome.files += r.copied_count;
+            outcome.skips.add_ignored(&r.skipped);
+            outcome.record(RunEvent::Copied {
+                count: r.copied_count,
+                ignored: r.skipped.clone(),
+            });
+
+            if !summary_only {
                 println!(
-                    "{}",
-                    format!(
-                        "{} {} {}",
-                        "    Ignored",
-                        r.skipped_count,
-                        if r.skipped_count == 1 {
-                            "entry"
-                        } else {
-                            "entries"
-                        }
-                    )
-                    .to_string()
-                    .dimmed()
+                    "  Copied {} {}",
+                    present::count(r.copied_count, "file", "files"),
+                    format!("in {:?}", start_time.elapsed()).dimmed()
                 );
+
+                if r.skipped_count > 0 {
+                    println!(
+                        "{}",
+                        present::detail(format!(
+                            "  Ignored {}",
+                            present::count(r.skipped_count, "entry", "entries")
+                        ))
+                    );
+                }
             }
         }
         Err(e) => {
@@ -331,26 +1014,48 @@ pub fn run_multi(data: &HashMap<String, String>, out_dir: &PathBuf, cli: &Cli, p
                 e.to_string().red(),
             );
 
+            // The first entry of the chain is the message printed above
+            let chain = e.chain();
+            if cli.verbose {
+                for cause in chain.iter().skip(1) {
+                    eprintln!("  {} {}", "caused by:".dimmed(), cause.red());
+                }
+            }
+
+            outcome.fail(Some(e.code()), chain.join(": "));
+            write_run_log(outcome, out_dir);
             exit(1);
         }
     }
 
+    outcome.timings.copy = start_time.elapsed();
+
     let start_time = Instant::now();
 
-    match project.render_templates(&PathBuf::from(out_dir), &data) {
+    outcome.record(RunEvent::PhaseStarted(Phase::Render));
+    match project.render_templates(&PathBuf::from(out_dir), &data, options) {
         Ok(r) => {
-            println!(
-                "\n  Rendered {} {} {} {}\n",
-                r.len(),
-                if r.len() == 1 { "file" } else { "files" },
-                "in".dimmed(),
-                format!("{:?}", start_time.elapsed()).dimmed()
-            );
+            outcome.files += r.iter().filter(|result| result.is_ok()).count();
+            outcome.failed |= r.iter().any(|result| result.is_err());
+
+            // Validation already reported a project without templates
+            if !summary_only && !r.is_empty() {
+                println!(
+                    "\n  Rendered {} {} {}\n",
+                    present::count(r.len(), "file", "files"),
+                    "in".dimmed(),
+                    format!("{:?}", start_time.elapsed()).dimmed()
+                );
+            } else if !summary_only {
+                println!();
+            }
 
             for result in r {
                 match result {
                     Ok(f) => {
-                        if cli.verbose {
+                        outcome.record(RunEvent::Rendered(f.path.clone()));
+
+                        if cli.verbose && !summary_only {
                             println!(
                                 "📄 Processed {} {} {}\n",
                                 f.path.to_string_lossy().bold(),
@@ -369,6 +1074,12 @@ pub fn run_multi(data: &HashMap<String, String>, out_dir: &PathBuf, cli: &Cli, p
                         }
                     }
                     Err(e) => {
+                        outcome.record(RunEvent::RenderFailed {
+                            file: e.file.clone(),
+                            code: e.code().to_string(),
+                            message: e.kind.to_string(),
+                        });
+
                         eprintln!(
                             "{} {}\n{}\n",
                             "⚠️ Could not process file".bright_yellow(),
@@ -387,21 +1098,280 @@ pub fn run_multi(data: &HashMap<String, String>, out_dir: &PathBuf, cli: &Cli, p
                 "Could not fill project".bright_red(),
                 e.to_string().red(),
             );
+
+            outcome.fail(None, e.to_string());
+        }
+    }
+
+    if out_dir.exists() {
+        match project.copy_imports(out_dir, data) {
+            Ok(copied) => outcome.files += copied.len(),
+            Err(e) => {
+                eprintln!(
+                    "❌ {}\n{}\n{}",
+                    "Could not copy imports".bright_red(),
+                    e.path.to_string_lossy().red(),
+                    e.to_string().red(),
+                );
+
+                outcome.fail(Some(e.code()), e.to_string());
+            }
+        }
+    }
+
+    // Set the modes the config asks for now that every output is written
+    if out_dir.exists() {
+        if let Err(e) = project.apply_modes(out_dir) {
+            eprintln!(
+                "❌ {}\n{}\n{}",
+                "Could not set file modes".bright_red(),
+                e.path.to_string_lossy().red(),
+                e.to_string().red(),
+            );
+
+            outcome.fail(Some(e.code()), e.to_string());
         }
     }
 
+    outcome.timings.render = start_time.elapsed();
+
     // print done
-    println!(
-        "  ✅ done {}\n",
-        format!("{:?}", start_time.elapsed()).dimmed()
-    );
+    if !summary_only {
+        println!(
+            "  ✅ done {}\n",
+            format!("{:?}", outcome.timings.render).dimmed()
+        );
+    }
+}
+
+pub fn run_multi(
+    data: &HashMap<String, String>,
+    out_dir: &PathBuf,
+    options: &GenerateOptions,
+    args: &FillArgs,
+    cli: &Cli,
+    project: &Project,
+) {
+    let summary_only = args.summary_only;
+    let start_time = Instant::now();
+    let mut outcome = Outcome {
+        deadline: options.timeout.map(|timeout| start_time + timeout),
+        log: project
+            .config
+            .run_log()
+            .then(|| RunLog::new(&project.config.slots, &project.config.hooks)),
+        ..Default::default()
+    };
+    outcome.record(RunEvent::Started {
+        run_id: data.get("_run_id").cloned().unwrap_or_default(),
+        project: project.get_name(),
+        data: data.clone().into_iter().collect(),
+        spackle: spackle::version(),
+    });
+
+    if project.is_hooks_only() {
+        if !summary_only {
+            println!(
+                "📂 Hooks-only project, running in {}\n",
+                out_dir.to_string_lossy().bold()
+            );
+        }
+
+        // Hooks run inside the output directory, so it needs to exist
+        if let Err(e) = fs::create_dir_all(out_dir) {
+            eprintln!("❌ {}", e.to_string().red());
+            outcome.fail(None, e.to_string());
+            write_run_log(&outcome, out_dir);
+            exit(1);
+        }
+    } else {
+        write_output(
+            data,
+            out_dir,
+            options,
+            summary_only,
+            cli,
+            project,
+            &mut outcome,
+        );
+
+        outcome.timed_out = outcome
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline);
+    }
+
+    // Taken before hooks change anything, so updates can tell their changes from the template's
+    let fingerprints = project.output_fingerprints(out_dir, data);
+
+    // Hooks aren't run against output that was cut short
+    if !project.config.hooks.is_empty() && !outcome.timed_out {
+        run_hooks(data, out_dir, options, args, cli, project, &mut outcome);
+    } else if project.config.hooks.is_empty() && !summary_only {
+        println!("🪝  No hooks to run\n");
+    }
+
+    outcome.timings.total = start_time.elapsed();
+
+    if outcome.timed_out {
+        // Hooks-only projects run in an existing directory, which isn't ours to remove
+        if !project.is_hooks_only() {
+            let _ = fs::remove_dir_all(out_dir);
+        }
+
+        let message = format!(
+            "Fill exceeded the time limit of {}s and was stopped",
+            options.timeout.unwrap_or_default().as_secs()
+        );
+        eprintln!("⏰ {}\n", message.bright_red());
+        outcome.fail(None, message);
+    }
+
+    // Marks the directory as generated, so hooks that ran aren't run again when filling it
+    // again. It's written after failures too, as long as the output is still there.
+    if out_dir.exists() {
+        let manifest = Manifest {
+            run_id: data.get("_run_id").cloned().unwrap_or_default(),
+            hooks_ran: outcome.hooks_ran.clone(),
+            managed: project.config.managed.clone(),
+            imported: project.imported_files(out_dir, data),
+            spackle: Some(spackle::version()),
+            answers: Answers::new(project, data).ok(),
+            files: fingerprints,
+        };
+        if let Err(e) = manifest.write(out_dir) {
+            eprintln!("{}\n", format!("⚠️ {}", e).yellow());
+            outcome.record(RunEvent::Warning(e.to_string()));
+        }
+    }
+
+    if summary_only {
+        println!("{}", outcome);
+    } else {
+        println!("⏱️  {}", outcome.timings.to_string().dimmed());
+    }
+
+    outcome.record(RunEvent::Finished {
+        ok: !outcome.failed,
+        files: outcome.files,
+        hooks_run: outcome.hooks_run,
+        hooks_failed: outcome.hooks_failed,
+        timed_out: outcome.timed_out,
+        seconds: outcome.timings.total.as_secs_f64(),
+    });
+    write_run_log(&outcome, out_dir);
+
+    if args.explain_skips {
+        if args.json {
+            // Reports record the spackle that produced them
+            let report = serde_json::to_value(&outcome.skips).map(|mut report| {
+                report["spackle"] = serde_json::json!(spackle::version());
+                report
+            });
+            match report {
+                Ok(json) => println!("{}", json),
+                Err(e) => eprintln!("{}", format!("⚠️ {}", e).yellow()),
+            }
+        } else {
+            println!("\n🙈 Skipped\n{}", outcome.skips);
+        }
+    }
+
+    if outcome.failed {
+        exit(1);
+    }
+}
+
+// What a run produced, reported in a single line with --summary-only
+#[derive(Default)]
+struct Outcome {
+    files: usize,
+    hooks_run: usize,
+    hooks_failed: usize,
+    /// The hooks recorded in the manifest, see [HookResult::has_run]
+    hooks_ran: Vec<String>,
+    failed: bool,
+    timings: Timings,
+    /// When the run has to be done by, from --timeout
+    deadline: Option<Instant>,
+    timed_out: bool,
+    /// Every file and hook that was skipped, for --explain-skips
+    skips: SkipReport,
+    /// The log of the run's events, unless the project opts out of it
+    log: Option<RunLog>,
+}
+
+impl Outcome {
+    fn record(&mut self, event: RunEvent) {
+        if let Some(log) = &mut self.log {
+            log.record(event);
+        }
+    }
 
-    if project.config.hooks.is_empty() {
-        println!("🪝  No hooks to run");
+    // Marks the run as failed, recording why
+    fn fail(&mut self, code: Option<&str>, message: String) {
+        self.failed = true;
+        self.record(RunEvent::Failed {
+            code: code.map(str::to_string),
+            message,
+        });
+    }
+}
+
+// Writes the run log, printing its path when the run failed so it can be attached to bug reports
+fn write_run_log(outcome: &Outcome, out_dir: &Path) {
+    let Some(log) = &outcome.log else {
         return;
+    };
+
+    match log.write(out_dir) {
+        Ok(path) if outcome.failed => {
+            eprintln!("📝 {} {}\n", "Run log written to".dimmed(), path.display())
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("{}\n", format!("⚠️ {}", e).yellow()),
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} · {} run · {} failed · {:.2}s",
+            if self.failed { "failed" } else { "ok" },
+            present::count(self.files, "file", "files"),
+            present::count(self.hooks_run, "hook", "hooks"),
+            self.hooks_failed,
+            self.timings.total.as_secs_f64()
+        )
+    }
+}
+
+fn run_hooks(
+    data: &HashMap<String, String>,
+    out_dir: &PathBuf,
+    options: &GenerateOptions,
+    args: &FillArgs,
+    cli: &Cli,
+    project: &Project,
+    outcome: &mut Outcome,
+) {
+    let summary_only = args.summary_only;
+    if cli.verbose && !summary_only {
+        print_hook_plan(&project.plan_hooks(out_dir, data));
     }
 
-    println!("🪝  Running hooks...\n");
+    if !summary_only {
+        println!("🪝  Running hooks...\n");
+    }
+
+    // Writes that are already there weren't made by this fill, so they're never offered up
+    let existing_writes = project
+        .config
+        .hooks
+        .iter()
+        .flat_map(|hook| hook.write_paths(out_dir))
+        .filter(|path| path.exists())
+        .collect::<Vec<_>>();
 
     let runtime = match tokio::runtime::Builder::new_multi_thread()
         .enable_all()
@@ -410,97 +1380,279 @@ pub fn run_multi(data: &HashMap<String, String>, out_dir: &PathBuf, cli: &Cli, p
         Ok(runtime) => runtime,
         Err(e) => {
             eprintln!("{}", e.to_string().red());
+            outcome.fail(None, e.to_string());
+            write_run_log(outcome, out_dir);
             exit(1);
         }
     };
 
-    runtime.block_on(async {
-        let stream = match project.run_hooks_stream(out_dir, &data, None) {
-            Ok(stream) => stream,
-            Err(e) => {
-                let _ = fs::remove_dir_all(out_dir);
+    outcome.record(RunEvent::PhaseStarted(Phase::Hooks));
+    let mut timed_out = false;
+    let hook_results = runtime.block_on(async {
+        let stream =
+            match project.run_hooks_stream(out_dir, &data, &args.forward_args, None, options) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    let _ = fs::remove_dir_all(out_dir);
 
-                eprintln!(
-                    "  ❌ {}\n  {}",
-                    "Error evaluating hooks".bright_red(),
-                    e.to_string().red()
-                );
+                    eprintln!(
+                        "  ❌ {}\n  {}",
+                        "Error evaluating hooks".bright_red(),
+                        e.to_string().red()
+                    );
 
-                exit(1);
-            }
-        };
+                    outcome.fail(Some(e.code()), e.to_string());
+                    write_run_log(outcome, out_dir);
+                    exit(1);
+                }
+            };
         pin!(stream);
 
         let mut start_time = Instant::now();
+        let mut hook_results = Vec::new();
+
+        let deadline = outcome.deadline;
+        loop {
+            // Dropping the stream on timeout stops the running hook
+            let next = match deadline {
+                Some(deadline) => {
+                    match tokio::time::timeout_at(deadline.into(), stream.next()).await {
+                        Ok(next) => next,
+                        Err(_) => {
+                            timed_out = true;
+                            break;
+                        }
+                    }
+                }
+                None => stream.next().await,
+            };
+            let Some(result) = next else {
+                break;
+            };
 
-        while let Some(result) = stream.next().await {
-            match result {
+            match result.event {
                 HookStreamResult::HookStarted(hook) => {
-                    println!("  🚀 {}", hook);
+                    outcome.record(RunEvent::HookStarted(hook.clone()));
+                    if !summary_only {
+                        println!("  🚀 {}", hook);
+                    }
                 }
-                HookStreamResult::HookDone(r) => match r {
-                    HookResult {
-                        kind: HookResultKind::Failed(error),
-                        ..
-                    } => {
-                        eprintln!(
-                            "    ❌ {}\n    {}\n",
-                            "failed".bright_red(),
-                            error.to_string().red()
-                        );
-
-                        if cli.verbose {
-                            if let HookError::CommandExited { stdout, stderr, .. } = error {
-                                eprintln!(
-                                    "\n    {}\n{}",
-                                    "stdout".bold().dimmed(),
-                                    String::from_utf8_lossy(&stdout)
-                                );
-                                eprintln!(
-                                    "    {}\n{}",
-                                    "stderr".bold().dimmed(),
-                                    String::from_utf8_lossy(&stderr)
-                                );
-                            }
+                // The log keeps the end of the output with the hook's result instead, since
+                // it's held in memory until the fill finishes
+                HookStreamResult::HookOutput { stream, line, .. } => {
+                    // Verbose fills show the output as it's written
+                    if cli.verbose && !summary_only {
+                        match stream {
+                            OutputStream::Stdout => print!("{}", line),
+                            OutputStream::Stderr => eprint!("{}", line),
                         }
                     }
-                    HookResult {
-                        kind: HookResultKind::Completed { stdout, stderr },
-                        ..
-                    } => {
-                        println!(
-                            "    ✅ done {}\n",
-                            format!("in {:?}", start_time.elapsed()).dimmed()
-                        );
-
-                        if cli.verbose {
-                            println!(
-                                "    {}\n{}",
-                                "stdout".bold().dimmed(),
-                                String::from_utf8_lossy(&stdout)
-                            );
-                            println!(
-                                "    {}\n{}",
-                                "stderr".bold().dimmed(),
-                                String::from_utf8_lossy(&stderr)
+                    continue;
+                }
+                HookStreamResult::HookDone(r) if summary_only => {
+                    outcome.record(RunEvent::hook_finished(&r));
+                    hook_results.push((r, start_time.elapsed()));
+                }
+                HookStreamResult::HookDone(r) => {
+                    outcome.record(RunEvent::hook_finished(&r));
+                    match &r {
+                        HookResult {
+                            kind: HookResultKind::Failed(error),
+                            ..
+                        } => {
+                            eprintln!(
+                                "    ❌ {}\n    {}\n",
+                                "failed".bright_red(),
+                                error.to_string().red()
                             );
                         }
+                        HookResult {
+                            kind: HookResultKind::Completed { duration, .. },
+                            ..
+                        } => {
+                            println!("    ✅ done {}\n", format!("in {:?}", duration).dimmed());
+                        }
+                        HookResult {
+                            kind: HookResultKind::Skipped(reason),
+                            ..
+                        } => {
+                            println!("    ⏩︎ skipping {}\n", reason.to_string().dimmed());
+                        }
                     }
-                    HookResult {
-                        kind: HookResultKind::Skipped(reason),
-                        ..
-                    } => {
-                        println!("    ⏩︎ skipping {}\n", reason.to_string().dimmed());
-                    }
-                },
+
+                    // Completed hooks report how long their command ran, rather than the time between events
+                    let elapsed = match &r.kind {
+                        HookResultKind::Completed { duration, .. } => *duration,
+                        _ => start_time.elapsed(),
+                    };
+                    hook_results.push((r, elapsed));
+                }
             };
 
             start_time = Instant::now();
         }
+
+        if !summary_only {
+            print_hook_summary(&hook_results);
+        }
+
+        hook_results
     });
+    outcome.timed_out = timed_out;
+    outcome
+        .skips
+        .add_hook_results(hook_results.iter().map(|(result, _)| result));
+
+    for (result, _) in &hook_results {
+        outcome.timings.record_hook(result);
+        if result.has_run() {
+            outcome.hooks_ran.push(result.hook.key.clone());
+        }
+
+        match result.kind {
+            HookResultKind::Completed { .. } => outcome.hooks_run += 1,
+            HookResultKind::Failed(_) => {
+                outcome.hooks_run += 1;
+                outcome.hooks_failed += 1;
+                outcome.failed = true;
+            }
+            HookResultKind::Skipped(_) => {}
+        }
+    }
+    if outcome.hooks_failed > 0 {
+        offer_write_cleanup(&hook_results, out_dir, &existing_writes, summary_only);
+    }
+}
+
+// Offers to remove the paths that completed hooks declared they write to,
+// since a failed run would otherwise leave them behind. Only paths the hooks
+// created inside the output directory are offered.
+fn offer_write_cleanup(
+    results: &[(HookResult, Duration)],
+    out_dir: &Path,
+    existing: &[PathBuf],
+    summary_only: bool,
+) {
+    let mut paths = results
+        .iter()
+        .filter(|(r, _)| matches!(r.kind, HookResultKind::Completed { .. }))
+        .flat_map(|(r, _)| r.hook.write_paths(out_dir))
+        .filter(|path| path.exists() && !existing.contains(path))
+        .filter(|path| is_removable(path, out_dir))
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths.dedup();
+
+    if paths.is_empty() {
+        return;
+    }
+
+    eprintln!(
+        "{}",
+        "⚠️ A hook failed after others wrote to these paths".bright_yellow()
+    );
+    for path in &paths {
+        eprintln!("  {}", path.display().to_string().yellow());
+    }
+    eprintln!();
+
+    if summary_only || !atty::is(atty::Stream::Stdout) {
+        return;
+    }
+
+    let remove = Confirm::new("Remove them?")
+        .with_default(false)
+        .prompt()
+        .unwrap_or(false);
+
+    if !remove {
+        return;
+    }
+
+    // A path may already be gone with a directory removed before it
+    for path in paths.into_iter().filter(|path| path.exists()) {
+        let result = if path.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+
+        if let Err(e) = result {
+            eprintln!("❌ {} {}", path.display(), e.to_string().red());
+        }
+    }
 }
 
-pub fn run_single(slot_data: &HashMap<String, String>, out_path: &PathBuf, cli: &Cli) {
+// Prints a table of the outcome of each hook
+fn print_hook_summary(results: &[(HookResult, Duration)]) {
+    if results.is_empty() {
+        return;
+    }
+
+    println!("📋 {}\n", "Hook summary".bold());
+
+    let names: Vec<String> = results
+        .iter()
+        .map(|(r, _)| match &r.hook.name {
+            Some(name) if *name != r.hook.key => format!("{} ({})", r.hook.key, name),
+            _ => r.hook.key.clone(),
+        })
+        .collect();
+    let name_width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+
+    for ((result, elapsed), name) in results.iter().zip(names) {
+        let name = format!("{:<width$}", name, width = name_width);
+        let elapsed = format!("{:>10}", format!("{:.2?}", elapsed));
+
+        match &result.kind {
+            HookResultKind::Completed { .. } => {
+                println!("  ✅ {}  {}", name.bold(), elapsed.dimmed());
+            }
+            HookResultKind::Skipped(reason) => {
+                println!(
+                    "  ⏩︎ {}  {}  {}",
+                    name.dimmed(),
+                    format!("{:>10}", "-").dimmed(),
+                    reason.to_string().dimmed()
+                );
+            }
+            HookResultKind::Failed(error) => {
+                println!(
+                    "  ❌ {}  {}  {}",
+                    name.bright_red().bold(),
+                    elapsed.dimmed(),
+                    error.to_string().lines().next().unwrap_or_default().red()
+                );
+            }
+        }
+    }
+
+    println!();
+}
+
+fn print_hook_plan(plan: &HookPlan) {
+    println!("🗺️  Hook plan\n");
+
+    for (i, hook) in plan.queued.iter().enumerate() {
+        println!("  {}. {}", i + 1, hook.key);
+    }
+
+    for (hook, reason) in &plan.skipped {
+        println!(
+            "{}",
+            format!("  - {} (skipped: {})", hook.key, reason).dimmed()
+        );
+    }
+
+    println!();
+}
+
+pub fn run_single(
+    slot_data: &HashMap<String, String>,
+    env: Option<&HashMap<String, String>>,
+    out_path: &PathBuf,
+    cli: &Cli,
+) {
     let start_time = Instant::now();
 
     let file_contents = match fs::read_to_string(&cli.project_path) {
@@ -524,7 +1676,7 @@ pub fn run_single(slot_data: &HashMap<String, String>, out_path: &PathBuf, cli:
     }
     .body;
 
-    let context = match tera::Context::from_serialize(slot_data) {
+    let mut context = match tera::Context::from_serialize(slot_data) {
         Ok(context) => context,
         Err(e) => {
             eprintln!(
@@ -535,6 +1687,9 @@ pub fn run_single(slot_data: &HashMap<String, String>, out_path: &PathBuf, cli:
             exit(1);
         }
     };
+    if let Some(env) = env {
+        context.insert(ENV_KEY, env);
+    }
 
     let result = match Tera::one_off(body, &context, false) {
         Ok(result) => result,
@@ -1,12 +1,19 @@
 use colored::Colorize;
-use spackle::config::Config;
+use spackle::{prelude::Config, slot};
+
+pub fn run(config: &Config, tree: bool) {
+    if tree {
+        print_tree(config);
+        return;
+    }
 
-pub fn run(config: &Config) {
     // Print slot info
     println!("🕳️  {}", "slots".truecolor(140, 200, 255).bold());
 
+    // Names and descriptions can refer to other slots, so show them with placeholders
+    let placeholders = slot::placeholder_data(&config.slots);
     config.slots.iter().for_each(|slot| {
-        println!("{}\n", slot);
+        println!("{}\n", slot.with_rendered_prompts(&placeholders));
     });
 
     // Print hook info
@@ -15,4 +22,113 @@ pub fn run(config: &Config) {
     config.hooks.iter().for_each(|hook| {
         println!("{}\n", hook);
     });
+
+    if config.templates.is_empty() {
+        return;
+    }
+
+    // Print inline templates
+    println!("📄 {}", "templates".truecolor(140, 200, 255).bold());
+
+    config.templates.iter().for_each(|template| {
+        println!(
+            "{}\n{}\n",
+            template.dest.clone().unwrap_or_default().bold(),
+            template
+                .content
+                .clone()
+                .unwrap_or_default()
+                .trim_end()
+                .dimmed()
+        );
+    });
+}
+
+// The kind shown for keys that are needed but aren't a slot or hook
+const MISSING: &str = "missing";
+
+// Prints the slots and hooks as a tree, where the children of an item are the items that need it
+fn print_tree(config: &Config) {
+    println!("🌳 {}", "dependency tree".truecolor(140, 200, 255).bold());
+
+    let items: Vec<(&String, &Vec<String>, &str)> = config
+        .slots
+        .iter()
+        .map(|slot| (&slot.key, &slot.needs, "slot"))
+        .chain(
+            config
+                .hooks
+                .iter()
+                .map(|hook| (&hook.key, &hook.needs, "hook")),
+        )
+        .collect();
+
+    let roots = items.iter().filter(|(_, needs, _)| needs.is_empty());
+    for (key, _, kind) in roots {
+        print_branch(&items, key, kind, "", true);
+    }
+
+    // Needs of keys that don't exist are shown as roots of their own, so what needs them
+    // isn't left out of the tree
+    let mut missing: Vec<&String> = Vec::new();
+    for need in items.iter().flat_map(|(_, needs, _)| needs.iter()) {
+        if !items.iter().any(|(key, _, _)| *key == need) && !missing.contains(&need) {
+            missing.push(need);
+        }
+    }
+    for key in missing {
+        print_branch(&items, key, MISSING, "", true);
+    }
+
+    println!();
+}
+
+fn print_branch(
+    items: &[(&String, &Vec<String>, &str)],
+    key: &String,
+    kind: &str,
+    prefix: &str,
+    last: bool,
+) {
+    println!(
+        "{}{}{} {}",
+        prefix.dimmed(),
+        if prefix.is_empty() {
+            ""
+        } else if last {
+            "└─ "
+        } else {
+            "├─ "
+        }
+        .dimmed(),
+        key.bold(),
+        if kind == MISSING {
+            format!("[{}]", kind).bright_red()
+        } else {
+            format!("[{}]", kind).truecolor(128, 128, 128)
+        }
+    );
+
+    let dependents: Vec<_> = items
+        .iter()
+        .filter(|(_, needs, _)| needs.contains(key))
+        .collect();
+
+    let child_prefix = if prefix.is_empty() {
+        "  ".to_string()
+    } else if last {
+        format!("{}   ", prefix)
+    } else {
+        format!("{}│  ", prefix)
+    };
+
+    for (i, (dependent, _, dependent_kind)) in dependents.iter().enumerate() {
+        print_branch(
+            items,
+            dependent,
+            dependent_kind,
+            &child_prefix,
+            i == dependents.len() - 1,
+        );
+    }
 }
@@ -1,14 +1,30 @@
-use clap::{command, Parser, Subcommand};
+use clap::{command, Args, Parser, Subcommand, ValueEnum};
 use colored::Colorize;
-use spackle::Project;
-use std::{path::PathBuf, process::exit};
+use spackle::{
+    answers::Answers,
+    config::{self, ProjectLayout},
+    prelude::Project,
+    user_config::UserConfig,
+};
+use std::{env, path::PathBuf, process::exit, sync::LazyLock};
+use util::{expand_path::expand_path, present};
+mod cache;
 mod check;
+mod data_flags;
+mod doctor;
 mod fill;
 mod info;
+mod migrate;
+mod plan;
+mod test;
+mod update;
 mod util;
 
+// Shown by --version, with the git revision when spackle was built from a checkout
+static VERSION: LazyLock<String> = LazyLock::new(|| spackle::version().to_string());
+
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(version = VERSION.as_str(), about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
@@ -17,41 +33,401 @@ struct Cli {
     #[arg(short = 'p', long = "project", default_value = ".", global = true)]
     project_path: PathBuf,
 
+    /// The spackle project directory to use. Unlike --project, fails if the path is a file
+    #[arg(long, global = true, conflicts_with_all = ["project_path", "project_file"])]
+    project_dir: Option<PathBuf>,
+
+    /// The single file spackle project to use. Unlike --project, fails if the path is a directory
+    #[arg(long, global = true, conflicts_with = "project_path")]
+    project_file: Option<PathBuf>,
+
     /// Whether to run in verbose mode.
     #[arg(short, long, global = true)]
     verbose: bool,
+
+    /// Never color output. Colors are also off when NO_COLOR is set or output isn't a terminal
+    #[arg(long, global = true)]
+    no_color: bool,
+}
+
+#[derive(Args)]
+pub struct FillArgs {
+    /// Assign data to a slot or hook
+    #[arg(short, long)]
+    pub data: Vec<String>,
+
+    /// Assign data to a slot or hook explicitly, e.g. --set slot.name=value or --set hook.key=false
+    #[arg(long, value_parser = data_flags::parse_set)]
+    pub set: Vec<data_flags::DataFlag>,
+
+    /// Whether to overwrite existing files
+    #[arg(short = 'O', long)]
+    pub overwrite: bool,
+
+    /// The location the output should be written to. If the project is a single file, this is the output file. If the project is a directory, this is the output directory.
+    #[arg(short = 'o', long = "out", global = true)]
+    pub out_path: Option<PathBuf>,
+
+    /// A TOML file of hook toggles, e.g. `docker = true` or `ci = "no"`. --data and --set take precedence over it
+    #[arg(long)]
+    pub hook_file: Option<PathBuf>,
+
+    /// A TOML file of extra template variables. These are added to the render context without validation and cannot share a key with a slot
+    #[arg(long)]
+    pub render_vars_file: Option<PathBuf>,
+
+    /// Add every environment variable to the template context under `env`, e.g. `{{ env.HOME }}`. These aren't validated, and any secrets in the environment can end up in the output
+    #[arg(long)]
+    pub data_stdin_env: bool,
+
+    /// The maximum number of bytes to render across all templates
+    #[arg(long)]
+    pub max_render_bytes: Option<usize>,
+
+    /// The maximum number of bytes to render for a single template
+    #[arg(long)]
+    pub max_file_bytes: Option<usize>,
+
+    /// The maximum number of seconds to spend rendering templates
+    #[arg(long)]
+    pub render_timeout: Option<u64>,
+
+    /// The number of bytes of each hook's stdout and stderr kept for the run log. Defaults to 64 KiB
+    #[arg(long)]
+    pub hook_output_limit: Option<usize>,
+
+    /// The maximum number of seconds for the whole fill, including hooks. Once reached, running hooks are stopped and the output is removed
+    #[arg(long)]
+    pub timeout: Option<u64>,
+
+    /// Re-read every written file and check it matches its source or rendered output, for filesystems that can silently corrupt writes. Doubles the I/O of the fill
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Treat warnings about the output path as errors
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Keep the temporary directory given to hooks instead of removing it after they complete
+    #[arg(long)]
+    pub keep_tmp: bool,
+
+    /// Only print a single line summarizing the outcome once filling completes
+    #[arg(long)]
+    pub summary_only: bool,
+
+    /// A TOML file saved by a previous fill with --save-answers. The fill uses the project recorded in it, and its values are applied before the hook file, --data and --set
+    #[arg(long)]
+    pub from_answers: Option<PathBuf>,
+
+    /// Save the project, slot values and hook toggles of this fill to a TOML file, to reproduce it with --from-answers. Sensitive slot values are left out and asked for again
+    #[arg(long)]
+    pub save_answers: Option<PathBuf>,
+
+    /// Whether templates error on undefined variables (true) or render them empty (false). Defaults to the project's `strict_variables`
+    #[arg(long)]
+    pub strict_vars: Option<bool>,
+
+    /// Once filling completes, list every file and hook that was skipped and why
+    #[arg(long)]
+    pub explain_skips: bool,
+
+    /// Print the --explain-skips list as JSON, on the last line of output
+    #[arg(long, requires = "explain_skips")]
+    pub json: bool,
+
+    /// Never prompt. Hooks without data use their default, and slots without data are an error
+    #[arg(long)]
+    pub non_interactive: bool,
+
+    /// Extra arguments appended to the command of the hook marked with `forward_args`
+    #[arg(last = true)]
+    pub forward_args: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct PlanArgs {
+    /// Assign data to a slot or hook. Slots without data use their default
+    #[arg(short, long)]
+    pub data: Vec<String>,
+
+    /// Assign data to a slot or hook explicitly, e.g. --set slot.name=value or --set hook.key=false
+    #[arg(long, value_parser = data_flags::parse_set)]
+    pub set: Vec<data_flags::DataFlag>,
+
+    /// The location the output would be written to
+    #[arg(short = 'o', long = "out")]
+    pub out_path: PathBuf,
+
+    /// A TOML file of hook toggles, e.g. `docker = true` or `ci = "no"`. --data and --set take precedence over it
+    #[arg(long)]
+    pub hook_file: Option<PathBuf>,
+
+    /// How to print the plan
+    #[arg(long, value_enum, default_value_t = PlanFormat::Text)]
+    pub format: PlanFormat,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum PlanFormat {
+    Text,
+    Json,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Gets info on a spackle project including the required inputs
     /// and their descriptions.
-    Info,
+    Info {
+        /// Print the dependency tree of slots and hooks based on their needs
+        #[arg(long)]
+        tree: bool,
+        /// Print a JSON Schema of the data a fill takes, with hooks under `hooks`
+        #[arg(long, conflicts_with = "tree")]
+        schema: bool,
+    },
     /// Fills a spackle project using the provided data
-    Fill {
-        /// Assign data to a slot or hook
+    Fill(FillArgs),
+    /// Shows the files a fill would write and the hooks it would run, without doing either
+    Plan(PlanArgs),
+    /// Checks the validity of a spackle project
+    Check {
+        /// Skip checking that hook commands can be found on PATH
+        #[arg(long)]
+        skip_command_check: bool,
+    },
+    /// Runs the test cases in the project's tests.d/ directory, filling it with the data of each
+    /// and checking the output against its expectations
+    Test,
+    /// Diagnoses common environment problems for a spackle project
+    Doctor {
+        /// An output directory of a previous fill, whose most recent run log is summarized
+        #[arg(short = 'o', long = "out")]
+        out_path: Option<PathBuf>,
+        /// A user hooks are run as, checked to exist (Unix only)
+        #[arg(long)]
+        run_as: Option<String>,
+    },
+    /// Renders the output of an earlier fill again with the answers recorded in its manifest,
+    /// applying what changed in the project since. Files changed in both get the new render
+    /// written alongside with .new added, and the update fails while any are left
+    Update {
+        /// The output directory to update
+        #[arg(short = 'o', long = "out")]
+        out_path: PathBuf,
+        /// Assign data to a slot or hook, over the recorded answers. Sensitive slots aren't recorded, so they need to be passed again
         #[arg(short, long)]
         data: Vec<String>,
-
-        /// Whether to overwrite existing files
-        #[arg(short = 'O', long)]
-        overwrite: bool,
-
-        /// The location the output should be written to. If the project is a single file, this is the output file. If the project is a directory, this is the output directory.
-        #[arg(short = 'o', long = "out", global = true)]
-        out_path: Option<PathBuf>,
     },
-    /// Checks the validity of a spackle project
-    Check,
+    /// Upgrades the project's spackle.toml from the spackle_version it declares to this spackle,
+    /// keeping the original alongside it
+    Migrate {
+        /// Print the migrated config instead of writing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Manages spackle's cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheCommands {
+    /// Removes cached remote projects, reporting how much space was freed
+    Clear,
 }
 
 fn main() {
-    println!("{}\n", "🚰 spackle".truecolor(200, 200, 255));
+    let mut cli = Cli::parse();
+
+    // User-level defaults sit under the project's config and flags, so a broken file only warns
+    let user_config = UserConfig::load().unwrap_or_else(|e| {
+        eprintln!("{}\n", format!("⚠️ {}, ignoring it", e).yellow());
+        UserConfig::default()
+    });
+    let no_color_env = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    if let Some(color) = present::color_override(
+        cli.no_color,
+        no_color_env,
+        user_config.color.unwrap_or_default(),
+    ) {
+        colored::control::set_override(color);
+    }
 
-    let cli = Cli::parse();
+    // JSON output is meant to be piped, so it's printed on its own
+    let is_json = matches!(
+        &cli.command,
+        Commands::Plan(PlanArgs {
+            format: PlanFormat::Json,
+            ..
+        }) | Commands::Info { schema: true, .. }
+    );
 
-    let project = match spackle::load_project(&cli.project_path) {
+    if !is_json {
+        println!("{}\n", "🚰 spackle".truecolor(200, 200, 255));
+    }
+
+    // --project-dir and --project-file pin the layout instead of inferring it from the path
+    let expected_layout = match (cli.project_dir.take(), cli.project_file.take()) {
+        (Some(dir), _) => {
+            cli.project_path = dir;
+            Some(ProjectLayout::Directory)
+        }
+        (None, Some(file)) => {
+            cli.project_path = file;
+            Some(ProjectLayout::SingleFile)
+        }
+        (None, None) => None,
+    };
+
+    // Expand ~ and environment variables, which the shell doesn't do in every case (e.g. --out=~/foo)
+    cli.project_path = expand_path(&cli.project_path);
+    if let Commands::Fill(FillArgs {
+        out_path: Some(out_path),
+        ..
+    }) = &mut cli.command
+    {
+        *out_path = expand_path(out_path);
+    }
+    if let Commands::Plan(PlanArgs { out_path, .. }) = &mut cli.command {
+        *out_path = expand_path(out_path);
+    }
+    if let Commands::Update { out_path, .. } = &mut cli.command {
+        *out_path = expand_path(out_path);
+    }
+
+    // An answers file records the project it was filled from, which replaces --project
+    let answers = match &cli.command {
+        Commands::Fill(FillArgs {
+            from_answers: Some(path),
+            ..
+        }) => match Answers::load(&expand_path(path)) {
+            Ok(answers) => Some(answers),
+            Err(e) => {
+                eprintln!(
+                    "❌ {}\n{}",
+                    "Error loading answers file".bright_red(),
+                    e.to_string().red()
+                );
+                exit(1);
+            }
+        },
+        // Updates use the answers recorded in the output's manifest
+        Commands::Update { out_path, .. } => Some(update::recorded_answers(out_path)),
+        _ => None,
+    };
+    if let Some(answers) = &answers {
+        cli.project_path = answers.project.path.clone();
+    }
+
+    // Doctor, migrate and cache work without loading the project, so broken and outdated
+    // configs can still be diagnosed and upgraded
+    let project = || load(&cli, expected_layout, &user_config, is_json);
+    match &cli.command {
+        Commands::Doctor { out_path, run_as } => doctor::run(
+            &cli.project_path,
+            out_path.as_deref().map(expand_path).as_deref(),
+            run_as.as_deref(),
+        ),
+        Commands::Migrate { dry_run } => migrate::run(&cli.project_path, *dry_run),
+        Commands::Cache {
+            command: CacheCommands::Clear,
+        } => cache::clear(),
+        Commands::Check { skip_command_check } => {
+            let project = project();
+            check::run(
+                &project,
+                !skip_command_check,
+                project.config.strict_variables(),
+            )
+        }
+        Commands::Info { tree, schema } => {
+            let project = project();
+            if *schema {
+                println!("{:#}", project.input_schema());
+            } else {
+                info::run(&project.config, *tree)
+            }
+        }
+        Commands::Fill(args) => fill::run(args, &project(), &cli, answers.as_ref(), &user_config),
+        Commands::Plan(args) => plan::run(args, &project()),
+        Commands::Test => test::run(&project()),
+        Commands::Update { out_path, data } => update::run(out_path, data, &project()),
+    }
+}
+
+// Loads the project, exiting with a message when it can't be, and prints its banner
+fn load(
+    cli: &Cli,
+    expected_layout: Option<ProjectLayout>,
+    user_config: &UserConfig,
+    is_json: bool,
+) -> Project {
+    // Missing paths and directories without config are reported when loading below
+    if let (Some(expected), Ok(layout)) =
+        (expected_layout, ProjectLayout::detect(&cli.project_path))
+    {
+        if expected != layout {
+            eprintln!(
+                "❌ {}\n{}",
+                "Unexpected project layout".bright_red(),
+                match layout {
+                    ProjectLayout::Directory => format!(
+                        "{} is a directory, use --project-dir or --project",
+                        cli.project_path.display()
+                    ),
+                    ProjectLayout::SingleFile => format!(
+                        "{} is a file, use --project-file or --project",
+                        cli.project_path.display()
+                    ),
+                }
+                .red()
+            );
+            exit(1);
+        }
+    }
+
+    let mut project = match spackle::prelude::load_project(&cli.project_path) {
         Ok(p) => p,
+        Err(config::Error::NotFound(path)) => {
+            eprintln!(
+                "❌ {}\n{}",
+                "Project not found".bright_red(),
+                format!(
+                    "{} does not exist, pass the project's directory with --project (-p)",
+                    path.display()
+                )
+                .red()
+            );
+            exit(1);
+        }
+        Err(config::Error::ConfigNotFound(path)) if path.is_dir() => {
+            eprintln!(
+                "❌ {}\n{}",
+                "Not a spackle project".bright_red(),
+                format!(
+                    "{} has no {}, create one to make it a project",
+                    path.display(),
+                    config::CONFIG_FILE
+                )
+                .red()
+            );
+            exit(1);
+        }
+        Err(config::Error::ConfigNotFound(path)) => {
+            eprintln!(
+                "❌ {}\n{}",
+                "Not a spackle project".bright_red(),
+                format!(
+                    "{} has no frontmatter. Single file projects start with their config between --- lines",
+                    path.display()
+                )
+                .red()
+            );
+            exit(1);
+        }
         Err(e) => {
             eprintln!(
                 "❌ {}\n{}",
@@ -61,54 +437,18 @@ fn main() {
             exit(1);
         }
     };
+    user_config.apply_to(&mut project.config);
 
-    print_project_info(&project);
-
-    match &cli.command {
-        Commands::Check => check::run(&project),
-        Commands::Info => info::run(&project.config),
-        Commands::Fill {
-            data,
-            overwrite,
-            out_path,
-        } => fill::run(data, overwrite, out_path, &project, &cli),
+    if !is_json {
+        print_project_info(&project);
     }
+
+    project
 }
 
 fn print_project_info(project: &Project) {
-    println!("📦 Using project {}\n", project.get_name().bold());
-
-    println!(
-        "  {}",
-        format!("📁 {}", project.path.to_string_lossy()).dimmed()
-    );
-
-    println!(
-        "{}",
-        format!(
-            "  🕳️  {} {}",
-            project.config.slots.len(),
-            if project.config.slots.len() == 1 {
-                "slot"
-            } else {
-                "slots"
-            }
-        )
-        .dimmed()
-    );
-
-    println!(
-        "{}",
-        format!(
-            "  🪝  {} {}",
-            project.config.hooks.len(),
-            if project.config.hooks.len() == 1 {
-                "hook"
-            } else {
-                "hooks"
-            }
-        )
-        .dimmed()
-    );
+    for line in present::project_banner(&project.summary()) {
+        println!("{}", line);
+    }
     println!();
 }
@@ -0,0 +1,94 @@
+use std::{path::Path, process::exit};
+
+use colored::Colorize;
+use spackle::migrate::{self, Migrated, CURRENT_VERSION};
+
+use crate::util::present;
+
+pub fn run(project_dir: &Path, dry_run: bool) {
+    let results = match migrate::migrate_project(project_dir) {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!(
+                "{}\n{}",
+                "❌ Error migrating config".bright_red(),
+                e.to_string().red()
+            );
+            exit(1);
+        }
+    };
+
+    let changed = results
+        .iter()
+        .filter(|(_, migrated)| migrated.is_changed())
+        .collect::<Vec<_>>();
+
+    if changed.is_empty() {
+        println!(
+            "✅ {} is up to date with spackle {}",
+            results[0].0.display(),
+            CURRENT_VERSION
+        );
+        return;
+    }
+
+    for (path, migrated) in &changed {
+        print_migrated(path, migrated);
+
+        if dry_run {
+            println!("\n{}", migrated.contents);
+        }
+    }
+
+    if dry_run {
+        return;
+    }
+
+    for (path, migrated) in &changed {
+        if let Err(e) = migrated.write(path) {
+            eprintln!(
+                "{}\n{}",
+                "❌ Error writing migrated config".bright_red(),
+                e.to_string().red()
+            );
+            exit(1);
+        }
+    }
+
+    let kept = match changed.as_slice() {
+        [(path, _)] => format!(
+            "The original was kept at {}",
+            migrate::backup_path(path).display()
+        ),
+        _ => format!(
+            "The originals were kept alongside them with .{} added",
+            migrate::BACKUP_EXT
+        ),
+    };
+    println!(
+        "\n  {}",
+        format!("📁 {}. Comments aren't carried over.", kept).dimmed()
+    );
+}
+
+fn print_migrated(path: &Path, migrated: &Migrated) {
+    println!(
+        "⬆️  Migrating {} from {} to spackle {}, applying {}",
+        path.display(),
+        migrated
+            .from
+            .as_deref()
+            .map(|version| format!("spackle {}", version))
+            .unwrap_or("an unversioned config".to_string()),
+        CURRENT_VERSION,
+        present::count(migrated.applied.len(), "migration", "migrations")
+    );
+    for migration in &migrated.applied {
+        println!(
+            "  {} {} {}",
+            "+".green(),
+            migration.description,
+            format!("({})", migration.version).dimmed()
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{data_flags, PlanArgs, PlanFormat};
+use colored::Colorize;
+use rocket::serde::json::serde_json;
+use spackle::prelude::{GenerateError, GenerateOptions, Project};
+use std::{collections::HashMap, process::exit};
+
+pub fn run(args: &PlanArgs, project: &Project) {
+    let hook_file_flags = match &args.hook_file {
+        Some(path) => match data_flags::load_hook_file(path) {
+            Ok(flags) => flags,
+            Err(e) => {
+                eprintln!("{}\n{}", "❌ Error reading hook file".bright_red(), e.red());
+                exit(1);
+            }
+        },
+        None => Vec::new(),
+    };
+
+    // Same precedence as fill, with slot defaults standing in for the answers fill would prompt
+    // for. Planning doesn't run anything, so defaults from commands are left out.
+    let (slots, _) = project.resolve_defaults(&GenerateOptions {
+        skip_default_commands: true,
+        ..Default::default()
+    });
+    let mut data = slots
+        .iter()
+        .filter_map(|slot| {
+            slot.default
+                .as_ref()
+                .map(|default| (slot.key.clone(), default.clone()))
+        })
+        .collect::<HashMap<_, _>>();
+    data.extend(
+        hook_file_flags
+            .into_iter()
+            .chain(data_flags::parse_data(&args.data))
+            .chain(args.set.iter().cloned())
+            .map(|flag| (flag.key, flag.value)),
+    );
+
+    let plan = match project.plan(&args.out_path, &data) {
+        Ok(plan) => plan,
+        Err(e) => {
+            if let PlanFormat::Json = args.format {
+                let mut error = serde_json::json!({ "code": e.code(), "message": e.to_string() });
+                // Copy errors carry the offending path and the causes leading to the root one
+                if let GenerateError::CopyError(copy_error) = &e {
+                    error["path"] = serde_json::json!(copy_error.path);
+                    error["chain"] = serde_json::json!(copy_error.chain());
+                }
+                println!("{}", serde_json::json!({ "error": error }));
+            } else {
+                eprintln!(
+                    "{}\n{}",
+                    "❌ Error planning fill".bright_red(),
+                    e.to_string().red()
+                );
+            }
+            exit(1);
+        }
+    };
+
+    match args.format {
+        PlanFormat::Json => match serde_json::to_string_pretty(&plan) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("❌ {}", e.to_string().red());
+                exit(1);
+            }
+        },
+        PlanFormat::Text => {
+            println!(
+                "📋 Planned fill of {}\n",
+                args.out_path.display().to_string().bold()
+            );
+            println!("{}", plan);
+
+            let overwrites = plan.overwrites();
+            if !overwrites.is_empty() {
+                println!(
+                    "\n{}",
+                    format!(
+                        "⚠️ {} existing file(s) would be overwritten when filling with --overwrite",
+                        overwrites.len()
+                    )
+                    .yellow()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::{process::exit, time::Instant};
+
+use colored::Colorize;
+use spackle::{
+    prelude::Project,
+    testing::{self, TestCase, TESTS_DIR},
+};
+
+pub fn run(project: &Project) {
+    println!("🧪 Running project tests\n");
+
+    let start_time = Instant::now();
+
+    let paths = match testing::discover(&project.path) {
+        Ok(paths) => paths,
+        Err(e) => {
+            eprintln!(
+                "{}\n{}",
+                "❌ Error discovering tests".bright_red(),
+                e.to_string().red()
+            );
+            exit(1);
+        }
+    };
+
+    if paths.is_empty() {
+        println!("  {}", format!("No tests found in {}/", TESTS_DIR).dimmed());
+        return;
+    }
+
+    let mut failed = 0;
+    for path in &paths {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let failures = TestCase::load(path).and_then(|case| case.run(&project.path));
+        match failures {
+            Ok(failures) if failures.is_empty() => {
+                println!("  {} {}", "✓".green(), name);
+            }
+            Ok(failures) => {
+                failed += 1;
+                println!("  {} {}", "✗".bright_red(), name.bright_red());
+                for failure in failures {
+                    println!("    {}", failure.red());
+                }
+            }
+            Err(e) => {
+                failed += 1;
+                println!("  {} {}", "✗".bright_red(), name.bright_red());
+                println!("    {}", e.to_string().red());
+            }
+        }
+    }
+
+    println!(
+        "\n  {} passed, {} failed {}",
+        paths.len() - failed,
+        failed,
+        format!("in {:?}", start_time.elapsed()).dimmed()
+    );
+
+    if failed > 0 {
+        exit(1);
+    }
+}
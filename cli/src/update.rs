@@ -0,0 +1,68 @@
+use std::{collections::HashMap, path::Path, process::exit};
+
+use colored::Colorize;
+use spackle::{
+    answers::Answers,
+    manifest::Manifest,
+    prelude::{GenerateOptions, Project},
+    update,
+};
+
+use crate::{data_flags, util::present};
+
+/// The answers the output was filled with. Their project is the one it's updated from.
+pub fn recorded_answers(out_dir: &Path) -> Answers {
+    let error = match Manifest::load(out_dir) {
+        Ok(Some(Manifest {
+            answers: Some(answers),
+            ..
+        })) => return answers,
+        Ok(Some(_)) => update::Error::NoAnswers(out_dir.to_path_buf()),
+        Ok(None) => update::Error::NotFilled(out_dir.to_path_buf()),
+        Err(e) => update::Error::ManifestError(e),
+    };
+
+    eprintln!(
+        "{}\n{}",
+        "❌ Error updating output".bright_red(),
+        error.to_string().red()
+    );
+    exit(1);
+}
+
+pub fn run(out_dir: &Path, data: &[String], project: &Project) {
+    let data = data_flags::parse_data(data)
+        .into_iter()
+        .map(|flag| (flag.key, flag.value))
+        .collect::<HashMap<_, _>>();
+
+    let update = match project.update(out_dir, &data, &GenerateOptions::default()) {
+        Ok(update) => update,
+        Err(e) => {
+            eprintln!(
+                "{}\n{}",
+                "❌ Error updating output".bright_red(),
+                e.to_string().red()
+            );
+            exit(1);
+        }
+    };
+
+    println!("🔄 Updated {}\n", out_dir.display().to_string().bold());
+    println!("{}", update);
+
+    // Conflicts are left for the user to merge, and fail the update until they are
+    let conflicts = update.conflicts();
+    if !conflicts.is_empty() {
+        eprintln!(
+            "\n{}",
+            format!(
+                "⚠️ {} changed in both the output and the project. Merge the new render written alongside each, with .{} added, then remove it",
+                present::count(conflicts.len(), "file was", "files were"),
+                update::NEW_EXT
+            )
+            .yellow()
+        );
+        exit(1);
+    }
+}
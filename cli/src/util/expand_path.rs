@@ -0,0 +1,44 @@
+use std::path::{Path, PathBuf};
+
+/// Expands a leading `~` and any `$VAR`/`${VAR}` references in a path.
+/// Paths that reference undefined variables are returned unchanged.
+pub fn expand_path(path: &Path) -> PathBuf {
+    match shellexpand::full(&path.to_string_lossy()) {
+        Ok(expanded) => PathBuf::from(expanded.as_ref()),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn tilde() {
+        let home = env::var("HOME").expect("HOME should be set");
+
+        assert_eq!(
+            expand_path(&PathBuf::from("~/projects/foo")),
+            PathBuf::from(home).join("projects/foo")
+        );
+    }
+
+    #[test]
+    fn env_var() {
+        env::set_var("SPACKLE_EXPAND_TEST", "/tmp/spackle");
+
+        assert_eq!(
+            expand_path(&PathBuf::from("$SPACKLE_EXPAND_TEST/out")),
+            PathBuf::from("/tmp/spackle/out")
+        );
+    }
+
+    #[test]
+    fn undefined_var() {
+        assert_eq!(
+            expand_path(&PathBuf::from("$SPACKLE_UNDEFINED_VAR/out")),
+            PathBuf::from("$SPACKLE_UNDEFINED_VAR/out")
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::PathBuf,
+};
+
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use inquire::{
+    autocompletion::{Autocomplete, Replacement},
+    CustomUserError,
+};
+use rocket::serde::{Deserialize, Serialize};
+use spackle::{
+    paths,
+    prelude::{Slot, SlotType},
+};
+
+/// The file in the user's config dir that previously entered slot values are kept in
+pub const HISTORY_FILE: &str = "history.toml";
+
+/// How many values are kept for each slot
+const MAX_VALUES: usize = 20;
+
+/// Values entered for text slots in previous fills, most recent first, by slot key
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct History {
+    #[serde(default)]
+    pub slots: BTreeMap<String, Vec<String>>,
+}
+
+impl History {
+    pub fn path() -> Option<PathBuf> {
+        paths::config_dir().map(|dir| dir.join(HISTORY_FILE))
+    }
+
+    /// Loads the history, starting a new one if it's missing or unreadable
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let contents = toml::to_string(self).map_err(std::io::Error::other)?;
+        fs::write(path, contents)
+    }
+
+    pub fn values(&self, key: &str) -> &[String] {
+        self.slots.get(key).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Moves the values of text slots to the front of their history. Sensitive slots and empty
+    /// values are never recorded.
+    pub fn record(&mut self, slots: &[Slot], data: &HashMap<String, String>) {
+        for slot in slots {
+            if slot.sensitive || !matches!(slot.r#type, SlotType::String) {
+                continue;
+            }
+
+            let Some(value) = data.get(&slot.key).filter(|value| !value.is_empty()) else {
+                continue;
+            };
+
+            let values = self.slots.entry(slot.key.clone()).or_default();
+            values.retain(|existing| existing != value);
+            values.insert(0, value.clone());
+            values.truncate(MAX_VALUES);
+        }
+    }
+}
+
+/// Suggests a slot's previous values, fuzzy matched against the input
+#[derive(Clone, Default)]
+pub struct HistoryCompleter {
+    values: Vec<String>,
+}
+
+impl HistoryCompleter {
+    pub fn new(values: &[String]) -> Self {
+        HistoryCompleter {
+            values: values.to_vec(),
+        }
+    }
+
+    // Keeps the history's order, most recent first, for values that match
+    fn matches(&self, input: &str) -> Vec<String> {
+        let matcher = SkimMatcherV2::default().smart_case();
+
+        self.values
+            .iter()
+            .filter(|value| input.is_empty() || matcher.fuzzy_match(value, input).is_some())
+            .cloned()
+            .collect()
+    }
+}
+
+impl Autocomplete for HistoryCompleter {
+    fn get_suggestions(&mut self, input: &str) -> Result<Vec<String>, CustomUserError> {
+        Ok(self.matches(input))
+    }
+
+    fn get_completion(
+        &mut self,
+        input: &str,
+        highlighted_suggestion: Option<String>,
+    ) -> Result<Replacement, CustomUserError> {
+        Ok(match highlighted_suggestion {
+            Some(suggestion) => Replacement::Some(suggestion),
+            None => self
+                .matches(input)
+                .into_iter()
+                .next()
+                .map(Replacement::Some)
+                .unwrap_or(Replacement::None),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record() {
+        let slots = vec![
+            Slot {
+                key: "org".to_string(),
+                ..Default::default()
+            },
+            Slot {
+                key: "token".to_string(),
+                sensitive: true,
+                ..Default::default()
+            },
+        ];
+
+        let mut history = History::default();
+        for org in ["a2-ai", "other", "a2-ai", ""] {
+            history.record(
+                &slots,
+                &HashMap::from([
+                    ("org".to_string(), org.to_string()),
+                    ("token".to_string(), "hunter2".to_string()),
+                ]),
+            );
+        }
+
+        assert_eq!(history.values("org"), ["a2-ai", "other"]);
+        assert!(history.values("token").is_empty());
+
+        let completer = HistoryCompleter::new(history.values("org"));
+        assert_eq!(completer.matches(""), vec!["a2-ai", "other"]);
+        assert_eq!(completer.matches("oth"), vec!["other"]);
+    }
+}
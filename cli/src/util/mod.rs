@@ -1 +1,4 @@
+pub mod expand_path;
 pub mod file_path_completer;
+pub mod history;
+pub mod present;
@@ -0,0 +1,84 @@
+use colored::Colorize;
+use spackle::{user_config::ColorChoice, ProjectSummary};
+use std::fmt::Display;
+
+/// Whether to force colors on or off, or `None` to color only when writing to a terminal.
+/// `--no-color` wins, then the user config's `color`, then `NO_COLOR`.
+pub fn color_override(
+    no_color_flag: bool,
+    no_color_env: bool,
+    choice: ColorChoice,
+) -> Option<bool> {
+    match choice {
+        _ if no_color_flag => Some(false),
+        ColorChoice::Always => Some(true),
+        ColorChoice::Never => Some(false),
+        ColorChoice::Auto if no_color_env => Some(false),
+        ColorChoice::Auto => None,
+    }
+}
+
+/// Formats a count with the noun that agrees with it, e.g. `1 slot` or `2 slots`
+pub fn count(n: usize, singular: &str, plural: &str) -> String {
+    format!("{} {}", n, if n == 1 { singular } else { plural })
+}
+
+/// A secondary line under a heading, indented and dimmed
+pub fn detail(text: impl Display) -> String {
+    format!("  {}", text).dimmed().to_string()
+}
+
+/// The lines introducing the project a command works on
+pub fn project_banner(summary: &ProjectSummary) -> Vec<String> {
+    vec![
+        format!("📦 Using project {}\n", summary.name.bold()),
+        detail(format!("📁 {}", summary.path.display())),
+        detail(format!("🕳️  {}", count(summary.slots, "slot", "slots"))),
+        detail(format!("🪝  {}", count(summary.hooks, "hook", "hooks"))),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn color() {
+        assert_eq!(color_override(false, false, ColorChoice::Auto), None);
+        assert_eq!(color_override(false, true, ColorChoice::Auto), Some(false));
+        assert_eq!(color_override(false, true, ColorChoice::Always), Some(true));
+        assert_eq!(
+            color_override(true, false, ColorChoice::Always),
+            Some(false)
+        );
+        assert_eq!(
+            color_override(false, false, ColorChoice::Never),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn banner() {
+        colored::control::set_override(false);
+
+        assert_eq!(count(0, "slot", "slots"), "0 slots");
+        assert_eq!(count(1, "entry", "entries"), "1 entry");
+
+        let summary = ProjectSummary {
+            name: "app".to_string(),
+            path: PathBuf::from("/templates/app"),
+            slots: 1,
+            hooks: 2,
+        };
+        assert_eq!(
+            project_banner(&summary),
+            vec![
+                "📦 Using project app\n",
+                "  📁 /templates/app",
+                "  🕳️  1 slot",
+                "  🪝  2 hooks",
+            ]
+        );
+    }
+}
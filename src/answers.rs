@@ -0,0 +1,264 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use thiserror::Error;
+
+use crate::{hook, version, Project, Version};
+
+/// The answers file format written by this version of spackle
+pub const ANSWERS_VERSION: i64 = 1;
+
+/// Everything needed to reproduce a fill: the project it used and the values of its slots and hooks
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Answers {
+    pub version: i64,
+    pub project: ProjectSource,
+    #[serde(default)]
+    pub slots: BTreeMap<String, String>,
+    #[serde(default)]
+    pub hooks: BTreeMap<String, bool>,
+    /// The spackle that saved the answers
+    pub spackle: Option<Version>,
+}
+
+/// Where the project of a fill came from
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectSource {
+    pub path: PathBuf,
+    /// The git revision the project was at, if it's in a git repository
+    pub rev: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Error reading answers file: {0}")]
+    ReadError(io::Error),
+    #[error("Error parsing answers file: {0}")]
+    ParseError(toml::de::Error),
+    #[error("Unsupported answers file version {0}, this version of spackle reads version {ANSWERS_VERSION}")]
+    UnsupportedVersion(i64),
+    #[error("Error serializing answers: {0}")]
+    SerializeError(toml::ser::Error),
+    #[error("Error writing answers file: {0}")]
+    WriteError(io::Error),
+    #[error("Invalid hook toggle: {0}")]
+    InvalidToggle(hook::ValidateError),
+}
+
+impl Error {
+    /// Every code returned by [Error::code]
+    pub const CODES: &'static [&'static str] = &[
+        "answers.read_error",
+        "answers.parse_error",
+        "answers.unsupported_version",
+        "answers.serialize_error",
+        "answers.write_error",
+        "answers.invalid_toggle",
+    ];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::ReadError(_) => "answers.read_error",
+            Error::ParseError(_) => "answers.parse_error",
+            Error::UnsupportedVersion(_) => "answers.unsupported_version",
+            Error::SerializeError(_) => "answers.serialize_error",
+            Error::WriteError(_) => "answers.write_error",
+            Error::InvalidToggle(_) => "answers.invalid_toggle",
+        }
+    }
+}
+
+impl ProjectSource {
+    /// Records the project's path, made absolute so the answers work from any directory,
+    /// and its git revision if it has one
+    pub fn of(path: &Path) -> Self {
+        let path = path.canonicalize().unwrap_or(path.to_path_buf());
+        let dir = if path.is_dir() {
+            path.as_path()
+        } else {
+            path.parent().unwrap_or(Path::new("."))
+        };
+
+        let rev = Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .current_dir(dir)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+
+        ProjectSource { path, rev }
+    }
+}
+
+impl Answers {
+    /// Splits the data of a fill into slot values and hook toggles.
+    /// Keys that aren't a slot or hook of the project, like `_run_id`, aren't recorded, and
+    /// neither are the values of sensitive slots, which are asked for again when filling.
+    pub fn new(project: &Project, data: &HashMap<String, String>) -> Result<Self, Error> {
+        let slots = project
+            .config
+            .slots
+            .iter()
+            .filter(|slot| !slot.sensitive)
+            .filter_map(|slot| {
+                data.get(&slot.key)
+                    .map(|value| (slot.key.clone(), value.clone()))
+            })
+            .collect();
+
+        let hooks = project
+            .config
+            .hooks
+            .iter()
+            .filter_map(|hook| data.get(&hook.key).map(|value| (&hook.key, value)))
+            .map(|(key, value)| {
+                hook::parse_toggle(key, value)
+                    .map(|enabled| (key.clone(), enabled))
+                    .map_err(Error::InvalidToggle)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Answers {
+            version: ANSWERS_VERSION,
+            project: ProjectSource::of(&project.path),
+            slots,
+            hooks,
+            spackle: Some(version()),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path).map_err(Error::ReadError)?;
+
+        // Check the version on its own first, so files from other versions get a clear error
+        // rather than one about fields that changed
+        let table = contents.parse::<toml::Table>().map_err(Error::ParseError)?;
+        match table
+            .get("version")
+            .and_then(|version| version.as_integer())
+        {
+            Some(ANSWERS_VERSION) => {}
+            Some(version) => return Err(Error::UnsupportedVersion(version)),
+            None => {}
+        }
+
+        toml::from_str(&contents).map_err(Error::ParseError)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let contents = toml::to_string(self).map_err(Error::SerializeError)?;
+
+        fs::write(path, contents).map_err(Error::WriteError)
+    }
+
+    /// The slot values and hook toggles as fill data
+    pub fn data(&self) -> HashMap<String, String> {
+        self.slots
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .chain(
+                self.hooks
+                    .iter()
+                    .map(|(key, enabled)| (key.clone(), enabled.to_string())),
+            )
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+    use walkdir::WalkDir;
+
+    use super::*;
+    use crate::{load_project, manifest::MANIFEST_FILE, GenerateOptions};
+
+    // The contents of every file in the directory, by relative path
+    fn read_tree(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+        WalkDir::new(dir)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file() && entry.file_name() != MANIFEST_FILE)
+            .map(|entry| {
+                (
+                    entry.path().strip_prefix(dir).unwrap().to_path_buf(),
+                    fs::read(entry.path()).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn refill_from_answers() {
+        let project = load_project(&PathBuf::from("tests/data/answers")).unwrap();
+        let tmp = TempDir::new("spackle").unwrap();
+        let data = HashMap::from([
+            ("name".to_string(), "Ada".to_string()),
+            ("greet".to_string(), "yes".to_string()),
+            ("shout".to_string(), "false".to_string()),
+        ]);
+
+        let first = tmp.path().join("first");
+        project
+            .generate_with_hooks(&first, &data, &[], &GenerateOptions::default())
+            .expect("generate_with_hooks failed, should have succeeded");
+
+        let answers_path = tmp.path().join("answers.toml");
+        Answers::new(&project, &data)
+            .expect("Failed to record answers")
+            .write(&answers_path)
+            .expect("Failed to write answers");
+
+        let answers = Answers::load(&answers_path).expect("Failed to load answers");
+        assert_eq!(answers.version, ANSWERS_VERSION);
+        assert_eq!(
+            answers.hooks,
+            BTreeMap::from([("greet".to_string(), true), ("shout".to_string(), false)])
+        );
+
+        let project = load_project(&answers.project.path).unwrap();
+        let second = tmp.path().join("second");
+        project
+            .generate_with_hooks(&second, &answers.data(), &[], &GenerateOptions::default())
+            .expect("generate_with_hooks failed, should have succeeded");
+
+        let first_tree = read_tree(&first);
+        assert!(first_tree.contains_key(Path::new("greeting.txt")));
+        assert_eq!(first_tree, read_tree(&second));
+    }
+
+    #[test]
+    fn sensitive_left_out() {
+        let mut project = load_project(&PathBuf::from("tests/data/answers")).unwrap();
+        project.config.slots[0].sensitive = true;
+
+        let data = HashMap::from([
+            ("name".to_string(), "Ada".to_string()),
+            ("greet".to_string(), "true".to_string()),
+        ]);
+        let answers = Answers::new(&project, &data).unwrap();
+
+        assert!(answers.slots.is_empty());
+        assert!(answers.hooks["greet"]);
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let tmp = TempDir::new("spackle").unwrap();
+        let path = tmp.path().join("answers.toml");
+        fs::write(&path, "version = 2\nsomething_new = true\n").unwrap();
+
+        assert!(matches!(
+            Answers::load(&path),
+            Err(Error::UnsupportedVersion(2))
+        ));
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+use walkdir::WalkDir;
+
+use crate::paths;
+
+/// The directory in the cache that remote projects are fetched to
+pub const PROJECTS_DIR: &str = "projects";
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Error reading cache: {0}")]
+    ReadError(io::Error),
+    #[error("Error removing {}: {1}", .0.display())]
+    RemoveError(PathBuf, io::Error),
+}
+
+impl Error {
+    /// Every code returned by [Error::code]
+    pub const CODES: &'static [&'static str] = &["cache.read_error", "cache.remove_error"];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::ReadError(_) => "cache.read_error",
+            Error::RemoveError(..) => "cache.remove_error",
+        }
+    }
+}
+
+/// Where remote projects are cached, under [paths::cache_dir]
+pub fn projects_dir() -> Option<PathBuf> {
+    paths::cache_dir().map(|dir| dir.join(PROJECTS_DIR))
+}
+
+/// The total size in bytes of the files in a directory
+pub fn size(dir: &Path) -> u64 {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// What clearing a cache directory removed
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Cleared {
+    /// The entries directly in the directory, e.g. one per cached project
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+/// Removes everything in a cache directory, keeping the directory itself
+pub fn clear(dir: &Path) -> Result<Cleared, Error> {
+    let mut cleared = Cleared::default();
+    if !dir.exists() {
+        return Ok(cleared);
+    }
+
+    for entry in fs::read_dir(dir).map_err(Error::ReadError)? {
+        let path = entry.map_err(Error::ReadError)?.path();
+        let bytes = size(&path);
+
+        if path.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        }
+        .map_err(|e| Error::RemoveError(path.clone(), e))?;
+
+        cleared.entries += 1;
+        cleared.bytes += bytes;
+    }
+
+    Ok(cleared)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn clear_projects() {
+        let dir = TempDir::new("spackle").unwrap();
+        assert_eq!(
+            clear(&dir.path().join("missing")).unwrap(),
+            Cleared::default()
+        );
+
+        fs::create_dir_all(dir.path().join("one/nested")).unwrap();
+        fs::write(dir.path().join("one/nested/file"), "12345").unwrap();
+        fs::write(dir.path().join("two"), "123").unwrap();
+
+        assert_eq!(size(dir.path()), 8);
+        assert_eq!(
+            clear(dir.path()).unwrap(),
+            Cleared {
+                entries: 2,
+                bytes: 8
+            }
+        );
+        assert!(dir.path().is_dir());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,198 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use walkdir::WalkDir;
+
+use crate::{
+    config::{find_config_file, HOOKS_DIR},
+    migrate::is_backup,
+    paths::resolve,
+    template::TEMPLATE_EXT,
+    testing::TESTS_DIR,
+};
+
+/// How a fill handles an entry of a project
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum FileClass {
+    /// Rendered to the output without its template extension
+    Template,
+    /// Copied to the output as is
+    Static,
+    /// Matched by the ignore globs, so neither copied nor rendered
+    Ignored,
+    /// Defines the project rather than its output, like the config file, the backups `migrate`
+    /// keeps of it, and the hooks and tests directories
+    Project,
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Invalid ignore glob: {0}")]
+    InvalidIgnore(globset::Error),
+    #[error("Error reading project: {0}")]
+    ReadError(walkdir::Error),
+}
+
+impl Error {
+    /// Every code returned by [Error::code]
+    pub const CODES: &'static [&'static str] = &["classify.invalid_ignore", "classify.read_error"];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::InvalidIgnore(_) => "classify.invalid_ignore",
+            Error::ReadError(_) => "classify.read_error",
+        }
+    }
+}
+
+/// An entry of a project, see [Classifier::entries]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// Relative to the project directory
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub class: FileClass,
+}
+
+/// Decides which entries of a project are templates, static files or neither. Both copying
+/// and rendering go through it, so every file is handled by exactly one of them.
+pub struct Classifier {
+    config_file: Option<&'static str>,
+    ignore: IgnoreSet,
+    // The output directory relative to the project, when it's nested inside it
+    excluded: Option<PathBuf>,
+}
+
+impl Classifier {
+    /// `ignore` holds the globs of the config's `ignore`, along with those of disabled hooks
+    /// in `ignore_if_disabled`
+    pub fn new(project_dir: &Path, ignore: &[String]) -> Result<Self, Error> {
+        Ok(Classifier {
+            config_file: find_config_file(project_dir),
+            ignore: IgnoreSet::new(ignore).map_err(Error::InvalidIgnore)?,
+            excluded: None,
+        })
+    }
+
+    /// Leaves the output directory out of [Classifier::entries] when it's inside the project,
+    /// so an earlier fill written there isn't copied or rendered into the new one
+    pub fn excluding(mut self, project_dir: &Path, out_dir: &Path) -> Self {
+        self.excluded = resolve(out_dir)
+            .strip_prefix(resolve(project_dir))
+            .ok()
+            .filter(|relative| !relative.as_os_str().is_empty())
+            .map(Path::to_path_buf);
+        self
+    }
+
+    /// Classifies an entry by its path relative to the project directory.
+    /// Directories are never templates, whatever their name.
+    pub fn classify(&self, path: &Path, is_dir: bool) -> FileClass {
+        if self.ignore.is_ignored(path) {
+            return FileClass::Ignored;
+        }
+
+        let Some(name) = path.file_name() else {
+            return FileClass::Static;
+        };
+
+        if self
+            .config_file
+            .is_some_and(|config_file| name == config_file)
+            || path == Path::new(HOOKS_DIR)
+            || path == Path::new(TESTS_DIR)
+            || (!is_dir && is_backup(path))
+        {
+            return FileClass::Project;
+        }
+
+        if !is_dir && name.to_string_lossy().ends_with(TEMPLATE_EXT) {
+            return FileClass::Template;
+        }
+
+        FileClass::Static
+    }
+
+    /// Walks the project directory, classifying every entry in path order. The contents of
+    /// ignored and project directories are left out, since nothing under them is written.
+    pub fn entries(&self, project_dir: &Path) -> Result<Vec<Entry>, Error> {
+        let mut entries = Vec::new();
+        let mut walk = WalkDir::new(project_dir)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter();
+
+        while let Some(entry) = walk.next() {
+            let entry = entry.map_err(Error::ReadError)?;
+            let path = entry
+                .path()
+                .strip_prefix(project_dir)
+                .unwrap_or(entry.path())
+                .to_path_buf();
+            let is_dir = entry.file_type().is_dir();
+            if is_dir && self.excluded.as_ref() == Some(&path) {
+                walk.skip_current_dir();
+                continue;
+            }
+
+            let class = self.classify(&path, is_dir);
+
+            if is_dir && matches!(class, FileClass::Ignored | FileClass::Project) {
+                walk.skip_current_dir();
+            }
+
+            entries.push(Entry {
+                path,
+                is_dir,
+                class,
+            });
+        }
+
+        Ok(entries)
+    }
+}
+
+// Ignore patterns evaluated in order, where the last matching pattern wins
+// and those starting with `!` re-include what earlier patterns ignored
+struct IgnoreSet {
+    set: GlobSet,
+    negated: Vec<bool>,
+}
+
+impl IgnoreSet {
+    fn new(patterns: &[String]) -> Result<Self, globset::Error> {
+        let mut builder = GlobSetBuilder::new();
+        let mut negated = Vec::new();
+
+        for pattern in patterns {
+            let (pattern, negate) = match pattern.strip_prefix('!') {
+                Some(pattern) => (pattern, true),
+                None => (pattern.as_str(), false),
+            };
+
+            builder.add(GlobBuilder::new(pattern).literal_separator(true).build()?);
+            negated.push(negate);
+        }
+
+        Ok(IgnoreSet {
+            set: builder.build()?,
+            negated,
+        })
+    }
+
+    // Patterns are matched against both the entry's name and its path
+    // relative to the project, so "*.log" applies at any depth
+    fn is_ignored(&self, relative_path: &Path) -> bool {
+        let mut matches = self.set.matches(relative_path);
+        if let Some(name) = relative_path.file_name() {
+            matches.extend(self.set.matches(name));
+        }
+
+        matches
+            .into_iter()
+            .max()
+            .is_some_and(|index| !self.negated[index])
+    }
+}
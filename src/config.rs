@@ -1,69 +1,773 @@
 use fronma::{engines::Toml, parser::parse_with_engine};
 use serde::Deserialize;
-use std::{collections::HashSet, fs, io, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    os::unix::fs::PermissionsExt,
+    path::{Component, Path, PathBuf},
+};
 
-use crate::{hook::Hook, slot::Slot};
+use crate::{
+    classify::{Classifier, FileClass},
+    hook::Hook,
+    is_reserved_key,
+    needs::{self, Needy},
+    paths::{glob_set, is_within},
+    slot::{Slot, SlotType},
+    template::{InlineTemplate, BASE64_EXT, TEMPLATE_EXT},
+};
 
 #[derive(Deserialize, Debug, Default)]
 pub struct Config {
     pub name: Option<String>,
+    /// The version of spackle the config was written for, see [crate::migrate]
+    pub spackle_version: Option<String>,
+    #[serde(default)]
+    pub kind: ProjectKind,
+    /// Files containing additional slots and hooks, relative to the config file
+    #[serde(default)]
+    pub slots_from: Vec<String>,
     #[serde(default)]
     pub ignore: Vec<String>,
+    /// Files and directories to ignore when copying if the keyed hook is disabled
+    #[serde(default)]
+    pub ignore_if_disabled: HashMap<String, Vec<String>>,
     #[serde(default)]
     pub slots: Vec<Slot>,
+    /// Groups of slots of which at most one may be set
+    #[serde(default)]
+    pub exclusive_groups: Vec<Vec<String>>,
     #[serde(default)]
     pub hooks: Vec<Hook>,
+    /// The shell hooks run their commands with, unless they set their own
+    pub default_shell: Option<String>,
+    /// Small templates defined in the config instead of as files
+    #[serde(default)]
+    pub templates: Vec<InlineTemplate>,
+    /// Globs of the outputs the template manages, as opposed to those users are expected to edit
+    #[serde(default)]
+    pub managed: Vec<String>,
+    /// Whether templates error on undefined variables rather than rendering them empty.
+    /// Defaults to true, see [Config::strict_variables]
+    pub strict_variables: Option<bool>,
+    /// Whether fills write a log of their events to the output directory. Defaults to true,
+    /// see [Config::run_log]
+    pub run_log: Option<bool>,
+    /// Modes to set on generated files, applied in order after generation on Unix
+    #[serde(default)]
+    pub chmod: Vec<Chmod>,
+    /// Whitespace normalization of rendered files
+    #[serde(default)]
+    pub render: RenderConfig,
+}
+
+/// Whitespace normalization applied to rendered files before they're written, so generated
+/// code doesn't change as soon as it's saved with a formatter. Base64 templates are left alone.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RenderConfig {
+    /// Remove spaces and tabs at the end of lines
+    #[serde(default)]
+    pub trim_trailing_whitespace: bool,
+    /// End non-empty files with a newline
+    #[serde(default)]
+    pub ensure_final_newline: bool,
+    /// Settings for the outputs matching a glob, a later override taking precedence over an
+    /// earlier one
+    #[serde(default)]
+    pub overrides: Vec<RenderOverride>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RenderOverride {
+    /// A glob of outputs, relative to the output directory
+    pub path: String,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub ensure_final_newline: Option<bool>,
+}
+
+/// The normalization of a single output, see [RenderConfig::for_path]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Normalize {
+    pub trim_trailing_whitespace: bool,
+    pub ensure_final_newline: bool,
+}
+
+impl RenderConfig {
+    /// The normalization of an output, relative to the output directory, once overrides apply
+    pub fn for_path(&self, path: &Path) -> Normalize {
+        let mut normalize = Normalize {
+            trim_trailing_whitespace: self.trim_trailing_whitespace,
+            ensure_final_newline: self.ensure_final_newline,
+        };
+
+        for rule in &self.overrides {
+            let matches =
+                glob_set(std::slice::from_ref(&rule.path)).is_ok_and(|globs| globs.is_match(path));
+            if !matches {
+                continue;
+            }
+
+            if let Some(trim) = rule.trim_trailing_whitespace {
+                normalize.trim_trailing_whitespace = trim;
+            }
+            if let Some(newline) = rule.ensure_final_newline {
+                normalize.ensure_final_newline = newline;
+            }
+        }
+
+        normalize
+    }
+}
+
+/// A mode for the generated files matching a glob, for templates whose sources can't carry it,
+/// e.g. when checked out on Windows
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Chmod {
+    /// A glob of outputs, relative to the output directory
+    pub path: String,
+    /// An octal mode, e.g. `"0755"`
+    pub mode: String,
+}
+
+impl Chmod {
+    /// The mode as a number, if it's valid octal no greater than `7777`
+    pub fn mode(&self) -> Option<u32> {
+        u32::from_str_radix(self.mode.trim_start_matches("0o"), 8)
+            .ok()
+            .filter(|mode| *mode <= 0o7777)
+    }
 }
 
 pub const CONFIG_FILE: &str = "spackle.toml";
 
+/// The config file names a project directory may use, in order of preference
+pub const CONFIG_FILES: &[&str] = &[CONFIG_FILE, "spackle.yaml", "spackle.json"];
+
+/// A directory of executable hook scripts named `NN-key`, run after the hooks in the config
+/// in the order of their numeric prefix. It is never copied to the output.
+pub const HOOKS_DIR: &str = "hooks.d";
+
+/// How deeply `slots_from` includes may nest
+pub const MAX_INCLUDE_DEPTH: usize = 4;
+
+// A file included via `slots_from`, which may only define slots, hooks and further includes
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Include {
+    #[serde(default)]
+    slots: Vec<Slot>,
+    #[serde(default)]
+    hooks: Vec<Hook>,
+    #[serde(default)]
+    slots_from: Vec<String>,
+}
+
+// A TOML `choices_file`
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ChoicesFile {
+    choices: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectKind {
+    /// Copies and renders files into a new output directory, then runs hooks
+    #[default]
+    Template,
+    /// Only runs hooks, in an output directory that may already exist
+    Hooks,
+}
+
 #[derive(Debug)]
 pub enum Error {
+    /// The project directory or file doesn't exist
+    NotFound(PathBuf),
+    /// The project directory exists but has none of the [CONFIG_FILES], or the single file
+    /// project has no frontmatter
+    ConfigNotFound(PathBuf),
     ReadError(io::Error),
     ParseError(toml::de::Error),
+    YamlParseError(serde_yaml::Error),
+    JsonParseError(serde_json::Error),
     FronmaError(fronma::error::Error),
     DuplicateKey(String),
+    MultipleForwardArgs(Vec<String>),
+    CyclicNeeds(Vec<String>),
+    UnknownHook(String),
+    UnknownSlot(String),
+    ReservedKey(String),
+    IncompleteTemplate(String),
+    IncludeReadError(String, PathBuf, io::Error),
+    IncludeParseError(PathBuf, toml::de::Error),
+    IncludeTooDeep(PathBuf),
+    InvalidManagedPattern(String, String),
+    DefaultFileReadError(String, PathBuf, io::Error),
+    ConflictingDefault(String),
+    ChoicesFileReadError(String, PathBuf, io::Error),
+    ChoicesFileParseError(String, PathBuf, String),
+    EmptyChoicesFile(String, PathBuf),
+    ConflictingChoices(String),
+    InvalidChmod(String, String),
+    InvalidRenderOverride(String, String),
+    InvalidCopyInto(String, String),
+    /// A key the config, or one of its slots or hooks, doesn't take, by its path in the config.
+    /// Only [parse_str] reports these, loading ignores them.
+    UnknownField(String),
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Error::NotFound(path) => write!(f, "Project not found\n{}", path.display()),
+            Error::ConfigNotFound(dir) => write!(
+                f,
+                "No config file found, expected one of {}\n{}",
+                CONFIG_FILES.join(", "),
+                dir.display()
+            ),
             Error::ReadError(e) => write!(f, "Error reading file\n{}", e),
             Error::ParseError(e) => write!(f, "Error parsing contents\n{}", e),
+            Error::YamlParseError(e) => write!(f, "Error parsing YAML contents\n{}", e),
+            Error::JsonParseError(e) => write!(f, "Error parsing JSON contents\n{}", e),
             Error::FronmaError(e) => write!(f, "Error parsing single file\n{:?}", e),
             Error::DuplicateKey(e) => write!(f, "Duplicate keys found\n{}", e),
+            Error::MultipleForwardArgs(keys) => write!(
+                f,
+                "Only one hook may forward args, found {}",
+                keys.join(", ")
+            ),
+            Error::UnknownHook(key) => write!(f, "Unknown hook referenced\n{}", key),
+            Error::UnknownSlot(key) => write!(f, "Unknown slot referenced\n{}", key),
+            Error::IncompleteTemplate(dest) => write!(
+                f,
+                "Inline templates must have both a dest and content\n{}",
+                dest
+            ),
+            Error::ReservedKey(key) => write!(
+                f,
+                "Key is reserved for spackle's own context values\n{}",
+                key
+            ),
+            Error::CyclicNeeds(keys) => write!(f, "Cyclic needs found\n{}", keys.join(" -> ")),
+            Error::IncludeReadError(include, path, e) => write!(
+                f,
+                "Error reading included file {} ({})\n{}",
+                include,
+                path.display(),
+                e
+            ),
+            Error::IncludeParseError(path, e) => {
+                write!(f, "Error parsing included file {}\n{}", path.display(), e)
+            }
+            Error::IncludeTooDeep(path) => write!(
+                f,
+                "Includes are nested more than {} deep\n{}",
+                MAX_INCLUDE_DEPTH,
+                path.display()
+            ),
+            Error::InvalidManagedPattern(pattern, e) => {
+                write!(f, "Invalid managed pattern {}\n{}", pattern, e)
+            }
+            Error::DefaultFileReadError(key, path, e) => write!(
+                f,
+                "Error reading default file for slot {} ({})\n{}",
+                key,
+                path.display(),
+                e
+            ),
+            Error::ConflictingDefault(key) => write!(
+                f,
+                "Slots may set either default or default_file, not both\n{}",
+                key
+            ),
+            Error::ChoicesFileReadError(key, path, e) => write!(
+                f,
+                "Error reading choices file for slot {} ({})\n{}",
+                key,
+                path.display(),
+                e
+            ),
+            Error::ChoicesFileParseError(key, path, e) => write!(
+                f,
+                "Error parsing choices file for slot {} ({})\n{}",
+                key,
+                path.display(),
+                e
+            ),
+            Error::EmptyChoicesFile(key, path) => write!(
+                f,
+                "Choices file for slot {} has no choices\n{}",
+                key,
+                path.display()
+            ),
+            Error::ConflictingChoices(key) => write!(
+                f,
+                "Slots may set either choices or choices_file, not both\n{}",
+                key
+            ),
+            Error::InvalidChmod(path, e) => write!(f, "Invalid chmod for {}\n{}", path, e),
+            Error::InvalidRenderOverride(path, e) => {
+                write!(f, "Invalid render override for {}\n{}", path, e)
+            }
+            Error::InvalidCopyInto(key, e) => write!(f, "Invalid copy_into for {}\n{}", key, e),
+            Error::UnknownField(path) => write!(
+                f,
+                "Unknown field {}\nIt's ignored when loading. A config written for an older spackle can be upgraded with `spackle migrate`",
+                path
+            ),
         }
     }
 }
 
+impl Error {
+    /// Every code returned by [Error::code]
+    pub const CODES: &'static [&'static str] = &[
+        "config.not_found",
+        "config.config_not_found",
+        "config.read_error",
+        "config.parse_error",
+        "config.yaml_parse_error",
+        "config.json_parse_error",
+        "config.single_file_parse_error",
+        "config.duplicate_key",
+        "config.multiple_forward_args",
+        "config.cyclic_needs",
+        "config.unknown_hook",
+        "config.unknown_slot",
+        "config.reserved_key",
+        "config.incomplete_template",
+        "config.include_read_error",
+        "config.include_parse_error",
+        "config.include_too_deep",
+        "config.invalid_managed_pattern",
+        "config.default_file_read_error",
+        "config.conflicting_default",
+        "config.choices_file_read_error",
+        "config.choices_file_parse_error",
+        "config.empty_choices_file",
+        "config.conflicting_choices",
+        "config.invalid_chmod",
+        "config.invalid_render_override",
+        "config.invalid_copy_into",
+        "config.unknown_field",
+    ];
+
+    /// Whether the project is missing, as opposed to existing with an invalid config,
+    /// e.g. to respond with a 404 rather than a 400
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Error::NotFound(_) | Error::ConfigNotFound(_))
+    }
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::NotFound(_) => "config.not_found",
+            Error::ConfigNotFound(_) => "config.config_not_found",
+            Error::ReadError(_) => "config.read_error",
+            Error::ParseError(_) => "config.parse_error",
+            Error::YamlParseError(_) => "config.yaml_parse_error",
+            Error::JsonParseError(_) => "config.json_parse_error",
+            Error::FronmaError(_) => "config.single_file_parse_error",
+            Error::DuplicateKey(_) => "config.duplicate_key",
+            Error::MultipleForwardArgs(_) => "config.multiple_forward_args",
+            Error::CyclicNeeds(_) => "config.cyclic_needs",
+            Error::UnknownHook(_) => "config.unknown_hook",
+            Error::UnknownSlot(_) => "config.unknown_slot",
+            Error::ReservedKey(_) => "config.reserved_key",
+            Error::IncompleteTemplate(_) => "config.incomplete_template",
+            Error::IncludeReadError(..) => "config.include_read_error",
+            Error::IncludeParseError(..) => "config.include_parse_error",
+            Error::IncludeTooDeep(_) => "config.include_too_deep",
+            Error::InvalidManagedPattern(..) => "config.invalid_managed_pattern",
+            Error::DefaultFileReadError(..) => "config.default_file_read_error",
+            Error::ConflictingDefault(_) => "config.conflicting_default",
+            Error::ChoicesFileReadError(..) => "config.choices_file_read_error",
+            Error::ChoicesFileParseError(..) => "config.choices_file_parse_error",
+            Error::EmptyChoicesFile(..) => "config.empty_choices_file",
+            Error::ConflictingChoices(_) => "config.conflicting_choices",
+            Error::InvalidChmod(..) => "config.invalid_chmod",
+            Error::InvalidRenderOverride(..) => "config.invalid_render_override",
+            Error::InvalidCopyInto(..) => "config.invalid_copy_into",
+            Error::UnknownField(_) => "config.unknown_field",
+        }
+    }
+}
+
+/// How a project is laid out on disk
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProjectLayout {
+    /// A directory with one of the [CONFIG_FILES]
+    Directory,
+    /// A single template with its config as frontmatter
+    SingleFile,
+}
+
+impl ProjectLayout {
+    /// Works out the layout of the project at the path, failing if nothing is there or the
+    /// directory has no config file. Single files are only known to be projects once parsed.
+    pub fn detect(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+
+        if path.is_dir() {
+            return match find_config_file(path) {
+                Some(_) => Ok(ProjectLayout::Directory),
+                None => Err(Error::ConfigNotFound(path.to_path_buf())),
+            };
+        }
+
+        if !path.exists() {
+            return Err(Error::NotFound(path.to_path_buf()));
+        }
+
+        Ok(ProjectLayout::SingleFile)
+    }
+}
+
 pub fn load(path: impl AsRef<Path>) -> Result<Config, Error> {
-    if path.as_ref().is_dir() {
-        return load_dir(path);
+    match ProjectLayout::detect(&path)? {
+        ProjectLayout::Directory => load_dir(path),
+        ProjectLayout::SingleFile => load_file(path),
     }
+}
 
-    load_file(path)
+/// Finds the name of the config file in a project directory, preferring `spackle.toml`
+pub fn find_config_file(dir: impl AsRef<Path>) -> Option<&'static str> {
+    CONFIG_FILES
+        .iter()
+        .find(|name| dir.as_ref().join(name).is_file())
+        .copied()
 }
 
 // Loads the config for the given directory
 pub fn load_dir(dir: impl AsRef<Path>) -> Result<Config, Error> {
-    let config_path = dir.as_ref().join(CONFIG_FILE);
+    if !dir.as_ref().is_dir() {
+        return Err(Error::NotFound(dir.as_ref().to_path_buf()));
+    }
+
+    let config_file =
+        find_config_file(&dir).ok_or_else(|| Error::ConfigNotFound(dir.as_ref().to_path_buf()))?;
+    let config_path = dir.as_ref().join(config_file);
 
     let config_str = fs::read_to_string(config_path).map_err(Error::ReadError)?;
 
-    let config = toml::from_str(&config_str).map_err(Error::ParseError)?;
+    let mut config = match Path::new(config_file).extension().and_then(|e| e.to_str()) {
+        Some("yaml") => serde_yaml::from_str(&config_str).map_err(Error::YamlParseError)?,
+        Some("json") => serde_json::from_str(&config_str).map_err(Error::JsonParseError)?,
+        _ => parse_toml(&config_str)?,
+    };
+    config.resolve_includes(dir.as_ref())?;
+    config.resolve_deprecated();
+    config.resolve_default_files(dir.as_ref())?;
+    config.resolve_choices_files(dir.as_ref())?;
+    config.resolve_default_shell();
+    config.load_hooks_dir(dir.as_ref())?;
+
+    Ok(config)
+}
+
+/// Parses the contents of a config file without touching the filesystem, e.g. to lint it in
+/// an editor. Unlike loading, keys the config, its slots or its hooks don't take are an error,
+/// see [Error::UnknownField]. The result should be checked with `Config::validate` before use.
+pub fn parse_str(s: &str) -> Result<Config, Error> {
+    let mut config = parse_toml(s)?;
+    config.resolve_deprecated();
+
+    let table = s.parse::<toml::Table>().map_err(Error::ParseError)?;
+    if let Some(path) = unknown_fields(&table).into_iter().next() {
+        return Err(Error::UnknownField(path));
+    }
 
     Ok(config)
 }
 
+// Parses a TOML config, ignoring keys it doesn't take so configs from other versions of
+// spackle still load
+fn parse_toml(s: &str) -> Result<Config, Error> {
+    toml::from_str(s).map_err(Error::ParseError)
+}
+
+// The paths of the keys in a TOML config that the config, its slots and its hooks don't take,
+// e.g. `hooks[0].optionl`
+fn unknown_fields(table: &toml::Table) -> Vec<String> {
+    let unknown = |table: &toml::Table, fields: &[&str], prefix: &str| {
+        table
+            .keys()
+            .filter(|key| !fields.contains(&key.as_str()))
+            .map(|key| format!("{}{}", prefix, key))
+            .collect::<Vec<_>>()
+    };
+
+    let mut paths = unknown(table, struct_fields::<Config>(), "");
+    for (key, fields) in [
+        ("slots", struct_fields::<Slot>()),
+        ("hooks", struct_fields::<Hook>()),
+    ] {
+        let items = table.get(key).and_then(|items| items.as_array());
+        for (i, item) in items.into_iter().flatten().enumerate() {
+            if let Some(item) = item.as_table() {
+                paths.extend(unknown(item, fields, &format!("{}[{}].", key, i)));
+            }
+        }
+    }
+
+    paths
+}
+
+// The names of the fields a struct deserializes, aliases included, as its derived
+// Deserialize impl passes them to the deserializer. Asking for them this way keeps
+// [unknown_fields] in step with the structs.
+fn struct_fields<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    struct FieldsDeserializer<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> serde::Deserializer<'de> for FieldsDeserializer<'_> {
+        type Error = serde::de::value::Error;
+
+        fn deserialize_any<V: serde::de::Visitor<'de>>(
+            self,
+            _: V,
+        ) -> Result<V::Value, Self::Error> {
+            Err(serde::de::Error::custom("only structs have fields"))
+        }
+
+        fn deserialize_struct<V: serde::de::Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            self.deserialize_any(visitor)
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
+            byte_buf option unit unit_struct newtype_struct seq tuple tuple_struct map enum
+            identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldsDeserializer(&mut fields));
+
+    fields
+}
+
 pub fn load_file(file: impl AsRef<Path>) -> Result<Config, Error> {
-    let file_contents = fs::read_to_string(file).map_err(Error::ReadError)?;
+    let file_contents = fs::read_to_string(&file).map_err(Error::ReadError)?;
 
-    parse_with_engine::<Config, Toml>(&file_contents)
+    let mut config = parse_with_engine::<Config, Toml>(&file_contents)
         .map(|parsed| parsed.headers)
-        .map_err(Error::FronmaError)
+        .map_err(|e| match e {
+            // A file without frontmatter is just a file, not a single file project
+            fronma::error::Error::MissingBeginningLine => {
+                Error::ConfigNotFound(file.as_ref().to_path_buf())
+            }
+            e => Error::FronmaError(e),
+        })?;
+    let dir = file.as_ref().parent().unwrap_or(Path::new("."));
+    config.resolve_includes(dir)?;
+    config.resolve_deprecated();
+    config.resolve_default_files(dir)?;
+    config.resolve_choices_files(dir)?;
+    config.resolve_default_shell();
+
+    Ok(config)
+}
+
+// Appends the slots and hooks from the included files, resolving paths relative to base_dir
+fn load_includes(
+    includes: &[String],
+    base_dir: &Path,
+    depth: usize,
+    slots: &mut Vec<Slot>,
+    hooks: &mut Vec<Hook>,
+) -> Result<(), Error> {
+    for include in includes {
+        let path = base_dir.join(include);
+
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(Error::IncludeTooDeep(path));
+        }
+
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| Error::IncludeReadError(include.clone(), path.clone(), e))?;
+        let included: Include =
+            toml::from_str(&contents).map_err(|e| Error::IncludeParseError(path.clone(), e))?;
+
+        slots.extend(included.slots);
+        hooks.extend(included.hooks);
+
+        load_includes(
+            &included.slots_from,
+            path.parent().unwrap_or(base_dir),
+            depth + 1,
+            slots,
+            hooks,
+        )?;
+    }
+
+    Ok(())
+}
+
+// Parses a hooks directory file name of the form `NN-key` into its order and key
+fn parse_hook_script_name(name: &str) -> Option<(u64, &str)> {
+    let (order, key) = name.split_once('-')?;
+
+    match (order.parse::<u64>(), key.is_empty()) {
+        (Ok(order), false) => Some((order, key)),
+        _ => None,
+    }
 }
 
 impl Config {
+    /// Appends a hook for each executable script in the project's hooks directory, if it has one.
+    /// Files that aren't executable or aren't named `NN-key` are ignored.
+    pub fn load_hooks_dir(&mut self, dir: &Path) -> Result<(), Error> {
+        let hooks_dir = dir.join(HOOKS_DIR);
+        if !hooks_dir.is_dir() {
+            return Ok(());
+        }
+
+        let mut scripts = Vec::new();
+        for entry in fs::read_dir(&hooks_dir).map_err(Error::ReadError)? {
+            let entry = entry.map_err(Error::ReadError)?;
+            let metadata = entry.metadata().map_err(Error::ReadError)?;
+            let file_name = entry.file_name().to_string_lossy().to_string();
+
+            if !metadata.is_file() || metadata.permissions().mode() & 0o111 == 0 {
+                continue;
+            }
+
+            if let Some((order, key)) = parse_hook_script_name(&file_name) {
+                scripts.push((order, key.to_string(), entry.path()));
+            }
+        }
+        scripts.sort();
+
+        for (_, key, path) in scripts {
+            if self.hooks.iter().any(|hook| hook.key == key) {
+                return Err(Error::DuplicateKey(format!(
+                    "{} (from {})",
+                    key,
+                    path.display()
+                )));
+            }
+
+            // Hooks run in the output directory, so the script is referenced by its absolute path
+            let path = fs::canonicalize(&path).map_err(Error::ReadError)?;
+
+            self.hooks.push(Hook {
+                key,
+                command: vec![path.to_string_lossy().to_string()],
+                from_hooks_dir: true,
+                ..Hook::default()
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Reads each slot's `default_file` into its `default`. Paths are relative to the project
+    /// directory, not the working directory, so a project behaves the same wherever it's run from.
+    pub fn resolve_default_files(&mut self, dir: &Path) -> Result<(), Error> {
+        for slot in &mut self.slots {
+            let Some(default_file) = &slot.default_file else {
+                continue;
+            };
+
+            if slot.default.is_some() {
+                return Err(Error::ConflictingDefault(slot.key.clone()));
+            }
+
+            let path = dir.join(default_file);
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| Error::DefaultFileReadError(slot.key.clone(), path.clone(), e))?;
+
+            // Files conventionally end in a newline that isn't part of the value
+            let contents = contents
+                .strip_suffix('\n')
+                .map(|s| s.strip_suffix('\r').unwrap_or(s))
+                .unwrap_or(&contents);
+            slot.default = Some(contents.to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Reads each slot's `choices_file` into its `choices`. A `.json` file holds a list of
+    /// strings, a `.toml` file a `choices` list, and any other file one choice per line, with
+    /// blank lines and lines starting with `#` skipped. Paths are relative to the project directory.
+    pub fn resolve_choices_files(&mut self, dir: &Path) -> Result<(), Error> {
+        for slot in &mut self.slots {
+            let Some(choices_file) = &slot.choices_file else {
+                continue;
+            };
+
+            if !slot.choices.is_empty() {
+                return Err(Error::ConflictingChoices(slot.key.clone()));
+            }
+
+            let path = dir.join(choices_file);
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| Error::ChoicesFileReadError(slot.key.clone(), path.clone(), e))?;
+            let parse_error =
+                |e: String| Error::ChoicesFileParseError(slot.key.clone(), path.clone(), e);
+
+            let choices = match path.extension().and_then(|e| e.to_str()) {
+                Some("json") => serde_json::from_str::<Vec<String>>(&contents)
+                    .map_err(|e| parse_error(e.to_string()))?,
+                Some("toml") => {
+                    toml::from_str::<ChoicesFile>(&contents)
+                        .map_err(|e| parse_error(e.to_string()))?
+                        .choices
+                }
+                _ => contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_string)
+                    .collect(),
+            };
+
+            if choices.is_empty() {
+                return Err(Error::EmptyChoicesFile(slot.key.clone(), path));
+            }
+            slot.choices = choices;
+        }
+
+        Ok(())
+    }
+
+    /// Sets `default_shell` as the shell of each hook that doesn't set its own.
+    /// Scripts in the hooks directory are executables, so they're loaded after this and left as is.
+    pub fn resolve_default_shell(&mut self) {
+        if let Some(default_shell) = &self.default_shell {
+            for hook in &mut self.hooks {
+                hook.shell.get_or_insert(default_shell.clone());
+            }
+        }
+    }
+
+    /// Reads the deprecated `optional` of hooks as their `default`, for configs written before
+    /// it moved onto the hook. A `default` set alongside it wins.
+    pub fn resolve_deprecated(&mut self) {
+        for hook in &mut self.hooks {
+            if let Some(optional) = &hook.optional {
+                hook.default = hook.default.or(optional.default());
+            }
+        }
+    }
+
+    /// Appends the slots and hooks from `slots_from` includes, relative to the config's directory.
+    /// Duplicate keys across files are caught by `validate`.
+    pub fn resolve_includes(&mut self, dir: &Path) -> Result<(), Error> {
+        load_includes(&self.slots_from, dir, 0, &mut self.slots, &mut self.hooks)
+    }
+
     pub fn validate(&self) -> Result<(), Error> {
         let hook_keys: HashSet<&String> = self.hooks.iter().map(|hook| &hook.key).collect();
         let slot_keys: HashSet<&String> = self.slots.iter().map(|slot| &slot.key).collect();
@@ -94,8 +798,227 @@ impl Config {
             ));
         }
 
+        // Reserved keys would shadow (or be shadowed by) injected context values
+        if let Some(key) = self
+            .slots
+            .iter()
+            .map(|slot| &slot.key)
+            .chain(self.hooks.iter().map(|hook| &hook.key))
+            .find(|key| is_reserved_key(key))
+        {
+            return Err(Error::ReservedKey(key.clone()));
+        }
+
+        // Inline templates are identified by their destination
+        if let Some((i, template)) = self
+            .templates
+            .iter()
+            .enumerate()
+            .find(|(_, template)| template.dest.is_none() || template.content.is_none())
+        {
+            return Err(Error::IncompleteTemplate(
+                template.dest.clone().unwrap_or(format!("templates[{}]", i)),
+            ));
+        }
+
+        let mut template_dests = HashSet::new();
+        if let Some(dest) = self
+            .templates
+            .iter()
+            .filter_map(|template| template.dest.as_ref())
+            .find(|dest| !template_dests.insert(*dest))
+        {
+            return Err(Error::DuplicateKey(dest.clone()));
+        }
+
+        // Forwarded args can only be routed to a single hook
+        let forwarding_hooks: Vec<String> = self
+            .hooks
+            .iter()
+            .filter(|hook| hook.forward_args)
+            .map(|hook| hook.key.clone())
+            .collect();
+
+        if forwarding_hooks.len() > 1 {
+            return Err(Error::MultipleForwardArgs(forwarding_hooks));
+        }
+
+        // Sort so the same error is always reported
+        let mut ignore_hooks = self.ignore_if_disabled.keys().collect::<Vec<_>>();
+        ignore_hooks.sort();
+        if let Some(key) = ignore_hooks
+            .into_iter()
+            .find(|key| !hook_keys.contains(key))
+        {
+            return Err(Error::UnknownHook(key.clone()));
+        }
+
+        if let Some(key) = self
+            .slots
+            .iter()
+            .flat_map(|slot| &slot.needed_by)
+            .find(|key| !hook_keys.contains(key))
+        {
+            return Err(Error::UnknownHook(key.clone()));
+        }
+
+        if let Some(key) = self
+            .exclusive_groups
+            .iter()
+            .flatten()
+            .find(|key| !slot_keys.contains(key))
+        {
+            return Err(Error::UnknownSlot(key.clone()));
+        }
+
+        let needs_graph: HashMap<String, Vec<String>> = self
+            .slots
+            .iter()
+            .map(|slot| (slot.key.clone(), slot.needs.clone()))
+            .chain(
+                self.hooks
+                    .iter()
+                    .map(|hook| (hook.key.clone(), hook.needs.clone())),
+            )
+            .collect();
+
+        if let Some(cycle) = needs::find_cycle(&needs_graph) {
+            return Err(Error::CyclicNeeds(cycle));
+        }
+
+        for pattern in &self.managed {
+            glob_set(std::slice::from_ref(pattern))
+                .map_err(|e| Error::InvalidManagedPattern(pattern.clone(), e.to_string()))?;
+        }
+
+        for chmod in &self.chmod {
+            glob_set(std::slice::from_ref(&chmod.path))
+                .map_err(|e| Error::InvalidChmod(chmod.path.clone(), e.to_string()))?;
+
+            if chmod.mode().is_none() {
+                return Err(Error::InvalidChmod(
+                    chmod.path.clone(),
+                    format!("{} is not an octal mode", chmod.mode),
+                ));
+            }
+        }
+
+        for rule in &self.render.overrides {
+            glob_set(std::slice::from_ref(&rule.path))
+                .map_err(|e| Error::InvalidRenderOverride(rule.path.clone(), e.to_string()))?;
+        }
+
+        for slot in &self.slots {
+            let Some(copy_into) = &slot.copy_into else {
+                continue;
+            };
+
+            if !matches!(slot.r#type, SlotType::Path) {
+                return Err(Error::InvalidCopyInto(
+                    slot.key.clone(),
+                    "only Path slots can be copied into the output".to_string(),
+                ));
+            }
+
+            if !is_within(&Path::new(".").join(copy_into), Path::new(".")) {
+                return Err(Error::InvalidCopyInto(
+                    slot.key.clone(),
+                    format!("{} is outside the output directory", copy_into),
+                ));
+            }
+        }
+
         Ok(())
     }
+
+    /// Whether templates error on undefined variables, which they do unless the config sets
+    /// `strict_variables = false`
+    pub fn strict_variables(&self) -> bool {
+        self.strict_variables.unwrap_or(true)
+    }
+
+    /// Whether fills write a run log, which they do unless the config sets `run_log = false`
+    pub fn run_log(&self) -> bool {
+        self.run_log.unwrap_or(true)
+    }
+
+    /// Finds the `managed` patterns that don't match any of the project's outputs. Outputs are
+    /// the files the project copies or renders, with template extensions removed, along with
+    /// inline templates, so templated names are taken as written.
+    pub fn unmatched_managed(&self, dir: &Path) -> Vec<&String> {
+        let entries = Classifier::new(dir, &self.ignore)
+            .and_then(|classifier| classifier.entries(dir))
+            .unwrap_or_default();
+
+        let outputs = entries
+            .into_iter()
+            .filter(|entry| !entry.is_dir)
+            .filter_map(|entry| match entry.class {
+                FileClass::Static => Some(entry.path),
+                FileClass::Template => {
+                    let relative = entry.path.to_string_lossy();
+                    let stripped = relative.strip_suffix(TEMPLATE_EXT).unwrap_or(&relative);
+                    Some(PathBuf::from(
+                        stripped.strip_suffix(BASE64_EXT).unwrap_or(stripped),
+                    ))
+                }
+                FileClass::Ignored | FileClass::Project => None,
+            })
+            .chain(
+                self.templates
+                    .iter()
+                    .filter_map(|template| template.dest.as_ref().map(PathBuf::from)),
+            )
+            .collect::<Vec<_>>();
+
+        self.managed
+            .iter()
+            .filter(|pattern| {
+                glob_set(std::slice::from_ref(pattern))
+                    .is_ok_and(|globs| !outputs.iter().any(|output| globs.is_match(output)))
+            })
+            .collect()
+    }
+
+    /// Gets the entries to ignore when copying, including those tied to hooks that are disabled
+    /// given the provided data
+    pub fn ignored(&self, data: &HashMap<String, String>) -> Vec<String> {
+        let mut ignored = self.ignore.clone();
+
+        for hook in &self.hooks {
+            if let Some(entries) = self.ignore_if_disabled.get(&hook.key) {
+                if !hook.is_enabled(data) {
+                    ignored.extend(entries.iter().cloned());
+                }
+            }
+        }
+
+        // Choices files inside the project are read into the config rather than written out.
+        // They come last, so no `!` entry re-includes them.
+        ignored.extend(
+            self.slots
+                .iter()
+                .filter_map(|slot| slot.choices_file.as_deref())
+                .filter_map(literal_glob),
+        );
+
+        ignored
+    }
+}
+
+// A glob matching only the path, relative to the project directory, or None when the path
+// leads outside of it
+fn literal_glob(path: &str) -> Option<String> {
+    let parts = Path::new(path)
+        .components()
+        .filter(|component| component != &Component::CurDir)
+        .map(|component| match component {
+            Component::Normal(part) => Some(globset::escape(&part.to_string_lossy())),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(parts.join("/"))
 }
 
 #[cfg(test)]
@@ -115,6 +1038,66 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn not_found() {
+        let dir = TempDir::new("spackle").unwrap();
+
+        let missing = dir.path().join("missing");
+        assert!(matches!(load(&missing), Err(Error::NotFound(path)) if path == missing));
+        assert!(matches!(load_dir(&missing), Err(Error::NotFound(path)) if path == missing));
+
+        assert!(matches!(
+            load(dir.path()),
+            Err(Error::ConfigNotFound(path)) if path == dir.path()
+        ));
+
+        fs::write(dir.path().join("spackle.toml"), "slots = 1").unwrap();
+        let result = load(dir.path());
+        assert!(matches!(&result, Err(Error::ParseError(_))));
+        assert!(!result.unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn detect_layout() {
+        let dir = TempDir::new("spackle").unwrap();
+
+        assert!(matches!(
+            ProjectLayout::detect(dir.path().join("missing")),
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            ProjectLayout::detect(dir.path()),
+            Err(Error::ConfigNotFound(_))
+        ));
+        assert_eq!(
+            ProjectLayout::detect("tests/data/proj1").unwrap(),
+            ProjectLayout::Directory
+        );
+        assert_eq!(
+            ProjectLayout::detect("tests/data/single_file.j2t").unwrap(),
+            ProjectLayout::SingleFile
+        );
+        assert!(load("tests/data/single_file.j2t").is_ok());
+    }
+
+    #[test]
+    fn single_file_not_found() {
+        let dir = TempDir::new("spackle").unwrap();
+
+        let missing = dir.path().join("missing.j2");
+        assert!(matches!(load(&missing), Err(Error::NotFound(path)) if path == missing));
+
+        let plain = dir.path().join("plain.j2");
+        fs::write(&plain, "Hello {{ name }}").unwrap();
+        let result = load(&plain);
+        assert!(matches!(&result, Err(Error::ConfigNotFound(path)) if *path == plain));
+        assert!(result.unwrap_err().is_not_found());
+
+        let invalid = dir.path().join("invalid.j2");
+        fs::write(&invalid, "---\nslots = 1\n---\nHello").unwrap();
+        assert!(matches!(load(&invalid), Err(Error::FronmaError(_))));
+    }
+
     #[test]
     fn dup_key() {
         let dir = Path::new("tests/data/conf_dup_key");
@@ -123,4 +1106,712 @@ mod tests {
 
         config.validate().expect_err("Expected error");
     }
+
+    #[test]
+    fn includes() {
+        let config = load_dir("tests/data/includes").expect("Expected ok");
+
+        assert_eq!(
+            config
+                .slots
+                .iter()
+                .map(|s| s.key.as_str())
+                .collect::<Vec<_>>(),
+            vec!["project_name", "db_host", "db_port", "ci_provider"]
+        );
+        assert_eq!(
+            config
+                .hooks
+                .iter()
+                .map(|h| h.key.as_str())
+                .collect::<Vec<_>>(),
+            vec!["setup_ci"]
+        );
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn includes_dup_key() {
+        let dir = TempDir::new("spackle").unwrap().into_path();
+
+        fs::write(
+            dir.join("spackle.toml"),
+            "slots_from = [\"more.toml\"]\n[[slots]]\nkey = \"a\"",
+        )
+        .unwrap();
+        fs::write(dir.join("more.toml"), "[[slots]]\nkey = \"a\"").unwrap();
+
+        let config = load_dir(&dir).expect("Expected ok");
+
+        config.validate().expect_err("Expected error");
+    }
+
+    #[test]
+    fn includes_missing() {
+        let dir = TempDir::new("spackle").unwrap().into_path();
+
+        fs::write(dir.join("spackle.toml"), "slots_from = [\"missing.toml\"]").unwrap();
+
+        assert!(matches!(
+            load_dir(&dir),
+            Err(Error::IncludeReadError(include, path, _))
+                if include == "missing.toml" && path == dir.join("missing.toml")
+        ));
+    }
+
+    #[test]
+    fn includes_too_deep() {
+        let dir = TempDir::new("spackle").unwrap().into_path();
+
+        // A file that includes itself would otherwise recurse forever
+        fs::write(dir.join("spackle.toml"), "slots_from = [\"self.toml\"]").unwrap();
+        fs::write(dir.join("self.toml"), "slots_from = [\"self.toml\"]").unwrap();
+
+        assert!(matches!(load_dir(&dir), Err(Error::IncludeTooDeep(_))));
+    }
+
+    #[test]
+    fn formats() {
+        let load = |file: &str, contents: &str| {
+            let dir = TempDir::new("spackle").unwrap().into_path();
+            fs::write(dir.join(file), contents).unwrap();
+
+            let config = load_dir(&dir).expect("Expected ok");
+            config.validate().expect("Expected ok");
+            format!("{:?}", config)
+        };
+
+        let toml = load(
+            "spackle.toml",
+            r#"
+            name = "project"
+            ignore = ["*.log"]
+
+            [[slots]]
+            key = "name"
+            default = "spackle"
+
+            [[hooks]]
+            key = "init"
+            command = ["git", "init"]
+            default = false
+            "#,
+        );
+        let yaml = load(
+            "spackle.yaml",
+            r#"
+name: project
+ignore: ["*.log"]
+slots:
+  - key: name
+    default: spackle
+hooks:
+  - key: init
+    command: [git, init]
+    default: false
+"#,
+        );
+        let json = load(
+            "spackle.json",
+            r#"{
+                "name": "project",
+                "ignore": ["*.log"],
+                "slots": [{ "key": "name", "default": "spackle" }],
+                "hooks": [{ "key": "init", "command": ["git", "init"], "default": false }]
+            }"#,
+        );
+
+        assert_eq!(toml, yaml);
+        assert_eq!(toml, json);
+    }
+
+    #[test]
+    fn formats_prefer_toml() {
+        let dir = TempDir::new("spackle").unwrap().into_path();
+
+        fs::write(dir.join("spackle.toml"), "name = \"toml\"").unwrap();
+        fs::write(dir.join("spackle.json"), "{ \"name\": \"json\" }").unwrap();
+        assert_eq!(find_config_file(&dir), Some(CONFIG_FILE));
+        assert_eq!(load_dir(&dir).unwrap().name, Some("toml".to_string()));
+
+        fs::remove_file(dir.join("spackle.toml")).unwrap();
+        assert_eq!(load_dir(&dir).unwrap().name, Some("json".to_string()));
+
+        fs::write(dir.join("spackle.json"), "{ \"name\": ").unwrap();
+        assert!(matches!(load_dir(&dir), Err(Error::JsonParseError(_))));
+    }
+
+    #[test]
+    fn default_file() {
+        let dir = TempDir::new("spackle").unwrap().into_path();
+
+        fs::write(
+            dir.join("spackle.toml"),
+            "[[slots]]\nkey = \"config\"\ndefault_file = \"defaults/config.yaml\"",
+        )
+        .unwrap();
+        fs::create_dir(dir.join("defaults")).unwrap();
+        fs::write(dir.join("defaults/config.yaml"), "name: spackle\n").unwrap();
+
+        // The path only exists relative to the project, not the working directory
+        assert!(!Path::new("defaults/config.yaml").exists());
+
+        let config = load_dir(&dir).expect("Expected ok");
+        assert_eq!(config.slots[0].default, Some("name: spackle".to_string()));
+
+        fs::write(
+            dir.join("spackle.toml"),
+            "[[slots]]\nkey = \"config\"\ndefault_file = \"missing.yaml\"",
+        )
+        .unwrap();
+        assert!(matches!(
+            load_dir(&dir),
+            Err(Error::DefaultFileReadError(key, path, _))
+                if key == "config" && path == dir.join("missing.yaml")
+        ));
+
+        fs::write(
+            dir.join("spackle.toml"),
+            "[[slots]]\nkey = \"config\"\ndefault = \"x\"\ndefault_file = \"defaults/config.yaml\"",
+        )
+        .unwrap();
+        assert!(matches!(load_dir(&dir), Err(Error::ConflictingDefault(_))));
+    }
+
+    #[test]
+    fn choices_file() {
+        let dir = TempDir::new("spackle").unwrap().into_path();
+        let config_with = |file: &str| {
+            format!("[[slots]]\nkey = \"licenses\"\ntype = \"List\"\nchoices_file = \"{file}\"")
+        };
+
+        fs::write(
+            dir.join("licenses.txt"),
+            "# SPDX ids\nMIT\n\n  Apache-2.0 \n",
+        )
+        .unwrap();
+        fs::write(dir.join("licenses.json"), r#"["MIT", "Apache-2.0"]"#).unwrap();
+        fs::write(
+            dir.join("licenses.toml"),
+            "choices = [\"MIT\", \"Apache-2.0\"]",
+        )
+        .unwrap();
+        fs::write(dir.join("empty.txt"), "# nothing yet\n").unwrap();
+        fs::write(dir.join("invalid.json"), "{}").unwrap();
+
+        for file in ["licenses.txt", "licenses.json", "licenses.toml"] {
+            fs::write(dir.join("spackle.toml"), config_with(file)).unwrap();
+            let config = load_dir(&dir).expect("Expected ok");
+            assert_eq!(config.slots[0].choices, vec!["MIT", "Apache-2.0"], "{file}");
+        }
+
+        fs::write(dir.join("spackle.toml"), config_with("missing.txt")).unwrap();
+        assert!(matches!(
+            load_dir(&dir),
+            Err(Error::ChoicesFileReadError(key, path, _))
+                if key == "licenses" && path == dir.join("missing.txt")
+        ));
+
+        fs::write(dir.join("spackle.toml"), config_with("invalid.json")).unwrap();
+        assert!(matches!(
+            load_dir(&dir),
+            Err(Error::ChoicesFileParseError(..))
+        ));
+
+        fs::write(dir.join("spackle.toml"), config_with("empty.txt")).unwrap();
+        assert!(matches!(load_dir(&dir), Err(Error::EmptyChoicesFile(..))));
+
+        fs::write(
+            dir.join("spackle.toml"),
+            config_with("licenses.txt") + "\nchoices = [\"MIT\"]",
+        )
+        .unwrap();
+        assert!(matches!(load_dir(&dir), Err(Error::ConflictingChoices(_))));
+    }
+
+    #[test]
+    fn hooks_dir() {
+        let config = load_dir("tests/data/hooks_dir").expect("Expected ok");
+        config.validate().expect("Expected ok");
+
+        // Scripts come after the config's hooks, ordered by their numeric prefix
+        assert_eq!(
+            config
+                .hooks
+                .iter()
+                .map(|hook| (hook.key.as_str(), hook.from_hooks_dir))
+                .collect::<Vec<_>>(),
+            vec![("from_toml", false), ("first", true), ("setup", true)]
+        );
+
+        let command = PathBuf::from(&config.hooks[1].command[0]);
+        assert!(command.is_absolute());
+        assert!(command.ends_with("hooks.d/2-first"));
+    }
+
+    #[test]
+    fn hooks_dir_duplicate_key() {
+        let dir = TempDir::new("spackle").unwrap().into_path();
+
+        fs::write(
+            dir.join("spackle.toml"),
+            "[[hooks]]\nkey = \"setup\"\ncommand = [\"true\"]",
+        )
+        .unwrap();
+        fs::create_dir(dir.join(HOOKS_DIR)).unwrap();
+        let script = dir.join(HOOKS_DIR).join("1-setup");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert!(matches!(
+            load_dir(&dir),
+            Err(Error::DuplicateKey(key)) if key.starts_with("setup")
+        ));
+    }
+
+    #[test]
+    fn parse_str_valid() {
+        let config = parse_str(
+            r#"
+            name = "project"
+
+            [[slots]]
+            key = "a"
+
+            [[hooks]]
+            key = "b"
+            command = ["echo"]
+            needs = ["a"]
+            "#,
+        )
+        .expect("Expected ok");
+
+        config.validate().expect("Expected ok");
+    }
+
+    #[test]
+    fn parse_str_unknown_field() {
+        let result = parse_str(
+            r#"
+            [[slots]]
+            key = "a"
+            unknown = true
+            "#,
+        );
+
+        match result {
+            Err(Error::UnknownField(path)) => assert_eq!(path, "slots[0].unknown"),
+            result => panic!("Expected unknown field error, got {:?}", result),
+        }
+
+        // Aliases and hooks' deprecated optional are known
+        parse_str(
+            r#"
+            [[slots]]
+            key = "a"
+            type = "Choice"
+            options = ["x"]
+
+            [[hooks]]
+            key = "b"
+            command = ["true"]
+            optional = { default = false }
+            "#,
+        )
+        .expect("Expected ok");
+    }
+
+    #[test]
+    fn unknown_fields_load() {
+        let dir = TempDir::new("spackle").unwrap();
+        fs::write(
+            dir.path().join("spackle.toml"),
+            "unknown = 1\n[[hooks]]\nkey = \"a\"\ncommand = [\"true\"]\noptionl = true\n",
+        )
+        .unwrap();
+
+        // Loading ignores what parse_str reports
+        load_dir(dir.path()).expect("Expected unknown fields to be ignored when loading");
+        let contents = fs::read_to_string(dir.path().join("spackle.toml")).unwrap();
+        assert_eq!(
+            unknown_fields(&contents.parse().unwrap()),
+            vec!["unknown", "hooks[0].optionl"]
+        );
+        assert!(parse_str(&contents)
+            .unwrap_err()
+            .to_string()
+            .contains("spackle migrate"));
+    }
+
+    #[test]
+    fn deprecated_optional() {
+        let config = parse_str(
+            r#"
+            [[hooks]]
+            key = "table"
+            command = ["true"]
+            optional = { default = false }
+
+            [[hooks]]
+            key = "bool"
+            command = ["true"]
+            optional = false
+
+            [[hooks]]
+            key = "both"
+            command = ["true"]
+            default = true
+            optional = { default = false }
+
+            [[hooks]]
+            key = "neither"
+            command = ["true"]
+            "#,
+        )
+        .expect("Expected ok");
+
+        assert_eq!(
+            config
+                .hooks
+                .iter()
+                .map(|hook| hook.default)
+                .collect::<Vec<_>>(),
+            vec![Some(false), Some(false), Some(true), None]
+        );
+
+        // Fixtures written with it still load, with it read as the default
+        let config = load_dir("tests/data/proj1").unwrap();
+        assert_eq!(config.hooks[0].default, Some(false));
+        assert_eq!(config.hooks[1].default, Some(true));
+    }
+
+    #[test]
+    fn parse_str_dup_key() {
+        let config = parse_str(
+            r#"
+            [[slots]]
+            key = "a"
+
+            [[hooks]]
+            key = "a"
+            command = ["echo"]
+            "#,
+        )
+        .expect("Expected ok");
+
+        assert!(matches!(config.validate(), Err(Error::DuplicateKey(_))));
+    }
+
+    #[test]
+    fn parse_str_cyclic_needs() {
+        let config = parse_str(
+            r#"
+            [[slots]]
+            key = "a"
+            needs = ["c"]
+
+            [[hooks]]
+            key = "b"
+            command = ["echo"]
+            needs = ["a"]
+
+            [[hooks]]
+            key = "c"
+            command = ["echo"]
+            needs = ["b"]
+            "#,
+        )
+        .expect("Expected ok");
+
+        match config.validate() {
+            Err(Error::CyclicNeeds(cycle)) => {
+                assert_eq!(cycle, vec!["a", "c", "b", "a"]);
+            }
+            result => panic!("Expected cyclic needs error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn ignore_if_disabled() {
+        let config = parse_str(
+            r#"
+            ignore = [".git"]
+            ignore_if_disabled = { docker = ["Dockerfile", ".dockerignore"], ci = [".github"] }
+
+            [[hooks]]
+            key = "docker"
+            command = ["true"]
+
+            [[hooks]]
+            key = "ci"
+            command = ["true"]
+            default = false
+            "#,
+        )
+        .expect("Expected ok");
+
+        config.validate().expect("Expected ok");
+
+        assert_eq!(config.ignored(&HashMap::new()), vec![".git", ".github"]);
+
+        assert_eq!(
+            config.ignored(&HashMap::from([
+                ("docker".to_string(), "false".to_string()),
+                ("ci".to_string(), "true".to_string()),
+            ])),
+            vec![".git", "Dockerfile", ".dockerignore"]
+        );
+    }
+
+    #[test]
+    fn choices_files_ignored() {
+        let config = parse_str(
+            r#"
+            ignore = ["!*.txt"]
+
+            [[slots]]
+            key = "license"
+            choices_file = "./shared/licenses[1].txt"
+
+            [[slots]]
+            key = "version"
+            choices_file = "../versions.txt"
+            "#,
+        )
+        .expect("Expected ok");
+
+        // Files outside the project aren't copied anyway
+        assert_eq!(
+            config.ignored(&HashMap::new()),
+            vec!["!*.txt", "shared/licenses[[]1[]].txt"]
+        );
+    }
+
+    #[test]
+    fn ignore_if_disabled_unknown_hook() {
+        let config = parse_str(
+            r#"
+            ignore_if_disabled = { docker = ["Dockerfile"] }
+            "#,
+        )
+        .expect("Expected ok");
+
+        assert!(matches!(config.validate(), Err(Error::UnknownHook(_))));
+    }
+
+    #[test]
+    fn needed_by_unknown_hook() {
+        let config = parse_str(
+            r#"
+            [[slots]]
+            key = "docker_base_image"
+            needed_by = ["docker"]
+            "#,
+        )
+        .expect("Expected ok");
+
+        assert!(matches!(config.validate(), Err(Error::UnknownHook(key)) if key == "docker"));
+    }
+
+    #[test]
+    fn incomplete_template() {
+        let config = parse_str(
+            r#"
+            [[templates]]
+            dest = ".python-version"
+            "#,
+        )
+        .expect("Expected ok");
+
+        assert!(
+            matches!(config.validate(), Err(Error::IncompleteTemplate(dest)) if dest == ".python-version")
+        );
+
+        let config = parse_str(
+            r#"
+            [[templates]]
+            content = "3.12"
+            "#,
+        )
+        .expect("Expected ok");
+
+        assert!(
+            matches!(config.validate(), Err(Error::IncompleteTemplate(dest)) if dest == "templates[0]")
+        );
+
+        let config = parse_str(
+            r#"
+            [[templates]]
+            dest = "a"
+            content = "1"
+
+            [[templates]]
+            dest = "a"
+            content = "2"
+            "#,
+        )
+        .expect("Expected ok");
+
+        assert!(matches!(config.validate(), Err(Error::DuplicateKey(dest)) if dest == "a"));
+    }
+
+    #[test]
+    fn reserved_key() {
+        let config = parse_str(
+            r#"
+            [[slots]]
+            key = "_project_name"
+            "#,
+        )
+        .expect("Expected ok");
+
+        assert!(
+            matches!(config.validate(), Err(Error::ReservedKey(key)) if key == "_project_name")
+        );
+
+        let config = parse_str(
+            r#"
+            [[hooks]]
+            key = "_tmp_dir"
+            command = ["true"]
+            "#,
+        )
+        .expect("Expected ok");
+
+        assert!(matches!(config.validate(), Err(Error::ReservedKey(key)) if key == "_tmp_dir"));
+
+        // Reserved prefixes, whether or not spackle sets the key today
+        for key in ["_private", "hook_ran_docker", "hook_enabled_docker"] {
+            let config = parse_str(&format!(
+                "[[slots]]\nkey = \"{key}\"\n\n[[hooks]]\nkey = \"other\"\ncommand = [\"true\"]\n"
+            ))
+            .expect("Expected ok");
+            assert!(
+                matches!(config.validate(), Err(Error::ReservedKey(reserved)) if reserved == key),
+                "Expected {} to be reserved",
+                key
+            );
+        }
+
+        // Keys that only contain a reserved prefix are fine
+        let config = parse_str(
+            r#"
+            [[slots]]
+            key = "my_hook_ran_"
+
+            [[hooks]]
+            key = "hook_1"
+            command = ["true"]
+            "#,
+        )
+        .expect("Expected ok");
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn exclusive_groups_unknown_slot() {
+        let config = parse_str(
+            r#"
+            exclusive_groups = [["a", "b"]]
+
+            [[slots]]
+            key = "a"
+            "#,
+        )
+        .expect("Expected ok");
+
+        assert!(matches!(config.validate(), Err(Error::UnknownSlot(_))));
+    }
+
+    #[test]
+    fn multiple_forward_args() {
+        let config = parse_str(
+            r#"
+            [[hooks]]
+            key = "a"
+            command = ["echo"]
+            forward_args = true
+
+            [[hooks]]
+            key = "b"
+            command = ["echo"]
+            forward_args = true
+            "#,
+        )
+        .expect("Expected ok");
+
+        assert!(matches!(
+            config.validate(),
+            Err(Error::MultipleForwardArgs(_))
+        ));
+    }
+
+    #[test]
+    fn managed() {
+        let dir = TempDir::new("spackle").unwrap().into_path();
+        fs::write(
+            dir.join("spackle.toml"),
+            "managed = [\"ci/**\", \"Makefile\", \"docs/**\", \"spackle.toml\", \"hooks.d/*\"]",
+        )
+        .unwrap();
+        fs::create_dir(dir.join("ci")).unwrap();
+        fs::write(dir.join("ci").join("build.yml"), "").unwrap();
+        fs::write(dir.join("Makefile.j2"), "").unwrap();
+        fs::create_dir(dir.join(HOOKS_DIR)).unwrap();
+        fs::write(dir.join(HOOKS_DIR).join("10-setup"), "").unwrap();
+
+        // The config and hooks aren't outputs
+        let config = load_dir(&dir).expect("load_dir failed, should have succeeded");
+        assert_eq!(
+            config.unmatched_managed(&dir),
+            vec!["docs/**", "spackle.toml", "hooks.d/*"]
+        );
+
+        let invalid = Config {
+            managed: vec!["ci/[".to_string()],
+            ..Default::default()
+        };
+        assert!(matches!(
+            invalid.validate(),
+            Err(Error::InvalidManagedPattern(pattern, _)) if pattern == "ci/["
+        ));
+    }
+
+    #[test]
+    fn default_shell() {
+        let mut config = parse_str(
+            r#"
+            default_shell = "sh"
+
+            [[hooks]]
+            key = "inherits"
+            command = ["echo first && echo second"]
+
+            [[hooks]]
+            key = "own"
+            command = ["true"]
+            shell = "bash"
+            "#,
+        )
+        .expect("parse_str failed, should have succeeded");
+        config.resolve_default_shell();
+
+        assert_eq!(config.hooks[0].shell.as_deref(), Some("sh"));
+        assert_eq!(config.hooks[1].shell.as_deref(), Some("bash"));
+
+        let results = crate::hook::run_hooks(
+            &config.hooks[..1].to_vec(),
+            ".",
+            &[],
+            &HashMap::new(),
+            &[],
+            None,
+        )
+        .expect("run_hooks failed, should have succeeded");
+        assert!(
+            matches!(&results[0].kind, crate::hook::HookResultKind::Completed { stdout, .. } if stdout == b"first\nsecond\n"),
+            "Expected the command to run in the shell, got {:?}",
+            results
+        );
+    }
 }
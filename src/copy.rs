@@ -1,14 +1,20 @@
 use std::{
     collections::HashMap,
     fmt::Display,
-    fs,
+    fs::{self, File},
+    io::{self, BufRead, BufReader},
     path::{Path, PathBuf},
 };
 
-use tera::{Context, Tera};
+use serde::{ser::SerializeStruct, Serialize, Serializer};
 use walkdir::WalkDir;
 
-use crate::{config::CONFIG_FILE, template::TEMPLATE_EXT};
+use crate::{
+    classify::{Classifier, FileClass},
+    config::Chmod,
+    paths::{glob_set, is_within},
+    template::{context_from_data, render_path},
+};
 
 #[derive(Debug)]
 pub struct Error {
@@ -28,66 +34,191 @@ impl std::error::Error for Error {
     }
 }
 
+impl Error {
+    /// Every code returned by [Error::code]
+    pub const CODES: &'static [&'static str] = &["copy.failed", "copy.escapes_output_dir"];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        if self.source.is::<EscapesOutputDir>() {
+            "copy.escapes_output_dir"
+        } else {
+            "copy.failed"
+        }
+    }
+
+    /// The messages of the error and each of its sources in turn, ending with the root cause
+    pub fn chain(&self) -> Vec<String> {
+        let mut chain = Vec::new();
+        let mut source: Option<&dyn std::error::Error> = Some(&*self.source);
+        while let Some(e) = source {
+            chain.push(e.to_string());
+            source = e.source();
+        }
+
+        chain
+    }
+}
+
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Error", 4)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("path", &self.path)?;
+        state.serialize_field("chain", &self.chain())?;
+        state.end()
+    }
+}
+
+/// The source of a copy error when a destination, once rendered, resolves outside the output directory
+#[derive(Debug, thiserror::Error)]
+#[error("Destination is outside the output directory")]
+pub struct EscapesOutputDir;
+
 pub struct CopyResult {
     pub copied_count: usize,
     pub skipped_count: usize,
+    /// The entries skipped by the ignore globs, relative to the source directory
+    pub skipped: Vec<PathBuf>,
+    /// Copied files whose destination didn't match the source when verified
+    pub mismatches: Vec<Mismatch>,
 }
 
-pub fn copy(
-    src: &Path,
-    dest: &Path,
-    skip: &Vec<String>,
-    data: &HashMap<String, String>,
-) -> Result<CopyResult, Error> {
-    let mut copied_count = 0;
-    let mut skipped_count = 0;
+/// Reported by [copy_with_progress] as each file is copied
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CopyProgress {
+    pub src: PathBuf,
+    pub dest: PathBuf,
+    /// The files copied so far, this one included
+    pub copied: usize,
+    /// The files the copy writes in all, not counting directories
+    pub total: usize,
+}
 
-    let entries = WalkDir::new(src)
-        .into_iter()
-        .filter_entry(|entry| {
-            // Skip those that match "skip"
-            if skip
-                .iter()
-                .any(|s| entry.file_name().to_string_lossy() == *s)
-            {
-                skipped_count += 1;
-                return false;
-            }
+#[derive(Debug)]
+pub struct Mismatch {
+    pub src: PathBuf,
+    pub dest: PathBuf,
+    /// The offset of the first byte that differs, or the length of the shorter file
+    pub offset: u64,
+}
 
-            // TODO pull these out and pass as args if possible
-            // Skip config file
-            if entry.file_name() == CONFIG_FILE {
-                return false;
-            }
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} does not match {} from byte {}",
+            self.dest.display(),
+            self.src.display(),
+            self.offset
+        )
+    }
+}
 
-            // Skip .j2 files
-            if entry.file_name().to_string_lossy().ends_with(TEMPLATE_EXT) {
-                return false;
-            }
+// Compares two files' contents without reading either into memory, returning the offset of
+// the first byte that differs
+fn first_difference(src: &Path, dest: &Path) -> Result<Option<u64>, Error> {
+    let error_at = |path: &Path| {
+        let path = path.to_path_buf();
+        move |e: io::Error| Error {
+            source: e.into(),
+            path,
+        }
+    };
+
+    let mut src_reader = File::open(src)
+        .map(|file| BufReader::with_capacity(64 * 1024, file))
+        .map_err(error_at(src))?;
+    let mut dest_reader = File::open(dest)
+        .map(|file| BufReader::with_capacity(64 * 1024, file))
+        .map_err(error_at(dest))?;
+    let mut offset = 0;
+
+    loop {
+        let src_chunk = src_reader.fill_buf().map_err(error_at(src))?;
+        let dest_chunk = dest_reader.fill_buf().map_err(error_at(dest))?;
+        if src_chunk.is_empty() || dest_chunk.is_empty() {
+            return Ok((src_chunk.len() != dest_chunk.len()).then_some(offset));
+        }
 
-            true
-        })
-        .collect::<Vec<_>>();
+        let len = src_chunk.len().min(dest_chunk.len());
+        if let Some(i) = src_chunk[..len]
+            .iter()
+            .zip(&dest_chunk[..len])
+            .position(|(a, b)| a != b)
+        {
+            return Ok(Some(offset + i as u64));
+        }
 
-    for entry in entries {
-        let entry = entry.map_err(|e| Error {
+        src_reader.consume(len);
+        dest_reader.consume(len);
+        offset += len as u64;
+    }
+}
+
+// Re-reads each copied file and compares it against its source
+fn verify_copies(copied: &[(PathBuf, PathBuf)]) -> Result<Vec<Mismatch>, Error> {
+    let mut mismatches = Vec::new();
+
+    for (src, dest) in copied {
+        if let Some(offset) = first_difference(src, dest)? {
+            mismatches.push(Mismatch {
+                src: src.clone(),
+                dest: dest.clone(),
+                offset,
+            });
+        }
+    }
+
+    Ok(mismatches)
+}
+
+// An entry under the source directory and where it will be copied to
+struct PlannedEntry {
+    src: PathBuf,
+    dest: PathBuf,
+    is_dir: bool,
+}
+
+// Walks the source directory, skipping ignored entries, and resolves the destination
+// of each remaining entry. Also returns the ignored entries, relative to the source directory.
+fn plan(
+    src: &Path,
+    dest: &Path,
+    skip: &[String],
+    data: &HashMap<String, String>,
+) -> Result<(Vec<PlannedEntry>, Vec<PathBuf>), Error> {
+    let entries = Classifier::new(src, skip)
+        .and_then(|classifier| classifier.excluding(src, dest).entries(src))
+        .map_err(|e| Error {
             source: e.into(),
             path: src.to_path_buf(),
         })?;
 
-        let src_path = entry.path();
-        let relative_path = src_path.strip_prefix(src).map_err(|e| Error {
-            source: e.into(),
-            path: src_path.to_path_buf(),
-        })?;
-        let dst_path_maybe_template = dest.join(relative_path);
+    let context = context_from_data(data);
+    let mut planned = Vec::new();
+
+    let mut ignored = Vec::new();
+    for entry in entries {
+        match entry.class {
+            FileClass::Static => {}
+            FileClass::Ignored => {
+                ignored.push(entry.path);
+                continue;
+            }
+            FileClass::Template | FileClass::Project => continue,
+        }
+
+        // Symlinks aren't followed or copied
+        let src_path = src.join(&entry.path);
+        if src_path.is_symlink() {
+            continue;
+        }
+        let dst_path_maybe_template = dest.join(&entry.path);
 
-        let context = Context::from_serialize(data).map_err(|e| Error {
-            source: e.into(),
-            path: src_path.to_path_buf(),
-        })?;
         let dst_path: PathBuf =
-            match Tera::one_off(&dst_path_maybe_template.to_string_lossy(), &context, false) {
+            match render_path(&dst_path_maybe_template.to_string_lossy(), &context) {
                 Ok(path) => path.into(),
                 Err(e) => {
                     return Err(Error {
@@ -97,33 +228,231 @@ pub fn copy(
                 }
             };
 
-        if entry.file_type().is_dir() {
-            fs::create_dir_all(&dst_path).map_err(|e| Error {
+        // Templated names could otherwise point anywhere, e.g. with `..` components
+        if !is_within(&dst_path, dest) {
+            return Err(Error {
+                source: EscapesOutputDir.into(),
+                path: dst_path,
+            });
+        }
+
+        planned.push(PlannedEntry {
+            src: src_path,
+            dest: dst_path,
+            is_dir: entry.is_dir,
+        });
+    }
+
+    Ok((planned, ignored))
+}
+
+/// Resolves the destination of every file that would be copied, without copying anything
+pub fn destinations(
+    src: &Path,
+    dest: &Path,
+    skip: &[String],
+    data: &HashMap<String, String>,
+) -> Result<Vec<PathBuf>, Error> {
+    let (planned, _) = plan(src, dest, skip, data)?;
+
+    Ok(planned
+        .into_iter()
+        .filter(|entry| !entry.is_dir)
+        .map(|entry| entry.dest)
+        .collect())
+}
+
+/// Lists the entries that would be skipped by the ignore globs, relative to the source directory.
+/// An ignored directory is listed once, without its contents.
+pub fn ignored(
+    src: &Path,
+    dest: &Path,
+    skip: &[String],
+    data: &HashMap<String, String>,
+) -> Result<Vec<PathBuf>, Error> {
+    let (_, ignored) = plan(src, dest, skip, data)?;
+
+    Ok(ignored)
+}
+
+pub fn copy(
+    src: &Path,
+    dest: &Path,
+    skip: &Vec<String>,
+    data: &HashMap<String, String>,
+    verify: bool,
+) -> Result<CopyResult, Error> {
+    copy_with_progress(src, dest, skip, data, verify, |_| {})
+}
+
+/// Like [copy], calling `on_progress` after each file is copied so large trees can show
+/// their progress
+pub fn copy_with_progress(
+    src: &Path,
+    dest: &Path,
+    skip: &Vec<String>,
+    data: &HashMap<String, String>,
+    verify: bool,
+    mut on_progress: impl FnMut(CopyProgress),
+) -> Result<CopyResult, Error> {
+    let mut copied = Vec::new();
+    let (planned, ignored) = plan(src, dest, skip, data)?;
+    let total = planned.iter().filter(|entry| !entry.is_dir).count();
+
+    for entry in planned {
+        if entry.is_dir {
+            fs::create_dir_all(&entry.dest).map_err(|e| Error {
                 source: e.into(),
-                path: dst_path.clone(),
+                path: entry.dest.clone(),
             })?;
-        } else if entry.file_type().is_file() {
-            if let Some(parent) = dst_path.parent() {
+        } else {
+            if let Some(parent) = entry.dest.parent() {
                 fs::create_dir_all(parent).map_err(|e| Error {
                     source: e.into(),
                     path: parent.to_path_buf(),
                 })?;
             }
-            fs::copy(src_path, &dst_path).map_err(|e| Error {
+            fs::copy(&entry.src, &entry.dest).map_err(|e| Error {
                 source: e.into(),
-                path: dst_path.clone(),
+                path: entry.dest.clone(),
             })?;
 
-            copied_count += 1;
+            copied.push((entry.src.clone(), entry.dest.clone()));
+            on_progress(CopyProgress {
+                src: entry.src,
+                dest: entry.dest,
+                copied: copied.len(),
+                total,
+            });
         }
     }
 
+    let mismatches = if verify {
+        verify_copies(&copied)?
+    } else {
+        Vec::new()
+    };
+
     Ok(CopyResult {
-        copied_count,
-        skipped_count,
+        copied_count: copied.len(),
+        skipped_count: ignored.len(),
+        skipped: ignored,
+        mismatches,
     })
 }
 
+/// Sets the modes of the output directory's files matching the `chmod` rules, a later rule
+/// taking precedence over an earlier one. Returns the files changed, relative to the output
+/// directory. Modes are only applied on Unix.
+#[cfg(unix)]
+pub fn apply_modes(out_dir: &Path, rules: &[Chmod]) -> Result<Vec<PathBuf>, Error> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut changed = Vec::new();
+    if rules.is_empty() {
+        return Ok(changed);
+    }
+
+    let globs = rules
+        .iter()
+        .map(|rule| {
+            glob_set(std::slice::from_ref(&rule.path)).map_err(|e| Error {
+                source: e.into(),
+                path: PathBuf::from(&rule.path),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for entry in WalkDir::new(out_dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+    {
+        let relative = entry.path().strip_prefix(out_dir).unwrap_or(entry.path());
+        let Some(mode) = rules
+            .iter()
+            .zip(&globs)
+            .filter(|(_, glob)| glob.is_match(relative))
+            .filter_map(|(rule, _)| rule.mode())
+            .next_back()
+        else {
+            continue;
+        };
+
+        fs::set_permissions(entry.path(), fs::Permissions::from_mode(mode)).map_err(|e| Error {
+            source: e.into(),
+            path: entry.path().to_path_buf(),
+        })?;
+        changed.push(relative.to_path_buf());
+    }
+
+    Ok(changed)
+}
+
+#[cfg(not(unix))]
+pub fn apply_modes(_out_dir: &Path, _rules: &[Chmod]) -> Result<Vec<PathBuf>, Error> {
+    Ok(Vec::new())
+}
+
+/// A file or directory from outside the project, named by a Path slot with `copy_into`, that's
+/// copied into the output as is. A directory's contents are copied into `dest`, and a file
+/// is copied into it under its own name.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Import {
+    /// The slot the source was given for
+    pub key: String,
+    pub src: PathBuf,
+    pub dest: PathBuf,
+}
+
+impl Import {
+    /// Pairs every file of the source with its destination, without copying anything
+    pub fn files(&self) -> Vec<(PathBuf, PathBuf)> {
+        if self.src.is_file() {
+            let name = self.src.file_name().unwrap_or_default();
+            return vec![(self.src.clone(), self.dest.join(name))];
+        }
+
+        WalkDir::new(&self.src)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                let relative = entry.path().strip_prefix(&self.src).unwrap_or(entry.path());
+                (entry.path().to_path_buf(), self.dest.join(relative))
+            })
+            .collect()
+    }
+
+    /// Copies the source into the destination. Ignore rules don't apply, since the source is
+    /// the user's rather than the template's. Returns the files written.
+    pub fn copy(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut copied = Vec::new();
+        fs::create_dir_all(&self.dest).map_err(|e| Error {
+            source: e.into(),
+            path: self.dest.clone(),
+        })?;
+
+        for (src, dest) in self.files() {
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent).map_err(|e| Error {
+                    source: e.into(),
+                    path: parent.to_path_buf(),
+                })?;
+            }
+            fs::copy(&src, &dest).map_err(|e| Error {
+                source: e.into(),
+                path: dest.clone(),
+            })?;
+
+            copied.push(dest);
+        }
+
+        Ok(copied)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -131,6 +460,143 @@ mod tests {
     use std::{collections::HashMap, fs};
     use tempdir::TempDir;
 
+    #[test]
+    fn progress() {
+        let src_dir = TempDir::new("spackle").unwrap().into_path();
+        let dst_dir = TempDir::new("spackle").unwrap().into_path().join("out");
+
+        fs::create_dir(src_dir.join("nested")).unwrap();
+        for name in ["a", "b", "nested/c"] {
+            fs::write(src_dir.join(name), name).unwrap();
+        }
+        fs::write(src_dir.join("skipped"), "").unwrap();
+
+        let mut progress = Vec::new();
+        let result = copy_with_progress(
+            &src_dir,
+            &dst_dir,
+            &vec!["skipped".to_string()],
+            &HashMap::new(),
+            false,
+            |p| progress.push(p),
+        )
+        .unwrap();
+
+        assert_eq!(result.copied_count, 3);
+        assert_eq!(
+            progress
+                .iter()
+                .map(|p| (p.dest.strip_prefix(&dst_dir).unwrap(), p.copied, p.total))
+                .collect::<Vec<_>>(),
+            vec![
+                (Path::new("a"), 1, 3),
+                (Path::new("b"), 2, 3),
+                (Path::new("nested/c"), 3, 3)
+            ]
+        );
+        // Each file is reported once it's on disk
+        assert!(progress.iter().all(|p| p.dest.exists()));
+    }
+
+    #[test]
+    fn nested_output_dir() {
+        let src_dir = TempDir::new("spackle").unwrap().into_path();
+        let dst_dir = src_dir.join("out");
+
+        fs::write(src_dir.join("file"), "contents").unwrap();
+        // An earlier fill already written to the output
+        fs::create_dir(&dst_dir).unwrap();
+        fs::write(dst_dir.join("file"), "contents").unwrap();
+
+        let result = copy(&src_dir, &dst_dir, &vec![], &HashMap::new(), false).unwrap();
+
+        assert_eq!(result.copied_count, 1);
+        assert!(!dst_dir.join("out").exists());
+        assert_eq!(
+            destinations(&src_dir, &dst_dir, &[], &HashMap::new()).unwrap(),
+            vec![dst_dir.join("file")]
+        );
+    }
+
+    #[test]
+    fn escapes_output_dir() {
+        let src_dir = TempDir::new("spackle").unwrap().into_path();
+        let dst_dir = TempDir::new("spackle").unwrap().into_path();
+
+        fs::write(src_dir.join("{{ name }}"), "contents").unwrap();
+
+        let result = copy(
+            &src_dir,
+            &dst_dir.join("out"),
+            &vec![],
+            &HashMap::from([("name".to_string(), "../escaped".to_string())]),
+            false,
+        );
+
+        assert!(
+            matches!(&result, Err(e) if e.source.is::<EscapesOutputDir>()),
+            "{:?}",
+            result.err()
+        );
+        assert!(!dst_dir.join("escaped").exists());
+
+        // A symlink in the output that points elsewhere is followed before checking
+        let outside = TempDir::new("spackle").unwrap().into_path();
+        fs::create_dir(dst_dir.join("out")).unwrap();
+        std::os::unix::fs::symlink(&outside, dst_dir.join("out").join("link")).unwrap();
+
+        let result = copy(
+            &src_dir,
+            &dst_dir.join("out"),
+            &vec![],
+            &HashMap::from([("name".to_string(), "link/escaped".to_string())]),
+            false,
+        );
+
+        assert!(matches!(&result, Err(e) if e.source.is::<EscapesOutputDir>()));
+        assert!(!outside.join("escaped").exists());
+    }
+
+    #[test]
+    fn error_chain() {
+        let src_dir = TempDir::new("spackle").unwrap().into_path();
+        let dst_dir = TempDir::new("spackle").unwrap().into_path();
+
+        fs::write(src_dir.join("{{ missing }}.txt"), "contents").unwrap();
+
+        let error = copy(&src_dir, &dst_dir, &vec![], &HashMap::new(), false)
+            .err()
+            .expect("copy succeeded, should have failed");
+
+        let chain = error.chain();
+        assert_eq!(chain.first(), Some(&error.to_string()));
+        assert!(chain.len() > 1, "{:?}", chain);
+        assert!(chain.last().unwrap().contains("missing"), "{:?}", chain);
+
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["code"], "copy.failed");
+        assert_eq!(
+            json["path"],
+            dst_dir.join("{{ missing }}.txt").to_string_lossy().as_ref()
+        );
+        assert_eq!(json["chain"].as_array().unwrap().len(), chain.len());
+    }
+
+    #[test]
+    fn skips_config_file() {
+        let src_dir = TempDir::new("spackle").unwrap().into_path();
+        let dst_dir = TempDir::new("spackle").unwrap().into_path();
+
+        // Only the file that is the project's config is skipped
+        fs::write(src_dir.join("spackle.yaml"), "name: project").unwrap();
+        fs::write(src_dir.join("spackle.json"), "{}").unwrap();
+
+        copy(&src_dir, &dst_dir, &vec![], &HashMap::new(), false).unwrap();
+
+        assert!(!dst_dir.join("spackle.yaml").exists());
+        assert!(dst_dir.join("spackle.json").exists());
+    }
+
     #[test]
     fn ignore_one() {
         let src_dir = TempDir::new("spackle").unwrap().into_path();
@@ -149,6 +615,7 @@ mod tests {
             &dst_dir,
             &vec!["file-0.txt".to_string()],
             &HashMap::from([("foo".to_string(), "bar".to_string())]),
+            false,
         )
         .unwrap();
 
@@ -184,6 +651,7 @@ mod tests {
             &dst_dir,
             &vec!["file-0.txt".to_string()],
             &HashMap::from([("foo".to_string(), "bar".to_string())]),
+            false,
         )
         .unwrap();
 
@@ -198,6 +666,56 @@ mod tests {
         }
     }
 
+    #[test]
+    fn ignore_negated() {
+        let src_dir = TempDir::new("spackle").unwrap().into_path();
+        let dst_dir = TempDir::new("spackle").unwrap().into_path();
+
+        let subdir = src_dir.join("logs");
+        fs::create_dir(&subdir).unwrap();
+
+        fs::write(src_dir.join("debug.log"), "debug").unwrap();
+        fs::write(src_dir.join("important.log"), "important").unwrap();
+        fs::write(subdir.join("important.log"), "important").unwrap();
+        fs::write(subdir.join("trace.log"), "trace").unwrap();
+        fs::write(src_dir.join("main.rs"), "fn main() {}").unwrap();
+
+        copy(
+            &src_dir,
+            &dst_dir,
+            &vec![
+                "*.log".to_string(),
+                "!important.log".to_string(),
+                "logs/important.log".to_string(),
+            ],
+            &HashMap::new(),
+            false,
+        )
+        .unwrap();
+
+        assert!(!dst_dir.join("debug.log").exists());
+        assert!(dst_dir.join("important.log").exists());
+        // Later patterns take precedence over earlier negations
+        assert!(!dst_dir.join("logs").join("important.log").exists());
+        assert!(!dst_dir.join("logs").join("trace.log").exists());
+        assert!(dst_dir.join("main.rs").exists());
+    }
+
+    #[test]
+    fn ignore_invalid_glob() {
+        let src_dir = TempDir::new("spackle").unwrap().into_path();
+        let dst_dir = TempDir::new("spackle").unwrap().into_path();
+
+        assert!(copy(
+            &src_dir,
+            &dst_dir,
+            &vec!["[".to_string()],
+            &HashMap::new(),
+            false,
+        )
+        .is_err());
+    }
+
     #[test]
     fn replace_file_name() {
         let src_dir = TempDir::new("spackle").unwrap().into_path();
@@ -223,6 +741,7 @@ mod tests {
                 ("template_name".to_string(), "template".to_string()),
                 ("_output_name".to_string(), "foo".to_string()),
             ]),
+            false,
         )
         .unwrap();
 
@@ -231,4 +750,95 @@ mod tests {
             "template.tmpl does not exist"
         );
     }
+
+    #[test]
+    fn verify() {
+        let src_dir = TempDir::new("spackle").unwrap().into_path();
+        let dst_dir = TempDir::new("spackle").unwrap().into_path();
+
+        for i in 0..3 {
+            fs::write(
+                src_dir.join(format!("file-{}.txt", i)),
+                format!("file-{}.txt", i),
+            )
+            .unwrap();
+        }
+
+        let result = copy(&src_dir, &dst_dir, &vec![], &HashMap::new(), true).unwrap();
+        assert_eq!(result.copied_count, 3);
+        assert!(result.mismatches.is_empty());
+
+        // Corrupt a destination once everything is copied, before it's verified. The same
+        // length with a different byte, and a truncated file, are both caught.
+        let dst_dir = TempDir::new("spackle").unwrap().into_path();
+        let result = copy_with_progress(&src_dir, &dst_dir, &vec![], &HashMap::new(), true, {
+            let dst_dir = dst_dir.clone();
+            move |progress| {
+                if progress.copied == progress.total {
+                    fs::write(dst_dir.join("file-0.txt"), "file-0.tXt").unwrap();
+                    fs::write(dst_dir.join("file-2.txt"), "file").unwrap();
+                }
+            }
+        })
+        .unwrap();
+
+        let mut mismatches = result
+            .mismatches
+            .iter()
+            .map(|mismatch| (mismatch.dest.clone(), mismatch.offset))
+            .collect::<Vec<_>>();
+        mismatches.sort();
+        assert_eq!(
+            mismatches,
+            vec![
+                (dst_dir.join("file-0.txt"), 8),
+                (dst_dir.join("file-2.txt"), 4)
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn apply_modes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let out_dir = TempDir::new("spackle").unwrap();
+        fs::create_dir(out_dir.path().join("scripts")).unwrap();
+        for file in ["scripts/build.sh", "scripts/secret.sh", "README.md"] {
+            fs::write(out_dir.path().join(file), "").unwrap();
+            fs::set_permissions(out_dir.path().join(file), fs::Permissions::from_mode(0o644))
+                .unwrap();
+        }
+
+        let rules = vec![
+            Chmod {
+                path: "scripts/*.sh".to_string(),
+                mode: "0755".to_string(),
+            },
+            Chmod {
+                path: "**/secret.sh".to_string(),
+                mode: "700".to_string(),
+            },
+        ];
+        let mut changed = super::apply_modes(out_dir.path(), &rules).unwrap();
+        changed.sort();
+        assert_eq!(
+            changed,
+            vec![
+                PathBuf::from("scripts/build.sh"),
+                PathBuf::from("scripts/secret.sh")
+            ]
+        );
+
+        let mode = |file: &str| {
+            fs::metadata(out_dir.path().join(file))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o7777
+        };
+        assert_eq!(mode("scripts/build.sh"), 0o755);
+        assert_eq!(mode("scripts/secret.sh"), 0o700);
+        assert_eq!(mode("README.md"), 0o644);
+    }
 }
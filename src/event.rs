@@ -0,0 +1,60 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// An event emitted by one of spackle's streams, stamped with its position and time of emission.
+#[derive(Serialize, Debug)]
+pub struct Event<T> {
+    /// Increases by one for every event emitted within a run
+    pub seq: u64,
+    pub at: DateTime<Utc>,
+    pub event: T,
+}
+
+/// Assigns sequence numbers to events. Clones share the same counter, so
+/// events from different streams in a run can be ordered against each other.
+#[derive(Clone, Debug, Default)]
+pub struct Sequence(Arc<AtomicU64>);
+
+impl Sequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wraps an event in an envelope, assigning the next sequence number and the current time.
+    pub fn wrap<T>(&self, event: T) -> Event<T> {
+        Event {
+            seq: self.0.fetch_add(1, Ordering::SeqCst),
+            at: Utc::now(),
+            event,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_sequence() {
+        let sequence = Sequence::new();
+        let other = sequence.clone();
+
+        let events = [
+            sequence.wrap("copy"),
+            other.wrap("hook"),
+            sequence.wrap("copy"),
+            other.wrap("hook"),
+        ];
+
+        assert_eq!(
+            events.iter().map(|e| e.seq).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3]
+        );
+        assert!(events.windows(2).all(|w| w[0].at <= w[1].at));
+    }
+}
@@ -2,16 +2,37 @@ use super::slot::Slot;
 use async_process::Stdio;
 use async_stream::stream;
 use colored::Colorize;
-use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fmt::Display, path::Path};
-use std::{io, process};
+use futures_lite::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    stream as lite_stream,
+};
+use serde::{Deserialize, Serialize, Serializer};
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    fmt::Display,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use std::{
+    io::{self, IsTerminal},
+    process,
+};
+use tempdir::TempDir;
 use tera::{Context, Tera};
 use thiserror::Error;
 use tokio::pin;
 use tokio_stream::{Stream, StreamExt};
 use users::User;
 
-use crate::needs::{is_satisfied, Needy};
+use crate::{
+    config::HOOKS_DIR,
+    event::{Event, Sequence},
+    manifest,
+    needs::{is_satisfied, Needy},
+    shell,
+    template::{context_from_data, text_variables, typed_context_from_data},
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Hook {
@@ -22,14 +43,41 @@ pub struct Hook {
     pub needs: Vec<String>,
     pub name: Option<String>,
     pub description: Option<String>,
+    /// A link to further documentation, shown by `info` and when prompting
+    pub docs_url: Option<String>,
     pub default: Option<bool>,
+    /// Where `default` was set before it moved onto the hook, read in its place when loading,
+    /// see [crate::config::Config::resolve_deprecated]. `spackle migrate` rewrites it.
+    #[serde(default, skip_serializing)]
+    pub optional: Option<Optional>,
+    /// Whether extra arguments passed at fill time are appended to this hook's command
+    #[serde(default)]
+    pub forward_args: bool,
+    /// A shell to run the command with, as `shell -c "command"`, see [shell::script].
+    /// Defaults to the project's `default_shell`, if it has one.
+    pub shell: Option<String>,
+    /// Paths the hook writes to, which may be outside the output directory.
+    /// Spackle doesn't enforce these, it only uses them to report and clean up.
+    #[serde(default)]
+    pub writes: Vec<String>,
+    /// Whether the hook is safe to run again when re-filling an already generated directory
+    #[serde(default = "default_idempotent")]
+    pub idempotent: bool,
+    /// Whether the hook needs a terminal, e.g. to log in. It shares spackle's stdin, stdout and
+    /// stderr instead of having them piped, so its output isn't captured. It fails when
+    /// spackle isn't attached to a terminal.
+    #[serde(default)]
+    pub interactive: bool,
+    /// Whether the hook was discovered as a script in the project's hooks directory
+    #[serde(skip)]
+    pub from_hooks_dir: bool,
 }
 
 impl Display for Hook {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{} {}\n{}",
+            "{} {}{}\n{}{}{}{}{}",
             self.key.bold(),
             if let Some(default) = &self.default {
                 format!(
@@ -40,12 +88,39 @@ impl Display for Hook {
                 "".to_string()
             }
             .dimmed(),
+            self.description
+                .clone()
+                .map(|s| format!("\n{}", s))
+                .unwrap_or_default()
+                .truecolor(180, 180, 180),
             self.command
                 .iter()
                 .map(|s| s.to_string())
                 .collect::<Vec<String>>()
                 .join(" ")
-                .dimmed()
+                .dimmed(),
+            if self.needs.is_empty() {
+                "".to_string()
+            } else {
+                format!("\nneeds {}", self.needs.join(", "))
+            }
+            .dimmed(),
+            self.r#if
+                .clone()
+                .map(|s| format!("\nif {}", s))
+                .unwrap_or_default()
+                .dimmed(),
+            self.docs_url
+                .clone()
+                .map(|s| format!("\ndocs {}", s))
+                .unwrap_or_default()
+                .dimmed(),
+            if self.from_hooks_dir {
+                format!("\nfrom {}", HOOKS_DIR)
+            } else {
+                "".to_string()
+            }
+            .dimmed(),
         )
     }
 }
@@ -59,19 +134,51 @@ impl Default for Hook {
             needs: vec![],
             name: None,
             description: None,
+            docs_url: None,
             default: None,
+            optional: None,
+            forward_args: false,
+            shell: None,
+            writes: vec![],
+            idempotent: true,
+            interactive: false,
+            from_hooks_dir: false,
+        }
+    }
+}
+
+/// A hook's deprecated `optional`, either `optional = { default = <bool> }` or, from before
+/// that, `optional = <bool>`
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Optional {
+    Table { default: Option<bool> },
+    Bool(bool),
+}
+
+impl Optional {
+    /// The `default` it stands for
+    pub fn default(&self) -> Option<bool> {
+        match self {
+            Optional::Table { default } => *default,
+            Optional::Bool(default) => Some(*default),
         }
     }
 }
 
+fn default_idempotent() -> bool {
+    true
+}
+
 impl Needy for Hook {
     fn key(&self) -> String {
         self.key.clone()
     }
 
     fn is_enabled(&self, data: &HashMap<String, String>) -> bool {
-        if data.contains_key(&self.key) {
-            return data[&self.key] == "true";
+        if let Some(value) = data.get(&self.key) {
+            // Invalid toggles are rejected before hooks run, see normalize_toggles
+            return parse_toggle(&self.key, value).unwrap_or(false);
         }
 
         self.default.unwrap_or(true)
@@ -83,18 +190,28 @@ impl Needy for Hook {
 }
 
 impl Hook {
-    fn evaluate_conditional(
-        &self,
-        context: &HashMap<String, String>,
-    ) -> Result<bool, ConditionalError> {
+    /// The same text as its [Display], without colors
+    pub fn to_plain_string(&self) -> String {
+        crate::strip_ansi(&self.to_string())
+    }
+
+    /// The variables the hook's command and conditional read, along with the keys it needs
+    pub fn referenced_variables(&self) -> Vec<String> {
+        self.command
+            .iter()
+            .chain(&self.r#if)
+            .flat_map(|text| text_variables(text))
+            .chain(self.needs.iter().cloned())
+            .collect()
+    }
+
+    fn evaluate_conditional(&self, context: &Context) -> Result<bool, ConditionalError> {
         let conditional = match &self.r#if {
             Some(conditional) => conditional,
             None => return Ok(true),
         };
 
-        let context = Context::from_serialize(context).map_err(ConditionalError::InvalidContext)?;
-
-        let condition_str = Tera::one_off(conditional, &context, false)
+        let condition_str = Tera::one_off(conditional, context, false)
             .map_err(ConditionalError::InvalidTemplate)?;
 
         let condition = condition_str
@@ -104,6 +221,142 @@ impl Hook {
 
         Ok(condition)
     }
+
+    /// Resolves the paths the hook declares it writes to. A leading `~` is expanded to
+    /// the home directory and relative paths are resolved against the output directory.
+    pub fn write_paths(&self, out_dir: &Path) -> Vec<PathBuf> {
+        let home = env::var_os("HOME").map(PathBuf::from);
+
+        self.writes
+            .iter()
+            .map(|write| match (write.strip_prefix('~'), &home) {
+                (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+                    home.join(rest.trim_start_matches('/'))
+                }
+                _ => out_dir.join(write),
+            })
+            .collect()
+    }
+
+    /// Gets the declared writes that may land outside the output directory
+    pub fn writes_outside_output(&self) -> Vec<&String> {
+        self.writes
+            .iter()
+            .filter(|write| {
+                let path = Path::new(write);
+                if write.starts_with('~') || path.has_root() {
+                    return true;
+                }
+
+                // Relative paths escape if they climb above where they started
+                let mut depth = 0i32;
+                path.components().any(|component| {
+                    match component {
+                        std::path::Component::ParentDir => depth -= 1,
+                        std::path::Component::Normal(_) => depth += 1,
+                        _ => {}
+                    }
+                    depth < 0
+                })
+            })
+            .collect()
+    }
+
+    /// The program the hook runs: its shell if it has one, otherwise the first argument of its command
+    pub fn program(&self) -> Option<&String> {
+        self.shell.as_ref().or(self.command.first())
+    }
+
+    /// Resolves the hook's shell, or the first argument of its command, against PATH
+    pub fn command_status(&self) -> CommandStatus {
+        let program = match self.program() {
+            Some(program) => program,
+            None => return CommandStatus::NotFound,
+        };
+
+        if program.contains("{{") {
+            return CommandStatus::Templated;
+        }
+
+        let program_path = Path::new(program);
+        if program_path.components().count() > 1 {
+            if !program_path.is_absolute() {
+                return CommandStatus::Relative;
+            }
+
+            if is_executable(program_path) {
+                return CommandStatus::Found(program_path.to_path_buf());
+            }
+
+            return CommandStatus::NotFound;
+        }
+
+        match find_executable(program) {
+            Some(path) => CommandStatus::Found(path),
+            None => CommandStatus::NotFound,
+        }
+    }
+}
+
+/// The result of resolving a hook's command before it is run
+#[derive(Serialize, Debug, PartialEq)]
+pub enum CommandStatus {
+    /// The command was found at the given path
+    Found(PathBuf),
+    /// The command could not be found on PATH
+    NotFound,
+    /// The command is templated, so it can only be resolved at fill time
+    Templated,
+    /// The command is a relative path, which resolves against the output directory at fill time
+    Relative,
+}
+
+/// Looks up a user by name, for checking the `run_as_user` that hooks are run as exists before
+/// running them
+pub fn find_user(name: &str) -> Option<User> {
+    users::get_user_by_name(name)
+}
+
+/// Finds an executable with the given name on PATH (also trying PATHEXT extensions on Windows)
+pub fn find_executable(program: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+
+    let extensions: Vec<String> = if cfg!(windows) {
+        env::var("PATHEXT")
+            .unwrap_or(".COM;.EXE;.BAT;.CMD".to_string())
+            .split(';')
+            .filter(|ext| !ext.is_empty())
+            .map(|ext| ext.to_string())
+            .collect()
+    } else {
+        vec![]
+    };
+
+    env::split_paths(&paths).find_map(|dir| {
+        let candidate = dir.join(program);
+        if is_executable(&candidate) {
+            return Some(candidate);
+        }
+
+        extensions.iter().find_map(|ext| {
+            let candidate = dir.join(format!("{}{}", program, ext));
+            is_executable(&candidate).then_some(candidate)
+        })
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
 }
 
 #[derive(Serialize, Debug)]
@@ -123,16 +376,53 @@ impl Display for ConditionalError {
     }
 }
 
+impl ConditionalError {
+    /// Every code returned by [ConditionalError::code]
+    pub const CODES: &'static [&'static str] = &[
+        "hook.conditional.invalid_context",
+        "hook.conditional.invalid_template",
+        "hook.conditional.not_boolean",
+    ];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            ConditionalError::InvalidContext(_) => "hook.conditional.invalid_context",
+            ConditionalError::InvalidTemplate(_) => "hook.conditional.invalid_template",
+            ConditionalError::NotBoolean(_) => "hook.conditional.not_boolean",
+        }
+    }
+}
+
 #[derive(Serialize, Debug)]
 pub struct HookResult {
     pub hook: Hook,
     pub kind: HookResultKind,
 }
 
+impl HookResult {
+    /// Whether the hook has run in the output directory, either just now or in an earlier
+    /// fill, which is why it was skipped
+    pub fn has_run(&self) -> bool {
+        matches!(
+            self.kind,
+            HookResultKind::Completed { .. } | HookResultKind::Skipped(SkipReason::AlreadyRan)
+        )
+    }
+}
+
 #[derive(Serialize, Debug)]
 pub enum HookResultKind {
     Skipped(SkipReason),
-    Completed { stdout: Vec<u8>, stderr: Vec<u8> },
+    /// The output of interactive hooks isn't captured, so it's empty
+    Completed {
+        stdout: Vec<u8>,
+        stderr: Vec<u8>,
+        exit_code: i32,
+        duration: Duration,
+        /// Whether stdout or stderr was cut to the output limit, keeping its end
+        truncated: bool,
+    },
     Failed(HookError),
 }
 
@@ -148,8 +438,9 @@ impl Display for HookResultKind {
     }
 }
 
+// Serialized through the impl below, which adds the code alongside the type tag
 #[derive(Serialize, Debug)]
-#[serde(tag = "type")]
+#[serde(tag = "type", remote = "Self")]
 pub enum HookError {
     ConditionalFailed(ConditionalError),
     CommandLaunchFailed(#[serde(skip)] io::Error),
@@ -157,7 +448,11 @@ pub enum HookError {
         exit_code: i32,
         stdout: Vec<u8>,
         stderr: Vec<u8>,
+        /// Whether stdout or stderr was cut to the output limit, keeping its end
+        truncated: bool,
     },
+    /// The hook is interactive but spackle isn't attached to a terminal
+    NoTerminal,
 }
 
 impl Display for HookError {
@@ -168,14 +463,54 @@ impl Display for HookError {
             HookError::CommandExited { exit_code, .. } => {
                 write!(f, "command exited with code {}", exit_code)
             }
+            HookError::NoTerminal => write!(f, "interactive hook needs a terminal"),
         }
     }
 }
 
-#[derive(Serialize, Debug)]
+impl HookError {
+    /// Every code returned by [HookError::code]
+    pub const CODES: &'static [&'static str] = &[
+        "hook.conditional_failed",
+        "hook.command_launch_failed",
+        "hook.command_exited",
+        "hook.no_terminal",
+    ];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            HookError::ConditionalFailed(_) => "hook.conditional_failed",
+            HookError::CommandLaunchFailed(_) => "hook.command_launch_failed",
+            HookError::CommandExited { .. } => "hook.command_exited",
+            HookError::NoTerminal => "hook.no_terminal",
+        }
+    }
+}
+
+impl Serialize for HookError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Coded<'a> {
+            code: &'static str,
+            #[serde(flatten, serialize_with = "HookError::serialize")]
+            error: &'a HookError,
+        }
+
+        Coded {
+            code: self.code(),
+            error: self,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub enum SkipReason {
     UserDisabled,
     FalseConditional,
+    /// The hook isn't idempotent and already ran in the output directory
+    AlreadyRan,
 }
 
 impl Display for SkipReason {
@@ -183,6 +518,7 @@ impl Display for SkipReason {
         match self {
             SkipReason::UserDisabled => write!(f, "user disabled"),
             SkipReason::FalseConditional => write!(f, "false conditional"),
+            SkipReason::AlreadyRan => write!(f, "already ran"),
         }
     }
 }
@@ -197,23 +533,80 @@ pub enum Error {
     InvalidConditional(Hook, ConditionalError),
     #[error("Setup failed: {0}")]
     SetupFailed(Hook, io::Error),
+    #[error("Error creating temporary directory: {0}")]
+    ErrorCreatingTmpDir(io::Error),
+    #[error("Invalid hook toggle: {0}")]
+    InvalidToggle(ValidateError),
+}
+
+impl Error {
+    /// Every code returned by [Error::code]
+    pub const CODES: &'static [&'static str] = &[
+        "hook.runtime_init_failed",
+        "hook.template_render_failed",
+        "hook.invalid_conditional",
+        "hook.setup_failed",
+        "hook.tmp_dir_failed",
+        "hook.invalid_toggle",
+    ];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::ErrorInitializingRuntime(_) => "hook.runtime_init_failed",
+            Error::ErrorRenderingTemplate(..) => "hook.template_render_failed",
+            Error::InvalidConditional(..) => "hook.invalid_conditional",
+            Error::SetupFailed(..) => "hook.setup_failed",
+            Error::ErrorCreatingTmpDir(_) => "hook.tmp_dir_failed",
+            Error::InvalidToggle(_) => "hook.invalid_toggle",
+        }
+    }
 }
 
 #[derive(Serialize, Debug)]
 pub enum HookStreamResult {
     HookStarted(String),
+    /// A line written by a running hook, sent as it's read. Unlike its result, which only keeps
+    /// the end of the output, every line is sent.
+    HookOutput {
+        key: String,
+        stream: OutputStream,
+        line: String,
+    },
     HookDone(HookResult),
 }
 
-pub fn run_hooks_stream(
-    dir: impl AsRef<Path>,
-    hooks: &Vec<Hook>,
-    slots: &Vec<Slot>,
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// How many bytes of each of a hook's stdout and stderr are kept in its [HookResult]
+pub const DEFAULT_OUTPUT_LIMIT: usize = 64 * 1024;
+
+// Longer lines are split, so output without newlines can't grow a line without bound
+const MAX_LINE_BYTES: usize = 64 * 1024;
+
+/// The hooks that will be run, in execution order, and those that will be skipped before running
+#[derive(Serialize, Debug)]
+pub struct HookPlan {
+    pub queued: Vec<Hook>,
+    pub skipped: Vec<(Hook, SkipReason)>,
+}
+
+/// Determines which hooks will run based on whether they're enabled and their needs are satisfied.
+/// Hooks that pass may still be skipped at run time by their conditional.
+/// Hooks that aren't idempotent are skipped if they're among those that already ran in the
+/// output directory, as recorded by its manifest.
+pub fn plan_hooks(
+    hooks: &[Hook],
+    slots: &[Slot],
     data: &HashMap<String, String>,
-    run_as_user: Option<User>,
-) -> Result<impl Stream<Item = HookStreamResult>, Error> {
-    let mut skipped_hooks = Vec::new();
-    let mut queued_hooks = Vec::new();
+    already_ran: &[String],
+) -> HookPlan {
+    let mut skipped = Vec::new();
+    let mut queued = Vec::new();
 
     let items: Vec<&dyn Needy> = {
         let mut items = slots
@@ -226,19 +619,100 @@ pub fn run_hooks_stream(
 
     for hook in hooks {
         if hook.is_enabled(data) && hook.is_satisfied(&items, data) {
-            queued_hooks.push(hook.clone());
+            if !hook.idempotent && already_ran.contains(&hook.key) {
+                skipped.push((hook.clone(), SkipReason::AlreadyRan));
+            } else {
+                queued.push(hook.clone());
+            }
         } else if hook.is_enabled(data) {
-            skipped_hooks.push((hook.clone(), SkipReason::FalseConditional));
+            skipped.push((hook.clone(), SkipReason::FalseConditional));
         } else {
-            skipped_hooks.push((hook.clone(), SkipReason::UserDisabled));
+            skipped.push((hook.clone(), SkipReason::UserDisabled));
         }
     }
 
+    HookPlan { queued, skipped }
+}
+
+/// The key hooks are namespaced under in the context of conditionals, e.g. `hooks.<key>.ran`
+pub const HOOKS_KEY: &str = "hooks";
+
+// The context conditionals are evaluated against: the data with slot values typed, plus whether
+// each hook is enabled and has run so far as `hook_enabled_<key>` and `hook_ran_<key>`, also
+// available as `hooks.<key>.enabled` and `hooks.<key>.ran` unless the data has its own `hooks`
+fn conditional_context(
+    data: &HashMap<String, String>,
+    slots: &[Slot],
+    hooks: &[Hook],
+    ran_hooks: &[String],
+) -> Context {
+    let mut context = typed_context_from_data(data, slots);
+
+    let mut namespace = tera::Map::new();
+    for hook in hooks {
+        let enabled = hook.is_enabled(data);
+        let ran = ran_hooks.contains(&hook.key);
+
+        context.insert(format!("hook_enabled_{}", hook.key), &enabled);
+        context.insert(format!("hook_ran_{}", hook.key), &ran);
+        namespace.insert(
+            hook.key.clone(),
+            serde_json::json!({ "enabled": enabled, "ran": ran }),
+        );
+    }
+
+    if !data
+        .keys()
+        .any(|key| key == HOOKS_KEY || key.starts_with(&format!("{HOOKS_KEY}.")))
+    {
+        context.insert(HOOKS_KEY, &namespace);
+    }
+
+    context
+}
+
+/// Runs the hooks, keeping at most `output_limit` bytes of each hook's stdout and stderr in
+/// its result. Output is read as it's written rather than buffered, so a hook writing a lot
+/// of it doesn't grow memory.
+pub fn run_hooks_stream(
+    dir: impl AsRef<Path>,
+    hooks: &Vec<Hook>,
+    slots: &[Slot],
+    data: &HashMap<String, String>,
+    forwarded_args: &[String],
+    run_as_user: Option<User>,
+    output_limit: usize,
+) -> Result<impl Stream<Item = Event<HookStreamResult>>, Error> {
+    // Hooks get a scratch directory that is removed once the stream is dropped,
+    // unless the caller provided their own
+    let tmp_dir = match data.get("_tmp_dir") {
+        Some(_) => None,
+        None => Some(TempDir::new("spackle").map_err(Error::ErrorCreatingTmpDir)?),
+    };
+
+    let mut data = normalize_toggles(data, hooks).map_err(Error::InvalidToggle)?;
+    if let Some(tmp_dir) = &tmp_dir {
+        data.insert(
+            "_tmp_dir".to_string(),
+            tmp_dir.path().to_string_lossy().to_string(),
+        );
+    }
+    let data = &data;
+    let tmp_dir_path = data["_tmp_dir"].clone();
+
+    let HookPlan {
+        queued: queued_hooks,
+        skipped: skipped_hooks,
+    } = plan_hooks(hooks, slots, data, &manifest::hooks_ran(dir.as_ref()));
+
     // Apply template to command
     let mut templated_hooks = Vec::new();
     for hook in queued_hooks {
-        let context = Context::from_serialize(data.clone())
-            .map_err(|e| Error::ErrorRenderingTemplate(hook.clone(), e))?;
+        // A single-string command under a shell is its script, so the values in it are quoted
+        let context = match (&hook.shell, hook.command.as_slice()) {
+            (Some(_), [_]) => context_from_data(&shell::quote_values(data)),
+            _ => context_from_data(data),
+        };
 
         let command = hook
             .command
@@ -249,6 +723,30 @@ pub fn run_hooks_stream(
             })
             .collect::<Result<Vec<String>, Error>>()?;
 
+        // Forwarded args come from the user as-is, so they're appended after templating
+        let forwarded: &[String] = if hook.forward_args {
+            forwarded_args
+        } else {
+            &[]
+        };
+        let command = match &hook.shell {
+            // The command runs as a script, which sees forwarded args as its positional parameters
+            Some(shell) => {
+                let mut shell_command =
+                    vec![shell.clone(), "-c".to_string(), shell::script(&command)];
+                if !forwarded.is_empty() {
+                    // The first argument after the script becomes $0
+                    shell_command.push(shell.clone());
+                    shell_command.extend(forwarded.iter().cloned());
+                }
+                shell_command
+            }
+            None => command
+                .into_iter()
+                .chain(forwarded.iter().cloned())
+                .collect(),
+        };
+
         templated_hooks.push(Hook {
             command,
             ..hook.clone()
@@ -278,98 +776,243 @@ pub fn run_hooks_stream(
     }
 
     let slot_data_owned = data.clone();
-    let hook_keys = hooks.iter().map(|h| h.key.clone()).collect::<Vec<String>>();
+    let slots_owned = slots.to_vec();
+    let hooks_owned = hooks.clone();
 
     Ok(stream! {
+        // Moved into the stream so the directory lives until all hooks are done
+        let _tmp_dir = tmp_dir;
+        let sequence = Sequence::new();
+
         for (hook, reason) in skipped_hooks {
-            yield HookStreamResult::HookStarted(hook.key.clone());
-            yield HookStreamResult::HookDone(HookResult {
+            yield sequence.wrap(HookStreamResult::HookStarted(hook.key.clone()));
+            yield sequence.wrap(HookStreamResult::HookDone(HookResult {
                 hook: hook.clone(),
                 kind: HookResultKind::Skipped(reason),
-            });
+            }));
         }
 
         let mut ran_hooks = Vec::new();
         for (hook, mut cmd) in commands {
-            yield HookStreamResult::HookStarted(hook.key.clone());
+            yield sequence.wrap(HookStreamResult::HookStarted(hook.key.clone()));
 
-            // Evaluate conditional
-            // also add to the context the run status of all hooks so far
+            // Evaluate conditional, with the run status of all hooks so far
             // TODO this can be evaluated outside of stream once "needs" is implemented
-            let mut cond_context = slot_data_owned.clone();
-            for hook in &hook_keys {
-                cond_context.insert(format!("hook_ran_{}", hook), "false".to_string());
-            }
-            for hook in ran_hooks.clone() {
-                cond_context.insert(format!("hook_ran_{}", hook), "true".to_string());
-            }
+            let cond_context = conditional_context(&slot_data_owned, &slots_owned, &hooks_owned, &ran_hooks);
 
             let condition = match hook.evaluate_conditional(&cond_context) {
                 Ok(condition) => condition,
                 Err(e) => {
-                    yield HookStreamResult::HookDone(HookResult {
+                    yield sequence.wrap(HookStreamResult::HookDone(HookResult {
                         hook: hook.clone(),
                         kind: HookResultKind::Failed(HookError::ConditionalFailed(e)),
-                    });
+                    }));
                     continue;
                 }
             };
 
             if !condition {
-                yield HookStreamResult::HookDone(HookResult {
+                yield sequence.wrap(HookStreamResult::HookDone(HookResult {
                     hook: hook.clone(),
                     kind: HookResultKind::Skipped(SkipReason::FalseConditional),
-                });
+                }));
+                continue;
+            }
+
+            if hook.interactive && !attached_to_terminal() {
+                yield sequence.wrap(HookStreamResult::HookDone(HookResult {
+                    hook: hook.clone(),
+                    kind: HookResultKind::Failed(HookError::NoTerminal),
+                }));
                 continue;
             }
 
-            let cmd_result = cmd.args(&hook.command[1..])
+            let start_time = Instant::now();
+            cmd.args(&hook.command[1..])
                 .current_dir(dir.as_ref())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .output().await;
+                .env("SPACKLE_TMP_DIR", &tmp_dir_path)
+                // Dropping the stream, e.g. when a run times out, stops the hook
+                .kill_on_drop(true);
+
+            let mut stdout = OutputTail::new(output_limit);
+            let mut stderr = OutputTail::new(output_limit);
+            let cmd_result = if hook.interactive {
+                cmd.stdin(Stdio::inherit())
+                    .stdout(Stdio::inherit())
+                    .stderr(Stdio::inherit())
+                    .status()
+                    .await
+            } else {
+                match cmd
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()
+                {
+                    Ok(mut child) => {
+                        let lines = output_lines(child.stdout.take(), OutputStream::Stdout)
+                            .merge(output_lines(child.stderr.take(), OutputStream::Stderr));
+                        pin!(lines);
+
+                        while let Some((stream, line)) = lines.next().await {
+                            match stream {
+                                OutputStream::Stdout => stdout.push(&line),
+                                OutputStream::Stderr => stderr.push(&line),
+                            }
+
+                            yield sequence.wrap(HookStreamResult::HookOutput {
+                                key: hook.key.clone(),
+                                stream,
+                                line: String::from_utf8_lossy(&line).to_string(),
+                            });
+                        }
+
+                        child.status().await
+                    }
+                    Err(e) => Err(e),
+                }
+            };
+            let truncated = stdout.truncated || stderr.truncated;
 
-            let output = match cmd_result {
-                Ok(output) => output,
+            let status = match cmd_result {
+                Ok(status) => status,
                 Err(e) => {
-                    yield HookStreamResult::HookDone(HookResult {
+                    yield sequence.wrap(HookStreamResult::HookDone(HookResult {
                         hook: hook.clone(),
                         kind: HookResultKind::Failed(HookError::CommandLaunchFailed(e)),
-                    });
+                    }));
                     continue;
                 }
             };
 
-            if !output.status.success() {
-                yield HookStreamResult::HookDone(HookResult {
+            if !status.success() {
+                yield sequence.wrap(HookStreamResult::HookDone(HookResult {
                     hook: hook.clone(),
                     kind: HookResultKind::Failed(HookError::CommandExited {
-                        exit_code: output.status.code().unwrap_or(1),
-                        stdout: output.stdout,
-                        stderr: output.stderr,
+                        exit_code: status.code().unwrap_or(1),
+                        stdout: stdout.into_bytes(),
+                        stderr: stderr.into_bytes(),
+                        truncated,
                     }),
-                });
+                }));
                 continue;
             }
 
             ran_hooks.push(hook.key.clone());
 
-            yield HookStreamResult::HookDone(HookResult {
+            yield sequence.wrap(HookStreamResult::HookDone(HookResult {
                 hook: hook.clone(),
                 kind: HookResultKind::Completed {
-                    stdout: output.stdout,
-                    stderr: output.stderr,
+                    exit_code: status.code().unwrap_or(0),
+                    stdout: stdout.into_bytes(),
+                    stderr: stderr.into_bytes(),
+                    duration: start_time.elapsed(),
+                    truncated,
                 }
-            });
+            }));
+        }
+    })
+}
+
+// Reads a hook's output a line at a time, newlines included
+fn output_lines<R: AsyncRead + Unpin>(
+    reader: Option<R>,
+    stream: OutputStream,
+) -> impl Stream<Item = (OutputStream, Vec<u8>)> {
+    let state = reader.map(|reader| (BufReader::new(reader), Vec::new()));
+    lite_stream::unfold(state, move |state| async move {
+        // A split line's partial character, carried over to start the next one
+        let (mut reader, mut line) = state?;
+
+        loop {
+            let Ok(buf) = reader.fill_buf().await else {
+                return None;
+            };
+            // The end of the output, with whatever didn't end in a newline
+            if buf.is_empty() {
+                return (!line.is_empty()).then_some(((stream, line), None));
+            }
+
+            let available = &buf[..buf.len().min(MAX_LINE_BYTES - line.len())];
+            let (taken, complete) = match available.iter().position(|byte| *byte == b'\n') {
+                Some(newline) => (newline + 1, true),
+                None => (
+                    available.len(),
+                    line.len() + available.len() == MAX_LINE_BYTES,
+                ),
+            };
+            line.extend_from_slice(&available[..taken]);
+            reader.consume(taken);
+
+            if complete {
+                let rest = line.split_off(line.len() - partial_char_len(&line));
+                return Some(((stream, line), Some((reader, rest))));
+            }
         }
     })
 }
 
+// How many bytes at the end of a line belong to a UTF-8 character that hasn't been read in
+// full, so splitting a long line doesn't cut the character in two
+fn partial_char_len(line: &[u8]) -> usize {
+    for (i, byte) in line.iter().rev().take(3).enumerate() {
+        // Continuation bytes are 0b10xxxxxx, the byte leading a character says how long it is
+        if byte & 0b1100_0000 != 0b1000_0000 {
+            let len = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            return if len > i + 1 { i + 1 } else { 0 };
+        }
+    }
+
+    0
+}
+
+// The end of a hook's stdout or stderr, up to a limit
+struct OutputTail {
+    bytes: VecDeque<u8>,
+    limit: usize,
+    truncated: bool,
+}
+
+impl OutputTail {
+    fn new(limit: usize) -> Self {
+        OutputTail {
+            bytes: VecDeque::new(),
+            limit,
+            truncated: false,
+        }
+    }
+
+    fn push(&mut self, line: &[u8]) {
+        self.bytes.extend(line);
+
+        let excess = self.bytes.len().saturating_sub(self.limit);
+        if excess > 0 {
+            self.bytes.drain(..excess);
+            self.truncated = true;
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.bytes.into()
+    }
+}
+
+// Whether spackle's stdin and stdout are both a terminal, which interactive hooks need
+fn attached_to_terminal() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
 pub fn run_hooks(
     hooks: &Vec<Hook>,
     dir: impl AsRef<Path>,
-    slots: &Vec<Slot>,
+    slots: &[Slot],
     data: &HashMap<String, String>,
+    forwarded_args: &[String],
     run_as_user: Option<User>,
 ) -> Result<Vec<HookResult>, Error> {
     let runtime = tokio::runtime::Builder::new_multi_thread()
@@ -378,14 +1021,22 @@ pub fn run_hooks(
         .map_err(Error::ErrorInitializingRuntime)?;
 
     let results = runtime.block_on(async {
-        let stream = run_hooks_stream(dir, hooks, slots, data, run_as_user)?;
+        let stream = run_hooks_stream(
+            dir,
+            hooks,
+            slots,
+            data,
+            forwarded_args,
+            run_as_user,
+            DEFAULT_OUTPUT_LIMIT,
+        )?;
         pin!(stream);
 
         let mut hook_results = Vec::new();
 
         while let Some(result) = stream.next().await {
-            match result {
-                HookStreamResult::HookStarted(_) => {}
+            match result.event {
+                HookStreamResult::HookStarted(_) | HookStreamResult::HookOutput { .. } => {}
                 HookStreamResult::HookDone(hook_result) => {
                     hook_results.push(hook_result);
                 }
@@ -413,6 +1064,48 @@ impl Display for ValidateError {
     }
 }
 
+impl ValidateError {
+    /// Every code returned by [ValidateError::code]
+    pub const CODES: &'static [&'static str] =
+        &["hook.toggle.unknown_key", "hook.toggle.not_a_boolean"];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            ValidateError::UnknownKey(_) => "hook.toggle.unknown_key",
+            ValidateError::NotABoolean(_) => "hook.toggle.not_a_boolean",
+        }
+    }
+}
+
+/// Parses the value of a hook toggle. This is the single place toggles are interpreted,
+/// accepting `true`/`false` along with the usual spellings of yes and no.
+pub fn parse_toggle(key: &str, value: &str) -> Result<bool, ValidateError> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "yes" | "y" | "on" | "1" => Ok(true),
+        "false" | "no" | "n" | "off" | "0" => Ok(false),
+        _ => Err(ValidateError::NotABoolean(key.to_string())),
+    }
+}
+
+/// Rewrites the toggles for the given hooks as `true` or `false`, so templates and
+/// conditionals see consistent values. Fails on the first toggle that can't be parsed,
+/// rather than treating it as disabled.
+pub fn normalize_toggles(
+    data: &HashMap<String, String>,
+    hooks: &[Hook],
+) -> Result<HashMap<String, String>, ValidateError> {
+    let mut data = data.clone();
+
+    for hook in hooks {
+        if let Some(value) = data.get_mut(&hook.key) {
+            *value = parse_toggle(&hook.key, value)?.to_string();
+        }
+    }
+
+    Ok(data)
+}
+
 pub fn validate_data(
     data: &HashMap<String, String>,
     hooks: &Vec<Hook>,
@@ -422,9 +1115,7 @@ pub fn validate_data(
             return Err(ValidateError::UnknownKey(entry.0.clone()));
         }
 
-        if entry.1.parse::<bool>().is_err() {
-            return Err(ValidateError::NotABoolean(entry.0.clone()));
-        }
+        parse_toggle(entry.0, entry.1)?;
     }
 
     Ok(())
@@ -436,6 +1127,24 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn plain_string() {
+        let hook = Hook {
+            key: "git".to_string(),
+            command: vec!["git".to_string(), "init".to_string()],
+            default: Some(false),
+            ..Default::default()
+        };
+
+        let plain = hook.to_plain_string();
+        assert!(!plain.contains('\u{1b}'), "{:?}", plain);
+        assert!(
+            plain.starts_with("git default off\ngit init"),
+            "{:?}",
+            plain
+        );
+    }
+
     #[test]
     fn basic() {
         let hooks = vec![Hook {
@@ -444,7 +1153,314 @@ mod tests {
             ..Hook::default()
         }];
 
-        assert!(run_hooks(&hooks, ".", &Vec::new(), &HashMap::new(), None).is_ok());
+        assert!(run_hooks(&hooks, ".", &Vec::new(), &HashMap::new(), &[], None).is_ok());
+    }
+
+    #[test]
+    fn completed_exit_code_and_duration() {
+        let hooks = vec![Hook {
+            key: "sleep".to_string(),
+            command: vec!["sleep".to_string(), "0.1".to_string()],
+            ..Hook::default()
+        }];
+
+        let results = run_hooks(&hooks, ".", &Vec::new(), &HashMap::new(), &[], None)
+            .expect("run_hooks failed, should have succeeded");
+
+        assert!(
+            matches!(
+                results[0].kind,
+                HookResultKind::Completed { exit_code: 0, duration, .. }
+                    if duration >= Duration::from_millis(100)
+            ),
+            "Expected hook to complete with its timing, got {:?}",
+            results
+        );
+    }
+
+    #[test]
+    fn interactive() {
+        let hooks = vec![Hook {
+            key: "login".to_string(),
+            command: vec!["echo".to_string(), "hello".to_string()],
+            interactive: true,
+            ..Hook::default()
+        }];
+
+        let results = run_hooks(&hooks, ".", &Vec::new(), &HashMap::new(), &[], None)
+            .expect("run_hooks failed, should have succeeded");
+
+        // Whether the hook may run depends on how the tests were started
+        if attached_to_terminal() {
+            assert!(
+                matches!(&results[0].kind, HookResultKind::Completed { stdout, .. } if stdout.is_empty()),
+                "Expected the hook's output to go to the terminal, got {:?}",
+                results
+            );
+        } else {
+            assert!(
+                matches!(
+                    results[0].kind,
+                    HookResultKind::Failed(HookError::NoTerminal)
+                ),
+                "Expected the hook to need a terminal, got {:?}",
+                results
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_sequence() {
+        let hooks = vec![
+            Hook {
+                key: "skipped".to_string(),
+                command: vec!["true".to_string()],
+                default: Some(false),
+                ..Hook::default()
+            },
+            Hook {
+                key: "1".to_string(),
+                command: vec!["true".to_string()],
+                ..Hook::default()
+            },
+            Hook {
+                key: "2".to_string(),
+                command: vec!["false".to_string()],
+                ..Hook::default()
+            },
+        ];
+
+        let stream = run_hooks_stream(
+            ".",
+            &hooks,
+            &Vec::new(),
+            &HashMap::new(),
+            &[],
+            None,
+            DEFAULT_OUTPUT_LIMIT,
+        )
+        .expect("run_hooks_stream failed, should have succeeded");
+        let events = stream.collect::<Vec<_>>().await;
+
+        assert_eq!(events.len(), 6);
+        assert_eq!(
+            events.iter().map(|e| e.seq).collect::<Vec<_>>(),
+            (0..6).collect::<Vec<_>>()
+        );
+        assert!(events.windows(2).all(|w| w[0].at <= w[1].at));
+    }
+
+    #[tokio::test]
+    async fn bounded_output() {
+        let hooks = vec![Hook {
+            key: "verbose".to_string(),
+            command: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "seq 1 1000; echo done >&2; head -c 100000 /dev/zero | tr '\\0' x".to_string(),
+            ],
+            ..Hook::default()
+        }];
+
+        let stream = run_hooks_stream(".", &hooks, &Vec::new(), &HashMap::new(), &[], None, 100)
+            .expect("run_hooks_stream failed, should have succeeded");
+        let events = stream.collect::<Vec<_>>().await;
+
+        // Every line is streamed, with the one too long for a line split in two
+        let lines = |of: OutputStream| {
+            events
+                .iter()
+                .filter_map(|event| match &event.event {
+                    HookStreamResult::HookOutput { stream, line, .. } if *stream == of => {
+                        Some(line.clone())
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let stdout_lines = lines(OutputStream::Stdout);
+        assert_eq!(stdout_lines.len(), 1002);
+        assert_eq!(stdout_lines[0], "1\n");
+        assert_eq!(stdout_lines[1000].len(), MAX_LINE_BYTES);
+        assert_eq!(
+            stdout_lines.iter().map(String::len).sum::<usize>(),
+            (1..=1000).map(|i| i.to_string().len() + 1).sum::<usize>() + 100000
+        );
+        assert_eq!(lines(OutputStream::Stderr), vec!["done\n"]);
+
+        // The result only keeps the end
+        let Some(HookStreamResult::HookDone(result)) = events.last().map(|event| &event.event)
+        else {
+            panic!("Expected the last event to be the hook's result");
+        };
+        match &result.kind {
+            HookResultKind::Completed {
+                stdout,
+                stderr,
+                truncated,
+                ..
+            } => {
+                assert!(truncated);
+                assert_eq!(stdout, &vec![b'x'; 100]);
+                assert_eq!(stderr, b"done\n");
+            }
+            kind => panic!("Expected the hook to complete, got {:?}", kind),
+        }
+    }
+
+    #[tokio::test]
+    async fn split_lines_keep_characters() {
+        // One byte, then two byte characters, so the split falls inside one
+        let hooks = vec![Hook {
+            key: "accents".to_string(),
+            command: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "printf x; head -c 40000 /dev/zero | tr '\\0' x | sed 's/x/é/g'".to_string(),
+            ],
+            ..Hook::default()
+        }];
+
+        let stream = run_hooks_stream(".", &hooks, &Vec::new(), &HashMap::new(), &[], None, 100)
+            .expect("run_hooks_stream failed, should have succeeded");
+        let lines = stream
+            .filter_map(|event| match event.event {
+                HookStreamResult::HookOutput { line, .. } => Some(line),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), MAX_LINE_BYTES - 1);
+        assert!(lines.iter().all(|line| !line.contains('\u{fffd}')));
+        assert_eq!(lines.concat(), format!("x{}", "é".repeat(40000)));
+    }
+
+    #[test]
+    fn partial_char_len() {
+        assert_eq!(super::partial_char_len(b"abc"), 0);
+        assert_eq!(super::partial_char_len("é".as_bytes()), 0);
+        assert_eq!(super::partial_char_len(&"é".as_bytes()[..1]), 1);
+        assert_eq!(super::partial_char_len(&"🦀".as_bytes()[..3]), 3);
+        assert_eq!(super::partial_char_len("a🦀".as_bytes()), 0);
+    }
+
+    #[test]
+    fn writes() {
+        let hook = Hook {
+            key: "install_git_hooks".to_string(),
+            writes: vec![
+                "~/.config/foo".to_string(),
+                "/etc/foo".to_string(),
+                "../sibling".to_string(),
+                "sub/../inside".to_string(),
+                ".git/hooks".to_string(),
+            ],
+            ..Hook::default()
+        };
+
+        assert_eq!(
+            hook.writes_outside_output(),
+            vec!["~/.config/foo", "/etc/foo", "../sibling"]
+        );
+
+        let paths = hook.write_paths(Path::new("/tmp/out"));
+        if let Some(home) = env::var_os("HOME") {
+            assert_eq!(paths[0], PathBuf::from(home).join(".config/foo"));
+        }
+        assert_eq!(paths[1], PathBuf::from("/etc/foo"));
+        assert_eq!(paths[4], PathBuf::from("/tmp/out/.git/hooks"));
+    }
+
+    #[test]
+    fn toggles() {
+        for value in ["true", "yes", "Y", "on", "1"] {
+            assert!(parse_toggle("docker", value).unwrap(), "{}", value);
+        }
+        for value in ["false", "No", "n", "off", "0"] {
+            assert!(!parse_toggle("docker", value).unwrap(), "{}", value);
+        }
+        assert!(matches!(
+            parse_toggle("docker", "maybe"),
+            Err(ValidateError::NotABoolean(key)) if key == "docker"
+        ));
+
+        let hooks = vec![Hook {
+            key: "docker".to_string(),
+            command: vec!["true".to_string()],
+            default: Some(false),
+            ..Hook::default()
+        }];
+
+        let data = normalize_toggles(
+            &HashMap::from([
+                ("docker".to_string(), "yes".to_string()),
+                ("name".to_string(), "yes".to_string()),
+            ]),
+            &hooks,
+        )
+        .unwrap();
+        assert_eq!(data["docker"], "true");
+        // Only hook toggles are rewritten
+        assert_eq!(data["name"], "yes");
+
+        // Garbage fails loudly instead of disabling the hook
+        assert!(matches!(
+            run_hooks_stream(
+                ".",
+                &hooks,
+                &[],
+                &HashMap::from([("docker".to_string(), "maybe".to_string())]),
+                &[],
+                None,
+                DEFAULT_OUTPUT_LIMIT,
+            ),
+            Err(Error::InvalidToggle(_))
+        ));
+    }
+
+    #[test]
+    fn plan() {
+        let hooks = vec![
+            Hook {
+                key: "disabled".to_string(),
+                command: vec!["true".to_string()],
+                default: Some(false),
+                ..Hook::default()
+            },
+            Hook {
+                key: "needs_disabled".to_string(),
+                command: vec!["true".to_string()],
+                needs: vec!["disabled".to_string()],
+                ..Hook::default()
+            },
+            Hook {
+                key: "queued".to_string(),
+                command: vec!["true".to_string()],
+                ..Hook::default()
+            },
+        ];
+
+        let plan = plan_hooks(&hooks, &[], &HashMap::new(), &[]);
+
+        assert_eq!(
+            plan.queued
+                .iter()
+                .map(|h| h.key.as_str())
+                .collect::<Vec<_>>(),
+            vec!["queued"]
+        );
+        assert_eq!(
+            plan.skipped
+                .iter()
+                .map(|(h, reason)| (h.key.as_str(), reason.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("disabled", SkipReason::UserDisabled),
+                ("needs_disabled", SkipReason::FalseConditional),
+            ]
+        );
     }
 
     #[test]
@@ -458,7 +1474,7 @@ mod tests {
             },
         ];
 
-        let results = run_hooks(&hooks, ".", &Vec::new(), &HashMap::new(), None)
+        let results = run_hooks(&hooks, ".", &Vec::new(), &HashMap::new(), &[], None)
             .expect("run_hooks failed, should have succeeded");
 
         assert!(
@@ -487,7 +1503,7 @@ mod tests {
             },
         ];
 
-        let results = run_hooks(&hooks, ".", &Vec::new(), &HashMap::new(), None)
+        let results = run_hooks(&hooks, ".", &Vec::new(), &HashMap::new(), &[], None)
             .expect("run_hooks failed, should have succeeded");
 
         assert!(results.iter().any(|x| matches!(x, HookResult {
@@ -531,7 +1547,7 @@ mod tests {
             },
         ];
 
-        let results = run_hooks(&hooks, ".", &Vec::new(), &HashMap::new(), None)
+        let results = run_hooks(&hooks, ".", &Vec::new(), &HashMap::new(), &[], None)
             .expect("run_hooks failed, should have succeeded");
 
         let skipped_hooks: Vec<_> = results
@@ -581,6 +1597,7 @@ mod tests {
             ".",
             &Vec::new(),
             &HashMap::from([("good_var".to_string(), "true".to_string())]),
+            &[],
             None,
         )
         .expect("run_hooks failed, should have succeeded");
@@ -612,6 +1629,7 @@ mod tests {
             ".",
             &Vec::new(),
             &HashMap::from([("".to_string(), "".to_string())]),
+            &[],
             None,
         )
         .expect("run_hooks failed, should have succeeded");
@@ -650,6 +1668,7 @@ mod tests {
             ".",
             &Vec::new(),
             &HashMap::from([("3".to_string(), "true".to_string())]),
+            &[],
             None,
         )
         .expect("run_hooks failed, should have succeeded");
@@ -704,6 +1723,7 @@ mod tests {
                 ("field_2".to_string(), "test".to_string()),
                 ("_output_name".to_string(), "spackle".to_string()),
             ]),
+            &[],
             None,
         )
         .expect("run_hooks failed, should have succeeded");
@@ -734,6 +1754,146 @@ mod tests {
         );
     }
 
+    #[test]
+    fn forwarded_args() {
+        let hooks = vec![
+            Hook {
+                key: "1".to_string(),
+                command: vec!["echo".to_string(), "{{ field_1 }}".to_string()],
+                forward_args: true,
+                ..Hook::default()
+            },
+            Hook {
+                key: "2".to_string(),
+                command: vec!["echo".to_string(), "{{ field_1 }}".to_string()],
+                ..Hook::default()
+            },
+        ];
+
+        let results = run_hooks(
+            &hooks,
+            ".",
+            &Vec::new(),
+            &HashMap::from([("field_1".to_string(), "hello".to_string())]),
+            &["--extra-flag".to_string(), "{{ field_1 }}".to_string()],
+            None,
+        )
+        .expect("run_hooks failed, should have succeeded");
+
+        let stdout_of = |key: &str| {
+            results
+                .iter()
+                .find_map(|x| match x {
+                    HookResult {
+                        hook,
+                        kind: HookResultKind::Completed { stdout, .. },
+                    } if hook.key == key => {
+                        Some(String::from_utf8_lossy(stdout).trim().to_string())
+                    }
+                    _ => None,
+                })
+                .unwrap_or_else(|| panic!("Expected hook {} to complete, got {:?}", key, results))
+        };
+
+        // Forwarded args are appended verbatim, without templating
+        assert_eq!(stdout_of("1"), "hello --extra-flag {{ field_1 }}");
+        assert_eq!(stdout_of("2"), "hello");
+    }
+
+    #[test]
+    fn shell_script_values() {
+        let hooks = vec![Hook {
+            key: "script".to_string(),
+            command: vec!["printf '%s|' {{ field_1 }} && echo".to_string()],
+            shell: Some("sh".to_string()),
+            ..Hook::default()
+        }];
+
+        let results = run_hooks(
+            &hooks,
+            ".",
+            &Vec::new(),
+            &HashMap::from([("field_1".to_string(), "it's; echo injected".to_string())]),
+            &[],
+            None,
+        )
+        .expect("run_hooks failed, should have succeeded");
+
+        // The value reaches the script as a single argument instead of being run
+        assert!(
+            matches!(&results[0].kind, HookResultKind::Completed { stdout, .. } if stdout == b"it's; echo injected|\n"),
+            "Expected the value to be quoted, got {:?}",
+            results
+        );
+    }
+
+    #[test]
+    fn tmp_dir() {
+        let hooks = vec![
+            Hook {
+                key: "1".to_string(),
+                command: vec![
+                    "bash".to_string(),
+                    "-c".to_string(),
+                    "touch \"$SPACKLE_TMP_DIR/scratch\" && echo {{ _tmp_dir }}".to_string(),
+                ],
+                ..Hook::default()
+            },
+            Hook {
+                key: "2".to_string(),
+                command: vec![
+                    "test".to_string(),
+                    "-f".to_string(),
+                    "{{ _tmp_dir }}/scratch".to_string(),
+                ],
+                ..Hook::default()
+            },
+        ];
+
+        let results = run_hooks(&hooks, ".", &Vec::new(), &HashMap::new(), &[], None)
+            .expect("run_hooks failed, should have succeeded");
+
+        let tmp_dir = match &results[0].kind {
+            HookResultKind::Completed { stdout, .. } => {
+                PathBuf::from(String::from_utf8_lossy(stdout).trim())
+            }
+            _ => panic!("Expected hook 1 to complete, got {:?}", results),
+        };
+
+        assert!(
+            matches!(results[1].kind, HookResultKind::Completed { .. }),
+            "Expected scratch file to be visible to later hooks, got {:?}",
+            results
+        );
+        assert!(!tmp_dir.exists(), "Expected tmp dir to be removed");
+    }
+
+    #[test]
+    fn tmp_dir_provided() {
+        let tmp_dir = TempDir::new("spackle").unwrap().into_path();
+
+        let hooks = vec![Hook {
+            key: "1".to_string(),
+            command: vec!["touch".to_string(), "{{ _tmp_dir }}/scratch".to_string()],
+            ..Hook::default()
+        }];
+
+        run_hooks(
+            &hooks,
+            ".",
+            &Vec::new(),
+            &HashMap::from([(
+                "_tmp_dir".to_string(),
+                tmp_dir.to_string_lossy().to_string(),
+            )]),
+            &[],
+            None,
+        )
+        .expect("run_hooks failed, should have succeeded");
+
+        assert!(tmp_dir.join("scratch").exists());
+    }
+
     #[test]
     fn invalid_templated_cmd() {
         let hooks = vec![Hook {
@@ -747,6 +1907,7 @@ mod tests {
             ".",
             &Vec::new(),
             &HashMap::from([("field_1".to_string(), "echo".to_string())]),
+            &[],
             None,
         )
         .expect_err("run_hooks succeeded, should have failed");
@@ -803,6 +1964,7 @@ mod tests {
                 ("number_slot".to_string(), "1".to_string()),
                 ("bool_slot".to_string(), "true".to_string()),
             ]),
+            &[],
             None,
         )
         .expect("run_hooks failed, should have succeeded");
@@ -835,7 +1997,7 @@ mod tests {
             },
         ];
 
-        let results = run_hooks(&hooks, ".", &Vec::new(), &HashMap::new(), None)
+        let results = run_hooks(&hooks, ".", &Vec::new(), &HashMap::new(), &[], None)
             .expect("run_hooks failed, should have succeeded");
 
         assert!(
@@ -858,7 +2020,7 @@ mod tests {
             ..Hook::default()
         }];
 
-        let results = run_hooks(&hooks, ".", &Vec::new(), &HashMap::new(), None)
+        let results = run_hooks(&hooks, ".", &Vec::new(), &HashMap::new(), &[], None)
             .expect("run_hooks failed, should have succeeded");
 
         assert!(
@@ -894,7 +2056,7 @@ mod tests {
             },
         ];
 
-        let results = run_hooks(&hooks, ".", &Vec::new(), &HashMap::new(), None)
+        let results = run_hooks(&hooks, ".", &Vec::new(), &HashMap::new(), &[], None)
             .expect("run_hooks failed, should have succeeded");
 
         assert!(
@@ -933,6 +2095,7 @@ mod tests {
             ".",
             &Vec::new(),
             &HashMap::from([("slot_a".to_string(), "false".to_string())]),
+            &[],
             None,
         )
         .expect("run_hooks failed, should have succeeded");
@@ -948,6 +2111,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn command_status() {
+        let hook = |command: &str| Hook {
+            key: "hook".to_string(),
+            command: vec![command.to_string()],
+            ..Hook::default()
+        };
+
+        assert!(matches!(
+            hook("echo").command_status(),
+            CommandStatus::Found(_)
+        ));
+        assert_eq!(
+            hook("spackle-definitely-missing-command").command_status(),
+            CommandStatus::NotFound
+        );
+        assert_eq!(
+            hook("{{ field_1 }}").command_status(),
+            CommandStatus::Templated
+        );
+        assert_eq!(
+            hook("./scripts/setup.sh").command_status(),
+            CommandStatus::Relative
+        );
+    }
+
     #[test]
     fn test_validate_data_non_boolean() {
         let data = HashMap::from([("hook_a".to_string(), "foo".to_string())]);
@@ -969,4 +2158,87 @@ mod tests {
 
         validate_data(&data, &hooks).expect_err("validate_data should have failed");
     }
+
+    #[test]
+    fn hook_error_serializes_code() {
+        let error = HookError::CommandExited {
+            exit_code: 2,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            truncated: false,
+        };
+
+        let json = serde_json::to_value(&error).expect("Failed to serialize hook error");
+
+        assert_eq!(json["code"], "hook.command_exited");
+        assert_eq!(json["type"], "CommandExited");
+        assert_eq!(json["exit_code"], 2);
+    }
+
+    #[test]
+    fn conditional_typed_values() {
+        let slots = vec![Slot {
+            key: "replicas".to_string(),
+            r#type: SlotType::Number,
+            ..Slot::default()
+        }];
+        let hooks = vec![Hook {
+            key: "scale".to_string(),
+            command: vec!["true".to_string()],
+            r#if: Some("{{ replicas > 1 }}".to_string()),
+            ..Hook::default()
+        }];
+
+        for (replicas, runs) in [("3", true), ("1", false)] {
+            let data = HashMap::from([("replicas".to_string(), replicas.to_string())]);
+            let results = run_hooks(&hooks, ".", &slots, &data, &[], None)
+                .expect("run_hooks failed, should have succeeded");
+
+            assert_eq!(
+                matches!(results[0].kind, HookResultKind::Completed { .. }),
+                runs,
+                "Expected the hook to {} with {} replicas, got {:?}",
+                if runs { "run" } else { "be skipped" },
+                replicas,
+                results
+            );
+        }
+    }
+
+    #[test]
+    fn conditional_enabled_state() {
+        // The first hook depends on whether a later one is turned on, before it has run
+        let hooks = vec![
+            Hook {
+                key: "configure_docker".to_string(),
+                command: vec!["true".to_string()],
+                r#if: Some("{{ hooks.docker.enabled and hook_enabled_docker }}".to_string()),
+                ..Hook::default()
+            },
+            Hook {
+                key: "docker".to_string(),
+                command: vec!["true".to_string()],
+                default: Some(false),
+                ..Hook::default()
+            },
+        ];
+
+        for (toggle, runs) in [("true", true), ("false", false)] {
+            let data = HashMap::from([("docker".to_string(), toggle.to_string())]);
+            let results = run_hooks(&hooks, ".", &[], &data, &[], None)
+                .expect("run_hooks failed, should have succeeded");
+
+            let configure = results
+                .iter()
+                .find(|r| r.hook.key == "configure_docker")
+                .unwrap();
+            assert_eq!(
+                matches!(configure.kind, HookResultKind::Completed { .. }),
+                runs,
+                "Expected configure_docker to follow the docker toggle {}, got {:?}",
+                toggle,
+                results
+            );
+        }
+    }
 }
@@ -1,20 +1,40 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap},
     fmt::Display,
+    fs, io,
     path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
+use heck::{ToKebabCase, ToSnakeCase, ToUpperCamelCase};
+use serde::{Deserialize, Serialize};
+use tempdir::TempDir;
 use template::RenderedFile;
 use thiserror::Error;
-use tokio_stream::Stream;
+use tokio_stream::{Stream, StreamExt};
 use users::User;
 
+pub mod answers;
+pub mod cache;
+pub mod classify;
 pub mod config;
 pub mod copy;
+pub mod event;
 pub mod hook;
+pub mod manifest;
+pub mod migrate;
 mod needs;
+pub mod paths;
+pub mod plan;
+pub mod prelude;
+pub mod run_log;
+pub mod schema;
+pub mod shell;
 pub mod slot;
 pub mod template;
+pub mod testing;
+pub mod update;
+pub mod user_config;
 
 #[derive(Error, Debug)]
 pub enum GenerateError {
@@ -28,6 +48,304 @@ pub enum GenerateError {
     TemplateError(#[from] tera::Error),
     #[error("Error rendering file: {0}")]
     FileError(#[from] template::FileError),
+    #[error("{} copied file(s) do not match their source", .0.len())]
+    VerifyError(Vec<copy::Mismatch>),
+    #[error("Error running hooks: {0}")]
+    HookError(Box<hook::Error>),
+    #[error("Generation exceeded the time limit of {0:?}")]
+    TimedOut(Duration),
+    #[error("Error writing manifest: {0}")]
+    ManifestError(manifest::Error),
+    #[error("The path given for {0} does not exist: {}", .1.display())]
+    MissingImport(String, PathBuf),
+    #[error("Planned output was not written: {}", .0.display())]
+    MissingOutput(PathBuf),
+}
+
+impl GenerateError {
+    /// Every code returned by [GenerateError::code]
+    pub const CODES: &'static [&'static str] = &[
+        "generate.already_exists",
+        "generate.bad_config",
+        "generate.copy_failed",
+        "generate.template_error",
+        "generate.file_error",
+        "generate.verify_failed",
+        "generate.hook_error",
+        "generate.timed_out",
+        "generate.manifest_error",
+        "generate.missing_import",
+        "generate.missing_output",
+    ];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages.
+    /// Wrapped errors have their own, more specific, codes.
+    pub fn code(&self) -> &'static str {
+        match self {
+            GenerateError::AlreadyExists(_) => "generate.already_exists",
+            GenerateError::BadConfig(_) => "generate.bad_config",
+            GenerateError::CopyError(_) => "generate.copy_failed",
+            GenerateError::TemplateError(_) => "generate.template_error",
+            GenerateError::FileError(_) => "generate.file_error",
+            GenerateError::VerifyError(_) => "generate.verify_failed",
+            GenerateError::HookError(_) => "generate.hook_error",
+            GenerateError::TimedOut(_) => "generate.timed_out",
+            GenerateError::ManifestError(_) => "generate.manifest_error",
+            GenerateError::MissingImport(..) => "generate.missing_import",
+            GenerateError::MissingOutput(_) => "generate.missing_output",
+        }
+    }
+}
+
+/// Options controlling how a project is generated
+#[derive(Debug, Default, Clone)]
+pub struct GenerateOptions {
+    /// Re-read every written file and compare it against its source or rendered output.
+    /// Off by default since it doubles the I/O of a generation.
+    pub verify: bool,
+    /// Check that every path [Project::outputs_for] plans exists once generation is done,
+    /// catching writes that silently didn't happen. Off by default since it stats every output.
+    pub verify_outputs: bool,
+    /// The maximum number of bytes rendered across all templates
+    pub max_total_bytes: Option<usize>,
+    /// The maximum number of bytes rendered for a single template
+    pub max_file_bytes: Option<usize>,
+    /// The wall-clock budget for rendering all templates
+    pub render_timeout: Option<Duration>,
+    /// The wall-clock budget for the whole run, including hooks. Only applies to
+    /// [Project::generate_with_hooks]
+    pub timeout: Option<Duration>,
+    /// Don't run slots' `default_from_command`, e.g. on a server where running
+    /// template-specified commands before generation isn't acceptable
+    pub skip_default_commands: bool,
+    /// Whether rendering errors on undefined variables, overriding the project's
+    /// `strict_variables`. Templates rendered outside a project are strict unless this is `Some(false)`.
+    pub strict_variables: Option<bool>,
+    /// How rendered files are normalized, overriding the project's `render` config.
+    /// Templates rendered outside a project aren't normalized unless this is set.
+    pub render: Option<config::RenderConfig>,
+    /// Variables added to the context of templates as a nested `env` object, e.g.
+    /// `{{ env.HOME }}`, usually the process environment. They aren't validated as slots, and
+    /// a slot keyed `env` is shadowed by them.
+    pub env: Option<HashMap<String, String>>,
+    /// How many bytes of each hook's stdout and stderr are kept in its result, defaulting to
+    /// [hook::DEFAULT_OUTPUT_LIMIT]. Every line is still streamed as it's written.
+    pub hook_output_limit: Option<usize>,
+}
+
+/// How long a slot's `default_from_command` may run before it's abandoned
+pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Error, Debug)]
+pub enum RenderVarsError {
+    #[error("Error reading render vars file: {0}")]
+    ReadError(io::Error),
+    #[error("Error parsing render vars file: {0}")]
+    ParseError(toml::de::Error),
+    #[error("Render var {0} has the same key as a declared slot")]
+    CollidesWithSlot(String),
+    #[error("Render var {0} has the same key as a declared hook")]
+    CollidesWithHook(String),
+    #[error("Render var {0} uses a key reserved by spackle")]
+    Reserved(String),
+}
+
+impl RenderVarsError {
+    /// Every code returned by [RenderVarsError::code]
+    pub const CODES: &'static [&'static str] = &[
+        "render_vars.read_error",
+        "render_vars.parse_error",
+        "render_vars.collides_with_slot",
+        "render_vars.collides_with_hook",
+        "render_vars.reserved",
+    ];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            RenderVarsError::ReadError(_) => "render_vars.read_error",
+            RenderVarsError::ParseError(_) => "render_vars.parse_error",
+            RenderVarsError::CollidesWithSlot(_) => "render_vars.collides_with_slot",
+            RenderVarsError::CollidesWithHook(_) => "render_vars.collides_with_hook",
+            RenderVarsError::Reserved(_) => "render_vars.reserved",
+        }
+    }
+}
+
+/// Loads freeform template variables from a TOML file.
+/// Unlike slot data these are not validated, so values of any type are converted to strings.
+pub fn load_render_vars(path: &Path) -> Result<HashMap<String, String>, RenderVarsError> {
+    let contents = fs::read_to_string(path).map_err(RenderVarsError::ReadError)?;
+    let table: toml::Table = toml::from_str(&contents).map_err(RenderVarsError::ParseError)?;
+
+    Ok(table
+        .into_iter()
+        .map(|(key, value)| match value {
+            toml::Value::String(s) => (key, s),
+            value => (key, value.to_string()),
+        })
+        .collect())
+}
+
+/// How long each phase of a run took
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct Timings {
+    pub copy: Duration,
+    pub render: Duration,
+    /// How long each completed hook's command ran, in execution order
+    pub hooks: Vec<(String, Duration)>,
+    pub total: Duration,
+}
+
+impl Timings {
+    /// Records the duration of a hook, if it completed
+    pub fn record_hook(&mut self, result: &hook::HookResult) {
+        if let hook::HookResultKind::Completed { duration, .. } = result.kind {
+            self.hooks.push((result.hook.key.clone(), duration));
+        }
+    }
+
+    pub fn hooks_total(&self) -> Duration {
+        self.hooks.iter().map(|(_, duration)| *duration).sum()
+    }
+}
+
+impl Display for Timings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "copy {:.2}s · render {:.2}s · hooks {:.2}s · total {:.2}s",
+            self.copy.as_secs_f64(),
+            self.render.as_secs_f64(),
+            self.hooks_total().as_secs_f64(),
+            self.total.as_secs_f64()
+        )
+    }
+}
+
+/// Keys spackle adds to the template and hook context. Slots and hooks may not use them.
+pub const RESERVED_KEYS: &[&str] = &[
+    "_output_name",
+    "_output_name_snake",
+    "_output_name_kebab",
+    "_output_name_pascal",
+    "_project_name",
+    "_project_name_snake",
+    "_project_name_kebab",
+    "_project_name_pascal",
+    "_run_id",
+    "_tmp_dir",
+    "_slots",
+    "_hooks",
+];
+
+/// Prefixes of keys that spackle adds or may add to the context, like `hook_ran_<key>` for
+/// hook conditionals. Slots and hooks may not use keys starting with them either.
+pub const RESERVED_PREFIXES: &[&str] = &["_", "hook_ran_", "hook_enabled_"];
+
+/// Whether a slot or hook key is one of the [RESERVED_KEYS] or starts with one of the
+/// [RESERVED_PREFIXES]
+pub fn is_reserved_key(key: &str) -> bool {
+    RESERVED_KEYS.contains(&key)
+        || RESERVED_PREFIXES
+            .iter()
+            .any(|prefix| key.starts_with(prefix))
+}
+
+/// Reserved keys that spackle always sets, replacing any supplied value. `_slots` and `_hooks`
+/// are added to the template context rather than the data, so supplied values are dropped.
+/// `_run_id` and `_tmp_dir` may instead be supplied by the caller to pin them for a run.
+pub const INJECTED_KEYS: &[&str] = &[
+    "_output_name",
+    "_output_name_snake",
+    "_output_name_kebab",
+    "_output_name_pascal",
+    "_project_name",
+    "_project_name_snake",
+    "_project_name_kebab",
+    "_project_name_pascal",
+    "_slots",
+    "_hooks",
+];
+
+// Inserts a name along with its cased variants, e.g. `_output_name_snake`
+fn insert_name(data: &mut HashMap<String, String>, key: &str, name: String) {
+    data.insert(format!("{}_snake", key), name.to_snake_case());
+    data.insert(format!("{}_kebab", key), name.to_kebab_case());
+    data.insert(format!("{}_pascal", key), name.to_upper_camel_case());
+    data.insert(key.to_string(), name);
+}
+
+/// Gets the keys in the supplied data that will be replaced by injected values, in sorted order
+pub fn overridden_keys(data: &HashMap<String, String>) -> Vec<String> {
+    let mut keys = INJECTED_KEYS
+        .iter()
+        .filter(|key| data.contains_key(**key))
+        .map(|key| key.to_string())
+        .collect::<Vec<_>>();
+    keys.sort();
+
+    keys
+}
+
+/// A supplied value that was normalized before entering the context, e.g. a hook toggle of `yes` becoming `true`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Coercion {
+    pub key: String,
+    pub from: String,
+    pub to: String,
+}
+
+impl Display for Coercion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {:?} → {:?}", self.key, self.from, self.to)
+    }
+}
+
+/// Compares supplied data against its normalized form, giving the values that changed in key order
+pub fn coercions(
+    supplied: &HashMap<String, String>,
+    normalized: &HashMap<String, String>,
+) -> Vec<Coercion> {
+    let mut coercions = supplied
+        .iter()
+        .filter_map(|(key, from)| match normalized.get(key) {
+            Some(to) if to != from => Some(Coercion {
+                key: key.clone(),
+                from: from.clone(),
+                to: to.clone(),
+            }),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    coercions.sort_by(|a, b| a.key.cmp(&b.key));
+
+    coercions
+}
+
+/// Generates a random (version 4) UUID identifying a single run.
+/// Templates and hooks can access it as `_run_id`; it is stable within a run but differs across runs.
+pub fn new_run_id() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("failed to generate random bytes for run id");
+
+    // Set the version (4) and variant (RFC 4122) bits
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
 }
 
 // Gets the output name as the canonicalized path's file stem
@@ -44,6 +362,28 @@ pub fn get_output_name(out_dir: &Path) -> String {
         .to_string()
 }
 
+/// Removes the ANSI escape sequences `colored` adds, for text shown where colors don't belong,
+/// like logs or files. Unlike turning colors off with `colored::control`, it doesn't change
+/// how anything else in the process is printed.
+pub fn strip_ansi(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            plain.push(c);
+            continue;
+        }
+
+        // A control sequence runs from `ESC [` to its final byte, in `@` through `~`
+        if chars.next() == Some('[') {
+            chars.by_ref().find(|c| ('@'..='~').contains(c));
+        }
+    }
+
+    plain
+}
+
 #[derive(Debug)]
 pub enum RunHooksError {
     BadConfig(config::Error),
@@ -59,16 +399,114 @@ impl Display for RunHooksError {
     }
 }
 
+impl RunHooksError {
+    /// Every code returned by [RunHooksError::code]
+    pub const CODES: &'static [&'static str] = &["run_hooks.bad_config", "run_hooks.hook_error"];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages.
+    /// Wrapped errors have their own, more specific, codes.
+    pub fn code(&self) -> &'static str {
+        match self {
+            RunHooksError::BadConfig(_) => "run_hooks.bad_config",
+            RunHooksError::HookError(_) => "run_hooks.hook_error",
+        }
+    }
+}
+
+/// Every error code the crate can return, across all of its error types
+pub fn error_codes() -> Vec<&'static str> {
+    [
+        answers::Error::CODES,
+        config::Error::CODES,
+        slot::Error::CODES,
+        hook::Error::CODES,
+        hook::HookError::CODES,
+        hook::ConditionalError::CODES,
+        hook::ValidateError::CODES,
+        manifest::Error::CODES,
+        migrate::Error::CODES,
+        template::FileErrorKind::CODES,
+        template::ValidateError::CODES,
+        copy::Error::CODES,
+        GenerateError::CODES,
+        RenderVarsError::CODES,
+        RunHooksError::CODES,
+        run_log::Error::CODES,
+        testing::Error::CODES,
+        update::Error::CODES,
+        cache::Error::CODES,
+        classify::Error::CODES,
+        user_config::Error::CODES,
+    ]
+    .concat()
+}
+
+/// The spackle that produced something, recorded in manifests, answers files and reports
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Version {
+    /// The crate version, e.g. `0.4.0`
+    pub version: String,
+    /// The git revision spackle was built from, when built from a checkout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_sha: Option<String>,
+}
+
+impl Version {
+    /// The major version, or `None` if the version isn't `major.minor.patch`
+    pub fn major(&self) -> Option<u64> {
+        migrate::parse_version(&self.version).map(|(major, _, _)| major)
+    }
+
+    /// Whether this version's major version is newer than the running spackle's, so what it
+    /// produced may not be understood
+    pub fn is_newer_major(&self) -> bool {
+        self.major()
+            .zip(version().major())
+            .is_some_and(|(major, current)| major > current)
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.git_sha {
+            Some(sha) => write!(f, "{} ({})", self.version, sha),
+            None => write!(f, "{}", self.version),
+        }
+    }
+}
+
+/// The version of the running spackle
+pub fn version() -> Version {
+    Version {
+        version: migrate::CURRENT_VERSION.to_string(),
+        git_sha: option_env!("SPACKLE_GIT_SHA").map(str::to_string),
+    }
+}
+
 // Loads the project from the specified directory or path and validates it
 pub fn load_project(path: &PathBuf) -> Result<Project, config::Error> {
     let config = config::load(path)?;
 
     config.validate()?;
 
-    Ok(Project {
+    let mut project = Project {
         config,
         path: path.to_owned(),
-    })
+    };
+    project.infer_needed_by();
+    project.infer_kind();
+
+    Ok(project)
+}
+
+/// What a frontend shows about a project before working with it. Loaded projects have a valid
+/// config, so there's no validity to report.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ProjectSummary {
+    pub name: String,
+    pub path: PathBuf,
+    pub slots: usize,
+    pub hooks: usize,
 }
 
 pub struct Project {
@@ -77,10 +515,96 @@ pub struct Project {
 }
 
 impl Project {
+    /// Sets `needed_by` for slots that only hooks read to the hooks that read them, so they're
+    /// only collected when one of those hooks is enabled. Slots read by templates, file names,
+    /// the project name or other slots, even behind a `default` filter or an `is defined`
+    /// test, are left alone, as are slots in exclusive groups and
+    /// every slot of a project whose templates don't parse.
+    pub fn infer_needed_by(&mut self) {
+        if !self.path.is_dir() {
+            return;
+        }
+
+        let Ok(mut variables) = template::referenced_variables(&self.path, &self.config.templates)
+        else {
+            return;
+        };
+        if let Some(name) = &self.config.name {
+            variables.extend(template::text_variables(name));
+        }
+
+        let hook_variables = self
+            .config
+            .hooks
+            .iter()
+            .map(|hook| (&hook.key, hook.referenced_variables()))
+            .collect::<Vec<_>>();
+
+        let inferred = self
+            .config
+            .slots
+            .iter()
+            .map(|slot| {
+                let read_elsewhere = template::reads_key(&variables, &slot.key)
+                    || self.config.slots.iter().any(|other| {
+                        other.key != slot.key
+                            && template::reads_key(&other.referenced_variables(), &slot.key)
+                    })
+                    || self
+                        .config
+                        .exclusive_groups
+                        .iter()
+                        .any(|group| group.contains(&slot.key));
+                if !slot.needed_by.is_empty() || read_elsewhere {
+                    return slot.needed_by.clone();
+                }
+
+                hook_variables
+                    .iter()
+                    .filter(|(_, variables)| template::reads_key(variables, &slot.key))
+                    .map(|(key, _)| key.to_string())
+                    .collect()
+            })
+            .collect::<Vec<_>>();
+
+        for (slot, needed_by) in self.config.slots.iter_mut().zip(inferred) {
+            slot.needed_by = needed_by;
+        }
+    }
+
+    /// Whether the project is a directory or a single file. The project was found when it was
+    /// loaded, so only the kind of path is checked.
+    pub fn layout(&self) -> config::ProjectLayout {
+        if self.path.is_dir() {
+            config::ProjectLayout::Directory
+        } else {
+            config::ProjectLayout::SingleFile
+        }
+    }
+
+    pub fn summary(&self) -> ProjectSummary {
+        ProjectSummary {
+            name: self.get_name(),
+            path: self.path.clone(),
+            slots: self.config.slots.len(),
+            hooks: self.config.hooks.len(),
+        }
+    }
+
     /// Gets the name of the project or if one isn't specified, from the directory name
     pub fn get_name(&self) -> String {
+        self.render_name(&HashMap::new())
+    }
+
+    /// Gets the name of the project, rendering it against the provided data since the name can reference slots.
+    /// If the name references data that isn't set yet, the directory name is used instead.
+    pub fn render_name(&self, data: &HashMap<String, String>) -> String {
         if let Some(name) = &self.config.name {
-            return name.clone();
+            let rendered = template::render_path(name, &template::context_from_data(data));
+
+            if let Ok(rendered) = rendered {
+                return rendered;
+            }
         }
 
         let path = match self.path.canonicalize() {
@@ -95,6 +619,167 @@ impl Project {
             .into_owned();
     }
 
+    /// The executables the project's hooks run, deduplicated in the order they're first used,
+    /// for checking prerequisites before a fill. Hooks run with a shell need both the shell and
+    /// the first word of their command. Templated programs, relative paths, variable assignments
+    /// and scripts in `hooks.d` are left out since they can only be resolved at fill time.
+    pub fn required_binaries(&self) -> Vec<String> {
+        let mut binaries: Vec<String> = Vec::new();
+
+        for hook in &self.config.hooks {
+            if hook.from_hooks_dir {
+                continue;
+            }
+
+            let first_arg = hook.command.first().map(String::as_str);
+            let programs = match &hook.shell {
+                // The command still starts with a program, even as a single-string script
+                Some(shell) => vec![
+                    Some(shell.as_str()),
+                    first_arg.and_then(|arg| arg.split_whitespace().next()),
+                ],
+                None => vec![first_arg],
+            };
+
+            for program in programs.into_iter().flatten() {
+                let path = Path::new(program);
+                if program.contains("{{")
+                    || program.contains("{%")
+                    || program.contains('=')
+                    || (path.components().count() > 1 && !path.is_absolute())
+                {
+                    continue;
+                }
+
+                if !binaries.iter().any(|binary| binary == program) {
+                    binaries.push(program.to_string());
+                }
+            }
+        }
+
+        binaries
+    }
+
+    /// Makes a project hooks-only when it has nothing to copy or render, going by the
+    /// [classify::Classifier], so the config and `hooks.d` don't count. Projects declared
+    /// hooks-only, single file projects and those with inline templates are left alone.
+    pub fn infer_kind(&mut self) {
+        if self.config.kind == config::ProjectKind::Hooks
+            || !self.path.is_dir()
+            || !self.config.templates.is_empty()
+        {
+            return;
+        }
+
+        let Ok(entries) = classify::Classifier::new(&self.path, &self.config.ignore)
+            .and_then(|classifier| classifier.entries(&self.path))
+        else {
+            return;
+        };
+
+        let writes_files = entries.iter().any(|entry| {
+            !entry.is_dir
+                && matches!(
+                    entry.class,
+                    classify::FileClass::Template | classify::FileClass::Static
+                )
+        });
+        if !writes_files {
+            self.config.kind = config::ProjectKind::Hooks;
+        }
+    }
+
+    /// Whether the project only runs hooks, because it's declared with `kind = "hooks"` or was
+    /// found to have nothing to write when it was loaded, see [Project::infer_kind]
+    pub fn is_hooks_only(&self) -> bool {
+        self.config.kind == config::ProjectKind::Hooks
+    }
+
+    /// The files and directories given for Path slots with `copy_into`, which generation copies
+    /// into the output after rendering. Slots left empty import nothing, and neither do
+    /// hooks-only projects since they don't generate anything.
+    pub fn imports(&self, out_dir: &Path, data: &HashMap<String, String>) -> Vec<copy::Import> {
+        if self.is_hooks_only() {
+            return Vec::new();
+        }
+
+        self.config
+            .slots
+            .iter()
+            .filter(|slot| matches!(slot.r#type, slot::SlotType::Path))
+            .filter_map(|slot| {
+                let copy_into = slot.copy_into.as_ref()?;
+                let value = data.get(&slot.key).filter(|value| !value.is_empty())?;
+
+                Some(copy::Import {
+                    key: slot.key.clone(),
+                    src: PathBuf::from(value),
+                    dest: out_dir.join(copy_into),
+                })
+            })
+            .collect()
+    }
+
+    /// The files the imports write, relative to the output directory
+    pub fn imported_files(&self, out_dir: &Path, data: &HashMap<String, String>) -> Vec<PathBuf> {
+        self.imports(out_dir, data)
+            .iter()
+            .flat_map(|import| import.files())
+            .filter_map(|(_, dest)| dest.strip_prefix(out_dir).ok().map(Path::to_path_buf))
+            .collect()
+    }
+
+    /// The [manifest::fingerprint] of each file generation wrote to the output directory, see
+    /// [Project::outputs_for]. Meant to be taken before hooks run, so updates can tell the
+    /// template's changes apart from the output's.
+    pub fn output_fingerprints(
+        &self,
+        out_dir: &Path,
+        data: &HashMap<String, String>,
+    ) -> BTreeMap<PathBuf, String> {
+        self.outputs_for(out_dir, data)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|path| {
+                let contents = fs::read(&path).ok()?;
+                let path = path.strip_prefix(out_dir).ok()?.to_path_buf();
+
+                Some((path, manifest::fingerprint(&contents)))
+            })
+            .collect()
+    }
+
+    /// Checks that the source of every import exists, before anything is written
+    pub fn check_imports(
+        &self,
+        out_dir: &Path,
+        data: &HashMap<String, String>,
+    ) -> Result<(), GenerateError> {
+        match self
+            .imports(out_dir, data)
+            .into_iter()
+            .find(|import| !import.src.exists())
+        {
+            Some(import) => Err(GenerateError::MissingImport(import.key, import.src)),
+            None => Ok(()),
+        }
+    }
+
+    /// Copies the imports into the output, returning the files written. Meant to run after
+    /// rendering, so an import takes precedence over a project file at the same path.
+    pub fn copy_imports(
+        &self,
+        out_dir: &Path,
+        data: &HashMap<String, String>,
+    ) -> Result<Vec<PathBuf>, copy::Error> {
+        let mut copied = Vec::new();
+        for import in self.imports(out_dir, data) {
+            copied.extend(import.copy()?);
+        }
+
+        Ok(copied)
+    }
+
     /// Generates a filled directory from the specified spackle project.
     ///
     /// out_dir is the path to what will become the filled directory
@@ -104,23 +789,55 @@ impl Project {
         out_dir: &PathBuf,
         slot_data: &HashMap<String, String>,
     ) -> Result<Vec<RenderedFile>, GenerateError> {
+        self.generate_with(project_dir, out_dir, slot_data, &GenerateOptions::default())
+    }
+
+    /// Generates a filled directory from the specified spackle project using the provided options.
+    ///
+    /// out_dir is the path to what will become the filled directory
+    pub fn generate_with(
+        &self,
+        project_dir: &PathBuf,
+        out_dir: &PathBuf,
+        slot_data: &HashMap<String, String>,
+        options: &GenerateOptions,
+    ) -> Result<Vec<RenderedFile>, GenerateError> {
+        // Hooks-only projects have nothing to write and may target an existing directory
+        if self.is_hooks_only() {
+            return Ok(Vec::new());
+        }
+
         if out_dir.exists() {
             return Err(GenerateError::AlreadyExists(out_dir.clone()));
         }
 
         let config = config::load_dir(project_dir).map_err(GenerateError::BadConfig)?;
 
-        let mut slot_data = slot_data.clone();
-        slot_data.insert("_project_name".to_string(), self.get_name());
-        slot_data.insert("_output_name".to_string(), get_output_name(out_dir));
+        let slot_data = self.context_data(out_dir, slot_data);
+
+        // Check the imports up front so a missing one doesn't leave a partial output behind
+        self.check_imports(out_dir, &slot_data)?;
+
+        let ignored = config.ignored(&slot_data);
 
         // Copy all non-template files to the output directory
-        copy::copy(project_dir, &out_dir, &config.ignore, &slot_data)
+        let copy_result = copy::copy(project_dir, &out_dir, &ignored, &slot_data, options.verify)
             .map_err(GenerateError::CopyError)?;
 
+        if !copy_result.mismatches.is_empty() {
+            return Err(GenerateError::VerifyError(copy_result.mismatches));
+        }
+
         // Render template files to the output directory
-        let results = template::fill(project_dir, out_dir, &slot_data)
-            .map_err(GenerateError::TemplateError)?;
+        let results = template::fill_with_context(
+            project_dir,
+            &self.config.templates,
+            &ignored,
+            out_dir,
+            &self.render_context(&slot_data),
+            &self.render_options(options),
+        )
+        .map_err(GenerateError::TemplateError)?;
 
         // Split vector into vector of rendered files and vector of errors
         let mut okay_results = Vec::new();
@@ -132,62 +849,567 @@ impl Project {
             }
         }
 
+        self.copy_imports(out_dir, &slot_data)
+            .map_err(GenerateError::CopyError)?;
+
+        // Set the modes the config asks for now that every output is written
+        self.apply_modes(out_dir)
+            .map_err(GenerateError::CopyError)?;
+
+        if options.verify_outputs {
+            self.verify_outputs(out_dir, &slot_data)?;
+        }
+
         Ok(okay_results)
     }
 
-    pub fn validate(&self) -> Result<(), template::ValidateError> {
-        template::validate(&self.path, &self.config.slots)
+    /// Generates the project and then runs its hooks in the output directory, as one run.
+    /// Hooks-only projects run in an existing output directory.
+    /// If the run exceeds `options.timeout`, running hooks are stopped, the output directory
+    /// is removed (unless the project is hooks-only) and [GenerateError::TimedOut] is returned.
+    pub fn generate_with_hooks(
+        &self,
+        out_dir: &PathBuf,
+        data: &HashMap<String, String>,
+        forwarded_args: &[String],
+        options: &GenerateOptions,
+    ) -> Result<(Vec<RenderedFile>, Vec<hook::HookResult>), GenerateError> {
+        let start_time = Instant::now();
+        // Resolve the run id once so generation and hooks share it
+        let data = self.context_data(out_dir, data);
+
+        let mut render_options = options.clone();
+        if let Some(timeout) = options.timeout {
+            render_options.render_timeout = Some(
+                options
+                    .render_timeout
+                    .map_or(timeout, |render_timeout| render_timeout.min(timeout)),
+            );
+        }
+
+        let result = self
+            .generate_with(&self.path, out_dir, &data, &render_options)
+            .and_then(|files| {
+                let fingerprints = self.output_fingerprints(out_dir, &data);
+                let mut hook_results = Vec::new();
+                let ran = self.run_hooks_within(
+                    out_dir,
+                    &data,
+                    forwarded_args,
+                    options,
+                    options
+                        .timeout
+                        .map(|timeout| (timeout, start_time + timeout)),
+                    &mut hook_results,
+                );
+
+                // Recorded last, so the hooks of this run don't see their own output as a re-run.
+                // Hooks that ran before the run was stopped are recorded too.
+                manifest::Manifest::new(&data["_run_id"], &self.config.managed, &hook_results)
+                    .with_imported(self.imported_files(out_dir, &data))
+                    .with_answers(answers::Answers::new(self, &data).ok())
+                    .with_files(fingerprints)
+                    .write(out_dir)
+                    .map_err(GenerateError::ManifestError)?;
+                ran?;
+
+                Ok((files, hook_results))
+            });
+
+        match result {
+            Err(GenerateError::FileError(template::FileError {
+                kind: template::FileErrorKind::RenderTimedOut(_),
+                ..
+            }))
+            | Err(GenerateError::TimedOut(_))
+                if options.timeout.is_some() =>
+            {
+                if !self.is_hooks_only() {
+                    let _ = fs::remove_dir_all(out_dir);
+                }
+
+                Err(GenerateError::TimedOut(options.timeout.unwrap_or_default()))
+            }
+            result => result,
+        }
+    }
+
+    // Runs the hooks to completion, giving up once the deadline passes. Results are added as
+    // hooks finish, so those that finished before the deadline are kept.
+    fn run_hooks_within(
+        &self,
+        out_dir: &Path,
+        data: &HashMap<String, String>,
+        forwarded_args: &[String],
+        options: &GenerateOptions,
+        deadline: Option<(Duration, Instant)>,
+        results: &mut Vec<hook::HookResult>,
+    ) -> Result<(), GenerateError> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| {
+                GenerateError::HookError(hook::Error::ErrorInitializingRuntime(e).into())
+            })?;
+
+        runtime.block_on(async {
+            let stream = self
+                .run_hooks_stream(out_dir, data, forwarded_args, None, options)
+                .map_err(|e| match e {
+                    RunHooksError::BadConfig(e) => GenerateError::BadConfig(e),
+                    RunHooksError::HookError(e) => GenerateError::HookError(e.into()),
+                })?;
+
+            let collect = async {
+                tokio::pin!(stream);
+                while let Some(result) = stream.next().await {
+                    if let hook::HookStreamResult::HookDone(result) = result.event {
+                        results.push(result);
+                    }
+                }
+            };
+
+            match deadline {
+                Some((timeout, deadline)) => tokio::time::timeout_at(deadline.into(), collect)
+                    .await
+                    .map_err(|_| GenerateError::TimedOut(timeout)),
+                None => {
+                    collect.await;
+                    Ok(())
+                }
+            }
+        })
+    }
+
+    /// Gets the project's slots with the defaults from their `default_from_command` filled in,
+    /// unless disabled by the options. A slot whose command fails keeps its static default,
+    /// and the failure is returned so it can be reported.
+    pub fn resolve_defaults(
+        &self,
+        options: &GenerateOptions,
+    ) -> (Vec<slot::Slot>, Vec<slot::Error>) {
+        let mut errors = Vec::new();
+
+        let slots = self
+            .config
+            .slots
+            .iter()
+            .map(|slot| {
+                if options.skip_default_commands {
+                    return slot.clone();
+                }
+
+                match slot.default_from_command(DEFAULT_COMMAND_TIMEOUT) {
+                    Ok(Some(default)) => slot::Slot {
+                        default: Some(default),
+                        ..slot.clone()
+                    },
+                    Ok(None) => slot.clone(),
+                    Err(e) => {
+                        errors.push(e);
+                        slot.clone()
+                    }
+                }
+            })
+            .collect();
+
+        (slots, errors)
+    }
+
+    /// Adds the values spackle injects into every context to the provided data.
+    /// A `_run_id` already present in the data is kept, so a run can share one id across steps.
+    fn context_data(
+        &self,
+        out_dir: &Path,
+        data: &HashMap<String, String>,
+    ) -> HashMap<String, String> {
+        let mut data = data.clone();
+        // Drop supplied values first so they can't leak into the rendered name
+        for key in INJECTED_KEYS {
+            data.remove(*key);
+        }
+
+        insert_name(&mut data, "_output_name", get_output_name(out_dir));
+        let project_name = self.render_name(&data);
+        insert_name(&mut data, "_project_name", project_name);
+        data.entry("_run_id".to_string()).or_insert_with(new_run_id);
+
+        data
+    }
+
+    /// Ensures render vars don't shadow any of the project's slots or hooks, or any of the keys
+    /// spackle adds to the context itself, like `_run_id`, `hook_ran_<key>`, `env` and `hooks`.
+    /// Render vars are merged into the context as-is, so a collision would silently bypass slot validation.
+    pub fn check_render_vars(&self, vars: &HashMap<String, String>) -> Result<(), RenderVarsError> {
+        let mut keys = vars.keys().collect::<Vec<_>>();
+        keys.sort();
+
+        for key in keys {
+            if is_reserved_key(key) || key == template::ENV_KEY || key == hook::HOOKS_KEY {
+                return Err(RenderVarsError::Reserved(key.clone()));
+            }
+            self.check_context_key(key)?;
+        }
+
+        Ok(())
+    }
+
+    /// Ensures the environment, added to the context under [template::ENV_KEY], doesn't shadow
+    /// any of the project's slots or hooks
+    pub fn check_env(&self) -> Result<(), RenderVarsError> {
+        self.check_context_key(template::ENV_KEY)
+    }
+
+    // Errors when a key added to the context is already taken by a slot or hook
+    fn check_context_key(&self, key: &str) -> Result<(), RenderVarsError> {
+        if self.config.slots.iter().any(|slot| slot.key == key) {
+            Err(RenderVarsError::CollidesWithSlot(key.to_string()))
+        } else if self.config.hooks.iter().any(|hook| hook.key == key) {
+            Err(RenderVarsError::CollidesWithHook(key.to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Validates the project's templates, returning the names of those validated
+    pub fn validate(&self) -> Result<Vec<String>, template::ValidateError> {
+        template::validate(
+            &self.path,
+            &self.config.templates,
+            &self.config.slots,
+            self.config.strict_variables(),
+        )
     }
 
     pub fn copy_files(
         &self,
         out_dir: &Path,
         data: &HashMap<String, String>,
+        options: &GenerateOptions,
     ) -> Result<copy::CopyResult, copy::Error> {
-        let mut data = data.clone();
-        data.insert("_project_name".to_string(), self.get_name());
-        data.insert("_output_name".to_string(), get_output_name(out_dir));
+        let data = self.context_data(out_dir, data);
 
-        copy::copy(&self.path, out_dir, &self.config.ignore, &data)
+        copy::copy(
+            &self.path,
+            out_dir,
+            &self.config.ignored(&data),
+            &data,
+            options.verify,
+        )
     }
 
     pub fn render_templates(
         &self,
         out_dir: &Path,
         data: &HashMap<String, String>,
+        options: &GenerateOptions,
     ) -> Result<Vec<Result<template::RenderedFile, template::FileError>>, tera::Error> {
-        let mut data = data.clone();
-        data.insert("_project_name".to_string(), self.get_name());
-        data.insert("_output_name".to_string(), get_output_name(out_dir));
+        let data = self.context_data(out_dir, data);
 
-        template::fill(&self.path, out_dir, &data)
+        template::fill_with_context(
+            &self.path,
+            &self.config.templates,
+            &self.config.ignored(&data),
+            out_dir,
+            &self.render_context(&data),
+            &self.render_options(options),
+        )
     }
 
-    /// Runs the hooks in the generated spackle project.
+    /// Sets the modes of the output's files matching the project's `chmod` rules, see
+    /// [copy::apply_modes]. Meant to run once every file is written.
+    pub fn apply_modes(&self, out_dir: &Path) -> Result<Vec<PathBuf>, copy::Error> {
+        copy::apply_modes(out_dir, &self.config.chmod)
+    }
+
+    // The context templates are rendered with: the data, along with the project's definitions
+    fn render_context(&self, data: &HashMap<String, String>) -> tera::Context {
+        let mut context = template::context_from_data(data);
+        template::insert_definitions(&mut context, &self.config.slots, &self.config.hooks);
+
+        context
+    }
+
+    // Resolves the options left to the project's config
+    fn render_options(&self, options: &GenerateOptions) -> GenerateOptions {
+        GenerateOptions {
+            strict_variables: Some(
+                options
+                    .strict_variables
+                    .unwrap_or(self.config.strict_variables()),
+            ),
+            render: Some(options.render.clone().unwrap_or(self.config.render.clone())),
+            ..options.clone()
+        }
+    }
+
+    /// Resolves the path of every file generation would write for the given data, with
+    /// templated names rendered and template extensions stripped, without writing anything.
+    /// A path is listed twice if both a copied file and a template write to it.
+    pub fn outputs_for(
+        &self,
+        out_dir: &Path,
+        data: &HashMap<String, String>,
+    ) -> Result<Vec<PathBuf>, GenerateError> {
+        if self.is_hooks_only() {
+            return Ok(vec![]);
+        }
+
+        let data = self.context_data(out_dir, data);
+
+        let mut outputs =
+            copy::destinations(&self.path, out_dir, &self.config.ignored(&data), &data)
+                .map_err(GenerateError::CopyError)?;
+        outputs.extend(template::destinations(
+            &self.path,
+            &self.config.templates,
+            &self.config.ignored(&data),
+            out_dir,
+            &data,
+        )?);
+        outputs.sort();
+
+        Ok(outputs)
+    }
+
+    /// A JSON Schema of the data a fill of the project takes, see [schema::input_schema]
+    pub fn input_schema(&self) -> serde_json::Value {
+        schema::input_schema(&self.get_name(), &self.config.slots, &self.config.hooks)
+    }
+
+    /// Checks that every path [Project::outputs_for] plans for the data exists, returning
+    /// [GenerateError::MissingOutput] for the first that doesn't
+    pub fn verify_outputs(
+        &self,
+        out_dir: &Path,
+        data: &HashMap<String, String>,
+    ) -> Result<(), GenerateError> {
+        match self
+            .outputs_for(out_dir, data)?
+            .into_iter()
+            .find(|path| !path.exists())
+        {
+            Some(path) => Err(GenerateError::MissingOutput(path)),
+            None => Ok(()),
+        }
+    }
+
+    /// Every entry of the project with how a fill would handle it, given the data. Ignored
+    /// directories are listed without their contents.
+    pub fn sources(
+        &self,
+        out_dir: &Path,
+        data: &HashMap<String, String>,
+    ) -> Result<Vec<classify::Entry>, classify::Error> {
+        let data = self.context_data(out_dir, data);
+
+        classify::Classifier::new(&self.path, &self.config.ignored(&data))?
+            .excluding(&self.path, out_dir)
+            .entries(&self.path)
+    }
+
+    /// Determines which hooks will run and which will be skipped, without running them.
+    pub fn plan_hooks(&self, out_dir: &Path, data: &HashMap<String, String>) -> hook::HookPlan {
+        let data = self.context_data(out_dir, data);
+
+        hook::plan_hooks(
+            &self.config.hooks,
+            &self.config.slots,
+            &data,
+            &manifest::hooks_ran(out_dir),
+        )
+    }
+
+    /// Works out which files and directories a fill would write and which hooks it would run,
+    /// without writing anything or running any commands. Existing files are planned as overwrites.
+    pub fn plan(
+        &self,
+        out_dir: &Path,
+        data: &HashMap<String, String>,
+    ) -> Result<plan::Plan, GenerateError> {
+        let mut outputs = self.outputs_for(out_dir, data)?;
+        // A template writing to the same path as a copied file replaces it
+        outputs.dedup();
+
+        let mut files = outputs
+            .iter()
+            .map(|path| plan::PlannedFile {
+                path: path.clone(),
+                action: if path.exists() {
+                    plan::FileAction::Overwrite
+                } else {
+                    plan::FileAction::Create
+                },
+            })
+            .collect::<Vec<_>>();
+
+        if !self.is_hooks_only() {
+            let context = self.context_data(out_dir, data);
+            files.extend(
+                copy::ignored(
+                    &self.path,
+                    out_dir,
+                    &self.config.ignored(&context),
+                    &context,
+                )
+                .map_err(GenerateError::CopyError)?
+                .into_iter()
+                .map(|path| plan::PlannedFile {
+                    path,
+                    action: plan::FileAction::Skip,
+                }),
+            );
+        }
+
+        let directories = outputs
+            .iter()
+            .flat_map(|path| path.ancestors().skip(1))
+            .filter(|dir| dir.starts_with(out_dir) && !dir.exists())
+            .map(Path::to_path_buf)
+            .collect::<BTreeSet<_>>();
+
+        let hook_plan = self.plan_hooks(out_dir, data);
+        let hooks = self
+            .config
+            .hooks
+            .iter()
+            .map(|hook| {
+                let action = match hook_plan.skipped.iter().find(|(h, _)| h.key == hook.key) {
+                    Some((_, reason)) => plan::HookAction::Skip(reason.clone()),
+                    None => match &hook.r#if {
+                        Some(conditional) => plan::HookAction::RunIf(conditional.clone()),
+                        None => plan::HookAction::Run,
+                    },
+                };
+
+                plan::PlannedHook {
+                    key: hook.key.clone(),
+                    action,
+                }
+            })
+            .collect();
+
+        Ok(plan::Plan {
+            spackle: version(),
+            out_dir: out_dir.to_path_buf(),
+            files,
+            directories: directories.into_iter().collect(),
+            imports: self.imports(out_dir, data),
+            hooks,
+        })
+    }
+
+    /// Runs the hooks in the generated spackle project. Each hook's output is streamed line by
+    /// line as it's written, and its result keeps as much of it as the options allow.
     ///
     /// out_dir is the path to the filled directory
     pub fn run_hooks_stream(
         &self,
         out_dir: &Path,
         data: &HashMap<String, String>,
+        forwarded_args: &[String],
         run_as_user: Option<User>,
-    ) -> Result<impl Stream<Item = hook::HookStreamResult>, RunHooksError> {
-        let mut data = data.clone();
-        data.insert("_project_name".to_string(), self.get_name());
-        data.insert("_output_name".to_string(), get_output_name(out_dir));
+        options: &GenerateOptions,
+    ) -> Result<impl Stream<Item = event::Event<hook::HookStreamResult>>, RunHooksError> {
+        let data = self.context_data(out_dir, data);
 
         let result = hook::run_hooks_stream(
             out_dir.to_owned(),
             &self.config.hooks,
             &self.config.slots,
             &data,
+            forwarded_args,
             run_as_user.clone(),
+            options
+                .hook_output_limit
+                .unwrap_or(hook::DEFAULT_OUTPUT_LIMIT),
         )
         .map_err(RunHooksError::HookError)?;
 
         Ok(result)
     }
 
+    /// Renders the project again for an output directory it filled, with the answers recorded
+    /// in its manifest, and applies what changed in the project since, see [update::apply].
+    /// `data` takes precedence over the answers, e.g. for sensitive slots, which aren't
+    /// recorded. Slots added since the fill use their default. Hooks aren't run, and imports
+    /// aren't copied again.
+    pub fn update(
+        &self,
+        out_dir: &Path,
+        data: &HashMap<String, String>,
+        options: &GenerateOptions,
+    ) -> Result<update::Update, update::Error> {
+        let manifest = manifest::Manifest::load(out_dir)
+            .map_err(update::Error::ManifestError)?
+            .ok_or_else(|| update::Error::NotFilled(out_dir.to_path_buf()))?;
+        let answers = manifest
+            .answers
+            .as_ref()
+            .ok_or_else(|| update::Error::NoAnswers(out_dir.to_path_buf()))?;
+
+        let (slots, _) = self.resolve_defaults(options);
+        let mut data = slots
+            .iter()
+            .filter_map(|slot| Some((slot.key.clone(), slot.default.clone()?)))
+            .chain(answers.data())
+            .chain(data.clone())
+            .collect::<HashMap<_, _>>();
+        // Rendering with the fill's run id keeps templates using it from changing
+        data.insert("_run_id".to_string(), manifest.run_id.clone());
+
+        let missing = slots
+            .iter()
+            .filter(|slot| slot.is_always_required() && !data.contains_key(&slot.key))
+            .map(|slot| slot.key.clone())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(update::Error::MissingData(missing));
+        }
+
+        if self.is_hooks_only() {
+            return Ok(update::Update::default());
+        }
+
+        // Named like the output so the render gets the same `_output_name`
+        let dir = TempDir::new("spackle-update").map_err(update::Error::TempDirError)?;
+        let render_dir = dir.path().join(get_output_name(out_dir));
+        let render_error = |e: GenerateError| update::Error::RenderError(Box::new(e));
+
+        let copied = self
+            .copy_files(&render_dir, &data, options)
+            .map_err(|e| render_error(GenerateError::CopyError(e)))?;
+        if !copied.mismatches.is_empty() {
+            return Err(render_error(GenerateError::VerifyError(copied.mismatches)));
+        }
+        for result in self
+            .render_templates(&render_dir, &data, options)
+            .map_err(|e| render_error(GenerateError::TemplateError(e)))?
+        {
+            result.map_err(|e| render_error(GenerateError::FileError(e)))?;
+        }
+        self.apply_modes(&render_dir)
+            .map_err(|e| render_error(GenerateError::CopyError(e)))?;
+
+        let update = update::apply(out_dir, &render_dir, &manifest.files)?;
+
+        // The render becomes what the next update compares against
+        let answers = answers::Answers::new(self, &data)
+            .ok()
+            .or_else(|| manifest.answers.clone());
+        manifest::Manifest {
+            managed: self.config.managed.clone(),
+            spackle: Some(version()),
+            answers,
+            files: manifest::fingerprints(&render_dir),
+            ..manifest
+        }
+        .write(out_dir)
+        .map_err(update::Error::ManifestError)?;
+
+        Ok(update)
+    }
+
     /// Runs the hooks in the generated spackle project.
     ///
     /// out_dir is the path to the filled directory
@@ -195,20 +1417,1012 @@ impl Project {
         &self,
         out_dir: &Path,
         data: &HashMap<String, String>,
+        forwarded_args: &[String],
         run_as_user: Option<User>,
     ) -> Result<Vec<hook::HookResult>, hook::Error> {
-        let mut data = data.clone();
-        data.insert("_project_name".to_string(), self.get_name());
-        data.insert("_output_name".to_string(), get_output_name(out_dir));
+        let data = self.context_data(out_dir, data);
 
         let result = hook::run_hooks(
             &self.config.hooks,
             out_dir,
             &self.config.slots,
             &data,
+            forwarded_args,
             run_as_user.clone(),
         )?;
 
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+    use walkdir::WalkDir;
+
+    use super::*;
+
+    #[test]
+    fn strip_ansi() {
+        assert_eq!(
+            super::strip_ansi("\u{1b}[1mkey\u{1b}[0m \u{1b}[38;2;128;128;128m[string]\u{1b}[0m"),
+            "key [string]"
+        );
+        assert_eq!(super::strip_ansi("no colors"), "no colors");
+    }
+
+    #[test]
+    fn run_id_format() {
+        let run_id = new_run_id();
+
+        assert_eq!(run_id.len(), 36);
+        assert_eq!(run_id.chars().nth(14), Some('4'));
+        assert_ne!(run_id, new_run_id());
+    }
+
+    #[test]
+    fn render_vars() {
+        let vars = load_render_vars(Path::new("tests/data/render_vars/vars.toml"))
+            .expect("load_render_vars failed, should have succeeded");
+
+        assert_eq!(
+            vars,
+            HashMap::from([
+                ("author".to_string(), "Jane Doe".to_string()),
+                ("year".to_string(), "2024".to_string()),
+                ("internal".to_string(), "true".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn render_vars_collision() {
+        let project = load_project(&PathBuf::from("tests/data/proj1")).unwrap();
+
+        assert!(project
+            .check_render_vars(&HashMap::from([("author".to_string(), "".to_string())]))
+            .is_ok());
+
+        assert!(matches!(
+            project.check_render_vars(&HashMap::from([
+                ("author".to_string(), "".to_string()),
+                ("slot_1".to_string(), "".to_string()),
+            ])),
+            Err(RenderVarsError::CollidesWithSlot(key)) if key == "slot_1"
+        ));
+        assert!(matches!(
+            project.check_render_vars(&HashMap::from([("hook_1".to_string(), "".to_string())])),
+            Err(RenderVarsError::CollidesWithHook(key)) if key == "hook_1"
+        ));
+
+        for key in ["_run_id", "hook_ran_hook_1", "env", "hooks"] {
+            assert!(
+                matches!(
+                    project.check_render_vars(&HashMap::from([(key.to_string(), "".to_string())])),
+                    Err(RenderVarsError::Reserved(reserved)) if reserved == key
+                ),
+                "Expected {} to be reserved",
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn definitions() {
+        let project = load_project(&PathBuf::from("tests/data/definitions")).unwrap();
+        let out_dir = TempDir::new("spackle").unwrap().into_path().join("out");
+
+        project
+            .generate(
+                &project.path,
+                &out_dir,
+                &HashMap::from([
+                    ("app_name".to_string(), "api".to_string()),
+                    ("port".to_string(), "9000".to_string()),
+                ]),
+            )
+            .expect("generate failed, should have succeeded");
+
+        assert_eq!(
+            fs::read_to_string(out_dir.join("CONFIGURATION.md")).unwrap(),
+            "# api configuration\n\n\
+             | Slot | Type | Description | Default |\n\
+             | --- | --- | --- | --- |\n\
+             | `app_name` | string | The name of the app | required |\n\
+             | `port` | number | The port the app listens on | 8080 |\n\n\
+             | Hook | Default |\n\
+             | --- | --- |\n\
+             | Initialize git | false |\n"
+        );
+
+        // Hook commands only see the data
+        let context = template::context_from_data(&project.context_data(&out_dir, &HashMap::new()));
+        assert!(context.get("_slots").is_none());
+    }
+
+    #[test]
+    fn templated_name() {
+        let project = Project {
+            config: config::Config {
+                name: Some("{{ app_name }}-app".to_string()),
+                ..Default::default()
+            },
+            path: PathBuf::from("tests/data/proj1"),
+        };
+
+        assert_eq!(
+            project.render_name(&HashMap::from([(
+                "app_name".to_string(),
+                "spackle".to_string()
+            )])),
+            "spackle-app"
+        );
+
+        // Falls back to the directory name until the slot is set
+        assert_eq!(project.get_name(), "proj1");
+    }
+
+    #[test]
+    fn outputs_for() {
+        let project = load_project(&PathBuf::from("tests/data/proj1")).unwrap();
+        let out_dir = PathBuf::from("/tmp/proj1_out");
+
+        let outputs = project
+            .outputs_for(
+                &out_dir,
+                &HashMap::from([
+                    ("slot_1".to_string(), "foo".to_string()),
+                    ("slot_2".to_string(), "2".to_string()),
+                ]),
+            )
+            .expect("outputs_for failed, should have succeeded");
+
+        // Copied and templated names are rendered, and only the last .j2 is stripped
+        assert_eq!(
+            outputs,
+            [
+                "2.j2",
+                "bad",
+                "foo",
+                "proj1",
+                "subdir/bad",
+                "subdir/file",
+                "subdir/foo"
+            ]
+            .iter()
+            .map(|path| out_dir.join(path))
+            .collect::<Vec<_>>()
+        );
+        assert!(!out_dir.exists());
+    }
+
+    #[test]
+    fn verify_outputs() {
+        let project = load_project(&PathBuf::from("tests/data/proj2")).unwrap();
+        let out_dir = TempDir::new("spackle").unwrap().into_path().join("out");
+        let data = HashMap::from([("defined_field".to_string(), "foo".to_string())]);
+
+        project
+            .generate_with(
+                &project.path,
+                &out_dir,
+                &data,
+                &GenerateOptions {
+                    verify_outputs: true,
+                    ..Default::default()
+                },
+            )
+            .expect("generate_with failed, should have succeeded");
+        project.verify_outputs(&out_dir, &data).unwrap();
+
+        fs::remove_file(out_dir.join("subdir/file.txt")).unwrap();
+        assert!(matches!(
+            project.verify_outputs(&out_dir, &data),
+            Err(GenerateError::MissingOutput(path)) if path == out_dir.join("subdir/file.txt")
+        ));
+    }
+
+    #[test]
+    fn sources() {
+        let project = load_project(&PathBuf::from("tests/data/classify")).unwrap();
+        let out_dir = TempDir::new("spackle").unwrap().into_path().join("out");
+        let data = HashMap::from([("title".to_string(), "Spackle".to_string())]);
+
+        let sources = project.sources(&out_dir, &data).unwrap();
+        let of_class = |class: classify::FileClass| {
+            sources
+                .iter()
+                .filter(|entry| entry.class == class && !entry.is_dir)
+                .map(|entry| entry.path.to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // Ignore globs apply to templates too, and directories are never templates
+        assert_eq!(of_class(classify::FileClass::Template), ["README.md.j2"]);
+        assert_eq!(
+            of_class(classify::FileClass::Static),
+            ["LICENSE", "data.j2/raw.txt"]
+        );
+        assert_eq!(of_class(classify::FileClass::Ignored), ["notes.draft.j2"]);
+        assert_eq!(of_class(classify::FileClass::Project), ["spackle.toml"]);
+
+        // Every copied or rendered file comes from exactly one source
+        let outputs = project.outputs_for(&out_dir, &data).unwrap();
+        assert_eq!(
+            outputs,
+            ["LICENSE", "README.md", "data.j2/raw.txt"]
+                .iter()
+                .map(|path| out_dir.join(path))
+                .collect::<Vec<_>>()
+        );
+
+        project
+            .generate(&project.path, &out_dir, &data)
+            .expect("generate failed, should have succeeded");
+        let written = WalkDir::new(&out_dir)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .filter(|path| {
+                !path.ends_with(manifest::MANIFEST_FILE)
+                    && !path.starts_with(out_dir.join(run_log::LOG_DIR))
+            })
+            .collect::<BTreeSet<_>>();
+        assert_eq!(written, outputs.into_iter().collect::<BTreeSet<_>>());
+        assert_eq!(
+            fs::read_to_string(out_dir.join("data.j2/raw.txt")).unwrap(),
+            "{{ not rendered }}\n"
+        );
+    }
+
+    #[test]
+    fn plan() {
+        let project = load_project(&PathBuf::from("tests/data/proj1")).unwrap();
+        let out_dir = TempDir::new("spackle").unwrap().into_path();
+        fs::write(out_dir.join("foo"), "existing").unwrap();
+
+        let plan = project
+            .plan(
+                &out_dir,
+                &HashMap::from([
+                    ("slot_1".to_string(), "foo".to_string()),
+                    ("slot_2".to_string(), "2".to_string()),
+                ]),
+            )
+            .expect("plan failed, should have succeeded");
+
+        assert_eq!(
+            plan.overwrites()
+                .iter()
+                .map(|file| file.path.clone())
+                .collect::<Vec<_>>(),
+            vec![out_dir.join("foo")]
+        );
+        assert_eq!(plan.files.len(), 7);
+        // Written files come first, in path order
+        let written = plan
+            .files
+            .iter()
+            .take_while(|file| file.action != plan::FileAction::Skip)
+            .map(|file| file.path.clone())
+            .collect::<Vec<_>>();
+        assert!(written.is_sorted());
+        assert!(plan.files[written.len()..]
+            .iter()
+            .all(|file| file.action == plan::FileAction::Skip));
+        assert_eq!(plan.directories, vec![out_dir.join("subdir")]);
+        assert_eq!(
+            plan.hooks,
+            vec![
+                plan::PlannedHook {
+                    key: "hook_1".to_string(),
+                    action: plan::HookAction::Skip(hook::SkipReason::UserDisabled),
+                },
+                plan::PlannedHook {
+                    key: "hook_2".to_string(),
+                    action: plan::HookAction::RunIf("{{hook_ran_hook_1}}".to_string()),
+                },
+            ]
+        );
+
+        // Nothing is written while planning
+        assert_eq!(fs::read_dir(&out_dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn generate_timeout() {
+        let project = load_project(&PathBuf::from("tests/data/hook_timeout")).unwrap();
+        let out_dir = TempDir::new("spackle").unwrap().into_path().join("out");
+
+        let start_time = Instant::now();
+        let result = project.generate_with_hooks(
+            &out_dir,
+            &HashMap::new(),
+            &[],
+            &GenerateOptions {
+                timeout: Some(Duration::from_millis(300)),
+                ..Default::default()
+            },
+        );
+
+        assert!(matches!(result, Err(GenerateError::TimedOut(_))));
+        // The hook is stopped rather than waited on, and the partial output is removed
+        assert!(start_time.elapsed() < Duration::from_secs(5));
+        assert!(!out_dir.exists());
+
+        // Without a timeout the same run completes
+        let fast = load_project(&PathBuf::from("tests/data/proj2")).unwrap();
+        let out_dir = TempDir::new("spackle").unwrap().into_path().join("out");
+        let (files, hooks) = fast
+            .generate_with_hooks(
+                &out_dir,
+                &HashMap::from([("defined_field".to_string(), "x".to_string())]),
+                &[],
+                &GenerateOptions {
+                    timeout: Some(Duration::from_secs(30)),
+                    ..Default::default()
+                },
+            )
+            .expect("generate_with_hooks failed, should have succeeded");
+        assert_eq!(files.len(), 1);
+        assert!(hooks.is_empty());
+    }
+
+    #[test]
+    fn toggle_coercions() {
+        let hooks = ["docker", "lint", "ci"]
+            .iter()
+            .map(|key| hook::Hook {
+                key: key.to_string(),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let supplied = HashMap::from([
+            ("docker".to_string(), "yes".to_string()),
+            ("lint".to_string(), "true".to_string()),
+            ("ci".to_string(), "0".to_string()),
+            ("name".to_string(), "yes".to_string()),
+        ]);
+
+        let normalized = hook::normalize_toggles(&supplied, &hooks).unwrap();
+
+        // Values already in canonical form and slot values aren't reported
+        assert_eq!(
+            coercions(&supplied, &normalized),
+            vec![
+                Coercion {
+                    key: "ci".to_string(),
+                    from: "0".to_string(),
+                    to: "false".to_string(),
+                },
+                Coercion {
+                    key: "docker".to_string(),
+                    from: "yes".to_string(),
+                    to: "true".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_defaults() {
+        let project = Project {
+            config: config::Config {
+                slots: vec![
+                    slot::Slot {
+                        key: "author".to_string(),
+                        default: Some("fallback".to_string()),
+                        default_from_command: vec!["echo".to_string(), "Jane".to_string()],
+                        ..Default::default()
+                    },
+                    slot::Slot {
+                        key: "broken".to_string(),
+                        default: Some("fallback".to_string()),
+                        default_from_command: vec!["false".to_string()],
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            },
+            path: PathBuf::from("tests/data/proj1"),
+        };
+
+        let (slots, errors) = project.resolve_defaults(&GenerateOptions::default());
+        assert_eq!(slots[0].default, Some("Jane".to_string()));
+        assert_eq!(slots[1].default, Some("fallback".to_string()));
+        assert!(matches!(
+            errors.as_slice(),
+            [slot::Error::DefaultCommandFailed(key, _)] if key == "broken"
+        ));
+
+        // Servers can opt out of running commands entirely
+        let (slots, errors) = project.resolve_defaults(&GenerateOptions {
+            skip_default_commands: true,
+            ..Default::default()
+        });
+        assert_eq!(slots[0].default, Some("fallback".to_string()));
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn hooks_dir() {
+        let project = load_project(&PathBuf::from("tests/data/hooks_dir")).unwrap();
+        let out_dir = TempDir::new("spackle").unwrap().into_path().join("out");
+
+        let (files, hooks) = project
+            .generate_with_hooks(&out_dir, &HashMap::new(), &[], &GenerateOptions::default())
+            .expect("generate_with_hooks failed, should have succeeded");
+
+        assert!(files.is_empty());
+        assert_eq!(hooks.len(), 3);
+        assert!(out_dir.join("README.md").exists());
+        assert!(!out_dir.join("hooks.d").exists());
+        for output in ["from_toml.txt", "first.txt", "setup.txt"] {
+            assert!(out_dir.join(output).exists(), "{} was not written", output);
+        }
+    }
+
+    #[test]
+    fn hooks_only() {
+        let declared = load_project(&PathBuf::from("tests/data/hooks_only")).unwrap();
+        assert!(declared.is_hooks_only());
+
+        // Inferred once when loading, through the project's kind
+        let inferred = load_project(&PathBuf::from("tests/data/hook")).unwrap();
+        assert_eq!(inferred.config.kind, config::ProjectKind::Hooks);
+
+        let template = load_project(&PathBuf::from("tests/data/proj2")).unwrap();
+        assert!(!template.is_hooks_only());
+
+        // Existing output directories are allowed and left untouched
+        let out_dir = TempDir::new("spackle").unwrap().into_path();
+        fs::write(out_dir.join("existing.txt"), "existing").unwrap();
+
+        let results = declared
+            .generate(&declared.path, &out_dir, &HashMap::new())
+            .expect("generate failed, should have succeeded");
+
+        assert!(results.is_empty());
+        assert_eq!(fs::read_dir(&out_dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn imports() {
+        let project = load_project(&PathBuf::from("tests/data/import")).unwrap();
+        let dataset = TempDir::new("spackle").unwrap().into_path();
+        fs::create_dir_all(dataset.join("raw")).unwrap();
+        fs::write(dataset.join("raw/export.csv"), "a,b\n1,2\n").unwrap();
+
+        let parent = TempDir::new("spackle").unwrap().into_path();
+        let out_dir = parent.join("out");
+        let data = HashMap::from([("dataset".to_string(), dataset.to_string_lossy().to_string())]);
+
+        let plan = project.plan(&out_dir, &data).expect("plan failed");
+        assert_eq!(plan.imports.len(), 1);
+        assert_eq!(plan.imports[0].dest, out_dir.join("data/"));
+
+        project
+            .generate_with_hooks(&out_dir, &data, &[], &GenerateOptions::default())
+            .expect("generate_with_hooks failed, should have succeeded");
+        assert_eq!(
+            fs::read_to_string(out_dir.join("data/raw/export.csv")).unwrap(),
+            "a,b\n1,2\n"
+        );
+
+        let manifest = manifest::Manifest::load(&out_dir).unwrap().unwrap();
+        assert_eq!(
+            manifest.imported,
+            vec![PathBuf::from("data/raw/export.csv")]
+        );
+
+        // Nothing is written when the source is missing
+        let missing_out = parent.join("missing");
+        let missing = HashMap::from([(
+            "dataset".to_string(),
+            dataset.join("missing").to_string_lossy().to_string(),
+        )]);
+        assert!(matches!(
+            project.generate(&project.path, &missing_out, &missing),
+            Err(GenerateError::MissingImport(..))
+        ));
+        assert!(!missing_out.exists());
+    }
+
+    #[test]
+    fn update() {
+        let project_dir = TempDir::new("spackle").unwrap().into_path();
+        let write =
+            |path: &str, contents: &str| fs::write(project_dir.join(path), contents).unwrap();
+        write(
+            "spackle.toml",
+            "[[slots]]\nkey = \"name\"\ntype = \"String\"\n",
+        );
+        write("README.md.j2", "# {{ name }}\n");
+        write("LICENSE", "MIT\n");
+        write("notes.txt", "v1\n");
+
+        let out_dir = TempDir::new("spackle").unwrap().into_path().join("out");
+        let data = HashMap::from([("name".to_string(), "app".to_string())]);
+        let project = load_project(&project_dir).unwrap();
+        project
+            .generate_with_hooks(&out_dir, &data, &[], &GenerateOptions::default())
+            .expect("generate_with_hooks failed, should have succeeded");
+
+        let manifest = manifest::Manifest::load(&out_dir).unwrap().unwrap();
+        assert_eq!(manifest.answers.unwrap().data(), data);
+        assert_eq!(
+            manifest.files.keys().collect::<Vec<_>>(),
+            vec![
+                Path::new("LICENSE"),
+                Path::new("README.md"),
+                Path::new("notes.txt")
+            ]
+        );
+
+        // The output and the project both change after the fill
+        fs::write(out_dir.join("notes.txt"), "mine\n").unwrap();
+        write("README.md.j2", "# {{ name }}!\n");
+        write("notes.txt", "v2\n");
+
+        let project = load_project(&project_dir).unwrap();
+        let actions = |update: update::Update| {
+            update
+                .files
+                .into_iter()
+                .map(|file| (file.path, file.action))
+                .collect::<Vec<_>>()
+        };
+        let update = project
+            .update(&out_dir, &HashMap::new(), &GenerateOptions::default())
+            .expect("update failed, should have succeeded");
+        assert_eq!(
+            actions(update),
+            vec![
+                (PathBuf::from("LICENSE"), update::FileAction::Unchanged),
+                (PathBuf::from("README.md"), update::FileAction::Updated),
+                (PathBuf::from("notes.txt"), update::FileAction::Conflict),
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(out_dir.join("README.md")).unwrap(),
+            "# app!\n"
+        );
+        assert_eq!(
+            fs::read_to_string(out_dir.join("notes.txt")).unwrap(),
+            "mine\n"
+        );
+        assert_eq!(
+            fs::read_to_string(out_dir.join("notes.txt.new")).unwrap(),
+            "v2\n"
+        );
+
+        // The conflict stands until its new render is removed, and the new render is then
+        // what the next update compares against
+        let update = project
+            .update(&out_dir, &HashMap::new(), &GenerateOptions::default())
+            .expect("update failed, should have succeeded");
+        assert_eq!(update.conflicts().len(), 1);
+
+        fs::remove_file(out_dir.join("notes.txt.new")).unwrap();
+        let update = project
+            .update(&out_dir, &HashMap::new(), &GenerateOptions::default())
+            .expect("update failed, should have succeeded");
+        assert!(update.conflicts().is_empty());
+        assert_eq!(
+            actions(update)[2],
+            (PathBuf::from("notes.txt"), update::FileAction::Kept)
+        );
+
+        assert!(matches!(
+            project.update(&project_dir, &HashMap::new(), &GenerateOptions::default()),
+            Err(update::Error::NotFilled(_))
+        ));
+    }
+
+    #[test]
+    fn rerun_skips_non_idempotent() {
+        let project = Project {
+            config: config::Config {
+                kind: config::ProjectKind::Hooks,
+                hooks: vec![
+                    hook::Hook {
+                        key: "commit".to_string(),
+                        command: vec!["true".to_string()],
+                        idempotent: false,
+                        ..Default::default()
+                    },
+                    hook::Hook {
+                        key: "push".to_string(),
+                        command: vec!["false".to_string()],
+                        idempotent: false,
+                        ..Default::default()
+                    },
+                    hook::Hook {
+                        key: "format".to_string(),
+                        command: vec!["true".to_string()],
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            },
+            path: PathBuf::from("."),
+        };
+        let out_dir = TempDir::new("spackle").unwrap().into_path();
+
+        let (_, first) = project
+            .generate_with_hooks(&out_dir, &HashMap::new(), &[], &GenerateOptions::default())
+            .expect("generate_with_hooks failed, should have succeeded");
+        assert!(matches!(first[1].kind, hook::HookResultKind::Failed(_)));
+
+        let manifest = manifest::Manifest::load(&out_dir)
+            .expect("Failed to load manifest")
+            .expect("Expected a manifest after the first run");
+        // The failed hook hasn't run, so it's tried again
+        assert_eq!(manifest.hooks_ran, vec!["commit", "format"]);
+
+        let (_, second) = project
+            .generate_with_hooks(&out_dir, &HashMap::new(), &[], &GenerateOptions::default())
+            .expect("generate_with_hooks failed, should have succeeded");
+        let kinds = second
+            .iter()
+            .map(|r| (r.hook.key.as_str(), &r.kind))
+            .collect::<Vec<_>>();
+        assert!(
+            matches!(
+                kinds[..],
+                [
+                    (
+                        "commit",
+                        hook::HookResultKind::Skipped(hook::SkipReason::AlreadyRan)
+                    ),
+                    ("push", hook::HookResultKind::Failed(_)),
+                    ("format", hook::HookResultKind::Completed { .. })
+                ]
+            ),
+            "Expected only the non-idempotent hook that ran to be skipped, got {:?}",
+            kinds
+        );
+
+        let manifest = manifest::Manifest::load(&out_dir)
+            .expect("Failed to load manifest")
+            .expect("Expected a manifest after the second run");
+        assert_eq!(manifest.hooks_ran, vec!["commit", "format"]);
+    }
+
+    #[test]
+    fn timings() {
+        let project = Project {
+            config: config::Config {
+                hooks: vec![
+                    hook::Hook {
+                        key: "a".to_string(),
+                        command: vec!["true".to_string()],
+                        ..Default::default()
+                    },
+                    hook::Hook {
+                        key: "b".to_string(),
+                        command: vec!["false".to_string()],
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            },
+            path: PathBuf::from("."),
+        };
+
+        let results = project
+            .run_hooks(Path::new("."), &HashMap::new(), &[], None)
+            .expect("run_hooks failed, should have succeeded");
+
+        let mut timings = Timings {
+            copy: Duration::from_millis(800),
+            render: Duration::from_millis(2300),
+            ..Default::default()
+        };
+        for result in &results {
+            timings.record_hook(result);
+        }
+
+        // Only completed hooks have a duration
+        assert_eq!(
+            timings
+                .hooks
+                .iter()
+                .map(|(key, _)| key.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a"]
+        );
+        assert!(timings
+            .to_string()
+            .starts_with("copy 0.80s · render 2.30s · hooks "));
+    }
+
+    #[test]
+    fn injected_keys_win() {
+        let project = Project {
+            config: config::Config {
+                name: Some("{{ _output_name }}-project".to_string()),
+                ..Default::default()
+            },
+            path: PathBuf::from("."),
+        };
+
+        let supplied = HashMap::from([
+            ("_output_name".to_string(), "supplied".to_string()),
+            ("_project_name".to_string(), "supplied".to_string()),
+            ("_run_id".to_string(), "supplied".to_string()),
+            ("_slots".to_string(), "supplied".to_string()),
+        ]);
+
+        assert_eq!(
+            overridden_keys(&supplied),
+            vec!["_output_name", "_project_name", "_slots"]
+        );
+
+        let data = project.context_data(Path::new("/tmp/out"), &supplied);
+        assert_eq!(data["_output_name"], "out");
+        assert_eq!(data["_project_name"], "out-project");
+        assert!(!data.contains_key("_slots"));
+        // The run id may be pinned by the caller
+        assert_eq!(data["_run_id"], "supplied");
+    }
+
+    #[test]
+    fn name_variants() {
+        let project = Project {
+            config: config::Config {
+                name: Some("Data Pipeline".to_string()),
+                ..Default::default()
+            },
+            path: PathBuf::from("."),
+        };
+
+        let data = project.context_data(
+            Path::new("/tmp/my cool-app"),
+            &HashMap::from([("_output_name_snake".to_string(), "supplied".to_string())]),
+        );
+        assert_eq!(data["_output_name"], "my cool-app");
+        assert_eq!(data["_output_name_snake"], "my_cool_app");
+        assert_eq!(data["_output_name_kebab"], "my-cool-app");
+        assert_eq!(data["_output_name_pascal"], "MyCoolApp");
+        assert_eq!(data["_project_name"], "Data Pipeline");
+        assert_eq!(data["_project_name_snake"], "data_pipeline");
+        assert_eq!(data["_project_name_kebab"], "data-pipeline");
+        assert_eq!(data["_project_name_pascal"], "DataPipeline");
+    }
+
+    #[test]
+    fn run_id_override() {
+        let project = Project {
+            config: config::Config {
+                hooks: vec![hook::Hook {
+                    key: "echo".to_string(),
+                    command: vec!["echo".to_string(), "{{ _run_id }}".to_string()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            path: PathBuf::from("."),
+        };
+
+        let results = project
+            .run_hooks(
+                Path::new("."),
+                &HashMap::from([("_run_id".to_string(), "fixed".to_string())]),
+                &[],
+                None,
+            )
+            .expect("run_hooks failed, should have succeeded");
+
+        assert!(
+            matches!(&results[0].kind, hook::HookResultKind::Completed { stdout, .. } if String::from_utf8_lossy(stdout).trim() == "fixed"),
+            "Expected run id to be overridden, got {:?}",
+            results
+        );
+    }
+
+    #[test]
+    fn error_codes_unique_and_documented() {
+        let codes = error_codes();
+        let unique = codes.iter().collect::<std::collections::HashSet<_>>();
+        assert_eq!(
+            unique.len(),
+            codes.len(),
+            "Expected unique codes, got {:?}",
+            codes
+        );
+
+        let catalog = fs::read_to_string("docs/errors.md").expect("Failed to read docs/errors.md");
+        for code in codes {
+            assert!(
+                catalog.contains(&format!("`{}`", code)),
+                "Expected {} to be documented in docs/errors.md",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn skip_report() {
+        let project = load_project(&PathBuf::from("tests/data/skips")).unwrap();
+        let tmp = TempDir::new("spackle").unwrap();
+        let out_dir = tmp.path().join("out");
+
+        project
+            .generate_with_hooks(&out_dir, &HashMap::new(), &[], &GenerateOptions::default())
+            .expect("generate_with_hooks failed, should have succeeded");
+
+        // Filling the same directory again skips the hook that isn't idempotent
+        let mut report = plan::SkipReport::default();
+        let copied = project
+            .copy_files(&out_dir, &HashMap::new(), &GenerateOptions::default())
+            .expect("copy_files failed, should have succeeded");
+        report.add_ignored(&copied.skipped);
+        let hook_results = project
+            .run_hooks(&out_dir, &HashMap::new(), &[], None)
+            .expect("run_hooks failed, should have succeeded");
+        report.add_hook_results(&hook_results);
+
+        let mut files = report
+            .files
+            .iter()
+            .map(|file| (file.path.clone(), file.reason.clone()))
+            .collect::<Vec<_>>();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            files,
+            vec![
+                (PathBuf::from("Dockerfile"), plan::FileSkipReason::Ignored),
+                (PathBuf::from("notes.txt"), plan::FileSkipReason::Ignored),
+            ]
+        );
+        assert_eq!(
+            report.hooks,
+            vec![
+                plan::SkippedHook {
+                    key: "docker".to_string(),
+                    reason: hook::SkipReason::UserDisabled,
+                },
+                plan::SkippedHook {
+                    key: "init".to_string(),
+                    reason: hook::SkipReason::AlreadyRan,
+                },
+                plan::SkippedHook {
+                    key: "lint".to_string(),
+                    reason: hook::SkipReason::FalseConditional,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn static_only() {
+        let project = load_project(&PathBuf::from("tests/data/static_only")).unwrap();
+        let tmp = TempDir::new("spackle").unwrap();
+        let out_dir = tmp.path().join("out");
+
+        assert!(project
+            .validate()
+            .is_ok_and(|templates| templates.is_empty()));
+        assert!(project.plan(&out_dir, &HashMap::new()).is_ok());
+
+        let (rendered, hook_results) = project
+            .generate_with_hooks(&out_dir, &HashMap::new(), &[], &GenerateOptions::default())
+            .expect("generate_with_hooks failed, should have succeeded");
+
+        assert!(rendered.is_empty());
+        assert!(hook_results.is_empty());
+        assert!(out_dir.join("README.md").exists());
+        assert!(out_dir.join("src").join("main.txt").exists());
+    }
+
+    #[test]
+    fn infer_needed_by() {
+        let project = load_project(&PathBuf::from("tests/data/hook_slots")).unwrap();
+        let needed_by = |key: &str| {
+            project
+                .config
+                .slots
+                .iter()
+                .find(|slot| slot.key == key)
+                .unwrap()
+                .needed_by
+                .clone()
+        };
+
+        // Read by a template
+        assert!(needed_by("name").is_empty());
+        assert!(needed_by("region").is_empty());
+        // Read by a template, guarded by a default or a test
+        assert!(needed_by("owner").is_empty());
+        assert!(needed_by("license").is_empty());
+        // Read by another slot's condition
+        assert!(needed_by("registry").is_empty());
+        // Only read by hooks
+        assert_eq!(needed_by("tag"), vec!["deploy"]);
+        assert_eq!(needed_by("db.host"), vec!["migrate"]);
+
+        let tag = project
+            .config
+            .slots
+            .iter()
+            .find(|slot| slot.key == "tag")
+            .unwrap();
+        assert!(!tag.is_needed(&project.config.hooks, &HashMap::new()));
+        assert!(tag.is_needed(
+            &project.config.hooks,
+            &HashMap::from([("deploy".to_string(), "true".to_string())])
+        ));
+    }
+
+    #[test]
+    fn required_binaries() {
+        let hook = |key: &str, command: &[&str]| hook::Hook {
+            key: key.to_string(),
+            command: command.iter().map(|arg| arg.to_string()).collect(),
+            ..Default::default()
+        };
+        let project = Project {
+            config: config::Config {
+                hooks: vec![
+                    hook("init", &["git", "init"]),
+                    hook("build", &["cargo", "build"]),
+                    hook("commit", &["git", "commit", "-m", "init"]),
+                    hook("tool", &["{{ tool }}", "run"]),
+                    hook("setup", &["./scripts/setup.sh"]),
+                    hook("env", &["/usr/bin/env"]),
+                    hook::Hook {
+                        shell: Some("bash".to_string()),
+                        ..hook("install", &["make", "&&", "make", "install"])
+                    },
+                    hook::Hook {
+                        shell: Some("sh".to_string()),
+                        ..hook("lint", &["shellcheck *.sh | tee lint.log"])
+                    },
+                    hook::Hook {
+                        shell: Some("sh".to_string()),
+                        ..hook("release", &["CI=1 cargo publish"])
+                    },
+                    hook::Hook {
+                        from_hooks_dir: true,
+                        ..hook("script", &["/project/hooks.d/10-script"])
+                    },
+                ],
+                ..Default::default()
+            },
+            path: PathBuf::from("."),
+        };
+
+        assert_eq!(
+            project.required_binaries(),
+            vec![
+                "git",
+                "cargo",
+                "/usr/bin/env",
+                "bash",
+                "make",
+                "sh",
+                "shellcheck"
+            ]
+        );
+    }
+
+    #[test]
+    fn load_project_not_found() {
+        let missing = load_project(&PathBuf::from("tests/data/missing"))
+            .err()
+            .unwrap();
+        assert!(matches!(missing, config::Error::NotFound(_)));
+        assert!(missing.is_not_found());
+
+        let not_project = load_project(&PathBuf::from("tests")).err().unwrap();
+        assert!(matches!(not_project, config::Error::ConfigNotFound(_)));
+        assert!(not_project.is_not_found());
+
+        let invalid = load_project(&PathBuf::from("tests/data/conf_dup_key"))
+            .err()
+            .unwrap();
+        assert!(!invalid.is_not_found());
+    }
+}
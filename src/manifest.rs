@@ -0,0 +1,250 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+use walkdir::WalkDir;
+
+use crate::{answers::Answers, hook::HookResult, paths::glob_set, run_log, version, Version};
+
+/// The file a fill records itself in, at the root of the output directory
+pub const MANIFEST_FILE: &str = ".spackle-manifest.toml";
+
+/// What a fill recorded about the output directory, so later runs can tell it was already generated
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Manifest {
+    pub run_id: String,
+    /// The hooks that completed in the output directory, in this fill or an earlier one, in
+    /// the order they ran
+    #[serde(default)]
+    pub hooks_ran: Vec<String>,
+    /// Globs of the outputs the template manages, from the project's `managed` config.
+    /// Other outputs are expected to be edited by users.
+    #[serde(default)]
+    pub managed: Vec<String>,
+    /// The files copied in from Path slots with `copy_into`, relative to the output directory
+    #[serde(default)]
+    pub imported: Vec<PathBuf>,
+    /// The spackle that wrote the manifest, missing from those written before it was recorded
+    pub spackle: Option<Version>,
+    /// The answers the output was filled with, so it can be rendered again when updating
+    pub answers: Option<Answers>,
+    /// A [fingerprint] of each file as the fill wrote it, before hooks ran, by path relative
+    /// to the output directory. Updating compares them to tell which files were changed since.
+    #[serde(default)]
+    pub files: BTreeMap<PathBuf, String>,
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Error reading manifest: {0}")]
+    ReadError(io::Error),
+    #[error("Error parsing manifest: {0}")]
+    ParseError(toml::de::Error),
+    #[error("Error serializing manifest: {0}")]
+    SerializeError(toml::ser::Error),
+    #[error("Error writing manifest: {0}")]
+    WriteError(io::Error),
+}
+
+impl Error {
+    /// Every code returned by [Error::code]
+    pub const CODES: &'static [&'static str] = &[
+        "manifest.read_error",
+        "manifest.parse_error",
+        "manifest.serialize_error",
+        "manifest.write_error",
+    ];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::ReadError(_) => "manifest.read_error",
+            Error::ParseError(_) => "manifest.parse_error",
+            Error::SerializeError(_) => "manifest.serialize_error",
+            Error::WriteError(_) => "manifest.write_error",
+        }
+    }
+}
+
+impl Manifest {
+    /// Records a run along with the hooks that have run, see [HookResult::has_run]
+    pub fn new(run_id: &str, managed: &[String], hook_results: &[HookResult]) -> Self {
+        Manifest {
+            run_id: run_id.to_string(),
+            managed: managed.to_vec(),
+            hooks_ran: hook_results
+                .iter()
+                .filter(|result| result.has_run())
+                .map(|result| result.hook.key.clone())
+                .collect(),
+            imported: Vec::new(),
+            spackle: Some(version()),
+            answers: None,
+            files: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_imported(mut self, imported: Vec<PathBuf>) -> Self {
+        self.imported = imported;
+        self
+    }
+
+    pub fn with_answers(mut self, answers: Option<Answers>) -> Self {
+        self.answers = answers;
+        self
+    }
+
+    pub fn with_files(mut self, files: BTreeMap<PathBuf, String>) -> Self {
+        self.files = files;
+        self
+    }
+
+    /// Loads the manifest from the output directory, if a fill has written one
+    pub fn load(out_dir: &Path) -> Result<Option<Self>, Error> {
+        let path = out_dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let contents = fs::read_to_string(path).map_err(Error::ReadError)?;
+        toml::from_str(&contents)
+            .map(Some)
+            .map_err(Error::ParseError)
+    }
+
+    pub fn write(&self, out_dir: &Path) -> Result<(), Error> {
+        let contents = toml::to_string(self).map_err(Error::SerializeError)?;
+
+        fs::write(out_dir.join(MANIFEST_FILE), contents).map_err(Error::WriteError)
+    }
+
+    /// Whether the output, relative to the output directory, is managed by the template
+    pub fn is_managed(&self, path: &Path) -> bool {
+        glob_set(&self.managed).is_ok_and(|globs| globs.is_match(path))
+    }
+}
+
+/// Whether a fill has already generated the output directory
+pub fn exists(out_dir: &Path) -> bool {
+    out_dir.join(MANIFEST_FILE).is_file()
+}
+
+/// The hooks that already ran in the output directory, as recorded by its manifest. None have
+/// if it doesn't have one, or it can't be read.
+pub fn hooks_ran(out_dir: &Path) -> Vec<String> {
+    Manifest::load(out_dir)
+        .ok()
+        .flatten()
+        .map(|manifest| manifest.hooks_ran)
+        .unwrap_or_default()
+}
+
+/// Identifies a file's contents, to tell whether they changed since being recorded. It isn't
+/// cryptographic, FNV-1a is enough for that, and unlike std's hasher it's stable across builds.
+pub fn fingerprint(contents: &[u8]) -> String {
+    let hash = contents.iter().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    });
+
+    format!("{:016x}", hash)
+}
+
+/// The [fingerprint] of every file in the directory, by path relative to it. The manifest and
+/// run logs are spackle's own and left out, as are files that can't be read.
+pub fn fingerprints(dir: &Path) -> BTreeMap<PathBuf, String> {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| {
+            let path = entry.path();
+            path != dir.join(MANIFEST_FILE) && path != dir.join(run_log::LOG_DIR)
+        })
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let contents = fs::read(entry.path()).ok()?;
+            let path = entry.path().strip_prefix(dir).ok()?.to_path_buf();
+
+            Some((path, fingerprint(&contents)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let dir = TempDir::new("spackle").unwrap();
+        assert!(!exists(dir.path()));
+        assert_eq!(Manifest::load(dir.path()).unwrap(), None);
+
+        let manifest = Manifest {
+            run_id: "run".to_string(),
+            hooks_ran: vec!["first".to_string()],
+            managed: vec!["ci/**".to_string()],
+            imported: vec![PathBuf::from("data/export.csv")],
+            spackle: Some(version()),
+            answers: None,
+            files: BTreeMap::from([(PathBuf::from("src/main.rs"), fingerprint(b"fn main() {}"))]),
+        };
+        manifest
+            .write(dir.path())
+            .expect("Failed to write manifest");
+
+        assert!(exists(dir.path()));
+        assert_eq!(Manifest::load(dir.path()).unwrap(), Some(manifest.clone()));
+
+        assert!(manifest.is_managed(Path::new("ci/nested/build.yml")));
+        assert!(!manifest.is_managed(Path::new("src/ci/build.yml")));
+    }
+
+    #[test]
+    fn spackle_version() {
+        let dir = TempDir::new("spackle").unwrap();
+
+        // Manifests from before the version was recorded still load
+        fs::write(dir.path().join(MANIFEST_FILE), "run_id = \"old\"\n").unwrap();
+        assert_eq!(Manifest::load(dir.path()).unwrap().unwrap().spackle, None);
+
+        let manifest = Manifest::new("run", &[], &[]);
+        assert_eq!(manifest.spackle, Some(version()));
+        assert!(!version().is_newer_major());
+
+        let newer = Version {
+            version: format!("{}.0.0", version().major().unwrap() + 1),
+            git_sha: Some("abc1234".to_string()),
+        };
+        assert!(newer.is_newer_major());
+        assert_eq!(newer.to_string(), format!("{} (abc1234)", newer.version));
+        assert!(!Version {
+            version: "unknown".to_string(),
+            git_sha: None
+        }
+        .is_newer_major());
+    }
+
+    #[test]
+    fn fingerprints() {
+        // The published FNV-1a test vectors
+        assert_eq!(fingerprint(b""), "cbf29ce484222325");
+        assert_eq!(fingerprint(b"a"), "af63dc4c8601ec8c");
+
+        let dir = TempDir::new("spackle").unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        fs::create_dir_all(dir.path().join(run_log::LOG_DIR)).unwrap();
+        fs::write(dir.path().join(run_log::LOG_DIR).join("run.json"), "{}").unwrap();
+        Manifest::new("run", &[], &[]).write(dir.path()).unwrap();
+
+        assert_eq!(
+            super::fingerprints(dir.path()),
+            BTreeMap::from([(PathBuf::from("src/main.rs"), fingerprint(b"fn main() {}"))])
+        );
+    }
+}
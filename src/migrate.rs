@@ -0,0 +1,497 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+use toml::{Table, Value};
+
+use crate::config::{find_config_file, CONFIG_FILE, MAX_INCLUDE_DEPTH};
+
+/// The version migrated configs are upgraded to, that of this spackle
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The extension added to a config's path for the copy kept when it's migrated
+pub const BACKUP_EXT: &str = "bak";
+
+/// A change to the config schema, applied to configs written for an earlier version of spackle
+#[derive(Debug)]
+pub struct Migration {
+    /// The version of spackle that made the change
+    pub version: &'static str,
+    pub description: &'static str,
+    // Rewrites the config in place, returning whether anything changed
+    apply: fn(&mut Table) -> bool,
+}
+
+/// Every migration, in the order they're applied
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: "0.2.0",
+        description: "hooks' `optional = <bool>` becomes `optional = { default = <bool> }`",
+        apply: optional_table,
+    },
+    Migration {
+        version: "0.3.0",
+        description: "hooks' `optional = { default = <bool> }` becomes `default = <bool>`",
+        apply: optional_default,
+    },
+    Migration {
+        version: "0.3.0",
+        description: "`needs` given as a single key becomes a list of keys",
+        apply: needs_list,
+    },
+];
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Error reading config: {0}")]
+    ReadError(io::Error),
+    #[error("Error parsing config: {0}")]
+    ParseError(toml::de::Error),
+    #[error("Error serializing config: {0}")]
+    SerializeError(toml::ser::Error),
+    #[error("Error writing config: {0}")]
+    WriteError(io::Error),
+    #[error("Invalid spackle_version {0:?}, expected a version like \"0.4.0\"")]
+    InvalidVersion(String),
+    #[error("The config was written for spackle {0}, which is newer than this spackle ({CURRENT_VERSION})")]
+    NewerVersion(String),
+    #[error("Only {CONFIG_FILE} configs can be migrated, not {}", .0.display())]
+    Unsupported(PathBuf),
+}
+
+impl Error {
+    /// Every code returned by [Error::code]
+    pub const CODES: &'static [&'static str] = &[
+        "migrate.read_error",
+        "migrate.parse_error",
+        "migrate.serialize_error",
+        "migrate.write_error",
+        "migrate.invalid_version",
+        "migrate.newer_version",
+        "migrate.unsupported",
+    ];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::ReadError(_) => "migrate.read_error",
+            Error::ParseError(_) => "migrate.parse_error",
+            Error::SerializeError(_) => "migrate.serialize_error",
+            Error::WriteError(_) => "migrate.write_error",
+            Error::InvalidVersion(_) => "migrate.invalid_version",
+            Error::NewerVersion(_) => "migrate.newer_version",
+            Error::Unsupported(_) => "migrate.unsupported",
+        }
+    }
+}
+
+/// A config upgraded to [CURRENT_VERSION]
+#[derive(Debug)]
+pub struct Migrated {
+    /// The config's `spackle_version`, if it declared one
+    pub from: Option<String>,
+    /// The migrations that changed the config
+    pub applied: Vec<&'static Migration>,
+    /// The upgraded config. Comments and the order of keys aren't kept, so this is the
+    /// original when there was nothing to change.
+    pub contents: String,
+    // Whether the contents carry a `spackle_version`, which slots_from includes don't
+    versioned: bool,
+}
+
+impl Migrated {
+    /// Whether the contents differ from the original
+    pub fn is_changed(&self) -> bool {
+        !self.applied.is_empty()
+            || (self.versioned && self.from.as_deref() != Some(CURRENT_VERSION))
+    }
+
+    /// Writes the contents to the config, keeping the original alongside it with [BACKUP_EXT]
+    /// added. Does nothing if there was nothing to change.
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        if !self.is_changed() {
+            return Ok(());
+        }
+
+        fs::copy(path, backup_path(path)).map_err(Error::WriteError)?;
+        fs::write(path, &self.contents).map_err(Error::WriteError)
+    }
+}
+
+/// Where [Migrated::write] keeps the original config
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(BACKUP_EXT);
+
+    path.with_file_name(name)
+}
+
+/// Whether the path is one [Migrated::write] keeps an original TOML config at
+pub fn is_backup(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix(BACKUP_EXT))
+        .is_some_and(|name| name.ends_with(".toml."))
+}
+
+/// The TOML config of a project directory, the only kind that can be migrated
+pub fn config_path(project_dir: &Path) -> Result<PathBuf, Error> {
+    match find_config_file(project_dir) {
+        Some(CONFIG_FILE) => Ok(project_dir.join(CONFIG_FILE)),
+        Some(name) => Err(Error::Unsupported(project_dir.join(name))),
+        None => Err(Error::Unsupported(project_dir.to_path_buf())),
+    }
+}
+
+/// Upgrades a TOML config by applying the migrations made since its `spackle_version`.
+/// Configs without one are taken to predate every migration.
+pub fn migrate_str(contents: &str) -> Result<Migrated, Error> {
+    let mut config = contents.parse::<Table>().map_err(Error::ParseError)?;
+
+    let from = match config.get("spackle_version") {
+        Some(Value::String(version)) => Some(version.clone()),
+        Some(version) => return Err(Error::InvalidVersion(version.to_string())),
+        None => None,
+    };
+    let from_version = from
+        .as_deref()
+        .map(|version| parse_version(version).ok_or(Error::InvalidVersion(version.to_string())))
+        .transpose()?;
+
+    if from_version.is_some_and(|version| version > current_version()) {
+        return Err(Error::NewerVersion(from.unwrap_or_default()));
+    }
+
+    let applied = apply_migrations(&mut config, from_version);
+
+    if applied.is_empty() && from.as_deref() == Some(CURRENT_VERSION) {
+        return Ok(Migrated {
+            from,
+            applied,
+            contents: contents.to_string(),
+            versioned: true,
+        });
+    }
+
+    config.insert(
+        "spackle_version".to_string(),
+        Value::String(CURRENT_VERSION.to_string()),
+    );
+
+    Ok(Migrated {
+        from,
+        applied,
+        contents: toml::to_string(&config).map_err(Error::SerializeError)?,
+        versioned: true,
+    })
+}
+
+/// Upgrades a file included via `slots_from`, applying the migrations made since the
+/// `spackle_version` of the config including it. Includes don't declare a version of their
+/// own, so none is added.
+pub fn migrate_include_str(contents: &str, from: Option<&str>) -> Result<Migrated, Error> {
+    let mut include = contents.parse::<Table>().map_err(Error::ParseError)?;
+
+    let from_version = from
+        .map(|version| parse_version(version).ok_or(Error::InvalidVersion(version.to_string())))
+        .transpose()?;
+    let applied = apply_migrations(&mut include, from_version);
+
+    let contents = if applied.is_empty() {
+        contents.to_string()
+    } else {
+        toml::to_string(&include).map_err(Error::SerializeError)?
+    };
+
+    Ok(Migrated {
+        from: from.map(str::to_string),
+        applied,
+        contents,
+        versioned: false,
+    })
+}
+
+/// Migrates the config of a project directory and the files it includes via `slots_from`,
+/// returning the path of each along with its result, the config first. Nothing is written,
+/// see [Migrated::write].
+pub fn migrate_project(project_dir: &Path) -> Result<Vec<(PathBuf, Migrated)>, Error> {
+    let path = config_path(project_dir)?;
+    let contents = fs::read_to_string(&path).map_err(Error::ReadError)?;
+    let migrated = migrate_str(&contents)?;
+
+    let mut results = Vec::new();
+    migrate_includes(
+        &contents,
+        project_dir,
+        migrated.from.as_deref(),
+        0,
+        &mut results,
+    )?;
+    results.insert(0, (path, migrated));
+
+    Ok(results)
+}
+
+// Migrates the includes of a config or include, and theirs in turn. Includes nested deeper
+// than a config may load are left for loading to report.
+fn migrate_includes(
+    contents: &str,
+    base_dir: &Path,
+    from: Option<&str>,
+    depth: usize,
+    results: &mut Vec<(PathBuf, Migrated)>,
+) -> Result<(), Error> {
+    if depth >= MAX_INCLUDE_DEPTH {
+        return Ok(());
+    }
+
+    let table = contents.parse::<Table>().map_err(Error::ParseError)?;
+    let includes = table
+        .get("slots_from")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str);
+
+    for include in includes {
+        let path = base_dir.join(include);
+        if results.iter().any(|(migrated, _)| *migrated == path) {
+            continue;
+        }
+
+        let contents = fs::read_to_string(&path).map_err(Error::ReadError)?;
+        results.push((path.clone(), migrate_include_str(&contents, from)?));
+
+        migrate_includes(
+            &contents,
+            path.parent().unwrap_or(base_dir),
+            from,
+            depth + 1,
+            results,
+        )?;
+    }
+
+    Ok(())
+}
+
+// Applies the migrations made since the version, returning those that changed anything
+fn apply_migrations(
+    config: &mut Table,
+    from_version: Option<(u64, u64, u64)>,
+) -> Vec<&'static Migration> {
+    MIGRATIONS
+        .iter()
+        .filter(|migration| from_version.is_none_or(|from| from < migration.version()))
+        .filter(|migration| (migration.apply)(config))
+        .collect()
+}
+
+impl Migration {
+    fn version(&self) -> (u64, u64, u64) {
+        parse_version(self.version).unwrap_or_default()
+    }
+}
+
+fn current_version() -> (u64, u64, u64) {
+    parse_version(CURRENT_VERSION).unwrap_or_default()
+}
+
+// Parses `major.minor.patch`, ignoring any pre-release or build suffix
+pub(crate) fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let core = version.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+
+    let version = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(version)
+}
+
+// The tables of a config's array of tables, e.g. its `[[hooks]]`
+fn tables<'a>(config: &'a mut Table, key: &str) -> impl Iterator<Item = &'a mut Table> {
+    config
+        .get_mut(key)
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_table_mut)
+}
+
+fn optional_table(config: &mut Table) -> bool {
+    let mut changed = false;
+    for hook in tables(config, "hooks") {
+        if let Some(Value::Boolean(default)) = hook.get("optional") {
+            let optional = Table::from_iter([("default".to_string(), Value::Boolean(*default))]);
+            hook.insert("optional".to_string(), Value::Table(optional));
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+fn optional_default(config: &mut Table) -> bool {
+    let mut changed = false;
+    for hook in tables(config, "hooks") {
+        let Some(Value::Table(optional)) = hook.remove("optional") else {
+            continue;
+        };
+
+        if let Some(default) = optional.get("default") {
+            hook.entry("default").or_insert(default.clone());
+        }
+        changed = true;
+    }
+
+    changed
+}
+
+fn needs_list(config: &mut Table) -> bool {
+    let mut changed = false;
+    for key in ["slots", "hooks"] {
+        for item in tables(config, key) {
+            if let Some(Value::String(need)) = item.get("needs") {
+                let needs = Value::Array(vec![Value::String(need.clone())]);
+                item.insert("needs".to_string(), needs);
+                changed = true;
+            }
+        }
+    }
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::{
+        classify::{Classifier, FileClass},
+        config,
+    };
+
+    #[test]
+    fn old_config() {
+        let contents = fs::read_to_string("tests/data/migrate/spackle.toml").unwrap();
+        assert!(config::parse_str(&contents).is_err());
+
+        let migrated = migrate_str(&contents).expect("Failed to migrate config");
+        assert_eq!(migrated.from, None);
+        assert_eq!(
+            migrated
+                .applied
+                .iter()
+                .map(|migration| migration.description)
+                .collect::<Vec<_>>(),
+            MIGRATIONS
+                .iter()
+                .map(|migration| migration.description)
+                .collect::<Vec<_>>()
+        );
+
+        let config = config::parse_str(&migrated.contents).expect("Failed to parse migrated");
+        assert_eq!(config.spackle_version.as_deref(), Some(CURRENT_VERSION));
+        assert_eq!(config.hooks[0].default, Some(false));
+        assert_eq!(config.hooks[1].default, Some(true));
+        assert_eq!(config.hooks[1].needs, vec!["license"]);
+
+        // Migrating again has nothing to change
+        let again = migrate_str(&migrated.contents).unwrap();
+        assert!(!again.is_changed());
+        assert_eq!(again.contents, migrated.contents);
+    }
+
+    #[test]
+    fn versions() {
+        // Only migrations newer than the declared version apply
+        let migrated = migrate_str(
+            "spackle_version = \"0.2.0\"\n\n[[hooks]]\nkey = \"a\"\ncommand = [\"true\"]\noptional = true\n",
+        )
+        .unwrap();
+        assert!(migrated.applied.is_empty());
+        assert!(migrated.is_changed());
+
+        assert!(matches!(
+            migrate_str("spackle_version = \"99.0.0\"\n"),
+            Err(Error::NewerVersion(_))
+        ));
+        assert!(matches!(
+            migrate_str("spackle_version = \"latest\"\n"),
+            Err(Error::InvalidVersion(_))
+        ));
+        assert_eq!(parse_version("0.4.0-rc.1"), Some((0, 4, 0)));
+        assert_eq!(parse_version("0.4"), None);
+    }
+
+    #[test]
+    fn includes() {
+        let dir = TempDir::new("spackle").unwrap();
+        fs::create_dir(dir.path().join("slots")).unwrap();
+        fs::write(
+            dir.path().join(CONFIG_FILE),
+            "slots_from = [\"slots/db.toml\"]\n\n[[slots]]\nkey = \"a\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("slots/db.toml"),
+            "slots_from = [\"more.toml\"]\n\n[[slots]]\nkey = \"b\"\nneeds = \"a\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("slots/more.toml"),
+            "[[slots]]\nkey = \"c\"\n",
+        )
+        .unwrap();
+
+        let results = migrate_project(dir.path()).unwrap();
+        assert_eq!(
+            results
+                .iter()
+                .map(|(path, migrated)| (path.clone(), migrated.is_changed()))
+                .collect::<Vec<_>>(),
+            vec![
+                (dir.path().join(CONFIG_FILE), true),
+                (dir.path().join("slots/db.toml"), true),
+                (dir.path().join("slots/more.toml"), false),
+            ]
+        );
+
+        for (path, migrated) in &results {
+            migrated.write(path).unwrap();
+        }
+        assert!(!dir.path().join("slots/more.toml.bak").exists());
+
+        let config = config::load_dir(dir.path()).expect("Failed to load migrated");
+        assert_eq!(config.slots[1].needs, vec!["a"]);
+    }
+
+    #[test]
+    fn write() {
+        let dir = TempDir::new("spackle").unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        fs::write(
+            &path,
+            "[[slots]]\nkey = \"a\"\n\n[[slots]]\nkey = \"b\"\nneeds = \"a\"\n",
+        )
+        .unwrap();
+
+        let results = migrate_project(dir.path()).unwrap();
+        let (config_path, migrated) = &results[0];
+        assert_eq!(*config_path, path);
+        migrated.write(&path).unwrap();
+
+        assert!(fs::read_to_string(backup_path(&path))
+            .unwrap()
+            .contains("needs = \"a\""));
+        assert!(config::load_dir(dir.path()).is_ok());
+
+        // The backup is kept out of fills
+        let classifier = Classifier::new(dir.path(), &[]).unwrap();
+        assert_eq!(
+            classifier.classify(Path::new("spackle.toml.bak"), false),
+            FileClass::Project
+        );
+        assert!(!is_backup(Path::new("notes.bak")));
+    }
+}
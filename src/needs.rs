@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub trait Needy {
     fn key(&self) -> String;
@@ -23,3 +23,43 @@ pub fn is_satisfied(
             None => false,
         })
 }
+
+/// Finds a cycle in the graph of needs, where each key maps to the keys it needs.
+/// Returns the keys making up the cycle, starting and ending with the same key.
+pub fn find_cycle(graph: &HashMap<String, Vec<String>>) -> Option<Vec<String>> {
+    fn visit(
+        key: &String,
+        graph: &HashMap<String, Vec<String>>,
+        path: &mut Vec<String>,
+        done: &mut HashSet<String>,
+    ) -> Option<Vec<String>> {
+        if let Some(start) = path.iter().position(|k| k == key) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(key.clone());
+            return Some(cycle);
+        }
+
+        if done.contains(key) {
+            return None;
+        }
+
+        path.push(key.clone());
+        for need in graph.get(key).into_iter().flatten() {
+            if let Some(cycle) = visit(need, graph, path, done) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+
+        done.insert(key.clone());
+        None
+    }
+
+    // Sort keys so the same cycle is always reported
+    let mut keys = graph.keys().collect::<Vec<_>>();
+    keys.sort();
+
+    let mut done = HashSet::new();
+    keys.into_iter()
+        .find_map(|key| visit(key, graph, &mut Vec::new(), &mut done))
+}
@@ -0,0 +1,186 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::{
+    env,
+    ffi::OsString,
+    path::{Component, Path, PathBuf},
+};
+
+/// The name of the user-level config file in [config_dir]
+pub const USER_CONFIG_FILE: &str = "config.toml";
+
+/// Resolves a path that may not exist yet to an absolute path with symlinks followed.
+/// Existing components are canonicalized as they're reached, so `..` after a symlink
+/// steps out of the link's target; the missing remainder is resolved lexically.
+pub fn resolve(path: &Path) -> PathBuf {
+    let path = match path.is_absolute() {
+        true => path.to_path_buf(),
+        false => env::current_dir().unwrap_or_default().join(path),
+    };
+
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            component => {
+                resolved.push(component);
+
+                if let Ok(canonical) = resolved.canonicalize() {
+                    resolved = canonical;
+                }
+            }
+        }
+    }
+
+    resolved
+}
+
+/// Builds a set of globs matching relative paths, like the output paths of `managed`, `chmod`
+/// and render overrides. Unlike ignore entries, `*` doesn't cross directories and a pattern
+/// without one only matches at the top level.
+pub fn glob_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(GlobBuilder::new(pattern).literal_separator(true).build()?);
+    }
+
+    builder.build()
+}
+
+/// Whether `path` is `base` or inside it, once both are resolved
+pub fn is_within(path: &Path, base: &Path) -> bool {
+    resolve(path).starts_with(resolve(base))
+}
+
+/// Whether a path is safe to remove on behalf of an output directory: strictly inside it, and
+/// never the root or the home directory, whatever the output directory is
+pub fn is_removable(path: &Path, out_dir: &Path) -> bool {
+    let path = resolve(path);
+    let out_dir = resolve(out_dir);
+
+    path != out_dir
+        && path.starts_with(&out_dir)
+        && path.parent().is_some()
+        && home_dir().is_none_or(|home| resolve(&home) != path)
+}
+
+/// The directory spackle caches data in: `$XDG_CACHE_HOME/spackle`, `~/.cache/spackle` if
+/// that isn't set, or `%LOCALAPPDATA%\spackle\cache` on Windows
+pub fn cache_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return env::var_os("LOCALAPPDATA")
+            .map(|dir| PathBuf::from(dir).join("spackle").join("cache"));
+    }
+
+    xdg_dir(env::var_os("XDG_CACHE_HOME"), home_dir(), ".cache")
+}
+
+/// The directory of spackle's user-level config: `$XDG_CONFIG_HOME/spackle`,
+/// `~/.config/spackle` if that isn't set, or `%APPDATA%\spackle` on Windows
+pub fn config_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("spackle"));
+    }
+
+    xdg_dir(env::var_os("XDG_CONFIG_HOME"), home_dir(), ".config")
+}
+
+/// The path of the user-level config file, which may not exist
+pub fn user_config_file() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(USER_CONFIG_FILE))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+// Resolves spackle's directory under an XDG base directory. Per the spec, a relative or empty
+// XDG variable is ignored in favor of the fallback under the home directory.
+fn xdg_dir(xdg: Option<OsString>, home: Option<PathBuf>, fallback: &str) -> Option<PathBuf> {
+    xdg.map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home.map(|home| home.join(fallback)))
+        .map(|dir| dir.join("spackle"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn nested() {
+        let project = TempDir::new("spackle").unwrap();
+
+        assert!(is_within(&project.path().join("out"), project.path()));
+        assert!(is_within(
+            &project.path().join("a/b/../out"),
+            project.path()
+        ));
+        assert!(!is_within(&project.path().join("../out"), project.path()));
+        assert!(!is_within(Path::new("/"), project.path()));
+    }
+
+    #[test]
+    fn removable() {
+        let out = TempDir::new("spackle").unwrap();
+
+        assert!(is_removable(&out.path().join("a/b"), out.path()));
+        assert!(!is_removable(out.path(), out.path()));
+        assert!(!is_removable(&out.path().join(".."), out.path()));
+        assert!(!is_removable(Path::new("/"), Path::new("/")));
+        if let Some(home) = home_dir() {
+            assert!(!is_removable(&home, Path::new("/")));
+        }
+    }
+
+    #[test]
+    fn relative() {
+        let cwd = env::current_dir().unwrap();
+
+        assert!(is_within(Path::new("out"), &cwd));
+        assert!(is_within(Path::new("./a/../out"), Path::new(".")));
+        assert!(!is_within(Path::new("../out"), Path::new(".")));
+    }
+
+    #[test]
+    fn symlink() {
+        let project = TempDir::new("spackle").unwrap();
+        let elsewhere = TempDir::new("spackle").unwrap();
+
+        // A link outside the project that points into it
+        let link = elsewhere.path().join("link");
+        std::os::unix::fs::symlink(project.path(), &link).unwrap();
+        assert!(is_within(&link.join("out"), project.path()));
+
+        // A link inside the project that points out of it
+        let escape = project.path().join("escape");
+        std::os::unix::fs::symlink(elsewhere.path(), &escape).unwrap();
+        fs::create_dir(elsewhere.path().join("out")).unwrap();
+        assert!(!is_within(&escape.join("out"), project.path()));
+    }
+
+    #[test]
+    fn xdg() {
+        let home = Some(PathBuf::from("/home/ada"));
+
+        assert_eq!(
+            xdg_dir(Some("/xdg/cache".into()), home.clone(), ".cache"),
+            Some(PathBuf::from("/xdg/cache/spackle"))
+        );
+        assert_eq!(
+            xdg_dir(None, home.clone(), ".cache"),
+            Some(PathBuf::from("/home/ada/.cache/spackle"))
+        );
+        assert_eq!(
+            xdg_dir(Some("relative".into()), home.clone(), ".config"),
+            Some(PathBuf::from("/home/ada/.config/spackle"))
+        );
+        assert_eq!(xdg_dir(None, None, ".cache"), None);
+    }
+}
@@ -0,0 +1,219 @@
+use colored::Colorize;
+use serde::Serialize;
+use std::{fmt::Display, path::PathBuf};
+
+use crate::{
+    copy::Import,
+    hook::{HookResult, HookResultKind, SkipReason},
+    Version,
+};
+
+/// What generation would do with a file
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum FileAction {
+    Create,
+    /// The file already exists and would be replaced
+    Overwrite,
+    /// The file is matched by an ignore glob and would not be copied
+    Skip,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PlannedFile {
+    pub path: PathBuf,
+    pub action: FileAction,
+}
+
+/// What running the hooks would do with a hook
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum HookAction {
+    Run,
+    /// The hook would run if its conditional, evaluated at run time, is true
+    RunIf(String),
+    Skip(SkipReason),
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PlannedHook {
+    pub key: String,
+    pub action: HookAction,
+}
+
+/// Everything a fill would do, worked out without writing files or running commands
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Plan {
+    /// The spackle that worked out the plan
+    pub spackle: Version,
+    pub out_dir: PathBuf,
+    /// The files that would be written, in path order, followed by the skipped ones in the
+    /// order they're found in the project. Skipped files are relative to the project directory.
+    pub files: Vec<PlannedFile>,
+    /// Directories that don't exist yet and would be created
+    pub directories: Vec<PathBuf>,
+    /// Files and directories from Path slots that would be copied in after rendering
+    pub imports: Vec<Import>,
+    /// Hooks in the order they would run
+    pub hooks: Vec<PlannedHook>,
+}
+
+impl Plan {
+    /// The files that already exist and would be replaced
+    pub fn overwrites(&self) -> Vec<&PlannedFile> {
+        self.files
+            .iter()
+            .filter(|file| file.action == FileAction::Overwrite)
+            .collect()
+    }
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for directory in &self.directories {
+            writeln!(f, "  {} {}/", "+".green(), directory.display())?;
+        }
+
+        for file in &self.files {
+            match file.action {
+                FileAction::Create => writeln!(f, "  {} {}", "+".green(), file.path.display())?,
+                FileAction::Overwrite => writeln!(
+                    f,
+                    "  {} {} {}",
+                    "~".yellow(),
+                    file.path.display(),
+                    "(overwrite)".yellow()
+                )?,
+                FileAction::Skip => writeln!(
+                    f,
+                    "{}",
+                    format!("  - {} (ignored)", file.path.display()).dimmed()
+                )?,
+            }
+        }
+
+        for import in &self.imports {
+            writeln!(
+                f,
+                "  {} {}/ {}",
+                "+".green(),
+                import.dest.display(),
+                format!("(import from {})", import.src.display()).dimmed()
+            )?;
+        }
+
+        for hook in &self.hooks {
+            match &hook.action {
+                HookAction::Run => writeln!(f, "  {} hook {}", ">".green(), hook.key)?,
+                HookAction::RunIf(conditional) => writeln!(
+                    f,
+                    "  {} hook {} {}",
+                    "?".cyan(),
+                    hook.key,
+                    format!("(if {})", conditional).dimmed()
+                )?,
+                HookAction::Skip(reason) => writeln!(
+                    f,
+                    "{}",
+                    format!("  - hook {} (skipped: {})", hook.key, reason).dimmed()
+                )?,
+            }
+        }
+
+        let count = |action: FileAction| self.files.iter().filter(|f| f.action == action).count();
+        let hooks_run = self
+            .hooks
+            .iter()
+            .filter(|hook| !matches!(hook.action, HookAction::Skip(_)))
+            .count();
+
+        write!(
+            f,
+            "\nPlan: {} to create, {} to overwrite, {} ignored, {} hook(s) to run",
+            count(FileAction::Create),
+            count(FileAction::Overwrite),
+            count(FileAction::Skip),
+            hooks_run
+        )
+    }
+}
+
+/// Why a fill didn't write a file
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum FileSkipReason {
+    /// Matched by an ignore glob, including those of disabled hooks in `ignore_if_disabled`
+    Ignored,
+}
+
+impl Display for FileSkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileSkipReason::Ignored => write!(f, "ignored"),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SkippedFile {
+    /// Relative to the project directory
+    pub path: PathBuf,
+    pub reason: FileSkipReason,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SkippedHook {
+    pub key: String,
+    pub reason: SkipReason,
+}
+
+/// Everything a fill skipped and why, gathered from the copy and hook phases
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct SkipReport {
+    pub files: Vec<SkippedFile>,
+    pub hooks: Vec<SkippedHook>,
+}
+
+impl SkipReport {
+    /// Records the entries the copy skipped, see [crate::copy::CopyResult::skipped]
+    pub fn add_ignored(&mut self, paths: &[PathBuf]) {
+        self.files.extend(paths.iter().map(|path| SkippedFile {
+            path: path.clone(),
+            reason: FileSkipReason::Ignored,
+        }));
+    }
+
+    /// Records the hooks that were skipped, ignoring those that ran
+    pub fn add_hook_results<'a>(&mut self, results: impl IntoIterator<Item = &'a HookResult>) {
+        self.hooks
+            .extend(results.into_iter().filter_map(|result| match &result.kind {
+                HookResultKind::Skipped(reason) => Some(SkippedHook {
+                    key: result.hook.key.clone(),
+                    reason: reason.clone(),
+                }),
+                _ => None,
+            }));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.hooks.is_empty()
+    }
+}
+
+impl Display for SkipReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "  Nothing was skipped");
+        }
+
+        let lines = self
+            .files
+            .iter()
+            .map(|file| format!("  - {} ({})", file.path.display(), file.reason))
+            .chain(
+                self.hooks
+                    .iter()
+                    .map(|hook| format!("  - hook {} ({})", hook.key, hook.reason)),
+            )
+            .collect::<Vec<_>>();
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
@@ -0,0 +1,14 @@
+//! The types and functions most consumers need, re-exported from one place so
+//! downstream crates don't depend on which module an item happens to live in.
+//! Items not listed here are still available from their modules.
+
+pub use crate::{
+    coercions,
+    config::{Config, Error as ConfigError, ProjectKind},
+    copy::Error as CopyError,
+    hook::{Hook, HookResult, HookResultKind, HookStreamResult},
+    load_project, load_render_vars, new_run_id,
+    slot::{Error as SlotError, Slot, SlotType},
+    GenerateError, GenerateOptions, Project, ProjectSummary, RenderVarsError, RunHooksError,
+    Timings,
+};
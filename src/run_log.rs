@@ -0,0 +1,367 @@
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+use crate::{
+    event::{Event, Sequence},
+    hook::{Hook, HookError, HookResult, HookResultKind},
+    slot::Slot,
+    template, Version,
+};
+
+/// The directory in the output directory that run logs are written to
+pub const LOG_DIR: &str = ".spackle";
+
+/// What sensitive values are replaced with in run logs
+pub const REDACTED: &str = "[redacted]";
+
+/// Something that happened during a run, as recorded in its log
+#[derive(Serialize, Debug)]
+pub enum RunEvent {
+    Started {
+        run_id: String,
+        project: String,
+        data: BTreeMap<String, String>,
+        spackle: Version,
+    },
+    PhaseStarted(Phase),
+    /// Paths of ignored entries are relative to the project directory
+    Copied {
+        count: usize,
+        ignored: Vec<PathBuf>,
+    },
+    Rendered(PathBuf),
+    RenderFailed {
+        file: String,
+        code: String,
+        message: String,
+    },
+    HookStarted(String),
+    HookFinished {
+        key: String,
+        /// e.g. `completed`, `skipped: user disabled` or `failed: ...`
+        result: String,
+        code: Option<String>,
+        exit_code: Option<i32>,
+        stdout: String,
+        stderr: String,
+        /// Whether stdout or stderr only has the end of the hook's output
+        truncated: bool,
+    },
+    Warning(String),
+    /// Something that stopped the run or made it fail
+    Failed {
+        code: Option<String>,
+        message: String,
+    },
+    Finished {
+        ok: bool,
+        files: usize,
+        hooks_run: usize,
+        hooks_failed: usize,
+        timed_out: bool,
+        seconds: f64,
+    },
+}
+
+#[derive(Serialize, Debug)]
+pub enum Phase {
+    Copy,
+    Render,
+    Hooks,
+}
+
+impl RunEvent {
+    pub fn hook_finished(result: &HookResult) -> Self {
+        let (code, exit_code, stdout, stderr, truncated) = match &result.kind {
+            HookResultKind::Completed {
+                stdout,
+                stderr,
+                exit_code,
+                truncated,
+                ..
+            } => (
+                None,
+                Some(*exit_code),
+                stdout.as_slice(),
+                stderr.as_slice(),
+                *truncated,
+            ),
+            HookResultKind::Failed(
+                e @ HookError::CommandExited {
+                    exit_code,
+                    stdout,
+                    stderr,
+                    truncated,
+                },
+            ) => (
+                Some(e.code().to_string()),
+                Some(*exit_code),
+                stdout.as_slice(),
+                stderr.as_slice(),
+                *truncated,
+            ),
+            HookResultKind::Failed(e) => {
+                (Some(e.code().to_string()), None, &[][..], &[][..], false)
+            }
+            HookResultKind::Skipped(_) => (None, None, &[][..], &[][..], false),
+        };
+
+        RunEvent::HookFinished {
+            key: result.hook.key.clone(),
+            result: result.kind.to_string(),
+            code,
+            exit_code,
+            stdout: String::from_utf8_lossy(stdout).into_owned(),
+            stderr: String::from_utf8_lossy(stderr).into_owned(),
+            truncated,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Error writing run log: {0}")]
+    WriteError(io::Error),
+    #[error("Error reading run log: {0}")]
+    ReadError(io::Error),
+    #[error("Error parsing run log: {0}")]
+    ParseError(serde_json::Error),
+}
+
+impl Error {
+    /// Every code returned by [Error::code]
+    pub const CODES: &'static [&'static str] = &[
+        "run_log.write_error",
+        "run_log.read_error",
+        "run_log.parse_error",
+    ];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::WriteError(_) => "run_log.write_error",
+            Error::ReadError(_) => "run_log.read_error",
+            Error::ParseError(_) => "run_log.parse_error",
+        }
+    }
+}
+
+/// A newline-delimited JSON log of a run's events, each in the envelope the streaming API uses.
+/// Events are kept until the run is over and then written at once, since a failed run may
+/// remove its output directory along the way.
+#[derive(Debug, Default)]
+pub struct RunLog {
+    events: Vec<Event<Value>>,
+    sequence: Sequence,
+    // The sensitive slots, whose values are redacted
+    sensitive_keys: Vec<String>,
+    // The hooks that read a sensitive slot, whose output is redacted
+    sensitive_hooks: Vec<String>,
+}
+
+impl RunLog {
+    /// Starts a log that redacts the values of sensitive slots from the run's data, along with
+    /// all output of the hooks that read one, since it may hold the value in any form
+    pub fn new(slots: &[Slot], hooks: &[Hook]) -> Self {
+        let sensitive_keys = slots
+            .iter()
+            .filter(|slot| slot.sensitive)
+            .map(|slot| slot.key.clone())
+            .collect::<Vec<_>>();
+
+        let sensitive_hooks = hooks
+            .iter()
+            .filter(|hook| {
+                let variables = hook.referenced_variables();
+                sensitive_keys
+                    .iter()
+                    .any(|key| template::reads_key(&variables, key))
+            })
+            .map(|hook| hook.key.clone())
+            .collect();
+
+        RunLog {
+            sensitive_keys,
+            sensitive_hooks,
+            ..Default::default()
+        }
+    }
+
+    pub fn record(&mut self, mut event: RunEvent) {
+        self.redact(&mut event);
+        let value = serde_json::to_value(event).unwrap_or(Value::Null);
+
+        self.events.push(self.sequence.wrap(value));
+    }
+
+    pub fn events(&self) -> &[Event<Value>] {
+        &self.events
+    }
+
+    /// Writes the log to a new `run-<timestamp>.jsonl` file in the output directory's
+    /// [LOG_DIR], or in the system's temporary directory if the output directory is gone,
+    /// returning its path
+    pub fn write(&self, out_dir: &Path) -> Result<PathBuf, Error> {
+        let dir = if out_dir.is_dir() {
+            out_dir.join(LOG_DIR)
+        } else {
+            env::temp_dir().join("spackle")
+        };
+        fs::create_dir_all(&dir).map_err(Error::WriteError)?;
+
+        let path = dir.join(format!(
+            "run-{}.jsonl",
+            Utc::now().format("%Y%m%dT%H%M%S%.3fZ")
+        ));
+
+        let mut contents = Vec::new();
+        for event in &self.events {
+            serde_json::to_writer(&mut contents, event).map_err(|e| Error::WriteError(e.into()))?;
+            contents.write_all(b"\n").map_err(Error::WriteError)?;
+        }
+        fs::write(&path, contents).map_err(Error::WriteError)?;
+
+        Ok(path)
+    }
+
+    // Replaces the fields of the event that hold sensitive values with [REDACTED]
+    fn redact(&self, event: &mut RunEvent) {
+        match event {
+            RunEvent::Started { data, .. } => {
+                for key in &self.sensitive_keys {
+                    if let Some(value) = data.get_mut(key) {
+                        *value = REDACTED.to_string();
+                    }
+                }
+            }
+            RunEvent::HookFinished {
+                key,
+                stdout,
+                stderr,
+                ..
+            } if self.sensitive_hooks.contains(key) => {
+                for output in [stdout, stderr] {
+                    if !output.is_empty() {
+                        *output = REDACTED.to_string();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The most recent run log written to the output directory, if any
+pub fn latest(out_dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(out_dir.join(LOG_DIR))
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("run-") && name.ends_with(".jsonl"))
+        })
+        // Timestamps sort in the order they were written
+        .max()
+}
+
+/// Reads the events of a run log, as written by [RunLog::write]
+pub fn read(path: &Path) -> Result<Vec<Value>, Error> {
+    fs::read_to_string(path)
+        .map_err(Error::ReadError)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(Error::ParseError))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn write_and_read() {
+        let dir = TempDir::new("spackle").unwrap();
+        let slots = vec![
+            Slot {
+                key: "name".to_string(),
+                ..Default::default()
+            },
+            Slot {
+                key: "token".to_string(),
+                sensitive: true,
+                ..Default::default()
+            },
+        ];
+        let data = HashMap::from([
+            ("name".to_string(), "app".to_string()),
+            ("token".to_string(), "hunter2".to_string()),
+        ]);
+
+        let hooks = vec![
+            Hook {
+                key: "push".to_string(),
+                command: vec!["push".to_string(), "{{ token }}".to_string()],
+                ..Default::default()
+            },
+            Hook {
+                key: "build".to_string(),
+                command: vec!["build".to_string(), "{{ name }}".to_string()],
+                ..Default::default()
+            },
+        ];
+        let completed = |hook: &Hook, stdout: &str| HookResult {
+            hook: hook.clone(),
+            kind: HookResultKind::Completed {
+                stdout: stdout.as_bytes().to_vec(),
+                stderr: vec![],
+                exit_code: 0,
+                duration: Default::default(),
+                truncated: false,
+            },
+        };
+
+        let mut log = RunLog::new(&slots, &hooks);
+        log.record(RunEvent::Started {
+            run_id: "run".to_string(),
+            project: "app".to_string(),
+            data: data.clone().into_iter().collect(),
+            spackle: crate::version(),
+        });
+        log.record(RunEvent::hook_finished(&completed(
+            &hooks[0],
+            "pushed with aHVudGVyMg==\n",
+        )));
+        log.record(RunEvent::hook_finished(&completed(
+            &hooks[1],
+            "built app\n",
+        )));
+
+        assert_eq!(latest(dir.path()), None);
+        let path = log.write(dir.path()).expect("Failed to write run log");
+        assert!(path.starts_with(dir.path().join(LOG_DIR)));
+        assert_eq!(latest(dir.path()), Some(path.clone()));
+
+        let events = read(&path).expect("Failed to read run log");
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[1]["seq"], 1);
+        assert_eq!(events[0]["event"]["Started"]["data"]["name"], "app");
+        assert_eq!(events[0]["event"]["Started"]["data"]["token"], REDACTED);
+        // Output of a hook that reads the secret is redacted whatever form it takes
+        assert_eq!(events[1]["event"]["HookFinished"]["stdout"], REDACTED);
+        assert_eq!(events[2]["event"]["HookFinished"]["stdout"], "built app\n");
+        assert!(!fs::read_to_string(&path).unwrap().contains("hunter2"));
+    }
+}
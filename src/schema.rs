@@ -0,0 +1,183 @@
+use serde_json::{json, Map, Value};
+
+use crate::{
+    hook::Hook,
+    slot::{Slot, SlotType},
+};
+
+/// The JSON Schema dialect [input_schema] produces
+pub const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// The property hooks are toggled under in documents described by [input_schema]
+pub const HOOKS_PROPERTY: &str = "hooks";
+
+/// Describes the data a fill takes as a JSON Schema, for form builders and editors. Each slot
+/// is a property typed after its slot type, and hooks are booleans under [HOOKS_PROPERTY].
+/// Slots are only listed as required when they always need a value, see
+/// [Slot::is_always_required], and aren't tied to hooks by `needed_by`.
+pub fn input_schema(title: &str, slots: &[Slot], hooks: &[Hook]) -> Value {
+    let properties = slots
+        .iter()
+        .map(|slot| (slot.key.clone(), slot_schema(slot)))
+        .collect::<Map<_, _>>();
+
+    let required = slots
+        .iter()
+        .filter(|slot| slot.is_always_required() && slot.needed_by.is_empty())
+        .map(|slot| slot.key.clone())
+        .collect::<Vec<_>>();
+
+    let mut schema = json!({
+        "$schema": DIALECT,
+        "title": title,
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    });
+
+    if !hooks.is_empty() {
+        let hook_properties = hooks
+            .iter()
+            .map(|hook| (hook.key.clone(), hook_schema(hook)))
+            .collect::<Map<_, _>>();
+
+        schema["properties"][HOOKS_PROPERTY] = json!({
+            "type": "object",
+            "properties": hook_properties,
+            "additionalProperties": false,
+        });
+    }
+
+    schema
+}
+
+fn slot_schema(slot: &Slot) -> Value {
+    let mut schema = match slot.r#type {
+        SlotType::Number => json!({ "type": "number" }),
+        SlotType::Boolean => json!({ "type": "boolean" }),
+        SlotType::String | SlotType::Path => json!({ "type": "string" }),
+        SlotType::Choice => json!({ "type": "string", "enum": slot.choices }),
+        SlotType::List => {
+            let mut items = json!({ "type": "string" });
+            if !slot.choices.is_empty() {
+                items["enum"] = json!(slot.choices);
+            }
+
+            json!({ "type": "array", "items": items, "uniqueItems": true })
+        }
+    };
+
+    schema["title"] = json!(slot.get_name());
+    if let Some(description) = &slot.description {
+        schema["description"] = json!(description);
+    }
+    if let Some(default) = slot
+        .default
+        .as_deref()
+        .and_then(|d| typed_value(&slot.r#type, d))
+    {
+        schema["default"] = default;
+    }
+    if let Some(example) = slot
+        .example
+        .as_deref()
+        .and_then(|e| typed_value(&slot.r#type, e))
+    {
+        schema["examples"] = json!([example]);
+    }
+    if slot.sensitive {
+        schema["writeOnly"] = json!(true);
+    }
+
+    schema
+}
+
+fn hook_schema(hook: &Hook) -> Value {
+    let mut schema = json!({
+        "type": "boolean",
+        "title": hook.name.clone().unwrap_or(hook.key.clone()),
+        "default": hook.default.unwrap_or(true),
+    });
+    if let Some(description) = &hook.description {
+        schema["description"] = json!(description);
+    }
+
+    schema
+}
+
+// Converts a value as written in the config to the JSON type of its slot. Values that don't
+// parse are left out rather than contradicting the schema's own type.
+fn typed_value(r#type: &SlotType, value: &str) -> Option<Value> {
+    match r#type {
+        SlotType::Number => value.parse::<f64>().ok().map(|n| json!(n)),
+        SlotType::Boolean => value.parse::<bool>().ok().map(|b| json!(b)),
+        SlotType::List => Some(json!(value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .collect::<Vec<_>>())),
+        SlotType::String | SlotType::Path | SlotType::Choice => Some(json!(value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+
+    #[test]
+    fn input_schema() {
+        let config = config::load_dir("tests/data/schema").unwrap();
+        let schema = super::input_schema("schema", &config.slots, &config.hooks);
+
+        assert_eq!(
+            schema,
+            json!({
+                "$schema": DIALECT,
+                "title": "schema",
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "title": "Package name",
+                        "description": "The name of the R package",
+                        "examples": ["tidyr"],
+                    },
+                    "version": { "type": "number", "title": "version", "default": 4.4 },
+                    "public": { "type": "boolean", "title": "public", "default": false },
+                    "licenses": {
+                        "type": "array",
+                        "title": "licenses",
+                        "items": { "type": "string", "enum": ["MIT", "GPL-3"] },
+                        "uniqueItems": true,
+                        "default": ["MIT"],
+                    },
+                    "ci": {
+                        "type": "string",
+                        "title": "ci",
+                        "enum": ["github", "gitlab"],
+                        "default": "github",
+                    },
+                    "token": {
+                        "type": "string",
+                        "title": "token",
+                        "writeOnly": true,
+                    },
+                    "hooks": {
+                        "type": "object",
+                        "properties": {
+                            "git": {
+                                "type": "boolean",
+                                "title": "Initialize git",
+                                "default": false,
+                                "description": "Runs git init in the output",
+                            },
+                        },
+                        "additionalProperties": false,
+                    },
+                },
+                "required": ["name"],
+            })
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+/// Quotes a value for a POSIX shell. Values made up of only safe characters are left bare.
+pub fn shell_quote(value: &str) -> String {
+    let is_safe = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c));
+
+    if is_safe {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+/// The data with each value quoted by [shell_quote], for rendering a script so the values
+/// templated into it are never parsed as shell syntax.
+pub fn quote_values(data: &HashMap<String, String>) -> HashMap<String, String> {
+    data.iter()
+        .map(|(key, value)| (key.clone(), shell_quote(value)))
+        .collect()
+}
+
+/// The script a hook's command runs as under a shell. A single-string command is the script
+/// itself, so it can use pipes, `&&` and other shell syntax, and is rendered with
+/// [quote_values]. Each argument of a longer command
+/// is quoted, so the shell runs it as the same argv it would have without one, whatever the
+/// values templated into it.
+pub fn script(command: &[String]) -> String {
+    match command {
+        [script] => script.clone(),
+        args => args
+            .iter()
+            .map(|arg| shell_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare() {
+        assert_eq!(shell_quote("slot.name=foo"), "slot.name=foo");
+        assert_eq!(shell_quote("./out/dir"), "./out/dir");
+    }
+
+    #[test]
+    fn quoted() {
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("hello world"), "'hello world'");
+        assert_eq!(shell_quote("$HOME"), "'$HOME'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn scripts() {
+        let command = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(script(&command(&["ls | wc -l"])), "ls | wc -l");
+        assert_eq!(
+            script(&command(&["echo", "a; rm -rf $HOME", "`id`"])),
+            r"echo 'a; rm -rf $HOME' '`id`'"
+        );
+    }
+}
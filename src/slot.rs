@@ -1,8 +1,20 @@
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fmt::Display};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::Read,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+use tera::Tera;
 
-use crate::needs::{is_satisfied, Needy};
+use crate::{
+    hook::Hook,
+    needs::{is_satisfied, Needy},
+    template::{context_from_data, text_variables},
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Slot {
@@ -11,9 +23,42 @@ pub struct Slot {
     pub r#type: SlotType,
     #[serde(default)]
     pub needs: Vec<String>,
+    /// Hooks that use this slot. If set, the slot is only collected when one of them is enabled
+    #[serde(default)]
+    pub needed_by: Vec<String>,
     pub name: Option<String>,
     pub description: Option<String>,
+    /// A link to further documentation, shown by `info` and when prompting
+    pub docs_url: Option<String>,
     pub default: Option<String>,
+    /// A file whose contents are the default, relative to the project directory.
+    /// It is read into `default` when the config is loaded.
+    pub default_file: Option<String>,
+    /// A command whose trimmed output is the default, e.g. `["git", "config", "user.name"]`.
+    /// Falls back to `default` if it fails.
+    #[serde(default)]
+    pub default_from_command: Vec<String>,
+    /// An example value shown as a hint when prompting, not used as a value
+    pub example: Option<String>,
+    /// The values a Choice slot or a List slot's elements are limited to. Also read from
+    /// `options`, the usual name for a Choice slot's values elsewhere.
+    #[serde(default, alias = "options")]
+    pub choices: Vec<String>,
+    /// A file listing the choices, relative to the project directory, for lists shared across
+    /// projects. It is read into `choices` when the config is loaded.
+    pub choices_file: Option<String>,
+    /// Whether to prompt for the value in an editor, for long String values
+    #[serde(default)]
+    pub editor: bool,
+    /// Whether the value is a secret, like a token. Sensitive values are redacted from run logs
+    #[serde(default)]
+    pub sensitive: bool,
+    /// A Tera expression over other slots, e.g. `cloud == "aws"`. If set, a value is
+    /// only required when it evaluates to true
+    pub required_if: Option<String>,
+    /// For Path slots, a directory of the output that the file or directory the value points
+    /// at is copied into, e.g. `data/`
+    pub copy_into: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, strum_macros::Display, Default, Clone)]
@@ -22,6 +67,13 @@ pub enum SlotType {
     #[default]
     String,
     Boolean,
+    /// A comma-separated list of values
+    List,
+    /// A path on the filesystem, e.g. an existing directory to copy into the output
+    Path,
+    /// Exactly one of the slot's `choices`
+    #[serde(alias = "choice")]
+    Choice,
 }
 
 impl Default for Slot {
@@ -30,9 +82,20 @@ impl Default for Slot {
             key: "".to_string(),
             r#type: SlotType::String,
             needs: vec![],
+            needed_by: vec![],
             name: None,
             description: None,
+            docs_url: None,
             default: None,
+            default_file: None,
+            default_from_command: vec![],
+            example: None,
+            choices: vec![],
+            choices_file: None,
+            editor: false,
+            sensitive: false,
+            required_if: None,
+            copy_into: None,
         }
     }
 }
@@ -41,17 +104,47 @@ impl Display for Slot {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{} {}{}",
+            "{} {} {}{}{}{}{}{}",
             self.key.bold(),
             ("[".to_owned() + &self.r#type.to_string() + "]")
                 .to_string()
                 .to_lowercase()
                 .truecolor(128, 128, 128),
+            match (&self.default, &self.required_if) {
+                _ if !self.default_from_command.is_empty() => {
+                    format!("default from `{}`", self.default_from_command.join(" ")).dimmed()
+                }
+                (Some(default), _) => format!("default {}", default).dimmed(),
+                (None, Some(condition)) => format!("required if {}", condition).yellow(),
+                (None, None) => "required".yellow(),
+            },
             self.description
                 .clone()
                 .map(|s| format!("\n{}", s))
                 .unwrap_or_default()
                 .truecolor(180, 180, 180),
+            self.example
+                .clone()
+                .map(|s| format!("\nexample {}", s))
+                .unwrap_or_default()
+                .dimmed(),
+            self.docs_url
+                .clone()
+                .map(|s| format!("\ndocs {}", s))
+                .unwrap_or_default()
+                .dimmed(),
+            if self.needs.is_empty() {
+                "".to_string()
+            } else {
+                format!("\nneeds {}", self.needs.join(", "))
+            }
+            .dimmed(),
+            if self.needed_by.is_empty() {
+                "".to_string()
+            } else {
+                format!("\nneeded by {}", self.needed_by.join(", "))
+            }
+            .dimmed(),
         )
     }
 }
@@ -77,7 +170,13 @@ impl Needy for Slot {
 pub enum Error {
     UnknownSlot(String),
     TypeMismatch(String, String),
+    InvalidChoice(String, String),
+    MissingChoices(String),
+    InvalidCondition(String, String),
+    DefaultCommandFailed(String, String),
+    InvalidPromptTemplate(String, String),
     UndefinedSlot(String),
+    ExclusiveViolation(Vec<String>),
 }
 
 impl Display for Error {
@@ -87,7 +186,57 @@ impl Display for Error {
             Error::TypeMismatch(key, r#type) => {
                 write!(f, "type mismatch for key {}: expected a {}", key, r#type)
             }
+            Error::InvalidChoice(key, value) => {
+                write!(f, "invalid choice for key {}: {}", key, value)
+            }
+            Error::MissingChoices(key) => {
+                write!(f, "choice slot {} needs at least one choice", key)
+            }
+            Error::InvalidCondition(key, e) => {
+                write!(f, "invalid required_if for key {}: {}", key, e)
+            }
+            Error::DefaultCommandFailed(key, reason) => {
+                write!(f, "default command failed for key {}: {}", key, reason)
+            }
+            Error::InvalidPromptTemplate(key, e) => {
+                write!(f, "invalid name or description for key {}: {}", key, e)
+            }
             Error::UndefinedSlot(key) => write!(f, "slot was not defined: {}", key),
+            Error::ExclusiveViolation(group) => write!(
+                f,
+                "only one of the following slots may be set: {}",
+                group.join(", ")
+            ),
+        }
+    }
+}
+
+impl Error {
+    /// Every code returned by [Error::code]
+    pub const CODES: &'static [&'static str] = &[
+        "slot.unknown_slot",
+        "slot.type_mismatch",
+        "slot.invalid_choice",
+        "slot.missing_choices",
+        "slot.invalid_condition",
+        "slot.default_command_failed",
+        "slot.invalid_prompt_template",
+        "slot.undefined_slot",
+        "slot.exclusive_violation",
+    ];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::UnknownSlot(_) => "slot.unknown_slot",
+            Error::TypeMismatch(..) => "slot.type_mismatch",
+            Error::InvalidChoice(..) => "slot.invalid_choice",
+            Error::MissingChoices(_) => "slot.missing_choices",
+            Error::InvalidCondition(..) => "slot.invalid_condition",
+            Error::DefaultCommandFailed(..) => "slot.default_command_failed",
+            Error::InvalidPromptTemplate(..) => "slot.invalid_prompt_template",
+            Error::UndefinedSlot(_) => "slot.undefined_slot",
+            Error::ExclusiveViolation(_) => "slot.exclusive_violation",
         }
     }
 }
@@ -96,34 +245,229 @@ impl Slot {
     pub fn get_name(&self) -> String {
         self.name.clone().unwrap_or(self.key.clone())
     }
+
+    /// The same text as its [Display], without colors
+    pub fn to_plain_string(&self) -> String {
+        crate::strip_ansi(&self.to_string())
+    }
+
+    /// Whether a value always has to be given, i.e. the slot has no default of any kind and no
+    /// `required_if`
+    pub fn is_always_required(&self) -> bool {
+        self.default.is_none() && self.default_from_command.is_empty() && self.required_if.is_none()
+    }
+
+    /// Whether the slot should be collected, i.e. it isn't tied to any hooks or
+    /// at least one of the hooks in `needed_by` is enabled given the provided data
+    pub fn is_needed(&self, hooks: &[Hook], data: &HashMap<String, String>) -> bool {
+        self.needed_by.is_empty()
+            || hooks
+                .iter()
+                .filter(|hook| self.needed_by.contains(&hook.key))
+                .any(|hook| hook.is_enabled(data))
+    }
+
+    /// Whether a value must be provided for the slot, evaluating `required_if` against the data
+    pub fn is_required(&self, data: &HashMap<String, String>) -> Result<bool, Error> {
+        let condition = match &self.required_if {
+            Some(condition) => condition,
+            None => return Ok(true),
+        };
+
+        let rendered = Tera::one_off(
+            &Slot::condition_template(condition),
+            &context_from_data(data),
+            false,
+        )
+        .map_err(|e| Error::InvalidCondition(self.key.clone(), e.to_string()))?;
+
+        Ok(rendered == "true")
+    }
+
+    /// The variables the slot's condition and prompts read, along with the keys it needs
+    pub fn referenced_variables(&self) -> Vec<String> {
+        self.required_if
+            .iter()
+            .map(|condition| Slot::condition_template(condition))
+            .chain(self.name.clone())
+            .chain(self.description.clone())
+            .flat_map(|text| text_variables(&text))
+            .chain(self.needs.iter().cloned())
+            .collect()
+    }
+
+    fn condition_template(condition: &str) -> String {
+        format!("{{% if {} %}}true{{% else %}}false{{% endif %}}", condition)
+    }
+
+    /// Renders the name and description against the data collected so far, so prompts can
+    /// refer to earlier answers. Text that fails to render is kept as written.
+    pub fn with_rendered_prompts(&self, data: &HashMap<String, String>) -> Slot {
+        let context = context_from_data(data);
+        let render = |text: &Option<String>| {
+            text.as_ref()
+                .map(|text| Tera::one_off(text, &context, false).unwrap_or(text.clone()))
+        };
+
+        Slot {
+            name: render(&self.name),
+            description: render(&self.description),
+            ..self.clone()
+        }
+    }
+
+    /// Checks that the name and description are valid templates
+    pub fn validate_prompts(&self) -> Result<(), Error> {
+        for text in [&self.name, &self.description].into_iter().flatten() {
+            Tera::default()
+                .add_raw_template(&self.key, text)
+                .map_err(|e| Error::InvalidPromptTemplate(self.key.clone(), e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs `default_from_command`, if set, and returns its trimmed output as long as it's a
+    /// valid value for the slot. Commands that outlive the timeout are killed.
+    pub fn default_from_command(&self, timeout: Duration) -> Result<Option<String>, Error> {
+        let Some((program, args)) = self.default_from_command.split_first() else {
+            return Ok(None);
+        };
+        let failed = |reason: String| Error::DefaultCommandFailed(self.key.clone(), reason);
+
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| failed(e.to_string()))?;
+
+        // Read while waiting, since a command filling the pipe would otherwise block forever
+        let stdout = child.stdout.take();
+        let reader = thread::spawn(move || {
+            let mut output = String::new();
+            stdout
+                .map(|mut stdout| stdout.read_to_string(&mut output))
+                .transpose()
+                .map(|_| output)
+        });
+
+        let deadline = Instant::now() + timeout;
+        let status = loop {
+            match child.try_wait().map_err(|e| failed(e.to_string()))? {
+                Some(status) => break status,
+                None if Instant::now() >= deadline => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(failed(format!("timed out after {:?}", timeout)));
+                }
+                None => thread::sleep(Duration::from_millis(10)),
+            }
+        };
+
+        if !status.success() {
+            return Err(failed(status.to_string()));
+        }
+
+        let stdout = reader
+            .join()
+            .map_err(|_| failed("couldn't read its output".to_string()))?
+            .map_err(|e| failed(e.to_string()))?;
+
+        let value = stdout.trim().to_string();
+        self.validate_value(&value)?;
+
+        Ok(Some(value))
+    }
+
+    /// Splits a List slot's value into its elements, ignoring empty ones
+    pub fn list_values(value: &str) -> Vec<&str> {
+        value
+            .split(',')
+            .map(|element| element.trim())
+            .filter(|element| !element.is_empty())
+            .collect()
+    }
+
+    /// Validates a single value against the slot's type.
+    /// This is the single source of truth for what a slot accepts, used for defaults,
+    /// supplied data and interactive prompts alike.
+    pub fn validate_value(&self, value: &str) -> Result<(), Error> {
+        // Any text is a string, path or list, and choices are checked against the slot's below
+        let valid = match self.r#type {
+            SlotType::Number => value.parse::<f64>().is_ok(),
+            SlotType::Boolean => value.parse::<bool>().is_ok(),
+            SlotType::String | SlotType::List | SlotType::Path | SlotType::Choice => true,
+        };
+
+        if !valid {
+            return Err(Error::TypeMismatch(
+                self.key.clone(),
+                self.r#type.to_string().to_lowercase(),
+            ));
+        }
+
+        if let SlotType::Choice = self.r#type {
+            if !self.choices.iter().any(|choice| choice == value) {
+                return Err(Error::InvalidChoice(self.key.clone(), value.to_string()));
+            }
+        }
+
+        if let SlotType::List = self.r#type {
+            if !self.choices.is_empty() {
+                if let Some(element) = Slot::list_values(value)
+                    .into_iter()
+                    .find(|element| !self.choices.iter().any(|choice| choice == element))
+                {
+                    return Err(Error::InvalidChoice(self.key.clone(), element.to_string()));
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 pub fn validate(slots: &Vec<Slot>) -> Result<(), Error> {
     for slot in slots {
+        if matches!(slot.r#type, SlotType::Choice) && slot.choices.is_empty() {
+            return Err(Error::MissingChoices(slot.key.clone()));
+        }
+
         if let Some(default_value) = &slot.default {
-            match slot.r#type {
-                SlotType::String => {
-                    // String always valid, no need to check
-                }
-                SlotType::Number => {
-                    if default_value.parse::<f64>().is_err() {
-                        return Err(Error::TypeMismatch(slot.key.clone(), "number".to_string()));
-                    }
-                }
-                SlotType::Boolean => {
-                    if default_value.parse::<bool>().is_err() {
-                        return Err(Error::TypeMismatch(slot.key.clone(), "boolean".to_string()));
-                    }
-                }
-            }
+            slot.validate_value(default_value)?;
+        }
+
+        if let Some(condition) = &slot.required_if {
+            Tera::default()
+                .add_raw_template(&slot.key, &Slot::condition_template(condition))
+                .map_err(|e| Error::InvalidCondition(slot.key.clone(), e.to_string()))?;
         }
     }
 
     Ok(())
 }
 
-pub fn validate_data(data: &HashMap<String, String>, slots: &Vec<Slot>) -> Result<(), Error> {
-    for entry in data.iter() {
+/// Placeholder values for the slots, e.g. `<name>` for the `name` slot, for showing
+/// templated prompts before any data is collected
+pub fn placeholder_data(slots: &[Slot]) -> HashMap<String, String> {
+    slots
+        .iter()
+        .map(|slot| (slot.key.clone(), format!("<{}>", slot.key)))
+        .collect()
+}
+
+pub fn validate_data(
+    data: &HashMap<String, String>,
+    slots: &Vec<Slot>,
+    exclusive_groups: &[Vec<String>],
+) -> Result<(), Error> {
+    // Sort so the same input always reports the same error
+    let mut entries = data.iter().collect::<Vec<_>>();
+    entries.sort();
+
+    for entry in entries {
         // Check if the data is assigned to a slot
         let slot = match slots.iter().find(|slot| slot.key == *entry.0) {
             Some(slot) => slot,
@@ -132,40 +476,270 @@ pub fn validate_data(data: &HashMap<String, String>, slots: &Vec<Slot>) -> Resul
             }
         };
 
-        // Verify the data type by trying to parse it as the slot type
-        if !match slot.r#type {
-            SlotType::String => entry.1.parse::<String>().is_ok(),
-            SlotType::Number => entry.1.parse::<f64>().is_ok(),
-            SlotType::Boolean => entry.1.parse::<bool>().is_ok(),
-        } {
-            return Err(Error::TypeMismatch(
-                entry.0.clone(),
-                slot.r#type.to_string(),
-            ));
+        if entry.1.is_empty() && is_skipped(slot, data, exclusive_groups)? {
+            continue;
         }
+
+        slot.validate_value(entry.1)?;
     }
 
-    // Ensure all slots are assigned data
+    // Ensure all required slots are assigned data
     for slot in slots.iter() {
-        if !data.iter().any(|data| *data.0 == slot.key) {
+        if !data.iter().any(|data| *data.0 == slot.key) && slot.is_required(data)? {
             return Err(Error::UndefinedSlot(slot.key.clone()));
         }
     }
 
+    // Ensure at most one slot in each exclusive group is set
+    for group in exclusive_groups {
+        let set_count = group
+            .iter()
+            .filter(|key| data.get(*key).is_some_and(|value| !value.is_empty()))
+            .count();
+
+        if set_count > 1 {
+            return Err(Error::ExclusiveViolation(group.clone()));
+        }
+    }
+
     Ok(())
 }
 
+// Whether a slot is left out on purpose, because it isn't required or another slot of its
+// exclusive group is set. Such slots are given empty values, which aren't of their type.
+fn is_skipped(
+    slot: &Slot,
+    data: &HashMap<String, String>,
+    exclusive_groups: &[Vec<String>],
+) -> Result<bool, Error> {
+    let answered_in_group = exclusive_groups
+        .iter()
+        .filter(|group| group.contains(&slot.key))
+        .flatten()
+        .any(|key| *key != slot.key && data.get(key).is_some_and(|value| !value.is_empty()));
+
+    Ok(answered_in_group || !slot.is_required(data)?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn plain_string() {
+        let slot = Slot {
+            key: "name".to_string(),
+            description: Some("The package name".to_string()),
+            ..Default::default()
+        };
+
+        let plain = slot.to_plain_string();
+        assert!(!plain.contains('\u{1b}'), "{:?}", plain);
+        assert_eq!(plain, "name [string] required\nThe package name");
+    }
+
+    #[test]
+    fn needed_by() {
+        let slot = Slot {
+            key: "docker_base_image".to_string(),
+            needed_by: vec!["docker".to_string()],
+            ..Default::default()
+        };
+        let hooks = vec![Hook {
+            key: "docker".to_string(),
+            default: Some(false),
+            ..Default::default()
+        }];
+
+        assert!(!slot.is_needed(&hooks, &HashMap::new()));
+        assert!(!slot.is_needed(
+            &hooks,
+            &HashMap::from([("docker".to_string(), "false".to_string())])
+        ));
+        assert!(slot.is_needed(
+            &hooks,
+            &HashMap::from([("docker".to_string(), "true".to_string())])
+        ));
+
+        // Slots not tied to hooks are always needed
+        assert!(Slot::default().is_needed(&hooks, &HashMap::new()));
+    }
+
+    #[test]
+    fn deterministic_errors() {
+        let slots = vec![
+            Slot {
+                key: "a".to_string(),
+                r#type: SlotType::Number,
+                ..Default::default()
+            },
+            Slot {
+                key: "b".to_string(),
+                r#type: SlotType::Boolean,
+                ..Default::default()
+            },
+        ];
+
+        let data = HashMap::from([
+            ("z".to_string(), "".to_string()),
+            ("b".to_string(), "not a bool".to_string()),
+            ("a".to_string(), "not a number".to_string()),
+            ("c".to_string(), "".to_string()),
+        ]);
+
+        for _ in 0..20 {
+            // Rebuild the map each time so its iteration order can vary
+            let data = data.clone().into_iter().collect::<HashMap<_, _>>();
+
+            assert!(matches!(
+                validate_data(&data, &slots, &[]),
+                Err(Error::TypeMismatch(key, _)) if key == "a"
+            ));
+        }
+    }
+
+    #[test]
+    fn default_from_command() {
+        let slot = |command: &[&str]| Slot {
+            key: "author".to_string(),
+            default_from_command: command.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            slot(&["echo", "  Jane Doe  "])
+                .default_from_command(Duration::from_secs(5))
+                .unwrap(),
+            Some("Jane Doe".to_string())
+        );
+        assert_eq!(
+            slot(&[])
+                .default_from_command(Duration::from_secs(5))
+                .unwrap(),
+            None
+        );
+
+        // More output than fits in a pipe's buffer
+        assert_eq!(
+            slot(&["sh", "-c", "head -c 100000 /dev/zero | tr '\\0' a"])
+                .default_from_command(Duration::from_secs(5))
+                .unwrap()
+                .map(|value| value.len()),
+            Some(100000)
+        );
+
+        for command in [
+            &["false"][..],
+            &["spackle-missing-command"],
+            &["sleep", "5"],
+        ] {
+            assert!(matches!(
+                slot(command).default_from_command(Duration::from_millis(200)),
+                Err(Error::DefaultCommandFailed(key, _)) if key == "author"
+            ));
+        }
+
+        // Output still has to be a valid value for the slot
+        let number = Slot {
+            r#type: SlotType::Number,
+            ..slot(&["echo", "not a number"])
+        };
+        assert!(matches!(
+            number.default_from_command(Duration::from_secs(5)),
+            Err(Error::TypeMismatch(..))
+        ));
+    }
+
+    #[test]
+    fn rendered_prompts() {
+        let slot = Slot {
+            key: "port".to_string(),
+            description: Some("Port for {{ service_name }} to listen on".to_string()),
+            ..Default::default()
+        };
+
+        let rendered = slot.with_rendered_prompts(&HashMap::from([(
+            "service_name".to_string(),
+            "api".to_string(),
+        )]));
+        assert_eq!(
+            rendered.description.as_deref(),
+            Some("Port for api to listen on")
+        );
+        assert_eq!(rendered.name, None);
+
+        // Falls back to the raw text when the data isn't there yet
+        let unrendered = slot.with_rendered_prompts(&HashMap::new());
+        assert_eq!(unrendered.description, slot.description);
+
+        assert!(slot.validate_prompts().is_ok());
+        let invalid = Slot {
+            name: Some("{{ unclosed".to_string()),
+            ..slot
+        };
+        assert!(matches!(
+            invalid.validate_prompts(),
+            Err(Error::InvalidPromptTemplate(key, _)) if key == "port"
+        ));
+    }
+
+    #[test]
+    fn required_if() {
+        let slots = vec![
+            Slot {
+                key: "cloud".to_string(),
+                ..Default::default()
+            },
+            Slot {
+                key: "region".to_string(),
+                required_if: Some("cloud == \"aws\"".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        let data = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+
+        assert!(matches!(
+            validate_data(&data(&[("cloud", "aws")]), &slots, &[]),
+            Err(Error::UndefinedSlot(key)) if key == "region"
+        ));
+        assert!(validate_data(
+            &data(&[("cloud", "aws"), ("region", "us-east-1")]),
+            &slots,
+            &[]
+        )
+        .is_ok());
+        assert!(validate_data(&data(&[("cloud", "gcp")]), &slots, &[]).is_ok());
+
+        // The condition can't be evaluated without the slots it refers to
+        assert!(matches!(
+            slots[1].is_required(&HashMap::new()),
+            Err(Error::InvalidCondition(key, _)) if key == "region"
+        ));
+
+        let invalid = vec![Slot {
+            key: "region".to_string(),
+            required_if: Some("cloud ==".to_string()),
+            ..Default::default()
+        }];
+        assert!(matches!(
+            validate(&invalid),
+            Err(Error::InvalidCondition(..))
+        ));
+    }
+
     #[test]
     fn empty() {
         let slots = vec![];
 
         let data = HashMap::new();
 
-        assert!(validate_data(&data, &slots).is_ok());
+        assert!(validate_data(&data, &slots, &[]).is_ok());
     }
 
     #[test]
@@ -186,7 +760,7 @@ mod tests {
             .map(|(k, v)| (k.to_string(), v.to_string()))
             .collect::<HashMap<String, String>>();
 
-        assert!(validate_data(&data, &slots).is_ok());
+        assert!(validate_data(&data, &slots, &[]).is_ok());
     }
 
     #[test]
@@ -207,7 +781,7 @@ mod tests {
             .map(|(k, v)| (k.to_string(), v.to_string()))
             .collect::<HashMap<String, String>>();
 
-        assert!(validate_data(&data, &slots).is_err());
+        assert!(validate_data(&data, &slots, &[]).is_err());
     }
 
     #[test]
@@ -222,7 +796,7 @@ mod tests {
             .map(|(k, v)| (k.to_string(), v.to_string()))
             .collect::<HashMap<String, String>>();
 
-        assert!(validate_data(&data, &slots).is_err());
+        assert!(validate_data(&data, &slots, &[]).is_err());
     }
 
     #[test]
@@ -245,7 +819,232 @@ mod tests {
             .map(|(k, v)| (k.to_string(), v.to_string()))
             .collect::<HashMap<String, String>>();
 
-        assert!(validate_data(&data, &slots).is_ok());
+        assert!(validate_data(&data, &slots, &[]).is_ok());
+    }
+
+    #[test]
+    fn list_choices() {
+        let slot = Slot {
+            key: "languages".to_string(),
+            r#type: SlotType::List,
+            choices: vec!["rust".to_string(), "python".to_string(), "r".to_string()],
+            ..Default::default()
+        };
+
+        assert!(slot.validate_value("").is_ok());
+        assert!(slot.validate_value("rust").is_ok());
+        assert!(slot.validate_value("rust, python").is_ok());
+        assert!(matches!(
+            slot.validate_value("rust,go"),
+            Err(Error::InvalidChoice(_, value)) if value == "go"
+        ));
+
+        // Free-form lists accept anything
+        let slot = Slot {
+            choices: vec![],
+            ..slot
+        };
+        assert!(slot.validate_value("rust,go").is_ok());
+
+        assert_eq!(Slot::list_values(" a, ,b,"), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn choice() {
+        let slot = Slot {
+            key: "license".to_string(),
+            r#type: SlotType::Choice,
+            choices: vec!["MIT".to_string(), "GPL-3".to_string()],
+            default: Some("MIT".to_string()),
+            ..Default::default()
+        };
+
+        assert!(slot.validate_value("GPL-3").is_ok());
+        // Exactly one choice, unlike a List
+        for value in ["", "mit", "MIT,GPL-3"] {
+            assert!(matches!(
+                slot.validate_value(value),
+                Err(Error::InvalidChoice(_, v)) if v == value
+            ));
+        }
+        assert!(validate(&vec![slot.clone()]).is_ok());
+
+        let bad_default = Slot {
+            default: Some("Apache-2.0".to_string()),
+            ..slot.clone()
+        };
+        assert!(matches!(
+            validate(&vec![bad_default]),
+            Err(Error::InvalidChoice(..))
+        ));
+
+        // Serializes as it's written in a config, for consumers that read slots as JSON
+        let json = serde_json::to_value(&slot).unwrap();
+        assert_eq!(json["type"], "Choice");
+        assert_eq!(json["choices"], serde_json::json!(["MIT", "GPL-3"]));
+        let parsed = serde_json::from_value::<Slot>(json).unwrap();
+        assert!(matches!(parsed.r#type, SlotType::Choice));
+        assert_eq!(parsed.choices, slot.choices);
+
+        let options = toml::from_str::<Slot>(
+            "key = \"license\"\ntype = \"Choice\"\noptions = [\"MIT\", \"GPL-3\"]",
+        )
+        .unwrap();
+        assert_eq!(options.choices, slot.choices);
+        assert!(matches!(options.r#type, SlotType::Choice));
+
+        let lowercase =
+            toml::from_str::<Slot>("key = \"license\"\ntype = \"choice\"\nchoices = [\"MIT\"]")
+                .unwrap();
+        assert!(matches!(lowercase.r#type, SlotType::Choice));
+
+        let no_choices = Slot {
+            choices: vec![],
+            default: None,
+            ..slot
+        };
+        assert!(matches!(
+            validate(&vec![no_choices]),
+            Err(Error::MissingChoices(key)) if key == "license"
+        ));
+    }
+
+    #[test]
+    fn validate_value() {
+        let cases = [
+            (SlotType::String, "anything", true),
+            (SlotType::String, "", true),
+            (SlotType::Number, "42", true),
+            (SlotType::Number, "3.14", true),
+            (SlotType::Number, "-1", true),
+            (SlotType::Number, "", false),
+            (SlotType::Number, "forty two", false),
+            (SlotType::Boolean, "true", true),
+            (SlotType::Boolean, "false", true),
+            (SlotType::Boolean, "True", false),
+            (SlotType::Boolean, "yes", false),
+            (SlotType::Boolean, "", false),
+        ];
+
+        for (r#type, value, valid) in cases {
+            let slot = Slot {
+                key: "key".to_string(),
+                r#type: r#type.clone(),
+                ..Default::default()
+            };
+
+            assert_eq!(
+                slot.validate_value(value).is_ok(),
+                valid,
+                "expected {:?} to be {} for {} slot",
+                value,
+                if valid { "valid" } else { "invalid" },
+                r#type
+            );
+
+            // Defaults and supplied data must agree with validate_value
+            let default_slot = Slot {
+                default: Some(value.to_string()),
+                ..slot.clone()
+            };
+            assert_eq!(validate(&vec![default_slot]).is_ok(), valid);
+
+            let data = HashMap::from([("key".to_string(), value.to_string())]);
+            assert_eq!(validate_data(&data, &vec![slot], &[]).is_ok(), valid);
+        }
+    }
+
+    #[test]
+    fn exclusive_groups() {
+        let slots = vec![
+            Slot {
+                key: "oauth_token".to_string(),
+                ..Default::default()
+            },
+            Slot {
+                key: "api_key".to_string(),
+                ..Default::default()
+            },
+        ];
+        let groups = vec![vec!["oauth_token".to_string(), "api_key".to_string()]];
+
+        let data = |oauth_token: &str, api_key: &str| {
+            HashMap::from([
+                ("oauth_token".to_string(), oauth_token.to_string()),
+                ("api_key".to_string(), api_key.to_string()),
+            ])
+        };
+
+        assert!(validate_data(&data("", ""), &slots, &groups).is_ok());
+        assert!(validate_data(&data("token", ""), &slots, &groups).is_ok());
+        assert!(matches!(
+            validate_data(&data("token", "key"), &slots, &groups),
+            Err(Error::ExclusiveViolation(_))
+        ));
+    }
+
+    #[test]
+    fn skipped_slots() {
+        let slots = vec![
+            Slot {
+                key: "cloud".to_string(),
+                ..Default::default()
+            },
+            Slot {
+                key: "port".to_string(),
+                r#type: SlotType::Number,
+                required_if: Some("cloud == \"aws\"".to_string()),
+                ..Default::default()
+            },
+            Slot {
+                key: "tier".to_string(),
+                r#type: SlotType::Choice,
+                choices: vec!["free".to_string(), "paid".to_string()],
+                ..Default::default()
+            },
+            Slot {
+                key: "seats".to_string(),
+                r#type: SlotType::Number,
+                ..Default::default()
+            },
+        ];
+        let groups = vec![vec!["tier".to_string(), "seats".to_string()]];
+
+        let data = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+
+        // Empty values stand for slots left out on purpose
+        assert!(validate_data(
+            &data(&[
+                ("cloud", "gcp"),
+                ("port", ""),
+                ("tier", "free"),
+                ("seats", "")
+            ]),
+            &slots,
+            &groups
+        )
+        .is_ok());
+        assert!(validate_data(
+            &data(&[("cloud", "gcp"), ("port", ""), ("tier", ""), ("seats", "3")]),
+            &slots,
+            &groups
+        )
+        .is_ok());
+
+        // But not for slots that need a value
+        assert!(matches!(
+            validate_data(
+                &data(&[("cloud", "aws"), ("port", ""), ("tier", "free"), ("seats", "")]),
+                &slots,
+                &groups
+            ),
+            Err(Error::TypeMismatch(key, _)) if key == "port"
+        ));
     }
 
     #[test]
@@ -261,6 +1060,6 @@ mod tests {
             .map(|(k, v)| (k.to_string(), v.to_string()))
             .collect::<HashMap<String, String>>();
 
-        assert!(validate_data(&data, &slots).is_err());
+        assert!(validate_data(&data, &slots, &[]).is_err());
     }
 }
@@ -1,16 +1,36 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     fmt::{Debug, Display},
-    fs, io,
+    fs,
+    io::{self, Write},
     path::{Path, PathBuf},
-    time::Duration,
+    time::{Duration, Instant},
+};
+use tera::{
+    ast::{Expr, ExprVal, LogicOperator, Node},
+    Context, Map, Tera, Value,
 };
-use tera::{Context, Tera};
 use thiserror::Error;
+use walkdir::WalkDir;
 
-use super::slot::Slot;
+use super::{
+    classify::{Classifier, FileClass},
+    config::{Normalize, CONFIG_FILE},
+    hook::Hook,
+    paths::is_within,
+    slot::{Slot, SlotType},
+    GenerateOptions, RESERVED_KEYS,
+};
 
 pub const TEMPLATE_EXT: &str = ".j2";
+/// Templates ending in `.b64.j2` render to base64, which is decoded and written as raw bytes
+pub const BASE64_EXT: &str = ".b64";
+/// The key [GenerateOptions::env] is added to the context under
+pub const ENV_KEY: &str = "env";
+/// The filter that makes a value safe to use as a file name, see [path_safe]
+pub const PATH_SAFE_FILTER: &str = "path_safe";
 
 #[derive(Error, Debug)]
 pub struct FileError {
@@ -24,6 +44,13 @@ impl Display for FileError {
     }
 }
 
+impl FileError {
+    /// A stable identifier for the kind of error, see [FileErrorKind::code]
+    pub fn code(&self) -> &'static str {
+        self.kind.code()
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum FileErrorKind {
     #[error("Error rendering contents: {0}")]
@@ -34,62 +61,772 @@ pub enum FileErrorKind {
     ErrorCreatingDest(io::ErrorKind),
     #[error("Error writing to destination: {0}")]
     ErrorWritingToDest(io::Error),
+    #[error("Error verifying destination: {0}")]
+    ErrorVerifyingDest(io::Error),
+    #[error("Destination contents do not match the rendered output")]
+    VerifyMismatch,
+    #[error("Rendered file exceeds the maximum size of {0} bytes")]
+    FileTooLarge(usize),
+    #[error("Rendered files exceed the maximum total size of {0} bytes")]
+    TotalTooLarge(usize),
+    #[error("Rendering exceeded the time budget of {0:?}")]
+    RenderTimedOut(Duration),
+    #[error("Destination is outside the output directory")]
+    EscapesOutputDir,
+    #[error("Rendered contents are not valid base64: {0}")]
+    InvalidBase64(base64::DecodeError),
+    #[error("Rendered name is empty")]
+    EmptyName,
+}
+
+impl FileErrorKind {
+    /// Every code returned by [FileErrorKind::code]
+    pub const CODES: &'static [&'static str] = &[
+        "template.render_contents_failed",
+        "template.render_name_failed",
+        "template.create_dest_failed",
+        "template.write_dest_failed",
+        "template.verify_dest_failed",
+        "template.verify_mismatch",
+        "template.file_too_large",
+        "template.total_too_large",
+        "template.render_timed_out",
+        "template.escapes_output_dir",
+        "template.invalid_base64",
+        "template.empty_name",
+    ];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            FileErrorKind::ErrorRenderingContents(_) => "template.render_contents_failed",
+            FileErrorKind::ErrorRenderingName(_) => "template.render_name_failed",
+            FileErrorKind::ErrorCreatingDest(_) => "template.create_dest_failed",
+            FileErrorKind::ErrorWritingToDest(_) => "template.write_dest_failed",
+            FileErrorKind::ErrorVerifyingDest(_) => "template.verify_dest_failed",
+            FileErrorKind::VerifyMismatch => "template.verify_mismatch",
+            FileErrorKind::FileTooLarge(_) => "template.file_too_large",
+            FileErrorKind::TotalTooLarge(_) => "template.total_too_large",
+            FileErrorKind::RenderTimedOut(_) => "template.render_timed_out",
+            FileErrorKind::EscapesOutputDir => "template.escapes_output_dir",
+            FileErrorKind::InvalidBase64(_) => "template.invalid_base64",
+            FileErrorKind::EmptyName => "template.empty_name",
+        }
+    }
+}
+
+// Collects rendered output, failing the render as soon as a size or time budget is exceeded
+// so a pathological template can't use unbounded memory or time
+struct BudgetWriter<'a> {
+    buf: Vec<u8>,
+    total: &'a mut usize,
+    options: &'a GenerateOptions,
+    deadline: Option<Instant>,
+    exceeded: Option<FileErrorKind>,
+}
+
+impl BudgetWriter<'_> {
+    fn check(&self, len: usize) -> Option<FileErrorKind> {
+        if let (Some(deadline), Some(timeout)) = (self.deadline, self.options.render_timeout) {
+            if Instant::now() > deadline {
+                return Some(FileErrorKind::RenderTimedOut(timeout));
+            }
+        }
+
+        if let Some(max) = self.options.max_file_bytes {
+            if self.buf.len() + len > max {
+                return Some(FileErrorKind::FileTooLarge(max));
+            }
+        }
+
+        if let Some(max) = self.options.max_total_bytes {
+            if *self.total + len > max {
+                return Some(FileErrorKind::TotalTooLarge(max));
+            }
+        }
+
+        None
+    }
+}
+
+impl Write for BudgetWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(kind) = self.check(buf.len()) {
+            let message = kind.to_string();
+            self.exceeded = Some(kind);
+            return Err(io::Error::other(message));
+        }
+
+        self.buf.extend_from_slice(buf);
+        *self.total += buf.len();
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Builds a render context from flat data, expanding dotted keys (`db.host`) into
+/// nested objects so templates can access them as `{{ db.host }}`.
+/// Keys are inserted shortest path first, so a plain `db` value wins over `db.host`.
+pub fn context_from_data(data: &HashMap<String, String>) -> Context {
+    context_with_values(data, |_, value| Value::String(value.to_string()))
+}
+
+/// Builds a context like [context_from_data], but with slot values converted to their type, so
+/// numbers compare as numbers and booleans are real booleans. Values that don't parse as their
+/// slot's type, and data that isn't for a slot, are left as strings.
+pub fn typed_context_from_data(data: &HashMap<String, String>, slots: &[Slot]) -> Context {
+    context_with_values(data, |key, value| {
+        let r#type = slots
+            .iter()
+            .find(|slot| slot.key == key)
+            .map(|slot| &slot.r#type);
+
+        match r#type {
+            Some(SlotType::Number) => value
+                .parse::<i64>()
+                .map(Value::from)
+                .or_else(|_| value.parse::<f64>().map(Value::from))
+                .unwrap_or(Value::String(value.to_string())),
+            Some(SlotType::Boolean) => value
+                .parse::<bool>()
+                .map(Value::Bool)
+                .unwrap_or(Value::String(value.to_string())),
+            _ => Value::String(value.to_string()),
+        }
+    })
+}
+
+/// Adds the project's slot and hook definitions to a context as `_slots` and `_hooks`, for
+/// templates that document the project's configuration. Only templates see them, never hook
+/// commands.
+pub fn insert_definitions(context: &mut Context, slots: &[Slot], hooks: &[Hook]) {
+    let slots = slots
+        .iter()
+        .map(|slot| {
+            serde_json::json!({
+                "key": slot.key,
+                "type": slot.r#type.to_string().to_lowercase(),
+                "name": slot.get_name(),
+                "description": slot.description,
+                "default": slot.default,
+                // Whether a value has to be given, as shown by `info`
+                "required": slot.is_always_required(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let hooks = hooks
+        .iter()
+        .map(|hook| {
+            serde_json::json!({
+                "key": hook.key,
+                "name": hook.name.clone().unwrap_or(hook.key.clone()),
+                "description": hook.description,
+                "default": hook.default.unwrap_or(true),
+                "command": hook.command,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    context.insert("_slots", &slots);
+    context.insert("_hooks", &hooks);
+}
+
+fn context_with_values(
+    data: &HashMap<String, String>,
+    to_value: impl Fn(&str, &str) -> Value,
+) -> Context {
+    let mut keys = data.keys().collect::<Vec<_>>();
+    keys.sort_by_key(|key| (key.matches('.').count(), *key));
+
+    let mut root = Map::new();
+    for key in keys {
+        insert_nested(&mut root, key, to_value(key, &data[key]));
+    }
+
+    let mut context = Context::new();
+    for (key, value) in root {
+        context.insert(key, &value);
+    }
+
+    context
+}
+
+fn insert_nested(map: &mut Map<String, Value>, key: &str, value: Value) {
+    match key.split_once('.') {
+        Some((head, rest)) => {
+            let entry = map.entry(head).or_insert_with(|| Value::Object(Map::new()));
+
+            if let Value::Object(nested) = entry {
+                insert_nested(nested, rest, value);
+            }
+        }
+        None => {
+            map.entry(key).or_insert(value);
+        }
+    }
+}
+
+// Defines every variable the template reads that the context doesn't have as an empty string,
+// so it renders like it would with a lenient undefined mode, which Tera doesn't have
+fn with_undefined_as_empty(tera: &Tera, template_name: &str, context: &Context) -> Context {
+    with_empty_variables(tera, template_name, context, |_| true)
+}
+
+// Like with_undefined_as_empty, but only for the variables the filter accepts
+fn with_empty_variables(
+    tera: &Tera,
+    template_name: &str,
+    context: &Context,
+    filter: impl Fn(&str) -> bool,
+) -> Context {
+    let mut root = match context.clone().into_json() {
+        Value::Object(root) => root,
+        _ => Map::new(),
+    };
+
+    let mut variables = Vec::new();
+    template_variables(
+        tera,
+        template_name,
+        Reads::Unguarded,
+        &mut Vec::new(),
+        &mut variables,
+    );
+    for variable in variables {
+        // Indexed access can't be defined without knowing the type of the container
+        if !variable.contains('[') && filter(&variable) {
+            insert_nested(&mut root, &variable, Value::String(String::new()));
+        }
+    }
+
+    Context::from_value(Value::Object(root)).unwrap_or_else(|_| context.clone())
+}
+
+// Which reads of a variable the collectors below count
+#[derive(Clone, Copy, PartialEq)]
+enum Reads {
+    // Every read, for knowing what a template depends on
+    All,
+    // Only reads that need the variable defined, leaving out those guarded by a `default`
+    // filter or an `is defined` test
+    Unguarded,
+}
+
+// Collects the variables read by a template, the templates it extends and those it includes.
+// Macro bodies are skipped since they only see their arguments.
+fn template_variables(
+    tera: &Tera,
+    template_name: &str,
+    reads: Reads,
+    visited: &mut Vec<String>,
+    variables: &mut Vec<String>,
+) {
+    if visited.iter().any(|name| name == template_name) {
+        return;
+    }
+    visited.push(template_name.to_string());
+
+    let Ok(template) = tera.get_template(template_name) else {
+        return;
+    };
+
+    for parent in template.parents.clone() {
+        template_variables(tera, &parent, reads, visited, variables);
+    }
+
+    let mut included = Vec::new();
+    node_variables(&template.ast, reads, variables, &mut included);
+    for name in included {
+        template_variables(tera, &name, reads, visited, variables);
+    }
+}
+
+fn node_variables(
+    nodes: &[Node],
+    reads: Reads,
+    variables: &mut Vec<String>,
+    included: &mut Vec<String>,
+) {
+    for node in nodes {
+        match node {
+            Node::VariableBlock(_, expr) => expr_variables(expr, reads, variables),
+            Node::Set(_, set) => expr_variables(&set.value, reads, variables),
+            Node::FilterSection(_, section, _) => {
+                section
+                    .filter
+                    .args
+                    .values()
+                    .for_each(|arg| expr_variables(arg, reads, variables));
+                node_variables(&section.body, reads, variables, included);
+            }
+            Node::Block(_, block, _) => node_variables(&block.body, reads, variables, included),
+            Node::Forloop(_, forloop, _) => {
+                expr_variables(&forloop.container, reads, variables);
+                node_variables(&forloop.body, reads, variables, included);
+                if let Some(body) = &forloop.empty_body {
+                    node_variables(body, reads, variables, included);
+                }
+            }
+            Node::If(r#if, _) => {
+                for (_, condition, body) in &r#if.conditions {
+                    expr_variables(condition, reads, variables);
+                    node_variables(body, reads, variables, included);
+                }
+                if let Some((_, body)) = &r#if.otherwise {
+                    node_variables(body, reads, variables, included);
+                }
+            }
+            Node::Include(_, names, _) => included.extend(names.iter().cloned()),
+            _ => {}
+        }
+    }
+}
+
+fn expr_variables(expr: &Expr, reads: Reads, variables: &mut Vec<String>) {
+    for filter in &expr.filters {
+        filter
+            .args
+            .values()
+            .for_each(|arg| expr_variables(arg, reads, variables));
+    }
+
+    match &expr.val {
+        // A variable given a default is expected to be undefined at times
+        ExprVal::Ident(ident)
+            if reads == Reads::All
+                || expr
+                    .filters
+                    .first()
+                    .is_none_or(|filter| filter.name != "default") =>
+        {
+            variables.push(ident.clone())
+        }
+        ExprVal::Math(math) => {
+            expr_variables(&math.lhs, reads, variables);
+            expr_variables(&math.rhs, reads, variables);
+        }
+        ExprVal::Logic(logic) => {
+            expr_variables(&logic.lhs, reads, variables);
+            expr_variables(&logic.rhs, reads, variables);
+        }
+        // `is defined` and `is undefined` need to see variables as they are
+        ExprVal::Test(test) => {
+            if reads == Reads::All || (test.name != "defined" && test.name != "undefined") {
+                variables.push(test.ident.clone());
+            }
+            test.args
+                .iter()
+                .for_each(|arg| expr_variables(arg, reads, variables));
+        }
+        ExprVal::MacroCall(call) => call
+            .args
+            .values()
+            .for_each(|arg| expr_variables(arg, reads, variables)),
+        ExprVal::FunctionCall(call) => call
+            .args
+            .values()
+            .for_each(|arg| expr_variables(arg, reads, variables)),
+        ExprVal::Array(values) => values
+            .iter()
+            .for_each(|value| expr_variables(value, reads, variables)),
+        ExprVal::StringConcat(concat) => {
+            for value in &concat.values {
+                if let ExprVal::Ident(ident) = value {
+                    variables.push(ident.clone());
+                }
+            }
+        }
+        ExprVal::In(r#in) => {
+            expr_variables(&r#in.lhs, reads, variables);
+            expr_variables(&r#in.rhs, reads, variables);
+        }
+        _ => {}
+    }
+}
+
+// Every variable read by text rendered as a template, like a hook command or a file name.
+// Text that doesn't parse reads nothing.
+pub(crate) fn text_variables(text: &str) -> Vec<String> {
+    let mut tera = Tera::default();
+    if tera.add_raw_template(text, text).is_err() {
+        return Vec::new();
+    }
+
+    let mut variables = Vec::new();
+    template_variables(&tera, text, Reads::All, &mut Vec::new(), &mut variables);
+
+    variables
+}
+
+/// Every variable read by the project's templates, in their contents and names, and by the
+/// names of the files copied alongside them. Reads guarded by a `default` filter or an
+/// `is defined` test count too.
+pub fn referenced_variables(
+    project_dir: &Path,
+    inline: &[InlineTemplate],
+) -> Result<Vec<String>, tera::Error> {
+    // Ignore globs can depend on the data, so every template is considered
+    let (tera, templates) = load_templates(project_dir, inline, &[], None)?;
+
+    let mut variables = Vec::new();
+    for (template_name, dest) in &templates {
+        template_variables(
+            &tera,
+            template_name,
+            Reads::All,
+            &mut Vec::new(),
+            &mut variables,
+        );
+        variables.extend(text_variables(dest));
+    }
+
+    for entry in WalkDir::new(project_dir).into_iter().filter_map(|e| e.ok()) {
+        let Ok(path) = entry.path().strip_prefix(project_dir) else {
+            continue;
+        };
+
+        let path = path.to_string_lossy();
+        if path.contains('{') {
+            variables.extend(text_variables(&path));
+        }
+    }
+
+    Ok(variables)
+}
+
+/// Whether any of the variables reads the key, either directly, through one of its fields or
+/// through an object it's nested in, e.g. `db` for the key `db.host`
+pub fn reads_key(variables: &[String], key: &str) -> bool {
+    variables.iter().any(|variable| {
+        let variable = variable.split('[').next().unwrap_or_default();
+
+        variable == key
+            || variable
+                .strip_prefix(key)
+                .is_some_and(|rest| rest.starts_with('.'))
+            || key
+                .strip_prefix(variable)
+                .is_some_and(|rest| rest.starts_with('.'))
+    })
 }
 
 #[derive(Debug, Clone)]
 pub struct RenderedFile {
     pub path: PathBuf,
+    /// The rendered contents as written, which for base64 templates is the text before decoding
     pub contents: String,
     pub elapsed: Duration,
+    /// Whether whitespace normalization changed the contents
+    pub normalized: bool,
+}
+
+/// A template defined inline in the config rather than as a `.j2` file
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct InlineTemplate {
+    /// The destination path relative to the output directory, which may be templated
+    pub dest: Option<String>,
+    pub content: Option<String>,
+}
+
+/// Makes a value safe to use as a single component of a path. Separators and control
+/// characters, newlines included, become `_`, as does a value made only of dots, which would
+/// otherwise refer to the current or parent directory.
+pub fn path_safe(value: &str) -> String {
+    let safe = value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+
+    if !safe.is_empty() && safe.chars().all(|c| c == '.') {
+        return "_".repeat(safe.len());
+    }
+
+    safe
+}
+
+/// Registers spackle's own filters, like [PATH_SAFE_FILTER], with Tera
+pub fn register_filters(tera: &mut Tera) {
+    tera.register_filter(
+        PATH_SAFE_FILTER,
+        |value: &Value, _: &HashMap<String, Value>| {
+            Ok(Value::String(path_safe(
+                value
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or(value.to_string())
+                    .as_str(),
+            )))
+        },
+    );
+}
+
+/// Renders a path or name that may reference slots, with spackle's filters available
+pub fn render_path(path: &str, context: &Context) -> Result<String, tera::Error> {
+    let mut tera = Tera::default();
+    register_filters(&mut tera);
+
+    tera.render_str(path, context)
+}
+
+// Loads the project's template files, other than ignored ones, along with its inline templates.
+// Each template is paired with the name its destination is rendered from, which for inline
+// templates is their dest with the template extension so they're treated like any other template.
+// Given the output directory, anything under it is left out when it's inside the project.
+fn load_templates(
+    project_dir: &Path,
+    inline: &[InlineTemplate],
+    ignore: &[String],
+    out_dir: Option<&Path>,
+) -> Result<(Tera, Vec<(String, String)>), tera::Error> {
+    let files = Classifier::new(project_dir, ignore)
+        .map(|classifier| match out_dir {
+            Some(out_dir) => classifier.excluding(project_dir, out_dir),
+            None => classifier,
+        })
+        .and_then(|classifier| classifier.entries(project_dir))
+        .map_err(|e| tera::Error::chain("Error finding templates", e))?
+        .into_iter()
+        .filter(|entry| entry.class == FileClass::Template)
+        .map(|entry| {
+            let name = entry.path.to_string_lossy().replace('\\', "/");
+            (project_dir.join(&entry.path), Some(name))
+        })
+        .collect::<Vec<_>>();
+
+    let mut tera = Tera::default();
+    register_filters(&mut tera);
+    tera.add_template_files(files)?;
+
+    let mut templates = tera
+        .get_template_names()
+        .map(|name| (name.to_string(), name.to_string()))
+        .collect::<Vec<_>>();
+
+    for template in inline {
+        let dest = template.dest.clone().unwrap_or_default();
+        let name = format!("{}[{}]", CONFIG_FILE, dest);
+
+        tera.add_raw_template(&name, template.content.as_deref().unwrap_or_default())?;
+        templates.push((name, dest + TEMPLATE_EXT));
+    }
+
+    Ok((tera, templates))
+}
+
+// Strips the template extension from a template's name, along with the base64 one for base64
+// templates, then renders what's left, giving its path relative to the output directory.
+// Stripping comes first so that only the source's own extension is removed, never one a slot
+// value happens to end with.
+fn output_name(tera: &Tera, template_name: &str, context: &Context) -> Result<String, tera::Error> {
+    let Some(stem) = template_name.strip_suffix(TEMPLATE_EXT) else {
+        return Ok(template_name.to_string());
+    };
+    let stem = match is_base64(template_name) {
+        true => stem.strip_suffix(BASE64_EXT).unwrap_or(stem),
+        false => stem,
+    };
+
+    tera.clone().render_str(stem, context)
+}
+
+// Whether a rendered name leaves nothing to name the file, e.g. a template named `.j2` or
+// one whose name renders to an empty string
+fn is_empty_name(name: &str) -> bool {
+    name.rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .is_empty()
+}
+
+fn is_base64(template_name: &str) -> bool {
+    template_name.ends_with(&(BASE64_EXT.to_owned() + TEMPLATE_EXT))
+}
+
+/// Resolves the destination of every template that would be rendered, without rendering anything.
+/// Templates matched by the `ignore` globs aren't rendered, as with [crate::copy::copy].
+pub fn destinations(
+    project_dir: &Path,
+    inline: &[InlineTemplate],
+    ignore: &[String],
+    out_dir: &Path,
+    data: &HashMap<String, String>,
+) -> Result<Vec<PathBuf>, tera::Error> {
+    let (tera, templates) = load_templates(project_dir, inline, ignore, Some(out_dir))?;
+    let context = context_from_data(data);
+
+    templates
+        .iter()
+        .map(|(_, dest)| output_name(&tera, dest, &context).map(|name| out_dir.join(name)))
+        .collect()
+}
+
+/// Applies whitespace normalization to rendered text. Line endings are kept as they are.
+pub fn normalize(text: &str, normalize: Normalize) -> String {
+    let mut normalized = if normalize.trim_trailing_whitespace {
+        text.split_inclusive('\n')
+            .map(|line| {
+                let content = line.trim_end_matches(['\n', '\r']);
+                let ending = &line[content.len()..];
+                format!("{}{}", content.trim_end_matches([' ', '\t']), ending)
+            })
+            .collect()
+    } else {
+        text.to_string()
+    };
+
+    if normalize.ensure_final_newline && !normalized.is_empty() && !normalized.ends_with('\n') {
+        normalized.push('\n');
+    }
+
+    normalized
 }
 
+/// Renders the project's templates to the output directory, other than those matched by the
+/// `ignore` globs
 pub fn fill(
     project_dir: &Path,
+    inline: &[InlineTemplate],
+    ignore: &[String],
     out_dir: &Path,
     data: &HashMap<String, String>,
+    options: &GenerateOptions,
+) -> Result<Vec<Result<RenderedFile, FileError>>, tera::Error> {
+    fill_with_context(
+        project_dir,
+        inline,
+        ignore,
+        out_dir,
+        &context_from_data(data),
+        options,
+    )
+}
+
+/// Renders the templates like [fill], but with a context built by the caller, so templates can
+/// use values that don't fit in string slots, like nested objects and arrays. The context isn't
+/// validated against the project's slots.
+pub fn fill_with_context(
+    project_dir: &Path,
+    inline: &[InlineTemplate],
+    ignore: &[String],
+    out_dir: &Path,
+    context: &Context,
+    options: &GenerateOptions,
 ) -> Result<Vec<Result<RenderedFile, FileError>>, tera::Error> {
-    let glob = project_dir.join("**").join("*".to_owned() + TEMPLATE_EXT);
+    let (tera, templates) = load_templates(project_dir, inline, ignore, Some(out_dir))?;
+
+    let mut context = context.clone();
+    if let Some(env) = &options.env {
+        context.insert(ENV_KEY, env);
+    }
+    let context = &context;
 
-    let tera = Tera::new(&glob.to_string_lossy())?;
-    let context = Context::from_serialize(data)?;
+    let deadline = options
+        .render_timeout
+        .map(|timeout| Instant::now() + timeout);
+    let mut total_bytes = 0;
 
-    let template_names = tera.get_template_names().collect::<Vec<_>>();
-    let rendered_templates = template_names.iter().map(|template_name| {
-        let start_time = std::time::Instant::now();
+    let rendered_templates = templates.iter().map(|(template_name, dest)| {
+        let start_time = Instant::now();
 
         // Render the file contents
-        let output = match tera.render(template_name, &context) {
-            Ok(o) => o,
+        let mut writer = BudgetWriter {
+            buf: Vec::new(),
+            total: &mut total_bytes,
+            options,
+            deadline,
+            exceeded: None,
+        };
+
+        if let Some(kind) = writer.check(0) {
+            return Err(FileError {
+                kind,
+                file: template_name.to_string(),
+            });
+        }
+
+        let lenient_context;
+        let context = if options.strict_variables.unwrap_or(true) {
+            context
+        } else {
+            lenient_context = with_undefined_as_empty(&tera, template_name, context);
+            &lenient_context
+        };
+
+        if let Err(e) = tera.render_to(template_name, context, &mut writer) {
+            return Err(FileError {
+                kind: writer
+                    .exceeded
+                    .unwrap_or(FileErrorKind::ErrorRenderingContents(e)),
+                file: template_name.to_string(),
+            });
+        }
+
+        let mut output = String::from_utf8_lossy(&writer.buf).into_owned();
+
+        // Base64 templates are decoded after rendering, ignoring any line wrapping
+        let bytes = if is_base64(dest) {
+            let encoded = output
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect::<String>();
+
+            BASE64.decode(encoded).map_err(|e| FileError {
+                kind: FileErrorKind::InvalidBase64(e),
+                file: template_name.to_string(),
+            })?
+        } else {
+            output.clone().into_bytes()
+        };
+
+        // Render the file name
+        let output_name = match output_name(&tera, dest, context) {
+            Ok(name) => name,
             Err(e) => {
                 return Err(FileError {
-                    kind: FileErrorKind::ErrorRenderingContents(e),
+                    kind: FileErrorKind::ErrorRenderingName(e),
                     file: template_name.to_string(),
                 });
             }
         };
-
-        // Render the file name
-        let mut template_name = template_name.to_string();
-        if template_name.ends_with(TEMPLATE_EXT) {
-            let mut tera = tera.clone();
-            template_name = match tera.render_str(&template_name, &context) {
-                Ok(s) => s,
-                Err(e) => {
-                    return Err(FileError {
-                        kind: FileErrorKind::ErrorRenderingName(e),
-                        file: template_name.to_string(),
-                    });
-                }
-            };
+        if is_empty_name(&output_name) {
+            return Err(FileError {
+                kind: FileErrorKind::EmptyName,
+                file: template_name.to_string(),
+            });
         }
+        let template_name = output_name.as_str();
 
-        let template_name = match template_name.strip_suffix(TEMPLATE_EXT) {
-            Some(name) => name,
-            None => template_name.as_str(),
-        };
+        // Normalize text outputs now that their path is known for overrides
+        let mut normalized = false;
+        let mut bytes = bytes;
+        if let (Some(render), false) = (&options.render, is_base64(dest)) {
+            let text = normalize(&output, render.for_path(Path::new(template_name)));
+            if text != output {
+                normalized = true;
+                output = text;
+                bytes = output.clone().into_bytes();
+            }
+        }
 
-        // Write the output
+        // Write the output, as long as the rendered name keeps it inside the output directory
         let output_dir = out_dir.join(template_name);
+        if !is_within(&output_dir, out_dir) {
+            return Err(FileError {
+                kind: FileErrorKind::EscapesOutputDir,
+                file: template_name.to_string(),
+            });
+        }
 
         match fs::create_dir_all(output_dir.parent().unwrap()) {
             Ok(_) => (),
@@ -104,15 +841,31 @@ pub fn fill(
             },
         }
 
-        fs::write(&output_dir, output.clone()).map_err(|e| FileError {
+        fs::write(&output_dir, &bytes).map_err(|e| FileError {
             kind: FileErrorKind::ErrorWritingToDest(e),
             file: template_name.to_string(),
         })?;
 
+        // Re-read the output to catch writes that were silently corrupted
+        if options.verify {
+            let written = fs::read(&output_dir).map_err(|e| FileError {
+                kind: FileErrorKind::ErrorVerifyingDest(e),
+                file: template_name.to_string(),
+            })?;
+
+            if written != bytes {
+                return Err(FileError {
+                    kind: FileErrorKind::VerifyMismatch,
+                    file: template_name.to_string(),
+                });
+            }
+        }
+
         Ok(RenderedFile {
             path: template_name.into(),
             contents: output,
             elapsed: start_time.elapsed(),
+            normalized,
         })
     });
 
@@ -124,27 +877,67 @@ pub enum ValidateError {
     RenderError(Vec<(String, tera::Error)>),
 }
 
-// Validates the templates in the directory against the slots
-// Returns an error if any of the templates reference a slot that doesn't exist
-pub fn validate(dir: &PathBuf, slots: &Vec<Slot>) -> Result<(), ValidateError> {
-    let glob = dir.join("**").join("*".to_owned() + TEMPLATE_EXT);
+impl ValidateError {
+    /// Every code returned by [ValidateError::code]
+    pub const CODES: &'static [&'static str] = &[
+        "template.validate.load_failed",
+        "template.validate.render_failed",
+    ];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            ValidateError::TeraError(_) => "template.validate.load_failed",
+            ValidateError::RenderError(_) => "template.validate.render_failed",
+        }
+    }
+}
 
-    let tera = Tera::new(&glob.to_string_lossy()).map_err(ValidateError::TeraError)?;
-    let mut context = Context::from_serialize(
-        slots
+// Validates the templates in the directory against the slots
+// Returns an error if any of the templates reference a slot that doesn't exist, otherwise the
+// names of the templates that were validated, which is empty for a project of only static files.
+// Without `strict`, undefined variables render empty and aren't errors.
+pub fn validate(
+    dir: &PathBuf,
+    inline: &[InlineTemplate],
+    slots: &Vec<Slot>,
+    strict: bool,
+) -> Result<Vec<String>, ValidateError> {
+    // Ignore globs can depend on the data, so every template is validated
+    let (tera, templates) =
+        load_templates(dir, inline, &[], None).map_err(ValidateError::TeraError)?;
+    let mut context = context_from_data(
+        &slots
             .iter()
-            .map(|s| (s.key.clone(), ""))
+            .map(|s| (s.key.clone(), "".to_string()))
             .collect::<HashMap<_, _>>(),
-    )
-    .map_err(ValidateError::TeraError)?;
-    context.insert("_project_name".to_string(), "");
-    context.insert("_output_name".to_string(), "");
+    );
+    for key in RESERVED_KEYS {
+        context.insert(*key, "");
+    }
+    // Hooks aren't known here, so only loops over them are checked
+    insert_definitions(&mut context, slots, &[]);
 
-    let errors = tera
-        .get_template_names()
-        .filter_map(|template_name| match tera.render(template_name, &context) {
-            Ok(_) => None,
-            Err(e) => Some((template_name.to_string(), e)),
+    let errors = templates
+        .iter()
+        .filter_map(|(template_name, _)| {
+            let result = if strict {
+                // The environment is only known when filling, so any variable under it is
+                // taken to be defined
+                tera.render(
+                    template_name,
+                    &with_empty_variables(&tera, template_name, &context, |variable| {
+                        variable.starts_with(&format!("{ENV_KEY}."))
+                    }),
+                )
+            } else {
+                tera.render(
+                    template_name,
+                    &with_undefined_as_empty(&tera, template_name, &context),
+                )
+            };
+
+            result.err().map(|e| (template_name.to_string(), e))
         })
         .collect::<Vec<_>>();
 
@@ -152,7 +945,276 @@ pub fn validate(dir: &PathBuf, slots: &Vec<Slot>) -> Result<(), ValidateError> {
         return Err(ValidateError::RenderError(errors));
     }
 
-    Ok(())
+    Ok(templates.into_iter().map(|(name, _)| name).collect())
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LintKind {
+    /// A String slot is used directly as an `if` condition
+    StringSlotInCondition(String),
+    /// A Number slot is compared against a string literal
+    NumberComparedToString(String),
+    /// A `hook_ran_*` key refers to a hook that doesn't exist
+    UnknownHookRan(String),
+    /// A filter is used that isn't registered with Tera
+    UnknownFilter(String),
+    /// A slot is used in a file name without [PATH_SAFE_FILTER], so a value with a `/` or
+    /// newline would change where the file is written
+    UnsafePathSlot(String),
+}
+
+impl Display for LintKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintKind::StringSlotInCondition(key) => write!(
+                f,
+                "string slot {} used as a condition is true whenever it is set",
+                key
+            ),
+            LintKind::NumberComparedToString(key) => {
+                write!(f, "number slot {} is compared to a string literal", key)
+            }
+            LintKind::UnknownHookRan(key) => {
+                write!(f, "{} does not correspond to any hook", key)
+            }
+            LintKind::UnknownFilter(name) => write!(f, "filter {} does not exist", name),
+            LintKind::UnsafePathSlot(key) => write!(
+                f,
+                "slot {} is used in the file name without the {} filter",
+                key, PATH_SAFE_FILTER
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LintFinding {
+    pub template: String,
+    pub kind: LintKind,
+}
+
+// Walks the AST of a template, collecting findings
+struct Linter<'a> {
+    tera: &'a Tera,
+    slots: &'a [Slot],
+    hooks: &'a [Hook],
+    findings: Vec<LintKind>,
+}
+
+impl Linter<'_> {
+    fn slot_type(&self, ident: &str) -> Option<&SlotType> {
+        // Slot keys may themselves be dotted, so match the longest key the identifier starts with
+        self.slots
+            .iter()
+            .filter(|slot| {
+                ident == slot.key
+                    || ident
+                        .strip_prefix(slot.key.as_str())
+                        .is_some_and(|rest| rest.starts_with('.'))
+            })
+            .max_by_key(|slot| slot.key.len())
+            .map(|slot| &slot.r#type)
+    }
+
+    fn nodes(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::VariableBlock(_, expr) => self.expr(expr),
+                Node::MacroDefinition(_, definition, _) => self.nodes(&definition.body),
+                Node::Set(_, set) => self.expr(&set.value),
+                Node::FilterSection(_, section, _) => {
+                    self.filter(&section.filter.name);
+                    section.filter.args.values().for_each(|arg| self.expr(arg));
+                    self.nodes(&section.body);
+                }
+                Node::Block(_, block, _) => self.nodes(&block.body),
+                Node::Forloop(_, forloop, _) => {
+                    self.expr(&forloop.container);
+                    self.nodes(&forloop.body);
+                    if let Some(body) = &forloop.empty_body {
+                        self.nodes(body);
+                    }
+                }
+                Node::If(r#if, _) => {
+                    for (_, condition, body) in &r#if.conditions {
+                        self.condition(condition);
+                        self.expr(condition);
+                        self.nodes(body);
+                    }
+                    if let Some((_, body)) = &r#if.otherwise {
+                        self.nodes(body);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Checks for string slots used for their truthiness
+    fn condition(&mut self, expr: &Expr) {
+        match &expr.val {
+            ExprVal::Ident(ident) if expr.filters.is_empty() => {
+                if let Some(SlotType::String) = self.slot_type(ident) {
+                    self.findings
+                        .push(LintKind::StringSlotInCondition(ident.clone()));
+                }
+            }
+            ExprVal::Logic(logic)
+                if matches!(logic.operator, LogicOperator::And | LogicOperator::Or) =>
+            {
+                self.condition(&logic.lhs);
+                self.condition(&logic.rhs);
+            }
+            _ => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        for filter in &expr.filters {
+            self.filter(&filter.name);
+            filter.args.values().for_each(|arg| self.expr(arg));
+        }
+
+        match &expr.val {
+            ExprVal::Ident(ident) => {
+                if let Some(key) = ident.strip_prefix("hook_ran_") {
+                    if !self.hooks.iter().any(|hook| hook.key == key) {
+                        self.findings.push(LintKind::UnknownHookRan(ident.clone()));
+                    }
+                }
+            }
+            ExprVal::Math(math) => {
+                self.expr(&math.lhs);
+                self.expr(&math.rhs);
+            }
+            ExprVal::Logic(logic) => {
+                if !matches!(logic.operator, LogicOperator::And | LogicOperator::Or) {
+                    self.comparison(&logic.lhs, &logic.rhs);
+                    self.comparison(&logic.rhs, &logic.lhs);
+                }
+                self.expr(&logic.lhs);
+                self.expr(&logic.rhs);
+            }
+            ExprVal::Test(test) => test.args.iter().for_each(|arg| self.expr(arg)),
+            ExprVal::MacroCall(call) => call.args.values().for_each(|arg| self.expr(arg)),
+            ExprVal::FunctionCall(call) => call.args.values().for_each(|arg| self.expr(arg)),
+            ExprVal::Array(values) => values.iter().for_each(|value| self.expr(value)),
+            ExprVal::In(r#in) => {
+                self.expr(&r#in.lhs);
+                self.expr(&r#in.rhs);
+            }
+            _ => {}
+        }
+    }
+
+    fn comparison(&mut self, lhs: &Expr, rhs: &Expr) {
+        if let (ExprVal::Ident(ident), ExprVal::String(_)) = (&lhs.val, &rhs.val) {
+            if lhs.filters.is_empty() && matches!(self.slot_type(ident), Some(SlotType::Number)) {
+                self.findings
+                    .push(LintKind::NumberComparedToString(ident.clone()));
+            }
+        }
+    }
+
+    // Checks for slots that could hold a separator or newline used in a file name without
+    // the path_safe filter
+    fn path(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            let Node::VariableBlock(_, expr) = node else {
+                continue;
+            };
+            let ExprVal::Ident(ident) = &expr.val else {
+                continue;
+            };
+
+            let safe = expr
+                .filters
+                .iter()
+                .any(|filter| filter.name == PATH_SAFE_FILTER);
+            let unsafe_type = matches!(
+                self.slot_type(ident),
+                Some(SlotType::String | SlotType::List | SlotType::Path)
+            );
+            if unsafe_type && !safe {
+                self.findings.push(LintKind::UnsafePathSlot(ident.clone()));
+            }
+        }
+    }
+
+    fn filter(&mut self, name: &str) {
+        if self.tera.get_filter(name).is_err() {
+            self.findings
+                .push(LintKind::UnknownFilter(name.to_string()));
+        }
+    }
+}
+
+/// Lints the templates in the directory for common pitfalls that render without erroring,
+/// along with the names of its templates and files
+pub fn lint(dir: &Path, slots: &[Slot], hooks: &[Hook]) -> Result<Vec<LintFinding>, tera::Error> {
+    let (tera, _) = load_templates(dir, &[], &[], None)?;
+    let mut findings = lint_names(dir, slots, hooks)?;
+
+    let mut template_names = tera.get_template_names().collect::<Vec<_>>();
+    template_names.sort();
+
+    for template_name in template_names {
+        let template = tera.get_template(template_name)?;
+
+        let mut linter = Linter {
+            tera: &tera,
+            slots,
+            hooks,
+            findings: Vec::new(),
+        };
+        linter.nodes(&template.ast);
+
+        findings.extend(linter.findings.into_iter().map(|kind| LintFinding {
+            template: template_name.to_string(),
+            kind,
+        }));
+    }
+
+    Ok(findings)
+}
+
+// Lints the templated names of the project's entries, each reported under its path
+fn lint_names(dir: &Path, slots: &[Slot], hooks: &[Hook]) -> Result<Vec<LintFinding>, tera::Error> {
+    let entries = Classifier::new(dir, &[])
+        .and_then(|classifier| classifier.entries(dir))
+        .map_err(|e| tera::Error::chain("Error finding templates", e))?;
+
+    let mut findings = Vec::new();
+    for entry in entries {
+        if matches!(entry.class, FileClass::Ignored | FileClass::Project) {
+            continue;
+        }
+
+        let name = entry.path.to_string_lossy().replace('\\', "/");
+        if !name.contains("{{") {
+            continue;
+        }
+
+        let mut tera = Tera::default();
+        if tera.add_raw_template(&name, &name).is_err() {
+            continue;
+        }
+
+        let mut linter = Linter {
+            tera: &tera,
+            slots,
+            hooks,
+            findings: Vec::new(),
+        };
+        linter.path(&tera.get_template(&name)?.ast);
+
+        findings.extend(linter.findings.into_iter().map(|kind| LintFinding {
+            template: name.clone(),
+            kind,
+        }));
+    }
+
+    Ok(findings)
 }
 
 #[cfg(test)]
@@ -160,6 +1222,7 @@ mod tests {
     use tempdir::TempDir;
 
     use super::*;
+    use crate::config::{RenderConfig, RenderOverride};
 
     #[test]
     fn fill_proj1() {
@@ -167,12 +1230,18 @@ mod tests {
 
         let result = fill(
             &PathBuf::from("tests/data/proj1"),
+            &[],
+            &[],
             &dir.join("proj1_filled"),
             &HashMap::from([
                 ("person_name".to_string(), "Joe Bloggs".to_string()),
                 ("person_age".to_string(), "42".to_string()),
                 ("file_name".to_string(), "main".to_string()),
             ]),
+            &GenerateOptions {
+                verify: true,
+                ..Default::default()
+            },
         );
 
         println!("{:?}", result);
@@ -180,29 +1249,649 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn nested_keys() {
+        let slots = crate::config::load_dir("tests/data/nested")
+            .expect("load_dir failed, should have succeeded")
+            .slots;
+        assert!(validate(&PathBuf::from("tests/data/nested"), &[], &slots, true).is_ok());
+
+        let dir = TempDir::new("spackle").unwrap().into_path();
+        let results = fill(
+            &PathBuf::from("tests/data/nested"),
+            &[],
+            &[],
+            &dir,
+            &HashMap::from([
+                ("db.host".to_string(), "localhost".to_string()),
+                ("db.port".to_string(), "5432".to_string()),
+            ]),
+            &GenerateOptions::default(),
+        )
+        .expect("fill failed, should have succeeded");
+
+        assert!(results.iter().all(|r| r.is_ok()), "{:?}", results);
+        assert_eq!(
+            fs::read_to_string(dir.join("database.toml")).unwrap(),
+            "host = \"localhost\"\nport = 5432\n"
+        );
+    }
+
+    #[test]
+    fn inline_templates() {
+        let dir = PathBuf::from("tests/data/inline");
+        let config = crate::config::load_dir(&dir).unwrap();
+        assert!(validate(&dir, &config.templates, &config.slots, true).is_ok());
+
+        let out_dir = TempDir::new("spackle").unwrap().into_path();
+        let data = HashMap::from([("python_version".to_string(), "3.12".to_string())]);
+
+        let results = fill(
+            &dir,
+            &config.templates,
+            &[],
+            &out_dir,
+            &data,
+            &GenerateOptions::default(),
+        )
+        .expect("fill failed, should have succeeded");
+        assert!(results.iter().all(|r| r.is_ok()), "{:?}", results);
+
+        assert_eq!(
+            fs::read_to_string(out_dir.join(".python-version")).unwrap(),
+            "3.12\n"
+        );
+        assert!(out_dir.join("3.12").join("CODEOWNERS").exists());
+
+        let mut dests = destinations(&dir, &config.templates, &[], &out_dir, &data).unwrap();
+        dests.sort();
+        assert_eq!(
+            dests,
+            vec![
+                out_dir.join(".python-version"),
+                out_dir.join("3.12/CODEOWNERS")
+            ]
+        );
+
+        // Inline templates are validated like file templates
+        let bad = vec![InlineTemplate {
+            dest: Some("bad".to_string()),
+            content: Some("{{ undefined }}".to_string()),
+        }];
+        assert!(validate(&dir, &bad, &config.slots, true).is_err());
+    }
+
+    #[test]
+    fn fill_with_nested_context() {
+        let project_dir = TempDir::new("spackle").unwrap().into_path();
+        let out_dir = TempDir::new("spackle").unwrap().into_path();
+
+        fs::write(
+            project_dir.join("{{ service.name }}.toml.j2"),
+            "{% for port in service.ports %}port = {{ port }}\n{% endfor %}",
+        )
+        .unwrap();
+
+        let mut context = Context::new();
+        context.insert(
+            "service",
+            &serde_json::json!({ "name": "api", "ports": [80, 443] }),
+        );
+        let results = fill_with_context(
+            &project_dir,
+            &[],
+            &[],
+            &out_dir,
+            &context,
+            &GenerateOptions::default(),
+        )
+        .expect("fill_with_context failed, should have succeeded");
+
+        assert!(results.iter().all(|r| r.is_ok()), "{:?}", results);
+        assert_eq!(
+            fs::read_to_string(out_dir.join("api.toml")).unwrap(),
+            "port = 80\nport = 443\n"
+        );
+    }
+
+    #[test]
+    fn fill_with_env() {
+        let project_dir = TempDir::new("spackle").unwrap().into_path();
+        fs::write(
+            project_dir.join("env.txt.j2"),
+            "{{ name }} by {{ env.SPACKLE_USER }}",
+        )
+        .unwrap();
+        let data = HashMap::from([("name".to_string(), "app".to_string())]);
+
+        // Without the environment, env is undefined like any other variable
+        let out_dir = TempDir::new("spackle").unwrap().into_path();
+        let results = fill(
+            &project_dir,
+            &[],
+            &[],
+            &out_dir,
+            &data,
+            &GenerateOptions::default(),
+        )
+        .unwrap();
+        assert!(results[0].is_err());
+
+        let out_dir = TempDir::new("spackle").unwrap().into_path();
+        let results = fill(
+            &project_dir,
+            &[],
+            &[],
+            &out_dir,
+            &data,
+            &GenerateOptions {
+                env: Some(HashMap::from([(
+                    "SPACKLE_USER".to_string(),
+                    "a2-ai".to_string(),
+                )])),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(results.iter().all(|r| r.is_ok()), "{:?}", results);
+        assert_eq!(
+            fs::read_to_string(out_dir.join("env.txt")).unwrap(),
+            "app by a2-ai"
+        );
+
+        // The environment isn't known until filling, so validation takes it to be defined
+        let slots = vec![Slot {
+            key: "name".to_string(),
+            ..Default::default()
+        }];
+        assert!(validate(&project_dir, &[], &slots, true).is_ok());
+    }
+
+    #[test]
+    fn strict_and_lenient_variables() {
+        let project_dir = TempDir::new("spackle").unwrap().into_path();
+        fs::write(
+            project_dir.join("out.txt.j2"),
+            "{{ name }}|{{ missing }}|{{ db.host }}|{{ port | default(value=5432) }}|\
+             {% if flag is defined %}defined{% else %}undefined{% endif %}",
+        )
+        .unwrap();
+        let data = HashMap::from([("name".to_string(), "app".to_string())]);
+        let slots = vec![Slot {
+            key: "name".to_string(),
+            ..Default::default()
+        }];
+
+        let fill_with = |strict_variables| {
+            let out_dir = TempDir::new("spackle").unwrap().into_path();
+            let options = GenerateOptions {
+                strict_variables,
+                ..Default::default()
+            };
+            let results = fill(&project_dir, &[], &[], &out_dir, &data, &options)
+                .expect("fill failed, should have succeeded");
+
+            (results, out_dir)
+        };
+
+        // Strict is the default
+        for strict_variables in [None, Some(true)] {
+            let (results, _) = fill_with(strict_variables);
+            assert!(matches!(
+                results[..],
+                [Err(FileError {
+                    kind: FileErrorKind::ErrorRenderingContents(_),
+                    ..
+                })]
+            ));
+        }
+        assert!(validate(&project_dir, &[], &slots, true).is_err());
+
+        let (results, out_dir) = fill_with(Some(false));
+        assert!(results.iter().all(|r| r.is_ok()), "{:?}", results);
+        assert_eq!(
+            fs::read_to_string(out_dir.join("out.txt")).unwrap(),
+            "app|||5432|undefined"
+        );
+        assert!(validate(&project_dir, &[], &slots, false).is_ok());
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        let project_dir = TempDir::new("spackle").unwrap().into_path();
+        let out_dir = TempDir::new("spackle").unwrap().into_path();
+
+        // Not valid UTF-8, so it could only be written by decoding. The first chunk is a
+        // multiple of three bytes so the two encoded halves join without padding
+        let icon = vec![0x89, b'P', b'N', b'G', 0xff, 0x00, 0xfe];
+        fs::write(
+            project_dir.join("{{ name }}.png.b64.j2"),
+            BASE64.encode(&icon[..3]) + "\n{{ tail }}\n",
+        )
+        .unwrap();
+
+        let data = HashMap::from([
+            ("name".to_string(), "icon".to_string()),
+            ("tail".to_string(), BASE64.encode(&icon[3..])),
+        ]);
+        let results = fill(
+            &project_dir,
+            &[],
+            &[],
+            &out_dir,
+            &data,
+            &GenerateOptions {
+                verify: true,
+                ..Default::default()
+            },
+        )
+        .expect("fill failed, should have succeeded");
+
+        assert!(results.iter().all(|r| r.is_ok()), "{:?}", results);
+        assert_eq!(fs::read(out_dir.join("icon.png")).unwrap(), icon);
+        assert_eq!(
+            destinations(&project_dir, &[], &[], &out_dir, &data).unwrap(),
+            vec![out_dir.join("icon.png")]
+        );
+
+        let data = HashMap::from([
+            ("name".to_string(), "icon".to_string()),
+            ("tail".to_string(), "not base64!".to_string()),
+        ]);
+        let results = fill(
+            &project_dir,
+            &[],
+            &[],
+            &TempDir::new("spackle").unwrap().into_path(),
+            &data,
+            &GenerateOptions::default(),
+        )
+        .expect("fill failed, should have succeeded");
+
+        assert!(matches!(
+            results.as_slice(),
+            [Err(FileError {
+                kind: FileErrorKind::InvalidBase64(_),
+                ..
+            })]
+        ));
+    }
+
+    #[test]
+    fn empty_name() {
+        let project_dir = TempDir::new("spackle").unwrap().into_path();
+        let out_dir = TempDir::new("spackle").unwrap().into_path();
+
+        fs::write(project_dir.join(".j2"), "nameless").unwrap();
+        fs::create_dir(project_dir.join("sub")).unwrap();
+        fs::write(project_dir.join("sub").join("{{ blank }}.j2"), "nameless").unwrap();
+        fs::write(project_dir.join(".gitignore.j2"), "target").unwrap();
+
+        let results = fill(
+            &project_dir,
+            &[],
+            &[],
+            &out_dir,
+            &HashMap::from([("blank".to_string(), "".to_string())]),
+            &GenerateOptions::default(),
+        )
+        .expect("fill failed, should have succeeded");
+
+        let mut empty = results
+            .iter()
+            .filter_map(|r| match r {
+                Err(FileError {
+                    kind: FileErrorKind::EmptyName,
+                    file,
+                }) => Some(file.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        empty.sort();
+        assert_eq!(empty, vec![".j2", "sub/{{ blank }}.j2"]);
+
+        // Dotfiles are named, they just start with a dot
+        assert_eq!(
+            fs::read_to_string(out_dir.join(".gitignore")).unwrap(),
+            "target"
+        );
+    }
+
+    #[test]
+    fn suffix_stripping() {
+        let project_dir = TempDir::new("spackle").unwrap().into_path();
+        let out_dir = TempDir::new("spackle").unwrap().into_path().join("out");
+
+        for name in [
+            ".env.j2",
+            "a.b.c.j2",
+            "archive.tar.gz.j2",
+            "{{ name }}.j2.j2",
+            "{{ value }}.j2",
+            "{{ value }}.b64.j2",
+        ] {
+            fs::write(project_dir.join(name), "").unwrap();
+        }
+        let data = HashMap::from([
+            ("name".to_string(), "config".to_string()),
+            // A value ending in an extension keeps it, whatever the template's own extension
+            ("value".to_string(), "data.b64".to_string()),
+        ]);
+
+        let mut outputs = destinations(&project_dir, &[], &[], &out_dir, &data).unwrap();
+        outputs.sort();
+        assert_eq!(
+            outputs,
+            [
+                ".env",
+                "a.b.c",
+                "archive.tar.gz",
+                "config.j2",
+                "data.b64",
+                "data.b64"
+            ]
+            .iter()
+            .map(|name| out_dir.join(name))
+            .collect::<Vec<_>>()
+        );
+
+        // Templates are only rendered, never copied under their source name as well
+        assert!(
+            crate::copy::destinations(&project_dir, &out_dir, &[], &data)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn escapes_output_dir() {
+        let out_dir = TempDir::new("spackle").unwrap().into_path().join("out");
+        let outside = TempDir::new("spackle").unwrap().into_path();
+
+        fs::create_dir(&out_dir).unwrap();
+        std::os::unix::fs::symlink(&outside, out_dir.join("link")).unwrap();
+
+        let template = |dest: &str| InlineTemplate {
+            dest: Some(dest.to_string()),
+            content: Some("evil".to_string()),
+        };
+        let templates = vec![
+            template("../escaped"),
+            template("{{ absolute }}"),
+            template("link/escaped"),
+            template("sub/../inside"),
+        ];
+
+        let results = fill(
+            &PathBuf::from("tests/data/render_budget"),
+            &templates,
+            &[],
+            &out_dir,
+            &HashMap::from([(
+                "absolute".to_string(),
+                outside.join("absolute").to_string_lossy().to_string(),
+            )]),
+            &GenerateOptions::default(),
+        )
+        .expect("fill failed, should have succeeded");
+
+        let escaped = results
+            .iter()
+            .filter(|r| {
+                matches!(
+                    r,
+                    Err(FileError {
+                        kind: FileErrorKind::EscapesOutputDir,
+                        ..
+                    })
+                )
+            })
+            .count();
+        assert_eq!(escaped, 3, "{:?}", results);
+
+        assert!(out_dir.join("inside").exists());
+        assert!(!out_dir.parent().unwrap().join("escaped").exists());
+        assert!(!outside.join("absolute").exists());
+        assert!(!outside.join("escaped").exists());
+    }
+
+    #[test]
+    fn context_plain_key_wins() {
+        let context = context_from_data(&HashMap::from([
+            ("db".to_string(), "plain".to_string()),
+            ("db.host".to_string(), "nested".to_string()),
+        ]));
+
+        assert_eq!(Tera::one_off("{{ db }}", &context, false).unwrap(), "plain");
+    }
+
+    #[test]
+    fn fill_budgets() {
+        let fill_with = |options: GenerateOptions| {
+            let dir = TempDir::new("spackle").unwrap().into_path();
+            let mut results = fill(
+                &PathBuf::from("tests/data/render_budget"),
+                &[],
+                &[],
+                &dir,
+                &HashMap::new(),
+                &options,
+            )
+            .expect("fill failed, should have succeeded");
+
+            results.sort_by_key(|r| match r {
+                Ok(f) => f.path.to_string_lossy().to_string(),
+                Err(e) => e.file.clone(),
+            });
+            results
+        };
+
+        let results = fill_with(GenerateOptions::default());
+        assert!(results.iter().all(|r| r.is_ok()), "{:?}", results);
+
+        let results = fill_with(GenerateOptions {
+            max_file_bytes: Some(5),
+            ..Default::default()
+        });
+        assert!(
+            results.iter().all(|r| matches!(
+                r,
+                Err(FileError {
+                    kind: FileErrorKind::FileTooLarge(5),
+                    ..
+                })
+            )),
+            "{:?}",
+            results
+        );
+
+        // Each file fits on its own, but not both together
+        let results = fill_with(GenerateOptions {
+            max_total_bytes: Some(15),
+            ..Default::default()
+        });
+        assert_eq!(
+            results
+                .iter()
+                .filter(|r| matches!(
+                    r,
+                    Err(FileError {
+                        kind: FileErrorKind::TotalTooLarge(15),
+                        ..
+                    })
+                ))
+                .count(),
+            1,
+            "{:?}",
+            results
+        );
+
+        let results = fill_with(GenerateOptions {
+            render_timeout: Some(Duration::ZERO),
+            ..Default::default()
+        });
+        assert!(
+            results.iter().all(|r| matches!(
+                r,
+                Err(FileError {
+                    kind: FileErrorKind::RenderTimedOut(_),
+                    ..
+                })
+            )),
+            "{:?}",
+            results
+        );
+    }
+
     #[test]
     fn validate_dir_proj1() {
         let result = validate(
             &PathBuf::from("tests/data/proj1"),
+            &[],
             &vec![Slot {
                 key: "defined_field".to_string(),
                 ..Default::default()
             }],
+            true,
         );
 
         assert!(result.is_err());
     }
 
+    fn lint_fixture(name: &str) -> Vec<LintKind> {
+        let dir = PathBuf::from("tests/data").join(name);
+        let config = crate::config::load_dir(&dir).unwrap();
+
+        lint(&dir, &config.slots, &config.hooks)
+            .unwrap()
+            .into_iter()
+            .map(|finding| finding.kind)
+            .collect()
+    }
+
+    #[test]
+    fn lint_string_slot_in_condition() {
+        assert_eq!(
+            lint_fixture("lint_string_condition"),
+            vec![LintKind::StringSlotInCondition("name".to_string())]
+        );
+    }
+
+    #[test]
+    fn lint_number_compared_to_string() {
+        assert_eq!(
+            lint_fixture("lint_number_comparison"),
+            vec![LintKind::NumberComparedToString("count".to_string())]
+        );
+    }
+
+    #[test]
+    fn lint_unknown_hook_ran() {
+        assert_eq!(
+            lint_fixture("lint_hook_ran"),
+            vec![LintKind::UnknownHookRan("hook_ran_missing".to_string())]
+        );
+    }
+
+    #[test]
+    fn lint_unknown_filter() {
+        assert_eq!(
+            lint_fixture("lint_unknown_filter"),
+            vec![LintKind::UnknownFilter("shout".to_string())]
+        );
+    }
+
+    #[test]
+    fn lint_unsafe_path_slot() {
+        assert_eq!(
+            lint_fixture("lint_path_slot"),
+            vec![LintKind::UnsafePathSlot("name".to_string())]
+        );
+
+        assert_eq!(path_safe("a/b\\c"), "a_b_c");
+        assert_eq!(path_safe("line\nbreak"), "line_break");
+        assert_eq!(path_safe(".."), "__");
+        assert_eq!(path_safe("v1.2"), "v1.2");
+
+        let mut context = Context::new();
+        context.insert("name", "../etc/passwd");
+        assert_eq!(
+            render_path("{{ name | path_safe }}.txt", &context).unwrap(),
+            ".._etc_passwd.txt"
+        );
+    }
+
+    #[test]
+    fn lint_clean() {
+        assert!(lint_fixture("proj2").is_empty());
+    }
+
     #[test]
     fn validate_dir_proj2() {
         let result = validate(
             &PathBuf::from("tests/data/proj2"),
+            &[],
             &vec![Slot {
                 key: "defined_field".to_string(),
                 ..Default::default()
             }],
+            true,
         );
 
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn normalize_whitespace() {
+        let both = Normalize {
+            trim_trailing_whitespace: true,
+            ensure_final_newline: true,
+        };
+        assert_eq!(normalize("a  \r\nb\t\n c ", both), "a\r\nb\n c\n");
+        assert_eq!(normalize("", both), "");
+        assert_eq!(normalize("a  ", Normalize::default()), "a  ");
+
+        let dir = TempDir::new("spackle").unwrap();
+        let project_dir = dir.path().join("project");
+        fs::create_dir(&project_dir).unwrap();
+        fs::write(project_dir.join("code.rs.j2"), "fn {{ name }}() {}  ").unwrap();
+        fs::write(project_dir.join("notes.md.j2"), "{{ name }}  ").unwrap();
+
+        let render = RenderConfig {
+            trim_trailing_whitespace: true,
+            ensure_final_newline: true,
+            overrides: vec![RenderOverride {
+                path: "*.md".to_string(),
+                trim_trailing_whitespace: Some(false),
+                ..Default::default()
+            }],
+        };
+        let out_dir = dir.path().join("out");
+        let mut results = fill(
+            &project_dir,
+            &[],
+            &[],
+            &out_dir,
+            &HashMap::from([("name".to_string(), "main".to_string())]),
+            &GenerateOptions {
+                render: Some(render),
+                ..Default::default()
+            },
+        )
+        .expect("fill failed, should have succeeded")
+        .into_iter()
+        .map(|result| result.expect("Failed to render file"))
+        .collect::<Vec<_>>();
+        results.sort_by(|a, b| a.path.cmp(&b.path));
+
+        assert!(results.iter().all(|file| file.normalized));
+        assert_eq!(
+            fs::read_to_string(out_dir.join("code.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+        assert_eq!(
+            fs::read_to_string(out_dir.join("notes.md")).unwrap(),
+            "main  \n"
+        );
+    }
 }
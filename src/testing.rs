@@ -0,0 +1,350 @@
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+};
+use tempdir::TempDir;
+use thiserror::Error;
+
+use crate::{
+    config,
+    hook::{HookResult, HookResultKind},
+    load_project,
+    template::RenderedFile,
+    GenerateError, GenerateOptions,
+};
+
+/// The directory of a project that test cases are discovered in. Like the hooks directory,
+/// it's part of the project rather than its output, so fills leave it out.
+pub const TESTS_DIR: &str = "tests.d";
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Error loading project: {0}")]
+    LoadError(config::Error),
+    #[error("Error creating output directory: {0}")]
+    TempDirError(io::Error),
+    #[error("Error filling project: {0}")]
+    FillError(Box<GenerateError>),
+    #[error("Error reading test case: {0}")]
+    ReadError(io::Error),
+    #[error("Error parsing test case: {0}")]
+    ParseError(toml::de::Error),
+}
+
+impl Error {
+    /// Every code returned by [Error::code]
+    pub const CODES: &'static [&'static str] = &[
+        "testing.load_error",
+        "testing.temp_dir_error",
+        "testing.fill_error",
+        "testing.read_error",
+        "testing.parse_error",
+    ];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::LoadError(_) => "testing.load_error",
+            Error::TempDirError(_) => "testing.temp_dir_error",
+            Error::FillError(_) => "testing.fill_error",
+            Error::ReadError(_) => "testing.read_error",
+            Error::ParseError(_) => "testing.parse_error",
+        }
+    }
+}
+
+/// A fill of a project into a temporary directory, for testing templates.
+/// Slots without a value use their default.
+///
+/// ```no_run
+/// use spackle::testing::TestFill;
+///
+/// let fill = TestFill::new("my-template")
+///     .with_slot("name", "app")
+///     .with_hook("git_init", false)
+///     .run()
+///     .unwrap();
+///
+/// fill.assert_file_contains("README.md", "# app");
+/// fill.assert_file_absent(".git");
+/// ```
+#[derive(Debug, Default)]
+pub struct TestFill {
+    project_dir: PathBuf,
+    data: HashMap<String, String>,
+    options: GenerateOptions,
+}
+
+impl TestFill {
+    pub fn new(project_dir: impl Into<PathBuf>) -> Self {
+        TestFill {
+            project_dir: project_dir.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_slot(mut self, key: &str, value: &str) -> Self {
+        self.data.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn with_hook(mut self, key: &str, enabled: bool) -> Self {
+        self.data.insert(key.to_string(), enabled.to_string());
+        self
+    }
+
+    pub fn with_options(mut self, options: GenerateOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Fills the project and runs its hooks. The output is removed once the result is dropped.
+    pub fn run(&self) -> Result<TestOutput, Error> {
+        let project = load_project(&self.project_dir).map_err(Error::LoadError)?;
+
+        let mut data = self.data.clone();
+        let (slots, _) = project.resolve_defaults(&self.options);
+        for slot in slots {
+            if let Some(default) = slot.default {
+                data.entry(slot.key).or_insert(default);
+            }
+        }
+
+        let dir = TempDir::new("spackle-test").map_err(Error::TempDirError)?;
+        let out_dir = dir.path().join(project.get_name());
+
+        let (files, hooks) = project
+            .generate_with_hooks(&out_dir, &data, &[], &self.options)
+            .map_err(|e| Error::FillError(Box::new(e)))?;
+
+        Ok(TestOutput {
+            files,
+            hooks,
+            out_dir,
+            _dir: dir,
+        })
+    }
+}
+
+/// The result of a [TestFill], with checks of its output. The `check_` methods return a
+/// description of what didn't match, and the `assert_` methods panic with it.
+#[derive(Debug)]
+pub struct TestOutput {
+    pub files: Vec<RenderedFile>,
+    pub hooks: Vec<HookResult>,
+    pub out_dir: PathBuf,
+    // Removes the output once dropped
+    _dir: TempDir,
+}
+
+impl TestOutput {
+    /// The path of an output, relative to the output directory
+    pub fn path(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.out_dir.join(path)
+    }
+
+    pub fn check_file_exists(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        if self.path(path).exists() {
+            Ok(())
+        } else {
+            Err(format!("{} was not written", path.display()))
+        }
+    }
+
+    pub fn check_file_absent(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        if self.path(path).exists() {
+            Err(format!("{} was written", path.display()))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn check_file_contains(&self, path: impl AsRef<Path>, needle: &str) -> Result<(), String> {
+        let path = path.as_ref();
+        match fs::read_to_string(self.path(path)) {
+            Ok(contents) if contents.contains(needle) => Ok(()),
+            Ok(_) => Err(format!("{} does not contain {:?}", path.display(), needle)),
+            Err(e) => Err(format!("{} could not be read: {}", path.display(), e)),
+        }
+    }
+
+    /// Checks that the hook completed, as opposed to being skipped or failing
+    pub fn check_hook_ran(&self, key: &str) -> Result<(), String> {
+        match self.hooks.iter().find(|result| result.hook.key == key) {
+            Some(HookResult {
+                kind: HookResultKind::Completed { .. },
+                ..
+            }) => Ok(()),
+            Some(result) => Err(format!("hook {} did not run: {}", key, result.kind)),
+            None => Err(format!("hook {} is not in the project", key)),
+        }
+    }
+
+    pub fn assert_file_exists(&self, path: impl AsRef<Path>) {
+        if let Err(e) = self.check_file_exists(path) {
+            panic!("{}", e);
+        }
+    }
+
+    pub fn assert_file_absent(&self, path: impl AsRef<Path>) {
+        if let Err(e) = self.check_file_absent(path) {
+            panic!("{}", e);
+        }
+    }
+
+    pub fn assert_file_contains(&self, path: impl AsRef<Path>, needle: &str) {
+        if let Err(e) = self.check_file_contains(path, needle) {
+            panic!("{}", e);
+        }
+    }
+
+    pub fn assert_hook_ran(&self, key: &str) {
+        if let Err(e) = self.check_hook_ran(key) {
+            panic!("{}", e);
+        }
+    }
+}
+
+/// A test of a project, as a TOML file in its [TESTS_DIR]: the data to fill it with and
+/// what the output should look like
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TestCase {
+    #[serde(default)]
+    pub slots: BTreeMap<String, String>,
+    #[serde(default)]
+    pub hooks: BTreeMap<String, bool>,
+    #[serde(default)]
+    pub expect: Expectations,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Expectations {
+    /// Outputs that should be written, relative to the output directory
+    #[serde(default)]
+    pub files: Vec<PathBuf>,
+    /// Outputs that shouldn't be written
+    #[serde(default)]
+    pub absent: Vec<PathBuf>,
+    /// Text each output should contain
+    #[serde(default)]
+    pub contains: BTreeMap<PathBuf, Vec<String>>,
+    /// Hooks that should complete
+    #[serde(default)]
+    pub hooks_ran: Vec<String>,
+}
+
+impl TestCase {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path).map_err(Error::ReadError)?;
+
+        toml::from_str(&contents).map_err(Error::ParseError)
+    }
+
+    /// Fills the project with the test's data, returning every expectation that wasn't met
+    pub fn run(&self, project_dir: &Path) -> Result<Vec<String>, Error> {
+        let mut fill = TestFill::new(project_dir);
+        for (key, value) in &self.slots {
+            fill = fill.with_slot(key, value);
+        }
+        for (key, enabled) in &self.hooks {
+            fill = fill.with_hook(key, *enabled);
+        }
+
+        let output = &fill.run()?;
+        let expect = &self.expect;
+
+        let checks = expect
+            .files
+            .iter()
+            .map(|path| output.check_file_exists(path))
+            .chain(
+                expect
+                    .absent
+                    .iter()
+                    .map(|path| output.check_file_absent(path)),
+            )
+            .chain(expect.contains.iter().flat_map(|(path, needles)| {
+                needles
+                    .iter()
+                    .map(move |needle| output.check_file_contains(path, needle))
+            }))
+            .chain(
+                expect
+                    .hooks_ran
+                    .iter()
+                    .map(|key| output.check_hook_ran(key)),
+            )
+            .filter_map(|check| check.err())
+            .collect();
+
+        Ok(checks)
+    }
+}
+
+/// The test cases of a project, the `.toml` files directly in its [TESTS_DIR], in path order
+pub fn discover(project_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let dir = project_dir.join(TESTS_DIR);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut paths = fs::read_dir(dir)
+        .map_err(Error::ReadError)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "toml"))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill() {
+        let output = TestFill::new("tests/data/testing")
+            .with_slot("name", "app")
+            .with_hook("license", false)
+            .run()
+            .expect("Failed to fill project");
+
+        output.assert_file_contains("README.md", "# app");
+        output.assert_file_absent("LICENSE");
+        output.assert_file_absent(TESTS_DIR);
+        output.assert_hook_ran("greet");
+        assert!(output.check_hook_ran("license").is_err());
+        assert!(output.check_file_contains("README.md", "# other").is_err());
+    }
+
+    #[test]
+    fn test_cases() {
+        let project_dir = Path::new("tests/data/testing");
+        let paths = discover(project_dir).expect("Failed to discover test cases");
+        let names = paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["defaults.toml", "failing.toml"]);
+
+        let passing = TestCase::load(&paths[0]).unwrap();
+        assert_eq!(passing.run(project_dir).unwrap(), Vec::<String>::new());
+
+        let failing = TestCase::load(&paths[1]).unwrap();
+        assert_eq!(
+            failing.run(project_dir).unwrap(),
+            vec![
+                "LICENSE was not written".to_string(),
+                "hook license did not run: skipped: user disabled".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use colored::Colorize;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+use crate::{
+    manifest::{self, fingerprint},
+    GenerateError,
+};
+
+/// The extension added to the new render of a file that was also changed in the output
+pub const NEW_EXT: &str = "new";
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("{0} has no manifest, it wasn't filled by spackle")]
+    NotFilled(PathBuf),
+    #[error("The manifest of {0} doesn't record the answers it was filled with, fill it again with this version of spackle to update it")]
+    NoAnswers(PathBuf),
+    #[error("No value for slots {}, pass them as data", .0.join(", "))]
+    MissingData(Vec<String>),
+    #[error("{0}")]
+    ManifestError(manifest::Error),
+    #[error("Error creating render directory: {0}")]
+    TempDirError(io::Error),
+    #[error("Error rendering project: {0}")]
+    RenderError(Box<GenerateError>),
+    #[error("Error reading {0}: {1}")]
+    ReadError(PathBuf, io::Error),
+    #[error("Error writing {0}: {1}")]
+    WriteError(PathBuf, io::Error),
+}
+
+impl Error {
+    /// Every code returned by [Error::code]
+    pub const CODES: &'static [&'static str] = &[
+        "update.not_filled",
+        "update.no_answers",
+        "update.missing_data",
+        "update.manifest_error",
+        "update.temp_dir_error",
+        "update.render_error",
+        "update.read_error",
+        "update.write_error",
+    ];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::NotFilled(_) => "update.not_filled",
+            Error::NoAnswers(_) => "update.no_answers",
+            Error::MissingData(_) => "update.missing_data",
+            Error::ManifestError(_) => "update.manifest_error",
+            Error::TempDirError(_) => "update.temp_dir_error",
+            Error::RenderError(_) => "update.render_error",
+            Error::ReadError(..) => "update.read_error",
+            Error::WriteError(..) => "update.write_error",
+        }
+    }
+}
+
+/// What an update did with a file, going by how it changed in the output and in the new
+/// render since the fill recorded it
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum FileAction {
+    /// New in the render, and written
+    Added,
+    /// Changed in the render but not in the output, so replaced with the new render
+    Updated,
+    /// The same in the output as in the new render
+    Unchanged,
+    /// Changed in the output but not in the render, so the output's changes were kept
+    Kept,
+    /// Changed in both. The new render was written alongside, with [NEW_EXT] added.
+    Conflict,
+    /// No longer rendered. It's left in the output.
+    Removed,
+}
+
+impl Display for FileAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileAction::Added => write!(f, "added"),
+            FileAction::Updated => write!(f, "updated"),
+            FileAction::Unchanged => write!(f, "unchanged"),
+            FileAction::Kept => write!(f, "kept local changes"),
+            FileAction::Conflict => write!(f, "conflict"),
+            FileAction::Removed => write!(f, "no longer rendered"),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct UpdatedFile {
+    /// Relative to the output directory
+    pub path: PathBuf,
+    pub action: FileAction,
+}
+
+/// The outcome of an update, with its files in path order
+#[derive(Serialize, Debug, Default)]
+pub struct Update {
+    pub files: Vec<UpdatedFile>,
+}
+
+impl Update {
+    /// The files whose new render was written alongside them, left to be merged by hand
+    pub fn conflicts(&self) -> Vec<&UpdatedFile> {
+        self.files
+            .iter()
+            .filter(|file| file.action == FileAction::Conflict)
+            .collect()
+    }
+}
+
+impl Display for Update {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for file in &self.files {
+            let path = file.path.display();
+            match file.action {
+                FileAction::Added => writeln!(f, "  {} {}", "+".green(), path)?,
+                FileAction::Updated => writeln!(f, "  {} {}", "~".yellow(), path)?,
+                FileAction::Unchanged => {}
+                FileAction::Conflict => writeln!(
+                    f,
+                    "  {} {} {}",
+                    "!".red(),
+                    path,
+                    format!("(conflict, see {})", new_path(&file.path).display()).red()
+                )?,
+                FileAction::Kept | FileAction::Removed => {
+                    writeln!(f, "{}", format!("  - {} ({})", path, file.action).dimmed())?
+                }
+            }
+        }
+
+        let count = |action: FileAction| self.files.iter().filter(|f| f.action == action).count();
+        write!(
+            f,
+            "\nUpdate: {} added, {} updated, {} unchanged, {} kept, {} conflicting, {} no longer rendered",
+            count(FileAction::Added),
+            count(FileAction::Updated),
+            count(FileAction::Unchanged),
+            count(FileAction::Kept),
+            count(FileAction::Conflict),
+            count(FileAction::Removed)
+        )
+    }
+}
+
+/// Where the new render of a conflicting file is written, with [NEW_EXT] added
+pub fn new_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(NEW_EXT);
+
+    path.with_file_name(name)
+}
+
+/// Applies a new render of the output to the output directory. Each file is compared three
+/// ways: its fingerprint in `recorded`, as the last fill or update wrote it, its contents in
+/// the output, and its contents in the render. Changes only the render made are applied,
+/// changes only the output made are kept, and where both changed the new render is written
+/// alongside with [NEW_EXT] added, there being no merging. That file is for the user to
+/// remove once they've merged it, and the conflict is reported again until they do. A file
+/// deleted from the output counts as changed there.
+pub fn apply(
+    out_dir: &Path,
+    render_dir: &Path,
+    recorded: &BTreeMap<PathBuf, String>,
+) -> Result<Update, Error> {
+    let rendered = manifest::fingerprints(render_dir);
+    let mut files = Vec::new();
+
+    for (path, new) in &rendered {
+        let dest = out_dir.join(path);
+        let current = if dest.is_file() {
+            let contents = fs::read(&dest).map_err(|e| Error::ReadError(path.clone(), e))?;
+            Some(fingerprint(&contents))
+        } else {
+            None
+        };
+
+        let action = match (recorded.get(path), current) {
+            (_, Some(current)) if current == *new => FileAction::Unchanged,
+            (None, None) => FileAction::Added,
+            (Some(recorded), Some(current)) if current == *recorded => FileAction::Updated,
+            // Until its new render is removed, a conflict hasn't been merged
+            (Some(recorded), _) if recorded == new && !new_path(&dest).exists() => FileAction::Kept,
+            _ => FileAction::Conflict,
+        };
+
+        let write_to = match action {
+            FileAction::Added | FileAction::Updated => Some(dest),
+            FileAction::Conflict => Some(new_path(&dest)),
+            _ => None,
+        };
+        if let Some(write_to) = write_to {
+            if let Some(parent) = write_to.parent() {
+                fs::create_dir_all(parent).map_err(|e| Error::WriteError(path.clone(), e))?;
+            }
+            fs::copy(render_dir.join(path), &write_to)
+                .map_err(|e| Error::WriteError(path.clone(), e))?;
+        }
+
+        files.push(UpdatedFile {
+            path: path.clone(),
+            action,
+        });
+    }
+
+    files.extend(
+        recorded
+            .keys()
+            .filter(|path| !rendered.contains_key(*path))
+            .map(|path| UpdatedFile {
+                path: path.clone(),
+                action: FileAction::Removed,
+            }),
+    );
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(Update { files })
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn apply() {
+        let out = TempDir::new("spackle").unwrap();
+        let render = TempDir::new("spackle").unwrap();
+        let write = |dir: &TempDir, path: &str, contents: &str| {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        };
+
+        // What the fill wrote
+        let recorded = ["same", "template", "local", "both", "deleted", "dropped"]
+            .iter()
+            .map(|path| (PathBuf::from(path), fingerprint(b"v1")))
+            .collect::<BTreeMap<_, _>>();
+
+        for (path, contents) in [
+            ("same", "v1"),
+            ("template", "v1"),
+            ("local", "edited"),
+            ("both", "edited"),
+            ("dropped", "v1"),
+            ("untracked", "mine"),
+        ] {
+            write(&out, path, contents);
+        }
+        for (path, contents) in [
+            ("same", "v1"),
+            ("template", "v2"),
+            ("local", "v1"),
+            ("both", "v2"),
+            ("deleted", "v2"),
+            ("nested/new", "v2"),
+            ("untracked", "v2"),
+        ] {
+            write(&render, path, contents);
+        }
+
+        let update = super::apply(out.path(), render.path(), &recorded).unwrap();
+        assert_eq!(
+            update
+                .files
+                .iter()
+                .map(|file| (file.path.to_str().unwrap(), file.action))
+                .collect::<Vec<_>>(),
+            vec![
+                ("both", FileAction::Conflict),
+                ("deleted", FileAction::Conflict),
+                ("dropped", FileAction::Removed),
+                ("local", FileAction::Kept),
+                ("nested/new", FileAction::Added),
+                ("same", FileAction::Unchanged),
+                ("template", FileAction::Updated),
+                ("untracked", FileAction::Conflict),
+            ]
+        );
+        assert_eq!(update.conflicts().len(), 3);
+
+        let read = |path: &str| fs::read_to_string(out.path().join(path)).unwrap();
+        assert_eq!(read("template"), "v2");
+        assert_eq!(read("local"), "edited");
+        assert_eq!(read("both"), "edited");
+        assert_eq!(read("both.new"), "v2");
+        assert_eq!(read("deleted.new"), "v2");
+        assert_eq!(read("nested/new"), "v2");
+        assert_eq!(read("untracked"), "mine");
+        assert_eq!(read("untracked.new"), "v2");
+        assert_eq!(read("dropped"), "v1");
+    }
+}
@@ -0,0 +1,129 @@
+use serde::Deserialize;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+use crate::{config::Config, paths};
+
+/// Defaults for every project a user fills, read from [paths::user_config_file].
+/// Flags given to a command take precedence over the project's config, which takes precedence
+/// over these.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct UserConfig {
+    /// Never prompt, using defaults for hooks and failing on missing slots
+    pub non_interactive: Option<bool>,
+    /// The directory fills are written into when no output path is given, each in a
+    /// directory named after the project
+    pub out_dir: Option<PathBuf>,
+    /// Whether output is colored
+    pub color: Option<ColorChoice>,
+    /// The default for projects that don't set `strict_variables`
+    pub strict_variables: Option<bool>,
+    /// The default for projects that don't set `run_log`
+    pub run_log: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// Colored when writing to a terminal
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Error reading user config {}: {1}", .0.display())]
+    ReadError(PathBuf, io::Error),
+    #[error("Error parsing user config {}: {1}", .0.display())]
+    ParseError(PathBuf, toml::de::Error),
+}
+
+impl Error {
+    /// Every code returned by [Error::code]
+    pub const CODES: &'static [&'static str] =
+        &["user_config.read_error", "user_config.parse_error"];
+
+    /// A stable identifier for the kind of error, for matching on without parsing messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::ReadError(..) => "user_config.read_error",
+            Error::ParseError(..) => "user_config.parse_error",
+        }
+    }
+}
+
+impl UserConfig {
+    /// Loads the user's config, or the defaults if there is none
+    pub fn load() -> Result<Self, Error> {
+        match paths::user_config_file() {
+            Some(path) if path.exists() => Self::load_from(&path),
+            _ => Ok(UserConfig::default()),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, Error> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| Error::ReadError(path.to_path_buf(), e))?;
+
+        toml::from_str(&contents).map_err(|e| Error::ParseError(path.to_path_buf(), e))
+    }
+
+    /// Fills in the project config's unset values with the user's defaults
+    pub fn apply_to(&self, config: &mut Config) {
+        config.strict_variables = config.strict_variables.or(self.strict_variables);
+        config.run_log = config.run_log.or(self.run_log);
+    }
+
+    pub fn non_interactive(&self) -> bool {
+        self.non_interactive.unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn precedence() {
+        let dir = TempDir::new("spackle").unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(
+            &path,
+            "non_interactive = true\ncolor = \"never\"\nstrict_variables = false\nrun_log = false\n",
+        )
+        .unwrap();
+
+        let user = UserConfig::load_from(&path).expect("Failed to load user config");
+        assert!(user.non_interactive());
+        assert_eq!(user.color, Some(ColorChoice::Never));
+
+        // The project's own settings win over the user's
+        let mut config = Config {
+            strict_variables: Some(true),
+            ..Default::default()
+        };
+        user.apply_to(&mut config);
+        assert!(config.strict_variables());
+        assert!(!config.run_log());
+
+        // Without either, the built-in defaults apply
+        let mut config = Config::default();
+        UserConfig::default().apply_to(&mut config);
+        assert!(config.strict_variables());
+        assert!(config.run_log());
+
+        fs::write(&path, "colour = \"never\"\n").unwrap();
+        assert!(matches!(
+            UserConfig::load_from(&path),
+            Err(Error::ParseError(..))
+        ));
+    }
+}